@@ -45,11 +45,10 @@ pub struct MultiNoiseBiomeSourceEntry {
 pub struct CheckerboardColumnBiomeSource {
     pub biomes: HolderValueSet<Biome>,
     #[serde(default)]
-    #[allow(private_interfaces)]
     pub scale: DefaultOnError<Ranged<u32, 0, 62>, DefaultToTwo>,
 }
 
-struct DefaultToTwo;
+pub struct DefaultToTwo;
 impl ValueProvider<Ranged<u32, 0, 62>> for DefaultToTwo {
     fn provide() -> Ranged<u32, 0, 62> {
         Ranged::new(2).unwrap()
@@ -2,7 +2,7 @@ use serde::Deserialize;
 use std::collections::BTreeMap;
 use util::identifier::IdentifierBuf;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize)]
 pub struct BlockState {
     #[serde(rename = "Name")]
     pub name: IdentifierBuf,
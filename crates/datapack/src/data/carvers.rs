@@ -83,8 +83,8 @@ pub struct CanyonCarverConfiguration {
 
 #[derive(Debug, Deserialize)]
 pub struct CanyonShapeConfiguration {
-    pub distance_factor: f32,
-    pub thickness: f32,
+    pub distance_factor: FloatProvider,
+    pub thickness: FloatProvider,
     pub width_smoothness: NonNegativeI32,
     pub horizontal_radius_factor: FloatProvider,
     pub vertical_radius_default_factor: f32,
@@ -248,7 +248,7 @@ pub struct SplinePoint {
 
 #[derive(Debug, Deserialize)]
 pub struct ConstantFunction {
-    argument: NoiseValue,
+    pub argument: NoiseValue,
 }
 
 #[derive(Debug, Deserialize)]
@@ -77,16 +77,19 @@ where
 {
     pub fn resolve<'a, 'b: 'a>(&'b self, datapack: &'b DataPack) -> DataPackResult<&'a T> {
         match self {
-            Holder::Reference(id) => {
-                let loaded_values = T::get_loaded_values(&datapack.registry_values);
-                if let Some(value) = loaded_values.get(id) {
-                    // fast path: value already loaded
-                    Ok(value)
-                } else {
-                    loaded_values.get_or_try_insert(id.clone(), || T::load(datapack, id))
-                }
-            }
+            Holder::Reference(id) => Self::resolve_reference(datapack, id),
             Holder::Direct(value) => Ok(value),
         }
     }
+
+    /// Resolves the value registered under `id`, e.g. one of the values of a tag.
+    pub fn resolve_reference<'a>(datapack: &'a DataPack, id: &Identifier) -> DataPackResult<&'a T> {
+        let loaded_values = T::get_loaded_values(&datapack.registry_values);
+        if let Some(value) = loaded_values.get(id) {
+            // fast path: value already loaded
+            Ok(value)
+        } else {
+            loaded_values.get_or_try_insert(id.to_owned(), || T::load(datapack, id))
+        }
+    }
 }
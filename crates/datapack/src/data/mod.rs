@@ -23,7 +23,7 @@ pub mod height_provider;
 pub mod holder;
 pub mod noise;
 pub mod sound_event;
-mod step;
+pub mod step;
 pub mod structure;
 pub mod surface_rules;
 pub mod tag;
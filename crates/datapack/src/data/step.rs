@@ -1,6 +1,6 @@
 use serde::Deserialize;
 
-#[derive(Debug, Deserialize, Hash, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Deserialize, Hash, Eq, PartialEq)]
 #[serde(rename_all = "snake_case")]
 #[cfg_attr(not(feature = "exhaustive_enums"), non_exhaustive)]
 pub enum CarvingStep {
@@ -1,7 +1,8 @@
 use crate::built_in_registries::{Block, Fluid};
 use crate::data::biome::Biome;
 use crate::data::carvers::ConfiguredWorldCarver;
-use crate::data::holder::Holder;
+use crate::data::feature::PlacedFeature;
+use crate::data::holder::{Holder, RegistryType};
 use crate::data::structure::set::StructureSet;
 use crate::{DataPack, DataPackError, DataPackResult};
 use ahash::{AHashMap, AHashSet};
@@ -56,6 +57,7 @@ tagged_registries! {
     block: Block["block"];
     configured_carver: ConfiguredWorldCarver["worldgen/configured_carver"];
     fluid: Fluid["fluid"];
+    placed_feature: PlacedFeature["worldgen/placed_feature"];
     structure_set: StructureSet["worldgen/structure_set"];
 }
 
@@ -208,6 +210,27 @@ impl<T> Default for HolderValueSet<T> {
     }
 }
 
+impl<T> HolderValueSet<T>
+where
+    T: RegistryType + TaggedRegistry,
+{
+    /// Resolves the values of the set in order, replacing tags with their values.
+    pub fn resolve<'a>(&'a self, datapack: &'a DataPack) -> DataPackResult<Vec<&'a T>> {
+        let mut values = Vec::new();
+        for value in &self.values {
+            match value {
+                TagOrHolder::Holder(holder) => values.push(holder.resolve(datapack)?),
+                TagOrHolder::Tag(tag) => {
+                    for id in HolderSet::<T>::resolve_tag(datapack, tag)? {
+                        values.push(Holder::resolve_reference(datapack, id)?);
+                    }
+                }
+            }
+        }
+        Ok(values)
+    }
+}
+
 impl<'de, T> Deserialize<'de> for HolderValueSet<T>
 where
     T: Deserialize<'de>,
@@ -13,7 +13,7 @@ use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 use std::{fs, io};
 use thiserror::Error;
-use util::identifier::IntoIdentifier;
+use util::identifier::{IdentifierBuf, IntoIdentifier};
 use zip::result::ZipError;
 use zip::ZipArchive;
 
@@ -28,6 +28,18 @@ pub enum DataPackError {
     NonUtf8FilePath,
     #[error("recursive tag")]
     RecursiveTag,
+    #[error("noise parameters must be registered to be sampled")]
+    UnregisteredNoise,
+    #[error("biome source has no biomes")]
+    EmptyBiomeSource,
+    #[error("unknown multi noise biome source preset: {0}")]
+    UnknownMultiNoisePreset(IdentifierBuf),
+    #[error("the features of the biomes have a cycle in their order")]
+    FeatureOrderCycle,
+    #[error("placing {0} features isn't supported yet")]
+    UnsupportedFeature(String),
+    #[error("the {0} placement modifier isn't supported yet")]
+    UnsupportedPlacementModifier(String),
     #[error("zip: {0}")]
     Zip(#[from] ZipError),
 }
@@ -0,0 +1,334 @@
+use crate::anvil::nbt::{compound, Compound, Tag};
+use crate::anvil::{invalid_data, DATA_VERSION};
+use crate::chunk::heightmap::{HeightmapTypeExt, HEIGHTMAP_TYPES};
+use crate::chunk::proto_chunk::ProtoChunk;
+use crate::chunk::section::{ChunkSection, PalettedContainer};
+use crate::chunk::ChunkAccess;
+use datapack::data::block_state::BlockState;
+use glam::IVec2;
+use std::io;
+use util::identifier::IdentifierBuf;
+
+/// Converts a chunk to the NBT stored in region files, equivalent to vanilla's
+/// `ChunkSerializer.write`. The chunk is saved as fully generated but unlit, so the game computes
+/// its light when loading it.
+pub fn write_chunk(chunk: &ProtoChunk) -> Compound {
+    let sections = chunk
+        .sections()
+        .iter()
+        .enumerate()
+        .map(|(index, section)| {
+            compound([
+                ("Y", Tag::Byte((chunk.min_section() + index as i32) as i8)),
+                ("block_states", write_block_states(section.states())),
+                ("biomes", write_biomes(section.biomes())),
+            ])
+            .into()
+        })
+        .collect();
+
+    let heightmaps = HEIGHTMAP_TYPES
+        .iter()
+        .map(|kind| {
+            let heightmap = chunk.heightmap(*kind);
+            let heights: Vec<u32> = (0..256)
+                .map(|index| {
+                    (heightmap.first_available(index & 15, index >> 4) - chunk.min_y()) as u32
+                })
+                .collect();
+            let bits = ceil_log2(chunk.height() as u32 + 1);
+            (
+                kind.serialization_key().to_owned(),
+                Tag::LongArray(pack_bits(&heights, bits)),
+            )
+        })
+        .collect();
+
+    let post_processing = chunk
+        .post_processing()
+        .iter()
+        .map(|offsets| {
+            Tag::List(
+                offsets
+                    .iter()
+                    .map(|&offset| Tag::Short(offset as i16))
+                    .collect(),
+            )
+        })
+        .collect();
+
+    compound([
+        ("DataVersion", Tag::Int(DATA_VERSION)),
+        ("xPos", Tag::Int(chunk.pos().x)),
+        ("yPos", Tag::Int(chunk.min_section())),
+        ("zPos", Tag::Int(chunk.pos().y)),
+        ("LastUpdate", Tag::Long(0)),
+        ("InhabitedTime", Tag::Long(0)),
+        ("Status", "minecraft:full".into()),
+        ("isLightOn", Tag::Byte(0)),
+        ("sections", Tag::List(sections)),
+        ("Heightmaps", Tag::Compound(heightmaps)),
+        ("block_entities", Tag::List(Vec::new())),
+        ("block_ticks", Tag::List(Vec::new())),
+        ("fluid_ticks", Tag::List(Vec::new())),
+        ("PostProcessing", Tag::List(post_processing)),
+        (
+            "structures",
+            compound([
+                ("starts", Tag::Compound(Compound::new())),
+                ("References", Tag::Compound(Compound::new())),
+            ])
+            .into(),
+        ),
+    ])
+}
+
+/// Reads a chunk from the NBT stored in region files, equivalent to the parts of vanilla's
+/// `ChunkSerializer.read` that load blocks, biomes, heightmaps and post-processing. Heightmaps
+/// that aren't stored are computed from the blocks.
+pub fn read_chunk(nbt: &Compound) -> io::Result<ProtoChunk> {
+    let pos = IVec2::new(get_int(nbt, "xPos")?, get_int(nbt, "zPos")?);
+    let min_section = get_int(nbt, "yPos")?;
+
+    // sections without blocks only store light, and may lie above or below the chunk
+    let mut sections = Vec::new();
+    for section in get(nbt, "sections", Tag::as_list)? {
+        let section = section
+            .as_compound()
+            .ok_or_else(|| invalid_data("section is not a compound"))?;
+        let Some(block_states) = section.get("block_states").and_then(Tag::as_compound) else {
+            continue;
+        };
+        let y = get_int(section, "Y")?;
+        let states = read_block_states(block_states)?;
+        let biomes = match section.get("biomes").and_then(Tag::as_compound) {
+            Some(biomes) => read_biomes(biomes)?,
+            None => PalettedContainer::filled(IdentifierBuf::new("minecraft:plains").unwrap()),
+        };
+        sections.push((y, ChunkSection::new(states, biomes)));
+    }
+    let section_count = sections
+        .iter()
+        .map(|(y, _)| y - min_section + 1)
+        .max()
+        .unwrap_or(0);
+    if sections.iter().any(|(y, _)| *y < min_section) {
+        return Err(invalid_data("section is below the chunk"));
+    }
+
+    let mut chunk = ProtoChunk::new(pos, min_section << 4, section_count << 4);
+    for (y, section) in sections {
+        chunk.sections_mut()[(y - min_section) as usize] = section;
+    }
+    chunk.prime_heightmaps();
+
+    if let Some(post_processing) = nbt.get("PostProcessing").and_then(Tag::as_list) {
+        let section_count = chunk.sections().len();
+        for (index, offsets) in post_processing.iter().take(section_count).enumerate() {
+            let offsets = offsets
+                .as_list()
+                .ok_or_else(|| invalid_data("post-processing offsets are not a list"))?;
+            for offset in offsets {
+                let offset = offset
+                    .as_i64()
+                    .ok_or_else(|| invalid_data("post-processing offset is not a number"))?;
+                chunk.add_packed_post_process(offset as u16, index);
+            }
+        }
+    }
+
+    let heightmaps = nbt
+        .get("Heightmaps")
+        .and_then(Tag::as_compound)
+        .cloned()
+        .unwrap_or_default();
+    let bits = ceil_log2(chunk.height() as u32 + 1);
+    let min_y = chunk.min_y();
+    for kind in HEIGHTMAP_TYPES {
+        let Some(data) = heightmaps
+            .get(kind.serialization_key())
+            .and_then(Tag::as_long_array)
+        else {
+            continue;
+        };
+        let heights = unpack_bits(data, bits, 256)?;
+        let heightmap = chunk.heightmap_mut(kind);
+        for (index, height) in heights.into_iter().enumerate() {
+            heightmap.set_first_available(
+                index as i32 & 15,
+                index as i32 >> 4,
+                min_y + height as i32,
+            );
+        }
+    }
+    Ok(chunk)
+}
+
+fn read_block_states(nbt: &Compound) -> io::Result<PalettedContainer<BlockState, 4>> {
+    let palette = get(nbt, "palette", Tag::as_list)?
+        .iter()
+        .map(|entry| {
+            let entry = entry
+                .as_compound()
+                .ok_or_else(|| invalid_data("block state is not a compound"))?;
+            let name = get(entry, "Name", Tag::as_str)?;
+            let properties = match entry.get("Properties").and_then(Tag::as_compound) {
+                Some(properties) => properties
+                    .iter()
+                    .map(|(name, value)| {
+                        let value = value
+                            .as_str()
+                            .ok_or_else(|| invalid_data("block property is not a string"))?;
+                        Ok((name.clone(), value.to_owned()))
+                    })
+                    .collect::<io::Result<_>>()?,
+                None => Default::default(),
+            };
+            Ok(BlockState {
+                name: identifier(name)?,
+                properties,
+            })
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    read_container(nbt, palette, 4)
+}
+
+fn read_biomes(nbt: &Compound) -> io::Result<PalettedContainer<IdentifierBuf, 2>> {
+    let palette = get(nbt, "palette", Tag::as_list)?
+        .iter()
+        .map(|biome| {
+            identifier(
+                biome
+                    .as_str()
+                    .ok_or_else(|| invalid_data("biome is not a string"))?,
+            )
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    read_container(nbt, palette, 1)
+}
+
+fn read_container<T: Clone + PartialEq, const BITS: u32>(
+    nbt: &Compound,
+    palette: Vec<T>,
+    min_bits: u32,
+) -> io::Result<PalettedContainer<T, BITS>> {
+    let size = PalettedContainer::<T, BITS>::SIZE;
+    let indices = match palette.len() {
+        0 => return Err(invalid_data("palette is empty")),
+        1 => vec![0; size],
+        len => {
+            let bits = ceil_log2(len as u32).max(min_bits);
+            let data = get(nbt, "data", Tag::as_long_array)?;
+            let indices = unpack_bits(data, bits, size)?;
+            if indices.iter().any(|&index| index as usize >= len) {
+                return Err(invalid_data("palette index is out of bounds"));
+            }
+            indices.into_iter().map(|index| index as u16).collect()
+        }
+    };
+    Ok(PalettedContainer::from_packed(palette, indices))
+}
+
+fn get<'a, T: ?Sized>(
+    nbt: &'a Compound,
+    name: &str,
+    as_type: impl FnOnce(&'a Tag) -> Option<&'a T>,
+) -> io::Result<&'a T> {
+    nbt.get(name)
+        .and_then(as_type)
+        .ok_or_else(|| invalid_data(format!("missing or invalid {name}")))
+}
+
+fn get_int(nbt: &Compound, name: &str) -> io::Result<i32> {
+    nbt.get(name)
+        .and_then(Tag::as_i64)
+        .map(|value| value as i32)
+        .ok_or_else(|| invalid_data(format!("missing or invalid {name}")))
+}
+
+fn identifier(value: &str) -> io::Result<IdentifierBuf> {
+    IdentifierBuf::new(value).map_err(|err| invalid_data(format!("{value}: {err}")))
+}
+
+fn write_block_states(states: &PalettedContainer<BlockState, 4>) -> Tag {
+    let (palette, indices) = states.packed();
+    let palette = palette
+        .into_iter()
+        .map(|state| {
+            let mut entry = Compound::from([("Name".to_owned(), state.name.to_string().into())]);
+            if !state.properties.is_empty() {
+                let properties = state
+                    .properties
+                    .iter()
+                    .map(|(name, value)| (name.clone(), value.as_str().into()))
+                    .collect();
+                entry.insert("Properties".to_owned(), Tag::Compound(properties));
+            }
+            Tag::Compound(entry)
+        })
+        .collect();
+    // block states use at least 4 bits, like vanilla's linear palette
+    write_container(palette, &indices, 4)
+}
+
+fn write_biomes(biomes: &PalettedContainer<IdentifierBuf, 2>) -> Tag {
+    let (palette, indices) = biomes.packed();
+    let palette = palette
+        .into_iter()
+        .map(|biome| biome.to_string().into())
+        .collect();
+    write_container(palette, &indices, 1)
+}
+
+/// Writes a paletted container, leaving out the data if there is only a single value.
+fn write_container(palette: Vec<Tag>, indices: &[u16], min_bits: u32) -> Tag {
+    let mut container = Compound::new();
+    if palette.len() > 1 {
+        let bits = ceil_log2(palette.len() as u32).max(min_bits);
+        let indices: Vec<u32> = indices.iter().map(|&index| index as u32).collect();
+        container.insert("data".to_owned(), Tag::LongArray(pack_bits(&indices, bits)));
+    }
+    container.insert("palette".to_owned(), Tag::List(palette));
+    Tag::Compound(container)
+}
+
+/// Packs values into longs with `bits` bits each, without splitting values between longs, like
+/// vanilla's `SimpleBitStorage`.
+pub(crate) fn pack_bits(values: &[u32], bits: u32) -> Vec<i64> {
+    let values_per_long = (64 / bits) as usize;
+    values
+        .chunks(values_per_long)
+        .map(|values| {
+            values
+                .iter()
+                .enumerate()
+                .fold(0u64, |long, (index, &value)| {
+                    long | (value as u64) << (index as u32 * bits)
+                }) as i64
+        })
+        .collect()
+}
+
+/// Unpacks `count` values stored by [`pack_bits`].
+pub(crate) fn unpack_bits(data: &[i64], bits: u32, count: usize) -> io::Result<Vec<u32>> {
+    let values_per_long = (64 / bits) as usize;
+    if data.len() != count.div_ceil(values_per_long) {
+        return Err(invalid_data(format!(
+            "expected {} longs of packed data, got {}",
+            count.div_ceil(values_per_long),
+            data.len()
+        )));
+    }
+    let mask = (1u64 << bits) - 1;
+    Ok((0..count)
+        .map(|index| {
+            let long = data[index / values_per_long] as u64;
+            (long >> ((index % values_per_long) as u32 * bits) & mask) as u32
+        })
+        .collect())
+}
+
+#[inline]
+pub(crate) fn ceil_log2(value: u32) -> u32 {
+    u32::BITS - (value.max(1) - 1).leading_zeros()
+}
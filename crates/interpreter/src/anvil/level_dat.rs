@@ -0,0 +1,137 @@
+use crate::anvil::nbt::{self, compound, Compound, Tag};
+use crate::anvil::{DATA_VERSION, VERSION_NAME};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use glam::IVec3;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// `NbtIo`'s version of the `level.dat` format, stored as `version`.
+const ANVIL_VERSION: i32 = 19133;
+
+/// The settings stored in a `level.dat`. Everything else is filled with the defaults of a new
+/// creative world using the vanilla dimensions.
+#[derive(Debug, Clone)]
+pub struct LevelDat<'a> {
+    pub level_name: &'a str,
+    pub seed: u64,
+    pub spawn: IVec3,
+}
+
+impl LevelDat<'_> {
+    pub fn to_nbt(&self) -> Compound {
+        let last_played = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_millis() as i64);
+        let dimension = |dimension_type: &str, generator: Tag| {
+            compound([("type", dimension_type.into()), ("generator", generator)]).into()
+        };
+        let noise_generator = |settings: &str, biome_source: Tag| {
+            compound([
+                ("type", "minecraft:noise".into()),
+                ("settings", settings.into()),
+                ("biome_source", biome_source),
+            ])
+            .into()
+        };
+        let multi_noise = |preset: &str| {
+            compound([
+                ("type", "minecraft:multi_noise".into()),
+                ("preset", preset.into()),
+            ])
+            .into()
+        };
+
+        let data = compound([
+            ("DataVersion", Tag::Int(DATA_VERSION)),
+            ("version", Tag::Int(ANVIL_VERSION)),
+            (
+                "Version",
+                compound([
+                    ("Id", Tag::Int(DATA_VERSION)),
+                    ("Name", VERSION_NAME.into()),
+                    ("Series", "main".into()),
+                    ("Snapshot", Tag::Byte(0)),
+                ])
+                .into(),
+            ),
+            ("LevelName", self.level_name.into()),
+            ("GameType", Tag::Int(1)),
+            ("Difficulty", Tag::Byte(2)),
+            ("hardcore", Tag::Byte(0)),
+            ("allowCommands", Tag::Byte(1)),
+            ("initialized", Tag::Byte(1)),
+            ("LastPlayed", Tag::Long(last_played)),
+            ("Time", Tag::Long(0)),
+            ("DayTime", Tag::Long(0)),
+            ("SpawnX", Tag::Int(self.spawn.x)),
+            ("SpawnY", Tag::Int(self.spawn.y)),
+            ("SpawnZ", Tag::Int(self.spawn.z)),
+            ("SpawnAngle", Tag::Float(0.0)),
+            (
+                "DataPacks",
+                compound([
+                    ("Enabled", Tag::List(vec!["vanilla".into()])),
+                    ("Disabled", Tag::List(Vec::new())),
+                ])
+                .into(),
+            ),
+            (
+                "WorldGenSettings",
+                compound([
+                    ("seed", Tag::Long(self.seed as i64)),
+                    ("generate_features", Tag::Byte(1)),
+                    ("bonus_chest", Tag::Byte(0)),
+                    (
+                        "dimensions",
+                        compound([
+                            (
+                                "minecraft:overworld",
+                                dimension(
+                                    "minecraft:overworld",
+                                    noise_generator(
+                                        "minecraft:overworld",
+                                        multi_noise("minecraft:overworld"),
+                                    ),
+                                ),
+                            ),
+                            (
+                                "minecraft:the_nether",
+                                dimension(
+                                    "minecraft:the_nether",
+                                    noise_generator(
+                                        "minecraft:nether",
+                                        multi_noise("minecraft:nether"),
+                                    ),
+                                ),
+                            ),
+                            (
+                                "minecraft:the_end",
+                                dimension(
+                                    "minecraft:the_end",
+                                    noise_generator(
+                                        "minecraft:end",
+                                        compound([("type", "minecraft:the_end".into())]).into(),
+                                    ),
+                                ),
+                            ),
+                        ])
+                        .into(),
+                    ),
+                ])
+                .into(),
+            ),
+        ]);
+        compound([("Data", data.into())])
+    }
+
+    /// Writes the gzip compressed `level.dat` file.
+    pub fn write(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = GzEncoder::new(file, Compression::default());
+        nbt::write_root(&mut encoder, "", &self.to_nbt())?;
+        encoder.finish()?.flush()
+    }
+}
@@ -0,0 +1,259 @@
+//! Saving generated chunks as a world in the Anvil format, which the game and external tools can
+//! open.
+
+pub mod chunk_serializer;
+pub mod level_dat;
+pub mod nbt;
+pub mod region;
+
+use crate::anvil::level_dat::LevelDat;
+use crate::anvil::region::RegionFile;
+use crate::chunk::proto_chunk::ProtoChunk;
+use crate::chunk::ChunkAccess;
+use ahash::AHashMap;
+use glam::IVec2;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// The data version of the game version chunks are saved for.
+pub const DATA_VERSION: i32 = 3955;
+/// The name of the game version chunks are saved for.
+pub const VERSION_NAME: &str = "1.21.1";
+
+/// Saves chunks of the overworld and a `level.dat` into a world folder, which is created if it
+/// doesn't exist. Region files that already exist are overwritten.
+pub fn write_world<'c>(
+    dir: impl AsRef<Path>,
+    level: &LevelDat,
+    chunks: impl IntoIterator<Item = &'c ProtoChunk>,
+) -> io::Result<()> {
+    let dir = dir.as_ref();
+    let region_dir = dir.join("region");
+    fs::create_dir_all(&region_dir)?;
+    level.write(dir.join("level.dat"))?;
+
+    let mut regions = AHashMap::new();
+    for chunk in chunks {
+        regions
+            .entry(region::region_pos(chunk.pos()))
+            .or_insert_with(RegionFile::new)
+            .set_chunk(chunk.pos(), &chunk_serializer::write_chunk(chunk))?;
+    }
+    for (region_pos, region) in regions {
+        let mut writer = BufWriter::new(File::create(
+            region_dir.join(region::region_file_name(region_pos)),
+        )?);
+        region.write(&mut writer)?;
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+/// Reads a chunk of the overworld from a world folder, returning `None` if it hasn't been
+/// generated.
+pub fn read_chunk(dir: impl AsRef<Path>, chunk_pos: IVec2) -> io::Result<Option<ProtoChunk>> {
+    let path = dir
+        .as_ref()
+        .join("region")
+        .join(region::region_file_name(region::region_pos(chunk_pos)));
+    let region = match RegionFile::open(path) {
+        Ok(region) => region,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    region
+        .chunk(chunk_pos)?
+        .map(|nbt| chunk_serializer::read_chunk(&nbt))
+        .transpose()
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::anvil::level_dat::LevelDat;
+    use crate::anvil::nbt::Tag;
+    use crate::anvil::region::RegionFile;
+    use crate::anvil::{chunk_serializer, read_chunk, write_world};
+    use crate::biomes::QuartPos;
+    use crate::block_state;
+    use crate::chunk::diff::{BiomeDiff, ChunkDiff, HeightmapDiff};
+    use crate::chunk::proto_chunk::ProtoChunk;
+    use crate::chunk::ChunkAccess;
+    use datapack::data::block_state::BlockState;
+    use datapack::data::holder::Holder;
+    use flate2::read::{GzDecoder, ZlibDecoder};
+    use glam::{IVec2, IVec3};
+    use std::collections::BTreeMap;
+    use std::fs;
+    use std::io::Read;
+    use std::path::Path;
+    use tempfile::TempDir;
+    use util::heightmap_type::HeightmapType;
+    use util::identifier::IdentifierBuf;
+
+    #[test]
+    fn test_write_world() {
+        let mut chunk = ProtoChunk::new(IVec2::new(-31, 2), -64, 384);
+        for x in 0..16 {
+            chunk.set_block_state(
+                IVec3::new(-496 + x, -64, 32),
+                block_state::simple_state("stone"),
+            );
+        }
+
+        let nbt = chunk_serializer::write_chunk(&chunk);
+        assert_eq!(nbt["yPos"], Tag::Int(-4));
+        let Tag::List(sections) = &nbt["sections"] else {
+            panic!("sections should be a list");
+        };
+        assert_eq!(sections.len(), 24);
+        let Tag::Compound(section) = &sections[0] else {
+            panic!("section should be a compound");
+        };
+        let Tag::Compound(block_states) = &section["block_states"] else {
+            panic!("block states should be a compound");
+        };
+        // 4 bits for each of the 4096 blocks
+        assert!(matches!(&block_states["data"], Tag::LongArray(data) if data.len() == 256));
+        let Tag::Compound(biomes) = &section["biomes"] else {
+            panic!("biomes should be a compound");
+        };
+        assert!(!biomes.contains_key("data"));
+        let Tag::Compound(heightmaps) = &nbt["Heightmaps"] else {
+            panic!("heightmaps should be a compound");
+        };
+        // 9 bits for heights up to 384, so 7 per long
+        let Tag::LongArray(world_surface) = &heightmaps["WORLD_SURFACE"] else {
+            panic!("heightmap should be a long array");
+        };
+        assert_eq!(world_surface.len(), 37);
+        assert_eq!(
+            world_surface[0],
+            1 | 1 << 9 | 1 << 18 | 1 << 27 | 1 << 36 | 1 << 45 | 1 << 54
+        );
+
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let level = LevelDat {
+            level_name: "test",
+            seed: 0,
+            spawn: IVec3::ZERO,
+        };
+        write_world(&dir, &level, [&chunk]).unwrap();
+
+        let region = fs::read(dir.join("region/r.-1.0.mca")).unwrap();
+        // the chunk is at (1, 2) in its region, and starts right after the header
+        let location = &region[(2 * 32 + 1) * 4..][..4];
+        assert_eq!(location, [0, 0, 2, 1]);
+        let length = u32::from_be_bytes(region[8192..8196].try_into().unwrap()) as usize;
+        assert_eq!(region[8196], 2);
+        assert_eq!(region.len(), 3 * 4096);
+        let mut data = Vec::new();
+        ZlibDecoder::new(&region[8197..8196 + length])
+            .read_to_end(&mut data)
+            .unwrap();
+        assert_eq!(data[..3], [10, 0, 0]);
+
+        let mut level_dat = Vec::new();
+        GzDecoder::new(fs::File::open(dir.join("level.dat")).unwrap())
+            .read_to_end(&mut level_dat)
+            .unwrap();
+        assert_eq!(
+            level_dat[..10],
+            [10, 0, 0, 10, 0, 4, b'D', b'a', b't', b'a']
+        );
+    }
+
+    #[test]
+    fn test_write_read_round_trip() {
+        let mut chunk = ProtoChunk::new(IVec2::new(5, -7), -64, 384);
+        let biomes = ["minecraft:forest", "minecraft:desert"]
+            .map(|biome| Holder::Reference(IdentifierBuf::new(biome).unwrap()));
+        chunk.fill_biomes(|pos| &biomes[(pos.0.y > 0) as usize]);
+        for y in -64i32..100 {
+            chunk.set_block_state(
+                IVec3::new(80 + y.rem_euclid(16), y, -112),
+                block_state::simple_state("stone"),
+            );
+        }
+
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let level = LevelDat {
+            level_name: "test",
+            seed: 0,
+            spawn: IVec3::ZERO,
+        };
+        write_world(&dir, &level, [&chunk]).unwrap();
+        let read = read_chunk(&dir, chunk.pos()).unwrap().unwrap();
+        assert!(ChunkDiff::compare(&read, &chunk).is_empty());
+        assert!(read_chunk(&dir, IVec2::new(5, -6)).unwrap().is_none());
+        assert!(read_chunk(&dir, IVec2::new(100, 0)).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_diff_reference_chunk() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_data/r.0.0.mca");
+        let region = RegionFile::open(path).unwrap();
+        assert!(region.chunk(IVec2::new(0, 0)).unwrap().is_none());
+        let nbt = region.chunk(IVec2::new(1, 0)).unwrap().unwrap();
+        let reference = chunk_serializer::read_chunk(&nbt).unwrap();
+        assert_eq!(reference.min_y(), -64);
+        assert_eq!(reference.height(), 384);
+        assert_eq!(
+            reference.block_state(IVec3::new(19, -59, 3)).name.path(),
+            "oak_leaves"
+        );
+
+        // build the same chunk, but with a missing leaf block and different biomes
+        let mut ours = ProtoChunk::new(IVec2::new(1, 0), -64, 384);
+        for z in 0..16 {
+            for x in 16..32 {
+                for (y, block) in [
+                    (-64, "bedrock"),
+                    (-63, "stone"),
+                    (-62, "stone"),
+                    (-61, "stone"),
+                ] {
+                    ours.set_block_state(IVec3::new(x, y, z), block_state::simple_state(block));
+                }
+                ours.set_block_state(
+                    IVec3::new(x, -60, z),
+                    BlockState {
+                        name: IdentifierBuf::new("grass_block").unwrap(),
+                        properties: BTreeMap::from([("snowy".to_owned(), "false".to_owned())]),
+                    },
+                );
+            }
+        }
+        ours.set_block_state(
+            IVec3::new(21, -59, 5),
+            block_state::simple_state("short_grass"),
+        );
+
+        let diff = ChunkDiff::compare(&ours, &reference);
+        assert_eq!(diff.blocks.len(), 1);
+        assert_eq!(diff.blocks[0].pos, IVec3::new(19, -59, 3));
+        assert_eq!(diff.blocks[0].ours, *block_state::air());
+        assert_eq!(diff.blocks[0].reference.name.path(), "oak_leaves");
+        // the reference has forests in the upper half of the lowest section
+        assert_eq!(diff.biomes.len(), 32);
+        assert!(diff.biomes.contains(&BiomeDiff {
+            pos: QuartPos::new(7, -16, 0),
+            ours: IdentifierBuf::new("plains").unwrap(),
+            reference: IdentifierBuf::new("forest").unwrap(),
+        }));
+        // the leaves are part of every heightmap except the one without leaves
+        assert_eq!(diff.heightmaps.len(), 5);
+        assert!(diff.heightmaps.contains(&HeightmapDiff {
+            heightmap: HeightmapType::MotionBlocking,
+            column: IVec2::new(3, 3),
+            ours: -60,
+            reference: -59,
+        }));
+    }
+}
@@ -0,0 +1,364 @@
+//! Minecraft's Named Binary Tag format, which chunks and `level.dat` are stored in.
+
+use crate::anvil::invalid_data;
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+
+pub type Compound = BTreeMap<String, Tag>;
+
+/// An NBT value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tag {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    /// A list of tags, which must all be of the same type.
+    List(Vec<Tag>),
+    Compound(Compound),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+const TAG_END: u8 = 0;
+/// The deepest nesting of lists and compounds that is read, like vanilla's `NbtAccounter`.
+const MAX_DEPTH: usize = 512;
+
+impl Tag {
+    /// The id of this tag's type in the binary format.
+    pub fn id(&self) -> u8 {
+        match self {
+            Tag::Byte(_) => 1,
+            Tag::Short(_) => 2,
+            Tag::Int(_) => 3,
+            Tag::Long(_) => 4,
+            Tag::Float(_) => 5,
+            Tag::Double(_) => 6,
+            Tag::ByteArray(_) => 7,
+            Tag::String(_) => 8,
+            Tag::List(_) => 9,
+            Tag::Compound(_) => 10,
+            Tag::IntArray(_) => 11,
+            Tag::LongArray(_) => 12,
+        }
+    }
+
+    /// Writes the payload of this tag, without its id and name.
+    pub fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        match self {
+            Tag::Byte(value) => writer.write_all(&value.to_be_bytes()),
+            Tag::Short(value) => writer.write_all(&value.to_be_bytes()),
+            Tag::Int(value) => writer.write_all(&value.to_be_bytes()),
+            Tag::Long(value) => writer.write_all(&value.to_be_bytes()),
+            Tag::Float(value) => writer.write_all(&value.to_be_bytes()),
+            Tag::Double(value) => writer.write_all(&value.to_be_bytes()),
+            Tag::ByteArray(values) => {
+                write_length(writer, values.len())?;
+                let bytes: Vec<u8> = values.iter().map(|&value| value as u8).collect();
+                writer.write_all(&bytes)
+            }
+            Tag::String(value) => write_string(writer, value),
+            Tag::List(values) => {
+                let id = values.first().map_or(TAG_END, Tag::id);
+                if values.iter().any(|value| value.id() != id) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "nbt list elements must have the same type",
+                    ));
+                }
+                writer.write_all(&[id])?;
+                write_length(writer, values.len())?;
+                values.iter().try_for_each(|value| value.write(writer))
+            }
+            Tag::Compound(compound) => write_compound(writer, compound),
+            Tag::IntArray(values) => {
+                write_length(writer, values.len())?;
+                let bytes: Vec<u8> = values
+                    .iter()
+                    .flat_map(|value| value.to_be_bytes())
+                    .collect();
+                writer.write_all(&bytes)
+            }
+            Tag::LongArray(values) => {
+                write_length(writer, values.len())?;
+                let bytes: Vec<u8> = values
+                    .iter()
+                    .flat_map(|value| value.to_be_bytes())
+                    .collect();
+                writer.write_all(&bytes)
+            }
+        }
+    }
+}
+
+impl Tag {
+    /// Reads the payload of a tag with the given type id.
+    pub fn read(reader: &mut impl Read, id: u8) -> io::Result<Tag> {
+        Self::read_nested(reader, id, 0)
+    }
+
+    fn read_nested(reader: &mut impl Read, id: u8, depth: usize) -> io::Result<Tag> {
+        if depth > MAX_DEPTH {
+            return Err(invalid_data("nbt is nested too deeply"));
+        }
+        Ok(match id {
+            1 => Tag::Byte(i8::from_be_bytes(read_array(reader)?)),
+            2 => Tag::Short(i16::from_be_bytes(read_array(reader)?)),
+            3 => Tag::Int(i32::from_be_bytes(read_array(reader)?)),
+            4 => Tag::Long(i64::from_be_bytes(read_array(reader)?)),
+            5 => Tag::Float(f32::from_be_bytes(read_array(reader)?)),
+            6 => Tag::Double(f64::from_be_bytes(read_array(reader)?)),
+            7 => {
+                let len = read_length(reader)?;
+                let bytes = read_bytes(reader, len)?;
+                Tag::ByteArray(bytes.into_iter().map(|byte| byte as i8).collect())
+            }
+            8 => Tag::String(read_string(reader)?),
+            9 => {
+                let [element_id] = read_array(reader)?;
+                let len = read_length(reader)?;
+                if element_id == TAG_END && len > 0 {
+                    return Err(invalid_data("nbt list of end tags is not empty"));
+                }
+                let values = (0..len)
+                    .map(|_| Self::read_nested(reader, element_id, depth + 1))
+                    .collect::<io::Result<_>>()?;
+                Tag::List(values)
+            }
+            10 => Tag::Compound(read_compound(reader, depth + 1)?),
+            11 => {
+                let len = read_length(reader)?;
+                let bytes = read_bytes(reader, len * 4)?;
+                Tag::IntArray(
+                    bytes
+                        .chunks_exact(4)
+                        .map(|value| i32::from_be_bytes(value.try_into().unwrap()))
+                        .collect(),
+                )
+            }
+            12 => {
+                let len = read_length(reader)?;
+                let bytes = read_bytes(reader, len * 8)?;
+                Tag::LongArray(
+                    bytes
+                        .chunks_exact(8)
+                        .map(|value| i64::from_be_bytes(value.try_into().unwrap()))
+                        .collect(),
+                )
+            }
+            _ => return Err(invalid_data(format!("invalid nbt tag id: {id}"))),
+        })
+    }
+
+    pub fn as_compound(&self) -> Option<&Compound> {
+        match self {
+            Tag::Compound(compound) => Some(compound),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[Tag]> {
+        match self {
+            Tag::List(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Tag::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_long_array(&self) -> Option<&[i64]> {
+        match self {
+            Tag::LongArray(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of a numeric tag as an `i64`, like vanilla's `NumericTag.getAsLong`.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Tag::Byte(value) => Some(value as i64),
+            Tag::Short(value) => Some(value as i64),
+            Tag::Int(value) => Some(value as i64),
+            Tag::Long(value) => Some(value),
+            Tag::Float(value) => Some(value as i64),
+            Tag::Double(value) => Some(value as i64),
+            _ => None,
+        }
+    }
+}
+
+impl From<Compound> for Tag {
+    fn from(value: Compound) -> Self {
+        Tag::Compound(value)
+    }
+}
+
+impl From<&str> for Tag {
+    fn from(value: &str) -> Self {
+        Tag::String(value.to_owned())
+    }
+}
+
+impl From<String> for Tag {
+    fn from(value: String) -> Self {
+        Tag::String(value)
+    }
+}
+
+/// Creates a compound from its entries.
+pub fn compound<const N: usize>(entries: [(&str, Tag); N]) -> Compound {
+    entries
+        .into_iter()
+        .map(|(name, tag)| (name.to_owned(), tag))
+        .collect()
+}
+
+/// Writes a root compound tag with the given name, which is usually empty.
+pub fn write_root(writer: &mut impl Write, name: &str, compound: &Compound) -> io::Result<()> {
+    writer.write_all(&[Tag::Compound(Compound::new()).id()])?;
+    write_string(writer, name)?;
+    write_compound(writer, compound)
+}
+
+/// Reads a root compound tag, returning its name and value.
+pub fn read_root(reader: &mut impl Read) -> io::Result<(String, Compound)> {
+    let [id] = read_array(reader)?;
+    if id != Tag::Compound(Compound::new()).id() {
+        return Err(invalid_data("nbt root tag must be a compound"));
+    }
+    let name = read_string(reader)?;
+    Ok((name, read_compound(reader, 0)?))
+}
+
+fn read_compound(reader: &mut impl Read, depth: usize) -> io::Result<Compound> {
+    let mut compound = Compound::new();
+    loop {
+        let [id] = read_array(reader)?;
+        if id == TAG_END {
+            return Ok(compound);
+        }
+        let name = read_string(reader)?;
+        compound.insert(name, Tag::read_nested(reader, id, depth)?);
+    }
+}
+
+fn read_array<const N: usize>(reader: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_bytes(reader: &mut impl Read, len: usize) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    reader.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(bytes)
+}
+
+fn read_length(reader: &mut impl Read) -> io::Result<usize> {
+    usize::try_from(i32::from_be_bytes(read_array(reader)?))
+        .map_err(|_| invalid_data("negative nbt array length"))
+}
+
+/// Reads a string in Java's modified UTF-8.
+fn read_string(reader: &mut impl Read) -> io::Result<String> {
+    let len = u16::from_be_bytes(read_array(reader)?) as usize;
+    let bytes = read_bytes(reader, len)?;
+    let mut units = Vec::with_capacity(len);
+    let mut bytes = bytes.into_iter();
+    while let Some(byte) = bytes.next() {
+        let mut continuation = || match bytes.next() {
+            Some(byte) if byte & 0xc0 == 0x80 => Ok((byte & 0x3f) as u16),
+            _ => Err(invalid_data("invalid modified utf-8")),
+        };
+        let unit = match byte {
+            0x00..=0x7f => byte as u16,
+            0xc0..=0xdf => ((byte & 0x1f) as u16) << 6 | continuation()?,
+            0xe0..=0xef => ((byte & 0x0f) as u16) << 12 | continuation()? << 6 | continuation()?,
+            _ => return Err(invalid_data("invalid modified utf-8")),
+        };
+        units.push(unit);
+    }
+    String::from_utf16(&units).map_err(|_| invalid_data("invalid modified utf-8"))
+}
+
+fn write_compound(writer: &mut impl Write, compound: &Compound) -> io::Result<()> {
+    for (name, tag) in compound {
+        writer.write_all(&[tag.id()])?;
+        write_string(writer, name)?;
+        tag.write(writer)?;
+    }
+    writer.write_all(&[TAG_END])
+}
+
+fn write_length(writer: &mut impl Write, len: usize) -> io::Result<()> {
+    let len = i32::try_from(len)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "nbt array is too long"))?;
+    writer.write_all(&len.to_be_bytes())
+}
+
+/// Writes a string in Java's modified UTF-8, which encodes null characters with two bytes and
+/// supplementary characters as surrogate pairs.
+fn write_string(writer: &mut impl Write, value: &str) -> io::Result<()> {
+    let mut bytes = Vec::with_capacity(value.len());
+    for unit in value.encode_utf16() {
+        match unit {
+            0x0001..=0x007f => bytes.push(unit as u8),
+            0x0000..=0x07ff => {
+                bytes.push(0xc0 | (unit >> 6) as u8);
+                bytes.push(0x80 | (unit & 0x3f) as u8);
+            }
+            _ => {
+                bytes.push(0xe0 | (unit >> 12) as u8);
+                bytes.push(0x80 | ((unit >> 6) & 0x3f) as u8);
+                bytes.push(0x80 | (unit & 0x3f) as u8);
+            }
+        }
+    }
+    let len = u16::try_from(bytes.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "nbt string is too long"))?;
+    writer.write_all(&len.to_be_bytes())?;
+    writer.write_all(&bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::anvil::nbt::{compound, read_root, write_root, Tag};
+
+    #[test]
+    fn test_write() {
+        let root = compound([
+            ("a", Tag::Short(-2)),
+            ("list", Tag::List(vec![Tag::Byte(1), Tag::Byte(2)])),
+            ("s", "\0é".into()),
+        ]);
+        let mut bytes = Vec::new();
+        write_root(&mut bytes, "", &root).unwrap();
+        #[rustfmt::skip]
+        assert_eq!(bytes, [
+            10, 0, 0,
+            2, 0, 1, b'a', 0xff, 0xfe,
+            9, 0, 4, b'l', b'i', b's', b't', 1, 0, 0, 0, 2, 1, 2,
+            8, 0, 1, b's', 0, 4, 0xc0, 0x80, 0xc3, 0xa9,
+            0,
+        ]);
+
+        let (name, read) = read_root(&mut bytes.as_slice()).unwrap();
+        assert_eq!(name, "");
+        assert_eq!(read, root);
+
+        let mixed = Tag::List(vec![Tag::Byte(1), Tag::Int(2)]);
+        assert!(mixed.write(&mut Vec::new()).is_err());
+    }
+}
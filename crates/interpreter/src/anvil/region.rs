@@ -0,0 +1,197 @@
+use crate::anvil::invalid_data;
+use crate::anvil::nbt::{self, Compound};
+use flate2::read::{GzDecoder, ZlibDecoder};
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use glam::IVec2;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The number of chunks along each axis of a region.
+pub const REGION_SIZE: i32 = 32;
+const SECTOR_SIZE: usize = 4096;
+const HEADER_SECTORS: usize = 2;
+/// The largest number of sectors a chunk can span, since the count is stored in a byte.
+const MAX_CHUNK_SECTORS: usize = 255;
+const COMPRESSION_GZIP: u8 = 1;
+const COMPRESSION_ZLIB: u8 = 2;
+const COMPRESSION_NONE: u8 = 3;
+/// Set on the compression type of chunks stored in a separate `.mcc` file.
+const EXTERNAL_FLAG: u8 = 0x80;
+
+/// Returns the name of the file storing a region, such as `r.0.-1.mca`.
+pub fn region_file_name(region_pos: IVec2) -> String {
+    format!("r.{}.{}.mca", region_pos.x, region_pos.y)
+}
+
+/// Returns the region that contains a chunk.
+#[inline]
+pub fn region_pos(chunk_pos: IVec2) -> IVec2 {
+    chunk_pos >> 5
+}
+
+/// The compressed NBT of a chunk in a region file.
+#[derive(Debug, Clone)]
+struct ChunkData {
+    compression: u8,
+    data: Vec<u8>,
+}
+
+/// A region of 32x32 chunks in the Anvil format, equivalent to vanilla's `RegionFile`.
+#[derive(Debug, Clone)]
+pub struct RegionFile {
+    /// The chunks, indexed by `z * 32 + x`.
+    chunks: Vec<Option<ChunkData>>,
+}
+
+impl RegionFile {
+    pub fn new() -> Self {
+        RegionFile {
+            chunks: vec![None; (REGION_SIZE * REGION_SIZE) as usize],
+        }
+    }
+
+    /// Compresses and stores the NBT of a chunk. Only the position of the chunk within its region
+    /// is used.
+    pub fn set_chunk(&mut self, chunk_pos: IVec2, chunk: &Compound) -> io::Result<()> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        nbt::write_root(&mut encoder, "", chunk)?;
+        let data = encoder.finish()?;
+        // the chunk data is preceded by its length and compression type
+        if (data.len() + 5).div_ceil(SECTOR_SIZE) > MAX_CHUNK_SECTORS {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "chunk is too large for a region file",
+            ));
+        }
+        self.chunks[Self::index(chunk_pos)] = Some(ChunkData {
+            compression: COMPRESSION_ZLIB,
+            data,
+        });
+        Ok(())
+    }
+
+    /// Decompresses and returns the NBT of a chunk, or `None` if the region doesn't contain it.
+    /// Only the position of the chunk within its region is used.
+    pub fn chunk(&self, chunk_pos: IVec2) -> io::Result<Option<Compound>> {
+        let Some(chunk) = &self.chunks[Self::index(chunk_pos)] else {
+            return Ok(None);
+        };
+        let data = chunk.data.as_slice();
+        let (_, compound) = match chunk.compression {
+            COMPRESSION_GZIP => nbt::read_root(&mut GzDecoder::new(data))?,
+            COMPRESSION_ZLIB => nbt::read_root(&mut ZlibDecoder::new(data))?,
+            COMPRESSION_NONE => nbt::read_root(&mut &*data)?,
+            compression => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!("unsupported chunk compression type: {compression}"),
+                ))
+            }
+        };
+        Ok(Some(compound))
+    }
+
+    /// Reads a region file. Chunks stored in separate `.mcc` files, which vanilla uses for chunks
+    /// larger than 1MiB, aren't supported.
+    pub fn read(reader: &mut impl Read) -> io::Result<Self> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        if bytes.len() < HEADER_SECTORS * SECTOR_SIZE {
+            return Err(invalid_data("region file header is truncated"));
+        }
+
+        let chunks = bytes[..SECTOR_SIZE]
+            .chunks_exact(4)
+            .map(|location| {
+                let location = u32::from_be_bytes(location.try_into().unwrap()) as usize;
+                if location == 0 {
+                    return Ok(None);
+                }
+                let start = (location >> 8) * SECTOR_SIZE;
+                let end = start + (location & 0xff) * SECTOR_SIZE;
+                let sectors = bytes
+                    .get(start..end.min(bytes.len()))
+                    .filter(|sectors| sectors.len() >= 5)
+                    .ok_or_else(|| invalid_data("chunk is outside of the region file"))?;
+                let length = u32::from_be_bytes(sectors[..4].try_into().unwrap()) as usize;
+                let compression = sectors[4];
+                if compression & EXTERNAL_FLAG != 0 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::Unsupported,
+                        "external chunk files are not supported",
+                    ));
+                }
+                let data = length
+                    .checked_sub(1)
+                    .and_then(|length| sectors.get(5..5 + length))
+                    .ok_or_else(|| invalid_data("chunk length is out of bounds"))?;
+                Ok(Some(ChunkData {
+                    compression,
+                    data: data.to_vec(),
+                }))
+            })
+            .collect::<io::Result<_>>()?;
+        Ok(RegionFile { chunks })
+    }
+
+    /// Reads the region file at `path`.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::read(&mut fs::read(path)?.as_slice())
+    }
+
+    pub fn has_chunk(&self, chunk_pos: IVec2) -> bool {
+        self.chunks[Self::index(chunk_pos)].is_some()
+    }
+
+    /// Writes the region file: a header with the location and timestamp of every chunk, followed
+    /// by the chunks, each padded to a multiple of 4KiB.
+    pub fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs() as u32);
+
+        let mut locations = Vec::with_capacity(SECTOR_SIZE);
+        let mut timestamps = Vec::with_capacity(SECTOR_SIZE);
+        let mut sector = HEADER_SECTORS;
+        for chunk in &self.chunks {
+            match chunk {
+                Some(ChunkData { data, .. }) => {
+                    let sectors = (data.len() + 5).div_ceil(SECTOR_SIZE);
+                    locations.extend_from_slice(&((sector << 8 | sectors) as u32).to_be_bytes());
+                    timestamps.extend_from_slice(&timestamp.to_be_bytes());
+                    sector += sectors;
+                }
+                None => {
+                    locations.extend_from_slice(&[0; 4]);
+                    timestamps.extend_from_slice(&[0; 4]);
+                }
+            }
+        }
+        writer.write_all(&locations)?;
+        writer.write_all(&timestamps)?;
+
+        for ChunkData { compression, data } in self.chunks.iter().flatten() {
+            writer.write_all(&(data.len() as u32 + 1).to_be_bytes())?;
+            writer.write_all(&[*compression])?;
+            writer.write_all(data)?;
+            let padding = (data.len() + 5).next_multiple_of(SECTOR_SIZE) - (data.len() + 5);
+            writer.write_all(&vec![0; padding])?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn index(chunk_pos: IVec2) -> usize {
+        let local = chunk_pos & (REGION_SIZE - 1);
+        (local.y * REGION_SIZE + local.x) as usize
+    }
+}
+
+impl Default for RegionFile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
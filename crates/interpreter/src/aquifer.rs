@@ -0,0 +1,646 @@
+use crate::block_state::{self, BlockStateExt};
+use crate::density_functions::compiler::DensityFunctionCompiler;
+use crate::density_functions::graph::{GraphEvaluator, NodeId};
+use crate::surface::PreliminarySurface;
+use datapack::data::block_state::BlockState;
+use datapack::data::noise::{NoiseGeneratorSettings, NoiseRouter};
+use datapack::DataPackResult;
+use glam::{IVec2, IVec3};
+use runtime::random_source::{AnyPositionalRandomFactory, PositionalRandomFactory, RandomSource};
+use util::identifier::Identifier;
+use util::math;
+
+const WATER: &Identifier = Identifier::new_const("water");
+const LAVA: &Identifier = Identifier::new_const("lava");
+
+/// `DimensionType.WAY_BELOW_MIN_Y`
+const WAY_BELOW_MIN_Y: i32 = -32512;
+
+const X_RANGE: u32 = 10;
+const Y_RANGE: u32 = 9;
+const Z_RANGE: u32 = 10;
+const X_SPACING: i32 = 16;
+const Y_SPACING: i32 = 12;
+const Z_SPACING: i32 = 16;
+
+/// The chunk offsets at which `NoiseBasedAquifer.computeFluid` checks the preliminary surface.
+const SURFACE_SAMPLING_OFFSETS_IN_CHUNKS: [(i32, i32); 13] = [
+    (0, 0),
+    (-2, -1),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-3, 0),
+    (-2, 0),
+    (-1, 0),
+    (1, 0),
+    (-2, 1),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+/// A fluid filling everything below a level, equivalent to `Aquifer.FluidStatus`.
+#[derive(Debug, Copy, Clone)]
+pub struct FluidStatus<'a> {
+    pub fluid_level: i32,
+    pub fluid_type: &'a BlockState,
+}
+
+impl<'a> FluidStatus<'a> {
+    pub fn new(fluid_level: i32, fluid_type: &'a BlockState) -> Self {
+        FluidStatus {
+            fluid_level,
+            fluid_type,
+        }
+    }
+
+    #[inline]
+    pub fn at(&self, y: i32) -> &'a BlockState {
+        if y < self.fluid_level {
+            self.fluid_type
+        } else {
+            block_state::air()
+        }
+    }
+}
+
+/// The fluid of a noise generator without aquifers: the default fluid up to the sea level, and
+/// lava below y=-54. Equivalent to `NoiseBasedChunkGenerator.createFluidPicker`.
+#[derive(Debug, Copy, Clone)]
+pub struct FluidPicker<'a> {
+    lava: FluidStatus<'a>,
+    sea: FluidStatus<'a>,
+}
+
+impl<'a> FluidPicker<'a> {
+    pub fn new(settings: &'a NoiseGeneratorSettings) -> Self {
+        FluidPicker {
+            lava: FluidStatus::new(-54, block_state::lava()),
+            sea: FluidStatus::new(settings.sea_level, &settings.default_fluid),
+        }
+    }
+
+    #[inline]
+    pub fn compute_fluid(&self, _x: i32, y: i32, _z: i32) -> FluidStatus<'a> {
+        if y < self.lava.fluid_level.min(self.sea.fluid_level) {
+            self.lava
+        } else {
+            self.sea
+        }
+    }
+}
+
+/// The noise router functions used by a [`NoiseBasedAquifer`], compiled into a shared graph.
+#[derive(Debug, Copy, Clone)]
+pub struct AquiferFunctions {
+    pub barrier: NodeId,
+    pub fluid_level_floodedness: NodeId,
+    pub fluid_level_spread: NodeId,
+    pub lava: NodeId,
+    pub erosion: NodeId,
+    pub depth: NodeId,
+}
+
+impl AquiferFunctions {
+    pub fn compile<'a>(
+        compiler: &mut DensityFunctionCompiler<'a>,
+        router: &'a NoiseRouter,
+    ) -> DataPackResult<Self> {
+        Ok(AquiferFunctions {
+            barrier: compiler.compile(&router.barrier)?,
+            fluid_level_floodedness: compiler.compile(&router.fluid_level_floodedness)?,
+            fluid_level_spread: compiler.compile(&router.fluid_level_spread)?,
+            lava: compiler.compile(&router.lava)?,
+            erosion: compiler.compile(&router.erosion)?,
+            depth: compiler.compile(&router.depth)?,
+        })
+    }
+}
+
+/// Decides which fluid fills the empty space of a chunk, equivalent to vanilla's `Aquifer`.
+pub enum Aquifer<'a> {
+    Disabled(FluidPicker<'a>),
+    NoiseBased(Box<NoiseBasedAquifer<'a>>),
+}
+
+impl<'a> Aquifer<'a> {
+    /// Creates the aquifer of a chunk, which is disabled unless the settings enable aquifers.
+    pub fn new(
+        settings: &'a NoiseGeneratorSettings,
+        functions: AquiferFunctions,
+        random: &AnyPositionalRandomFactory,
+        chunk_pos: IVec2,
+        min_y: i32,
+        height: i32,
+    ) -> Self {
+        let fluid_picker = FluidPicker::new(settings);
+        if settings.aquifers_enabled {
+            Aquifer::NoiseBased(Box::new(NoiseBasedAquifer::new(
+                functions,
+                random.clone(),
+                fluid_picker,
+                chunk_pos,
+                min_y,
+                height,
+            )))
+        } else {
+            Aquifer::Disabled(fluid_picker)
+        }
+    }
+
+    /// Returns the fluid or air at `pos` given the final density there, or `None` if the block is
+    /// solid. The functions are computed with `evaluator`, which must be evaluating the graph the
+    /// aquifer functions were compiled into. Like in vanilla, the barrier is computed in the
+    /// current cell of the evaluator, and the other functions at single points.
+    pub fn compute_substance(
+        &mut self,
+        evaluator: &mut GraphEvaluator,
+        preliminary_surface: &mut dyn PreliminarySurface,
+        pos: IVec3,
+        density: f64,
+    ) -> Option<&'a BlockState> {
+        match self {
+            Aquifer::Disabled(fluid_picker) => {
+                if density > 0.0 {
+                    None
+                } else {
+                    Some(fluid_picker.compute_fluid(pos.x, pos.y, pos.z).at(pos.y))
+                }
+            }
+            Aquifer::NoiseBased(aquifer) => {
+                aquifer.compute_substance(evaluator, preliminary_surface, pos, density)
+            }
+        }
+    }
+
+    /// Whether the last fluid returned by [`compute_substance`](Self::compute_substance) may flow,
+    /// and so needs a fluid tick scheduled.
+    pub fn should_schedule_fluid_update(&self) -> bool {
+        match self {
+            Aquifer::Disabled(_) => false,
+            Aquifer::NoiseBased(aquifer) => aquifer.should_schedule_fluid_update,
+        }
+    }
+}
+
+/// Vanilla's `Aquifer.NoiseBasedAquifer`, which places a randomly offset aquifer center in each
+/// cell of a 16x12x16 grid, gives each one a fluid level, and separates neighbouring aquifers with
+/// barriers.
+pub struct NoiseBasedAquifer<'a> {
+    functions: AquiferFunctions,
+    random: AnyPositionalRandomFactory,
+    fluid_picker: FluidPicker<'a>,
+    aquifer_cache: Vec<Option<FluidStatus<'a>>>,
+    aquifer_location_cache: Vec<Option<IVec3>>,
+    should_schedule_fluid_update: bool,
+    min_grid_x: i32,
+    min_grid_y: i32,
+    min_grid_z: i32,
+    grid_size_x: i32,
+    grid_size_z: i32,
+}
+
+impl<'a> NoiseBasedAquifer<'a> {
+    pub fn new(
+        functions: AquiferFunctions,
+        random: AnyPositionalRandomFactory,
+        fluid_picker: FluidPicker<'a>,
+        chunk_pos: IVec2,
+        min_y: i32,
+        height: i32,
+    ) -> Self {
+        let min_block: IVec2 = chunk_pos << 4;
+        let min_grid_x = grid_x(min_block.x) - 1;
+        let max_grid_x = grid_x(min_block.x + 15) + 1;
+        let min_grid_y = grid_y(min_y) - 1;
+        let max_grid_y = grid_y(min_y + height) + 1;
+        let min_grid_z = grid_z(min_block.y) - 1;
+        let max_grid_z = grid_z(min_block.y + 15) + 1;
+        let grid_size_x = max_grid_x - min_grid_x + 1;
+        let grid_size_y = max_grid_y - min_grid_y + 1;
+        let grid_size_z = max_grid_z - min_grid_z + 1;
+        let size = (grid_size_x * grid_size_y * grid_size_z) as usize;
+        NoiseBasedAquifer {
+            functions,
+            random,
+            fluid_picker,
+            aquifer_cache: vec![None; size],
+            aquifer_location_cache: vec![None; size],
+            should_schedule_fluid_update: false,
+            min_grid_x,
+            min_grid_y,
+            min_grid_z,
+            grid_size_x,
+            grid_size_z,
+        }
+    }
+
+    #[inline]
+    fn index(&self, x: i32, y: i32, z: i32) -> usize {
+        let x = x - self.min_grid_x;
+        let y = y - self.min_grid_y;
+        let z = z - self.min_grid_z;
+        ((y * self.grid_size_z + z) * self.grid_size_x + x) as usize
+    }
+
+    pub fn compute_substance(
+        &mut self,
+        evaluator: &mut GraphEvaluator,
+        preliminary_surface: &mut dyn PreliminarySurface,
+        pos: IVec3,
+        density: f64,
+    ) -> Option<&'a BlockState> {
+        if density > 0.0 {
+            self.should_schedule_fluid_update = false;
+            return None;
+        }
+
+        let global_fluid = self
+            .fluid_picker
+            .compute_fluid(pos.x, pos.y, pos.z)
+            .at(pos.y);
+        if global_fluid.is(LAVA) {
+            self.should_schedule_fluid_update = false;
+            return Some(block_state::lava());
+        }
+
+        // find the three closest aquifer centers
+        let grid_x = (pos.x - 5).div_euclid(X_SPACING);
+        let grid_y = (pos.y + 1).div_euclid(Y_SPACING);
+        let grid_z = (pos.z - 5).div_euclid(Z_SPACING);
+        let mut distances = [i32::MAX; 3];
+        let mut locations = [IVec3::ZERO; 3];
+        for offset_x in 0..=1 {
+            for offset_y in -1..=1 {
+                for offset_z in 0..=1 {
+                    let location = self.aquifer_location(
+                        grid_x + offset_x,
+                        grid_y + offset_y,
+                        grid_z + offset_z,
+                    );
+                    let distance = location.distance_squared(pos);
+                    if distances[0] >= distance {
+                        distances = [distance, distances[0], distances[1]];
+                        locations = [location, locations[0], locations[1]];
+                    } else if distances[1] >= distance {
+                        distances = [distances[0], distance, distances[1]];
+                        locations = [locations[0], location, locations[1]];
+                    } else if distances[2] >= distance {
+                        distances[2] = distance;
+                        locations[2] = location;
+                    }
+                }
+            }
+        }
+
+        let closest = self.aquifer_status(evaluator, preliminary_surface, locations[0]);
+        let closest_similarity = similarity(distances[0], distances[1]);
+        let state = closest.at(pos.y);
+        if closest_similarity <= 0.0 {
+            self.should_schedule_fluid_update = closest_similarity >= similarity(10 * 10, 12 * 12);
+            return Some(state);
+        }
+        if state.is(WATER)
+            && self
+                .fluid_picker
+                .compute_fluid(pos.x, pos.y - 1, pos.z)
+                .at(pos.y - 1)
+                .is(LAVA)
+        {
+            self.should_schedule_fluid_update = true;
+            return Some(state);
+        }
+
+        let mut barrier = None;
+        let second = self.aquifer_status(evaluator, preliminary_surface, locations[1]);
+        let pressure =
+            closest_similarity * self.pressure(evaluator, &mut barrier, pos, closest, second);
+        if density + pressure > 0.0 {
+            self.should_schedule_fluid_update = false;
+            return None;
+        }
+
+        let third = self.aquifer_status(evaluator, preliminary_surface, locations[2]);
+        let similarity_1_3 = similarity(distances[0], distances[2]);
+        if similarity_1_3 > 0.0 {
+            let pressure = closest_similarity
+                * similarity_1_3
+                * self.pressure(evaluator, &mut barrier, pos, closest, third);
+            if density + pressure > 0.0 {
+                self.should_schedule_fluid_update = false;
+                return None;
+            }
+        }
+        let similarity_2_3 = similarity(distances[1], distances[2]);
+        if similarity_2_3 > 0.0 {
+            let pressure = closest_similarity
+                * similarity_2_3
+                * self.pressure(evaluator, &mut barrier, pos, second, third);
+            if density + pressure > 0.0 {
+                self.should_schedule_fluid_update = false;
+                return None;
+            }
+        }
+
+        self.should_schedule_fluid_update = true;
+        Some(state)
+    }
+
+    /// The randomly offset center of the aquifer in a grid cell.
+    fn aquifer_location(&mut self, x: i32, y: i32, z: i32) -> IVec3 {
+        let index = self.index(x, y, z);
+        if let Some(location) = self.aquifer_location_cache[index] {
+            return location;
+        }
+        let mut random = self.random.at(IVec3::new(x, y, z));
+        let location = IVec3::new(
+            x * X_SPACING + random.next_u32(X_RANGE) as i32,
+            y * Y_SPACING + random.next_u32(Y_RANGE) as i32,
+            z * Z_SPACING + random.next_u32(Z_RANGE) as i32,
+        );
+        self.aquifer_location_cache[index] = Some(location);
+        location
+    }
+
+    /// The barrier pressure between two aquifers at `pos`. The barrier noise is computed at most
+    /// once per position, and remembered in `barrier`.
+    fn pressure(
+        &self,
+        evaluator: &mut GraphEvaluator,
+        barrier: &mut Option<f64>,
+        pos: IVec3,
+        first: FluidStatus,
+        second: FluidStatus,
+    ) -> f64 {
+        let first_state = first.at(pos.y);
+        let second_state = second.at(pos.y);
+        if (first_state.is(LAVA) && second_state.is(WATER))
+            || (first_state.is(WATER) && second_state.is(LAVA))
+        {
+            return 2.0;
+        }
+
+        let level_difference = (first.fluid_level - second.fluid_level).abs();
+        if level_difference == 0 {
+            return 0.0;
+        }
+        let middle = 0.5 * (first.fluid_level + second.fluid_level) as f64;
+        let offset = pos.y as f64 + 0.5 - middle;
+        let distance = level_difference as f64 / 2.0 - offset.abs();
+        let pressure = if offset > 0.0 {
+            if distance > 0.0 {
+                distance / 1.5
+            } else {
+                distance / 2.5
+            }
+        } else {
+            let distance = 3.0 + distance;
+            if distance > 0.0 {
+                distance / 3.0
+            } else {
+                distance / 10.0
+            }
+        };
+
+        let barrier = if !(-2.0..=2.0).contains(&pressure) {
+            0.0
+        } else {
+            *barrier.get_or_insert_with(|| evaluator.compute(self.functions.barrier, pos))
+        };
+        2.0 * (barrier + pressure)
+    }
+
+    fn aquifer_status(
+        &mut self,
+        evaluator: &mut GraphEvaluator,
+        preliminary_surface: &mut dyn PreliminarySurface,
+        location: IVec3,
+    ) -> FluidStatus<'a> {
+        let index = self.index(grid_x(location.x), grid_y(location.y), grid_z(location.z));
+        if let Some(status) = self.aquifer_cache[index] {
+            return status;
+        }
+        let status = self.compute_fluid(evaluator, preliminary_surface, location);
+        self.aquifer_cache[index] = Some(status);
+        status
+    }
+
+    fn compute_fluid(
+        &self,
+        evaluator: &mut GraphEvaluator,
+        preliminary_surface: &mut dyn PreliminarySurface,
+        pos: IVec3,
+    ) -> FluidStatus<'a> {
+        let global_fluid = self.fluid_picker.compute_fluid(pos.x, pos.y, pos.z);
+        let mut min_surface_level = i32::MAX;
+        let top = pos.y + 12;
+        let bottom = pos.y - 12;
+        let mut surface_flooded = false;
+        for (chunk_x, chunk_z) in SURFACE_SAMPLING_OFFSETS_IN_CHUNKS {
+            let x = pos.x + (chunk_x << 4);
+            let z = pos.z + (chunk_z << 4);
+            let surface_level = preliminary_surface.preliminary_surface_level(x, z);
+            let fluid_y = surface_level + 8;
+            let center = chunk_x == 0 && chunk_z == 0;
+            if center && bottom > fluid_y {
+                return global_fluid;
+            }
+            let above_surface = top > fluid_y;
+            if above_surface || center {
+                let surface_fluid = self.fluid_picker.compute_fluid(x, fluid_y, z);
+                if !surface_fluid.at(fluid_y).is_air() {
+                    if center {
+                        surface_flooded = true;
+                    }
+                    if above_surface {
+                        return surface_fluid;
+                    }
+                }
+            }
+            min_surface_level = min_surface_level.min(surface_level);
+        }
+
+        let fluid_level = self.compute_surface_level(
+            evaluator,
+            pos,
+            global_fluid,
+            min_surface_level,
+            surface_flooded,
+        );
+        FluidStatus::new(
+            fluid_level,
+            self.compute_fluid_type(evaluator, pos, global_fluid, fluid_level),
+        )
+    }
+
+    fn compute_surface_level(
+        &self,
+        evaluator: &mut GraphEvaluator,
+        pos: IVec3,
+        global_fluid: FluidStatus,
+        min_surface_level: i32,
+        surface_flooded: bool,
+    ) -> i32 {
+        let (flooded, randomized) = if self.is_deep_dark_region(evaluator, pos) {
+            (-1.0, -1.0)
+        } else {
+            let depth_below_surface = min_surface_level + 8 - pos.y;
+            let near_surface = if surface_flooded {
+                math::clamped_map(depth_below_surface as f64, 0.0, 64.0, 1.0, 0.0)
+            } else {
+                0.0
+            };
+            let floodedness = math::clamp(
+                evaluator.compute_point(self.functions.fluid_level_floodedness, pos),
+                -1.0,
+                1.0,
+            );
+            let flooded_threshold = math::map(near_surface, 1.0, 0.0, -0.3, 0.8);
+            let randomized_threshold = math::map(near_surface, 1.0, 0.0, -0.8, 0.4);
+            (
+                floodedness - flooded_threshold,
+                floodedness - randomized_threshold,
+            )
+        };
+
+        if flooded > 0.0 {
+            global_fluid.fluid_level
+        } else if randomized > 0.0 {
+            self.compute_randomized_fluid_surface_level(evaluator, pos, min_surface_level)
+        } else {
+            WAY_BELOW_MIN_Y
+        }
+    }
+
+    /// Equivalent of `OverworldBiomeBuilder.isDeepDarkRegion`.
+    fn is_deep_dark_region(&self, evaluator: &mut GraphEvaluator, pos: IVec3) -> bool {
+        evaluator.compute_point(self.functions.erosion, pos) < -0.225f32 as f64
+            && evaluator.compute_point(self.functions.depth, pos) > 0.9f32 as f64
+    }
+
+    fn compute_randomized_fluid_surface_level(
+        &self,
+        evaluator: &mut GraphEvaluator,
+        pos: IVec3,
+        min_surface_level: i32,
+    ) -> i32 {
+        let grid = IVec3::new(
+            pos.x.div_euclid(16),
+            pos.y.div_euclid(40),
+            pos.z.div_euclid(16),
+        );
+        let middle = grid.y * 40 + 20;
+        let spread = evaluator.compute_point(self.functions.fluid_level_spread, grid) * 10.0;
+        min_surface_level.min(middle + math::quantize(spread, 3))
+    }
+
+    fn compute_fluid_type(
+        &self,
+        evaluator: &mut GraphEvaluator,
+        pos: IVec3,
+        global_fluid: FluidStatus<'a>,
+        fluid_level: i32,
+    ) -> &'a BlockState {
+        if fluid_level <= -10 && fluid_level != WAY_BELOW_MIN_Y && !global_fluid.fluid_type.is(LAVA)
+        {
+            let grid = IVec3::new(
+                pos.x.div_euclid(64),
+                pos.y.div_euclid(40),
+                pos.z.div_euclid(64),
+            );
+            if evaluator.compute_point(self.functions.lava, grid).abs() > 0.3 {
+                return block_state::lava();
+            }
+        }
+        global_fluid.fluid_type
+    }
+}
+
+#[inline]
+fn similarity(first_distance: i32, second_distance: i32) -> f64 {
+    1.0 - (second_distance - first_distance).abs() as f64 / 25.0
+}
+
+#[inline]
+fn grid_x(x: i32) -> i32 {
+    x.div_euclid(X_SPACING)
+}
+
+#[inline]
+fn grid_y(y: i32) -> i32 {
+    y.div_euclid(Y_SPACING)
+}
+
+#[inline]
+fn grid_z(z: i32) -> i32 {
+    z.div_euclid(Z_SPACING)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aquifer::{Aquifer, AquiferFunctions};
+    use crate::density_functions::compiler::DensityFunctionCompiler;
+    use crate::density_functions::graph::GraphEvaluator;
+    use crate::random_state::RandomState;
+    use crate::test_util::{empty_datapack, noise_settings};
+    use datapack::data::noise::NoiseGeneratorSettings;
+    use glam::{IVec2, IVec3};
+
+    /// Noise settings with a sea level of 63 and the given constant floodedness.
+    fn settings(aquifers_enabled: bool, floodedness: f64) -> NoiseGeneratorSettings {
+        let mut settings = noise_settings(-64, 384, 63);
+        settings.noise_router.fluid_level_floodedness =
+            serde_json::from_str(&floodedness.to_string()).unwrap();
+        settings.aquifers_enabled = aquifers_enabled;
+        settings
+    }
+
+    /// Fills a column of open space at the origin, returning the name of the block at each y.
+    fn column(settings: &NoiseGeneratorSettings, ys: &[i32]) -> Vec<String> {
+        let datapack = empty_datapack();
+        let random_state = RandomState::from_settings(&datapack, settings, 0);
+        let mut compiler = DensityFunctionCompiler::new(&datapack, &random_state);
+        let functions = AquiferFunctions::compile(&mut compiler, &settings.noise_router).unwrap();
+        let graph = compiler.finish();
+        let mut evaluator = GraphEvaluator::new(&graph);
+        let mut aquifer = Aquifer::new(
+            settings,
+            functions,
+            random_state.aquifer_random(),
+            IVec2::ZERO,
+            -64,
+            384,
+        );
+        ys.iter()
+            .map(|&y| {
+                let state = aquifer
+                    .compute_substance(&mut evaluator, &mut |_, _| 100, IVec3::new(0, y, 0), -1.0)
+                    .unwrap();
+                state.name.path().to_owned()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_disabled() {
+        assert_eq!(
+            column(&settings(false, 0.0), &[80, 63, 62, 0, -54, -55]),
+            ["air", "air", "water", "water", "water", "lava"]
+        );
+    }
+
+    #[test]
+    fn test_noise_based() {
+        // fully flooded aquifers take the sea level
+        assert_eq!(
+            column(&settings(true, 1.0), &[80, 63, 62, 0, -54, -55]),
+            ["air", "air", "water", "water", "water", "lava"]
+        );
+        // without floodedness the caves are empty, except for the lava at the bottom
+        assert_eq!(
+            column(&settings(true, -1.0), &[80, 62, 0, -54, -55]),
+            ["air", "air", "air", "air", "lava"]
+        );
+    }
+}
@@ -0,0 +1,126 @@
+use crate::biomes::climate::ClimateSampler;
+use crate::biomes::{BiomeSourceImpl, QuartPos};
+use datapack::data::biome::Biome;
+use datapack::data::holder::Holder;
+use glam::{DVec3, IVec3};
+use sha2::{Digest, Sha256};
+
+/// Looks up biomes at block resolution, by picking one of the 8 surrounding quart positions with
+/// vanilla's fuzzy zoom, so that biome borders aren't aligned to the quart grid.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BiomeManager {
+    biome_zoom_seed: u64,
+}
+
+impl BiomeManager {
+    /// Creates a biome manager for a world seed, obfuscating it like `BiomeManager.obfuscateSeed`.
+    pub fn new(seed: u64) -> Self {
+        Self::from_zoom_seed(obfuscate_seed(seed))
+    }
+
+    /// Creates a biome manager from an already obfuscated seed, e.g. the one sent to clients.
+    pub fn from_zoom_seed(biome_zoom_seed: u64) -> Self {
+        BiomeManager { biome_zoom_seed }
+    }
+
+    #[inline]
+    pub fn biome_zoom_seed(&self) -> u64 {
+        self.biome_zoom_seed
+    }
+
+    /// Returns the quart position whose biome is used at the given block position.
+    pub fn zoom(&self, pos: IVec3) -> QuartPos {
+        let pos = pos - 2;
+        let quart = pos >> 2;
+        let fraction = (pos & 3).as_dvec3() / 4.0;
+
+        let mut closest_corner = 0;
+        let mut closest_distance = f64::INFINITY;
+        for corner in 0..8 {
+            let offset = IVec3::new((corner >> 2) & 1, (corner >> 1) & 1, corner & 1);
+            let distance = self.fiddled_distance(quart + offset, fraction - offset.as_dvec3());
+            if closest_distance > distance {
+                closest_corner = corner;
+                closest_distance = distance;
+            }
+        }
+
+        QuartPos(
+            quart
+                + IVec3::new(
+                    (closest_corner >> 2) & 1,
+                    (closest_corner >> 1) & 1,
+                    closest_corner & 1,
+                ),
+        )
+    }
+
+    /// Returns the biome at a block position.
+    pub fn get_biome<'s>(
+        &self,
+        source: &'s impl BiomeSourceImpl,
+        pos: IVec3,
+        climate_sampler: &mut ClimateSampler,
+    ) -> &'s Holder<Biome> {
+        source.get_noise_biome(self.zoom(pos), climate_sampler)
+    }
+
+    fn fiddled_distance(&self, quart: IVec3, fraction: DVec3) -> f64 {
+        let mut seed = lcg_next(self.biome_zoom_seed, quart.x as u64);
+        seed = lcg_next(seed, quart.y as u64);
+        seed = lcg_next(seed, quart.z as u64);
+        seed = lcg_next(seed, quart.x as u64);
+        seed = lcg_next(seed, quart.y as u64);
+        seed = lcg_next(seed, quart.z as u64);
+        let fiddle_x = fiddle(seed);
+        seed = lcg_next(seed, self.biome_zoom_seed);
+        let fiddle_y = fiddle(seed);
+        seed = lcg_next(seed, self.biome_zoom_seed);
+        let fiddle_z = fiddle(seed);
+        let square = |value: f64| value * value;
+        square(fraction.z + fiddle_z)
+            + square(fraction.y + fiddle_y)
+            + square(fraction.x + fiddle_x)
+    }
+}
+
+/// Equivalent of `BiomeManager.obfuscateSeed`: the first 8 bytes of the SHA-256 of the seed, both
+/// little endian.
+pub fn obfuscate_seed(seed: u64) -> u64 {
+    let hash = Sha256::digest(seed.to_le_bytes());
+    u64::from_le_bytes(hash[..8].try_into().unwrap())
+}
+
+/// Equivalent of `LinearCongruentialGenerator.next`.
+fn lcg_next(seed: u64, salt: u64) -> u64 {
+    seed.wrapping_mul(
+        seed.wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407),
+    )
+    .wrapping_add(salt)
+}
+
+fn fiddle(seed: u64) -> f64 {
+    let value = ((seed as i64) >> 24).rem_euclid(1024) as f64 / 1024.0;
+    (value - 0.5) * 0.9
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::biomes::biome_manager::{obfuscate_seed, BiomeManager};
+    use crate::biomes::QuartPos;
+    use glam::IVec3;
+
+    #[test]
+    fn test_zoom() {
+        assert_eq!(obfuscate_seed(0), 0x7a0b81a1f57055af);
+        let biome_manager = BiomeManager::new(12345);
+        let zoom = |x, y, z| biome_manager.zoom(IVec3::new(x, y, z));
+        assert_eq!(zoom(0, 0, 0), QuartPos::new(-1, 0, -1));
+        assert_eq!(zoom(1, 64, -1), QuartPos::new(0, 15, -1));
+        assert_eq!(zoom(-37, -60, 113), QuartPos::new(-10, -15, 28));
+        assert_eq!(zoom(1000, 320, -1000), QuartPos::new(249, 80, -250));
+        assert_eq!(zoom(6, 6, 6), QuartPos::new(1, 1, 1));
+        assert_eq!(zoom(-1, -1, -1), QuartPos::new(-1, 0, -1));
+    }
+}
@@ -0,0 +1,107 @@
+use crate::biomes::climate::ClimateSampler;
+use crate::biomes::{BiomeHolder, BiomeSourceImpl, QuartPos};
+use ahash::AHashSet;
+use datapack::data::biome::Biome;
+use datapack::data::biome_source::CheckerboardColumnBiomeSource;
+use datapack::data::holder::Holder;
+use datapack::data::tag::{HolderSet, TagOrHolder};
+use datapack::{DataPack, DataPackError, DataPackResult};
+
+/// Repeats its biomes in diagonal stripes of columns, `1 << (scale + 2)` blocks wide.
+#[derive(Debug)]
+pub struct CheckerboardColumnBiomeSourceImpl<'a> {
+    biomes: Vec<BiomeHolder<'a>>,
+    bit_shift: u32,
+}
+
+impl<'a> CheckerboardColumnBiomeSourceImpl<'a> {
+    pub fn new(
+        datapack: &'a DataPack,
+        source: &'a CheckerboardColumnBiomeSource,
+    ) -> DataPackResult<Self> {
+        let mut added_biomes = AHashSet::new();
+        let mut biomes = Vec::new();
+        for value in &source.biomes.values {
+            match value {
+                TagOrHolder::Holder(holder @ Holder::Reference(id)) => {
+                    if added_biomes.insert(&**id) {
+                        biomes.push(BiomeHolder::Borrowed(holder));
+                    }
+                }
+                TagOrHolder::Holder(holder @ Holder::Direct(_)) => {
+                    biomes.push(BiomeHolder::Borrowed(holder));
+                }
+                TagOrHolder::Tag(tag) => {
+                    for id in HolderSet::<Biome>::resolve_tag(datapack, tag)? {
+                        if added_biomes.insert(&**id) {
+                            biomes.push(BiomeHolder::reference(id.clone()));
+                        }
+                    }
+                }
+            }
+        }
+        if biomes.is_empty() {
+            return Err(DataPackError::EmptyBiomeSource);
+        }
+        Ok(CheckerboardColumnBiomeSourceImpl {
+            biomes,
+            bit_shift: **source.scale + 2,
+        })
+    }
+}
+
+impl BiomeSourceImpl for CheckerboardColumnBiomeSourceImpl<'_> {
+    fn get_noise_biome(
+        &self,
+        pos: QuartPos,
+        _climate_sampler: &mut ClimateSampler,
+    ) -> &Holder<Biome> {
+        let index = (pos.0.x >> self.bit_shift) + (pos.0.z >> self.bit_shift);
+        &self.biomes[index.rem_euclid(self.biomes.len() as i32) as usize]
+    }
+
+    fn possible_biomes(&self) -> Vec<&Holder<Biome>> {
+        self.biomes.iter().map(|biome| &**biome).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::biomes::checkerboard::CheckerboardColumnBiomeSourceImpl;
+    use crate::biomes::{BiomeSourceImpl, QuartPos};
+    use crate::test_util::{datapack, with_climate_sampler, zero_router};
+    use datapack::data::biome_source::CheckerboardColumnBiomeSource;
+    use datapack::data::holder::Holder;
+
+    #[test]
+    fn test_checkerboard() {
+        let datapack = datapack([(
+            "tags/worldgen/biome/dry.json",
+            r#"{ "values": ["minecraft:desert", "minecraft:plains", "minecraft:badlands"] }"#,
+        )]);
+        // plains is only added once, even though the tag contains it again
+        let source: CheckerboardColumnBiomeSource = serde_json::from_str(
+            r##"{ "biomes": ["minecraft:plains", "#minecraft:dry"], "scale": 0 }"##,
+        )
+        .unwrap();
+
+        let biome_source = CheckerboardColumnBiomeSourceImpl::new(&datapack, &source).unwrap();
+        with_climate_sampler(&datapack, &zero_router(), |sampler| {
+            let mut biome_at = |x, z| {
+                let Holder::Reference(biome) =
+                    biome_source.get_noise_biome(QuartPos::new(x, 0, z), sampler)
+                else {
+                    unreachable!();
+                };
+                biome.to_string()
+            };
+            // with a scale of 0, each biome is a 4 quart (16 block) wide column
+            assert_eq!(biome_at(0, 0), "minecraft:plains");
+            assert_eq!(biome_at(3, 3), "minecraft:plains");
+            assert_eq!(biome_at(4, 0), "minecraft:desert");
+            assert_eq!(biome_at(4, 4), "minecraft:badlands");
+            assert_eq!(biome_at(0, -4), "minecraft:badlands");
+            assert_eq!(biome_at(12, 0), "minecraft:plains");
+        });
+    }
+}
@@ -0,0 +1,536 @@
+use crate::biomes::QuartPos;
+use crate::density_functions::compiler::DensityFunctionCompiler;
+use crate::density_functions::graph::{DensityFunctionGraph, GraphEvaluator, NodeId};
+use crate::density_functions::optimizer;
+use crate::random_state::RandomState;
+use datapack::data::biome::{ClimateParameter, ClimateParameterPoint};
+use datapack::data::noise::NoiseRouter;
+use datapack::{DataPack, DataPackResult};
+use glam::IVec3;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const PARAMETER_COUNT: usize = 7;
+
+/// Equivalent of `Climate.quantizeCoord`.
+#[inline]
+pub fn quantize_coord(coord: f32) -> i64 {
+    (coord * 10000.0) as i64
+}
+
+#[inline]
+pub fn unquantize_coord(coord: i64) -> f32 {
+    coord as f32 / 10000.0
+}
+
+/// A quantized climate interval, equivalent to `Climate.Parameter`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Parameter {
+    pub min: i64,
+    pub max: i64,
+}
+
+impl Parameter {
+    pub fn point(value: f32) -> Self {
+        Self::span(value, value)
+    }
+
+    pub fn span(min: f32, max: f32) -> Self {
+        Parameter {
+            min: quantize_coord(min),
+            max: quantize_coord(max),
+        }
+    }
+
+    pub fn from_parameter(parameter: &ClimateParameter) -> Self {
+        Self::span(*parameter.interval.min, *parameter.interval.max)
+    }
+
+    /// The distance from `value` to the closest end of this interval, or zero if it's inside.
+    #[inline]
+    pub fn distance(self, value: i64) -> i64 {
+        let above = value - self.max;
+        let below = self.min - value;
+        if above > 0 {
+            above
+        } else {
+            below.max(0)
+        }
+    }
+
+    #[inline]
+    pub fn union(self, other: Parameter) -> Self {
+        Parameter {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+}
+
+/// The climate region a biome occupies, equivalent to `Climate.ParameterPoint`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ParameterPoint {
+    pub temperature: Parameter,
+    pub humidity: Parameter,
+    pub continentalness: Parameter,
+    pub erosion: Parameter,
+    pub depth: Parameter,
+    pub weirdness: Parameter,
+    pub offset: i64,
+}
+
+impl ParameterPoint {
+    pub fn from_point(point: &ClimateParameterPoint) -> Self {
+        ParameterPoint {
+            temperature: Parameter::from_parameter(&point.temperature),
+            humidity: Parameter::from_parameter(&point.humidity),
+            continentalness: Parameter::from_parameter(&point.continentalness),
+            erosion: Parameter::from_parameter(&point.erosion),
+            depth: Parameter::from_parameter(&point.depth),
+            weirdness: Parameter::from_parameter(&point.weirdness),
+            offset: quantize_coord(*point.offset),
+        }
+    }
+
+    fn parameter_space(&self) -> [Parameter; PARAMETER_COUNT] {
+        [
+            self.temperature,
+            self.humidity,
+            self.continentalness,
+            self.erosion,
+            self.depth,
+            self.weirdness,
+            Parameter {
+                min: self.offset,
+                max: self.offset,
+            },
+        ]
+    }
+}
+
+/// A quantized sample of the climate at a position, equivalent to `Climate.TargetPoint`.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct TargetPoint {
+    pub temperature: i64,
+    pub humidity: i64,
+    pub continentalness: i64,
+    pub erosion: i64,
+    pub depth: i64,
+    pub weirdness: i64,
+}
+
+impl TargetPoint {
+    pub fn new(
+        temperature: f32,
+        humidity: f32,
+        continentalness: f32,
+        erosion: f32,
+        depth: f32,
+        weirdness: f32,
+    ) -> Self {
+        TargetPoint {
+            temperature: quantize_coord(temperature),
+            humidity: quantize_coord(humidity),
+            continentalness: quantize_coord(continentalness),
+            erosion: quantize_coord(erosion),
+            depth: quantize_coord(depth),
+            weirdness: quantize_coord(weirdness),
+        }
+    }
+
+    fn to_parameter_array(self) -> [i64; PARAMETER_COUNT] {
+        [
+            self.temperature,
+            self.humidity,
+            self.continentalness,
+            self.erosion,
+            self.depth,
+            self.weirdness,
+            0,
+        ]
+    }
+}
+
+/// The six climate functions of a noise router, compiled into a single graph.
+#[derive(Debug)]
+pub struct ClimateFunctions<'a> {
+    graph: DensityFunctionGraph<'a>,
+    roots: [NodeId; 6],
+}
+
+impl<'a> ClimateFunctions<'a> {
+    pub fn new(
+        datapack: &'a DataPack,
+        random_state: &'a RandomState<'a>,
+        router: &'a NoiseRouter,
+    ) -> DataPackResult<Self> {
+        let mut compiler = DensityFunctionCompiler::new(datapack, random_state);
+        let mut roots = [
+            compiler.compile(&router.temperature)?,
+            compiler.compile(&router.vegetation)?,
+            compiler.compile(&router.continents)?,
+            compiler.compile(&router.erosion)?,
+            compiler.compile(&router.depth)?,
+            compiler.compile(&router.ridges)?,
+        ];
+        let (graph, _) = optimizer::optimize(compiler.finish(), &mut roots);
+        Ok(ClimateFunctions { graph, roots })
+    }
+
+    pub fn sampler(&self) -> ClimateSampler<'_, 'a> {
+        ClimateSampler {
+            evaluator: GraphEvaluator::new(&self.graph),
+            roots: self.roots,
+        }
+    }
+}
+
+/// Samples the climate at quart positions, equivalent to `Climate.Sampler`. Each sampler keeps its
+/// own caches, so use one per thread.
+#[derive(Debug)]
+pub struct ClimateSampler<'g, 'a> {
+    evaluator: GraphEvaluator<'g, 'a>,
+    roots: [NodeId; 6],
+}
+
+impl ClimateSampler<'_, '_> {
+    pub fn sample(&mut self, pos: QuartPos) -> TargetPoint {
+        let pos = pos.to_block();
+        let [temperature, humidity, continentalness, erosion, depth, weirdness] = self
+            .roots
+            .map(|root| self.evaluator.compute(root, pos) as f32);
+        TargetPoint::new(
+            temperature,
+            humidity,
+            continentalness,
+            erosion,
+            depth,
+            weirdness,
+        )
+    }
+
+    /// Computes only the erosion at a block position, which is all `TheEndBiomeSource` needs.
+    pub fn erosion(&mut self, pos: IVec3) -> f64 {
+        self.evaluator.compute(self.roots[3], pos)
+    }
+}
+
+/// A list of values and the climate regions they occupy, equivalent to `Climate.ParameterList`.
+#[derive(Debug)]
+pub struct ParameterList<T> {
+    values: Vec<(ParameterPoint, T)>,
+    tree: RTree,
+}
+
+impl<T> ParameterList<T> {
+    /// Panics if `values` is empty.
+    pub fn new(values: Vec<(ParameterPoint, T)>) -> Self {
+        let tree = RTree::new(&values);
+        ParameterList { values, tree }
+    }
+
+    #[inline]
+    pub fn values(&self) -> &[(ParameterPoint, T)] {
+        &self.values
+    }
+
+    /// Finds the value whose region is closest to `target`, equivalent to `findValue`.
+    pub fn find_value(&self, target: TargetPoint) -> &T {
+        &self.values[self.tree.search(target)].1
+    }
+
+    /// Finds the closest value by checking every region, equivalent to `findValueBruteForce`.
+    pub fn find_value_brute_force(&self, target: TargetPoint) -> &T {
+        let target = target.to_parameter_array();
+        let (_, value) = self
+            .values
+            .iter()
+            .min_by_key(|(point, _)| distance(&point.parameter_space(), &target))
+            .unwrap();
+        value
+    }
+}
+
+#[inline]
+fn distance(space: &[Parameter; PARAMETER_COUNT], target: &[i64; PARAMETER_COUNT]) -> i64 {
+    space
+        .iter()
+        .zip(target)
+        .map(|(parameter, &value)| {
+            let distance = parameter.distance(value);
+            distance * distance
+        })
+        .sum()
+}
+
+/// Vanilla's `Climate.RTree`, which speeds up the nearest region search with a tree of bounding
+/// boxes. The tree is built exactly like in vanilla, since ties between equally close regions are
+/// broken by the search order.
+#[derive(Debug)]
+struct RTree {
+    root: RTreeNode,
+    spaces: Vec<[Parameter; PARAMETER_COUNT]>,
+    /// The index of the previous result, which vanilla uses as the starting point of the next
+    /// search. Vanilla keeps one per thread, this is shared between threads.
+    last_result: AtomicUsize,
+}
+
+#[derive(Debug)]
+struct RTreeNode {
+    space: [Parameter; PARAMETER_COUNT],
+    kind: RTreeNodeKind,
+}
+
+#[derive(Debug)]
+enum RTreeNodeKind {
+    Leaf(usize),
+    SubTree(Vec<RTreeNode>),
+}
+
+const CHILDREN_PER_NODE: usize = 6;
+
+impl RTree {
+    fn new<T>(values: &[(ParameterPoint, T)]) -> Self {
+        assert!(
+            !values.is_empty(),
+            "need at least one value to build the search tree"
+        );
+        let spaces: Vec<_> = values
+            .iter()
+            .map(|(point, _)| point.parameter_space())
+            .collect();
+        let leaves = spaces
+            .iter()
+            .enumerate()
+            .map(|(index, &space)| RTreeNode {
+                space,
+                kind: RTreeNodeKind::Leaf(index),
+            })
+            .collect();
+        RTree {
+            root: RTreeNode::build(leaves),
+            spaces,
+            last_result: AtomicUsize::new(usize::MAX),
+        }
+    }
+
+    fn search(&self, target: TargetPoint) -> usize {
+        let target = target.to_parameter_array();
+        let last_result = self.last_result.load(Ordering::Relaxed);
+        let last_result = (last_result != usize::MAX).then_some(last_result);
+        let result = self.root.search(&target, last_result, &self.spaces);
+        self.last_result.store(result, Ordering::Relaxed);
+        result
+    }
+}
+
+impl RTreeNode {
+    fn sub_tree(children: Vec<RTreeNode>) -> Self {
+        let space = children
+            .iter()
+            .map(|child| child.space)
+            .reduce(|space, child_space| {
+                std::array::from_fn(|index| space[index].union(child_space[index]))
+            })
+            .expect("sub tree needs at least one child");
+        RTreeNode {
+            space,
+            kind: RTreeNodeKind::SubTree(children),
+        }
+    }
+
+    fn build(mut nodes: Vec<RTreeNode>) -> Self {
+        assert!(!nodes.is_empty(), "need at least one child to build a node");
+        if nodes.len() == 1 {
+            return nodes.pop().unwrap();
+        }
+        if nodes.len() <= CHILDREN_PER_NODE {
+            nodes.sort_by_key(|node| {
+                node.space
+                    .iter()
+                    .map(|parameter| ((parameter.min + parameter.max) / 2).abs())
+                    .sum::<i64>()
+            });
+            return RTreeNode::sub_tree(nodes);
+        }
+
+        // vanilla sorts the same list in every dimension, so ties are kept in the order of the
+        // previous sort
+        let mut order: Vec<usize> = (0..nodes.len()).collect();
+        let mut best_cost = i64::MAX;
+        let mut best = (0, Vec::new());
+        for dimension in 0..PARAMETER_COUNT {
+            Self::sort(&mut order, |&index| nodes[index].space, dimension, false);
+            let cost = Self::bucket_sizes(nodes.len())
+                .scan(0, |start, size| {
+                    let bucket = &order[*start..*start + size];
+                    *start += size;
+                    Some(Self::cost(bucket.iter().map(|&index| &nodes[index])))
+                })
+                .sum();
+            if cost < best_cost {
+                best_cost = cost;
+                best = (dimension, order.clone());
+            }
+        }
+
+        let (best_dimension, best_order) = best;
+        let mut nodes: Vec<_> = nodes.into_iter().map(Some).collect();
+        let mut best_order = best_order.into_iter();
+        let mut buckets: Vec<_> = Self::bucket_sizes(nodes.len())
+            .map(|size| {
+                RTreeNode::sub_tree(
+                    best_order
+                        .by_ref()
+                        .take(size)
+                        .map(|index| nodes[index].take().unwrap())
+                        .collect(),
+                )
+            })
+            .collect();
+        Self::sort(&mut buckets, |bucket| bucket.space, best_dimension, true);
+        RTreeNode::sub_tree(
+            buckets
+                .into_iter()
+                .map(|bucket| match bucket.kind {
+                    RTreeNodeKind::SubTree(children) => RTreeNode::build(children),
+                    RTreeNodeKind::Leaf(_) => unreachable!(),
+                })
+                .collect(),
+        )
+    }
+
+    /// Sorts by the middle of each dimension, starting at `dimension`.
+    fn sort<T>(
+        items: &mut [T],
+        space: impl Fn(&T) -> [Parameter; PARAMETER_COUNT],
+        dimension: usize,
+        absolute: bool,
+    ) {
+        let key = |item: &T, dimension: usize| {
+            let parameter = space(item)[dimension % PARAMETER_COUNT];
+            let middle = (parameter.min + parameter.max) / 2;
+            if absolute {
+                middle.abs()
+            } else {
+                middle
+            }
+        };
+        items.sort_by(|a, b| {
+            (dimension..dimension + PARAMETER_COUNT)
+                .map(|dimension| key(a, dimension).cmp(&key(b, dimension)))
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+    }
+    /// The sizes of the buckets `len` nodes are split into, equivalent to vanilla's `bucketize`.
+    fn bucket_sizes(len: usize) -> impl Iterator<Item = usize> {
+        let bucket_size = (CHILDREN_PER_NODE as f64)
+            .powf((((len as f64) - 0.01).ln() / (CHILDREN_PER_NODE as f64).ln()).floor())
+            as usize;
+        (0..len)
+            .step_by(bucket_size)
+            .map(move |start| bucket_size.min(len - start))
+    }
+
+    fn cost<'n>(nodes: impl Iterator<Item = &'n RTreeNode> + Clone) -> i64 {
+        (0..PARAMETER_COUNT)
+            .map(|dimension| {
+                let min = nodes.clone().map(|node| node.space[dimension].min).min();
+                let max = nodes.clone().map(|node| node.space[dimension].max).max();
+                (max.unwrap() - min.unwrap()).abs()
+            })
+            .sum()
+    }
+
+    fn search(
+        &self,
+        target: &[i64; PARAMETER_COUNT],
+        best: Option<usize>,
+        spaces: &[[Parameter; PARAMETER_COUNT]],
+    ) -> usize {
+        let children = match &self.kind {
+            RTreeNodeKind::Leaf(index) => return *index,
+            RTreeNodeKind::SubTree(children) => children,
+        };
+        let mut best_distance = best.map_or(i64::MAX, |best| distance(&spaces[best], target));
+        let mut best = best;
+        for child in children {
+            let child_distance = distance(&child.space, target);
+            if best_distance <= child_distance {
+                continue;
+            }
+            let leaf = child.search(target, best, spaces);
+            let leaf_distance = match child.kind {
+                RTreeNodeKind::Leaf(index) if index == leaf => child_distance,
+                _ => distance(&spaces[leaf], target),
+            };
+            if best_distance <= leaf_distance {
+                continue;
+            }
+            best_distance = leaf_distance;
+            best = Some(leaf);
+        }
+        // the root always has at least one child closer than i64::MAX
+        best.expect("search found no leaf")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::biomes::climate::{Parameter, ParameterList, ParameterPoint, TargetPoint};
+
+    #[test]
+    fn test_quantize() {
+        assert_eq!(
+            Parameter::span(-0.45, 0.2),
+            Parameter {
+                min: -4500,
+                max: 2000
+            }
+        );
+        assert_eq!(Parameter::point(1.0).distance(12000), 2000);
+        assert_eq!(Parameter::span(-1.0, 1.0).distance(5000), 0);
+    }
+
+    #[test]
+    fn test_search_matches_brute_force() {
+        // a deterministic spread of overlapping regions, enough to need a few tree levels
+        let mut seed = 1u64;
+        let mut next = move || {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((seed >> 33) % 4001) as f32 / 1000.0 - 2.0
+        };
+        let mut parameter = || {
+            let (a, b) = (next(), next());
+            Parameter::span(a.min(b), a.max(b))
+        };
+        let values = (0..300)
+            .map(|index| {
+                let point = ParameterPoint {
+                    temperature: parameter(),
+                    humidity: parameter(),
+                    continentalness: parameter(),
+                    erosion: parameter(),
+                    depth: parameter(),
+                    weirdness: parameter(),
+                    offset: index % 3,
+                };
+                (point, index)
+            })
+            .collect();
+        let list = ParameterList::new(values);
+        for _ in 0..500 {
+            let target = TargetPoint::new(next(), next(), next(), next(), next(), next());
+            let value = *list.find_value(target);
+            let expected = *list.find_value_brute_force(target);
+            let distance = |index: i64| {
+                let point = &list.values()[index as usize].0;
+                super::distance(&point.parameter_space(), &target.to_parameter_array())
+            };
+            // ties may be broken differently, but the distance must be the same
+            assert_eq!(distance(value), distance(expected));
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use crate::biomes::climate::ClimateSampler;
+use crate::biomes::{BiomeHolder, BiomeSourceImpl, QuartPos};
+use datapack::data::biome::Biome;
+use datapack::data::biome_source::FixedBiomeSource;
+use datapack::data::holder::Holder;
+
+/// Picks the same biome everywhere. Also used by flat and debug chunk generators.
+#[derive(Debug)]
+pub struct FixedBiomeSourceImpl<'a> {
+    biome: BiomeHolder<'a>,
+}
+
+impl<'a> FixedBiomeSourceImpl<'a> {
+    pub fn new(source: &'a FixedBiomeSource) -> Self {
+        Self::from_biome(&source.biome)
+    }
+
+    pub fn from_biome(biome: &'a Holder<Biome>) -> Self {
+        FixedBiomeSourceImpl {
+            biome: BiomeHolder::Borrowed(biome),
+        }
+    }
+
+    pub(crate) fn from_holder(biome: BiomeHolder<'a>) -> Self {
+        FixedBiomeSourceImpl { biome }
+    }
+}
+
+impl BiomeSourceImpl for FixedBiomeSourceImpl<'_> {
+    fn get_noise_biome(
+        &self,
+        _pos: QuartPos,
+        _climate_sampler: &mut ClimateSampler,
+    ) -> &Holder<Biome> {
+        &self.biome
+    }
+
+    fn possible_biomes(&self) -> Vec<&Holder<Biome>> {
+        vec![&self.biome]
+    }
+}
@@ -1,3 +1,147 @@
-trait BiomeSourceImpl {
-    fn get_noise_biome(pos: QuartPos, climate_sampler: &ClimateSampler);
+pub mod biome_manager;
+pub mod checkerboard;
+pub mod climate;
+pub mod fixed;
+pub mod multi_noise;
+pub mod presets;
+pub mod temperature;
+pub mod the_end;
+
+use crate::biomes::checkerboard::CheckerboardColumnBiomeSourceImpl;
+use crate::biomes::climate::ClimateSampler;
+use crate::biomes::fixed::FixedBiomeSourceImpl;
+use crate::biomes::multi_noise::MultiNoiseBiomeSourceImpl;
+use crate::biomes::the_end::TheEndBiomeSourceImpl;
+use datapack::data::biome::Biome;
+use datapack::data::biome_source::BiomeSource;
+use datapack::data::holder::Holder;
+use datapack::data::world_preset::ChunkGenerator;
+use datapack::{DataPack, DataPackResult};
+use glam::IVec3;
+use std::ops::Deref;
+use util::identifier::IdentifierBuf;
+
+/// A position in quarts, the 4 block resolution biomes are stored at.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct QuartPos(pub IVec3);
+
+impl QuartPos {
+    #[inline]
+    pub fn new(x: i32, y: i32, z: i32) -> Self {
+        QuartPos(IVec3::new(x, y, z))
+    }
+
+    #[inline]
+    pub fn from_block(pos: IVec3) -> Self {
+        QuartPos(pos >> 2)
+    }
+
+    #[inline]
+    pub fn to_block(self) -> IVec3 {
+        self.0 << 2
+    }
+}
+
+pub trait BiomeSourceImpl {
+    fn get_noise_biome(
+        &self,
+        pos: QuartPos,
+        climate_sampler: &mut ClimateSampler,
+    ) -> &Holder<Biome>;
+
+    /// Every biome this source can return, without duplicates and in the order of vanilla's
+    /// `BiomeSource.possibleBiomes`.
+    fn possible_biomes(&self) -> Vec<&Holder<Biome>>;
+}
+
+/// The runtime implementation of any [`BiomeSource`].
+#[derive(Debug)]
+pub enum AnyBiomeSource<'a> {
+    Fixed(FixedBiomeSourceImpl<'a>),
+    MultiNoise(MultiNoiseBiomeSourceImpl<'a>),
+    Checkerboard(CheckerboardColumnBiomeSourceImpl<'a>),
+    TheEnd(TheEndBiomeSourceImpl),
+}
+
+impl<'a> AnyBiomeSource<'a> {
+    pub fn new(datapack: &'a DataPack, source: &'a BiomeSource) -> DataPackResult<Self> {
+        Ok(match source {
+            BiomeSource::Fixed(source) => AnyBiomeSource::Fixed(FixedBiomeSourceImpl::new(source)),
+            BiomeSource::MultiNoise(source) => {
+                AnyBiomeSource::MultiNoise(MultiNoiseBiomeSourceImpl::new(datapack, source)?)
+            }
+            BiomeSource::Checkerboard(source) => AnyBiomeSource::Checkerboard(
+                CheckerboardColumnBiomeSourceImpl::new(datapack, source)?,
+            ),
+            BiomeSource::TheEnd(_) => AnyBiomeSource::TheEnd(TheEndBiomeSourceImpl::new()),
+        })
+    }
+
+    /// Returns the biome source of a chunk generator. Flat generators use their configured biome,
+    /// and the debug generator is all plains.
+    pub fn for_generator(
+        datapack: &'a DataPack,
+        generator: &'a ChunkGenerator,
+    ) -> DataPackResult<Self> {
+        match generator {
+            ChunkGenerator::Noise(generator) => Self::new(datapack, &generator.biome_source),
+            ChunkGenerator::Flat(generator) => Ok(AnyBiomeSource::Fixed(
+                FixedBiomeSourceImpl::from_biome(&generator.settings.biome),
+            )),
+            ChunkGenerator::Debug(_) => {
+                Ok(AnyBiomeSource::Fixed(FixedBiomeSourceImpl::from_holder(
+                    BiomeHolder::reference(IdentifierBuf::new("minecraft:plains").unwrap()),
+                )))
+            }
+        }
+    }
+}
+
+impl BiomeSourceImpl for AnyBiomeSource<'_> {
+    fn get_noise_biome(
+        &self,
+        pos: QuartPos,
+        climate_sampler: &mut ClimateSampler,
+    ) -> &Holder<Biome> {
+        match self {
+            AnyBiomeSource::Fixed(source) => source.get_noise_biome(pos, climate_sampler),
+            AnyBiomeSource::MultiNoise(source) => source.get_noise_biome(pos, climate_sampler),
+            AnyBiomeSource::Checkerboard(source) => source.get_noise_biome(pos, climate_sampler),
+            AnyBiomeSource::TheEnd(source) => source.get_noise_biome(pos, climate_sampler),
+        }
+    }
+
+    fn possible_biomes(&self) -> Vec<&Holder<Biome>> {
+        match self {
+            AnyBiomeSource::Fixed(source) => source.possible_biomes(),
+            AnyBiomeSource::MultiNoise(source) => source.possible_biomes(),
+            AnyBiomeSource::Checkerboard(source) => source.possible_biomes(),
+            AnyBiomeSource::TheEnd(source) => source.possible_biomes(),
+        }
+    }
+}
+
+/// A biome holder that is either borrowed from the datapack, or created at runtime, e.g. when
+/// resolving a tag.
+#[derive(Debug)]
+pub(crate) enum BiomeHolder<'a> {
+    Borrowed(&'a Holder<Biome>),
+    Owned(Box<Holder<Biome>>),
+}
+
+impl BiomeHolder<'_> {
+    pub(crate) fn reference(id: IdentifierBuf) -> Self {
+        BiomeHolder::Owned(Box::new(Holder::Reference(id)))
+    }
+}
+
+impl Deref for BiomeHolder<'_> {
+    type Target = Holder<Biome>;
+
+    fn deref(&self) -> &Holder<Biome> {
+        match self {
+            BiomeHolder::Borrowed(holder) => holder,
+            BiomeHolder::Owned(holder) => holder,
+        }
+    }
 }
@@ -0,0 +1,142 @@
+use crate::biomes::climate::{ClimateSampler, ParameterList, ParameterPoint};
+use crate::biomes::{presets, BiomeSourceImpl, QuartPos};
+use ahash::AHashSet;
+use datapack::data::biome::Biome;
+use datapack::data::biome_source::MultiNoiseBiomeSource;
+use datapack::data::holder::Holder;
+use datapack::{DataPack, DataPackError, DataPackResult};
+
+/// Picks the biome whose climate region is closest to the climate at each position.
+#[derive(Debug)]
+pub struct MultiNoiseBiomeSourceImpl<'a> {
+    parameters: ParameterList<&'a Holder<Biome>>,
+}
+
+impl<'a> MultiNoiseBiomeSourceImpl<'a> {
+    pub fn new(datapack: &'a DataPack, source: &'a MultiNoiseBiomeSource) -> DataPackResult<Self> {
+        match source {
+            MultiNoiseBiomeSource::Direct(entries) => {
+                if entries.is_empty() {
+                    return Err(DataPackError::EmptyBiomeSource);
+                }
+                Ok(MultiNoiseBiomeSourceImpl {
+                    parameters: ParameterList::new(
+                        entries
+                            .iter()
+                            .map(|entry| {
+                                (ParameterPoint::from_point(&entry.parameters), &entry.biome)
+                            })
+                            .collect(),
+                    ),
+                })
+            }
+            MultiNoiseBiomeSource::Preset(preset) => {
+                let preset = &preset.resolve(datapack)?.preset;
+                let entries = presets::preset_entries(preset)
+                    .ok_or_else(|| DataPackError::UnknownMultiNoisePreset(preset.clone()))?;
+                Ok(MultiNoiseBiomeSourceImpl {
+                    parameters: ParameterList::new(
+                        entries
+                            .iter()
+                            .map(|(point, biome)| (*point, biome))
+                            .collect(),
+                    ),
+                })
+            }
+        }
+    }
+
+    #[inline]
+    pub fn parameters(&self) -> &ParameterList<&'a Holder<Biome>> {
+        &self.parameters
+    }
+}
+
+impl BiomeSourceImpl for MultiNoiseBiomeSourceImpl<'_> {
+    fn get_noise_biome(
+        &self,
+        pos: QuartPos,
+        climate_sampler: &mut ClimateSampler,
+    ) -> &Holder<Biome> {
+        self.parameters.find_value(climate_sampler.sample(pos))
+    }
+
+    fn possible_biomes(&self) -> Vec<&Holder<Biome>> {
+        let mut added_biomes = AHashSet::new();
+        let mut biomes = Vec::new();
+        for &(_, biome) in self.parameters.values() {
+            // direct biomes are never equal to each other, like in vanilla
+            let is_new = match biome {
+                Holder::Reference(id) => added_biomes.insert(&**id),
+                Holder::Direct(_) => true,
+            };
+            if is_new {
+                biomes.push(biome);
+            }
+        }
+        biomes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::biomes::multi_noise::MultiNoiseBiomeSourceImpl;
+    use crate::biomes::{BiomeSourceImpl, QuartPos};
+    use crate::test_util::{empty_datapack, with_climate_sampler, zero_router};
+    use datapack::data::biome_source::MultiNoiseBiomeSource;
+    use datapack::data::holder::Holder;
+    use datapack::data::noise::NoiseRouter;
+
+    #[test]
+    fn test_multi_noise() {
+        // temperature rises with y, everything else is zero
+        let router = NoiseRouter {
+            temperature: serde_json::from_str(
+                r#"{
+                    "type": "minecraft:mul",
+                    "argument1": 0.01,
+                    "argument2": { "type": "minecraft:y_clamped_gradient", "from_y": -100, "to_y": 100, "from_value": -100, "to_value": 100 }
+                }"#,
+            )
+            .unwrap(),
+            ..zero_router()
+        };
+        let source: MultiNoiseBiomeSource = serde_json::from_str(
+            r#"{
+                "biomes": [
+                    {
+                        "biome": "minecraft:snowy_plains",
+                        "parameters": { "temperature": [-1, -0.2], "humidity": 0, "continentalness": 0, "erosion": 0, "depth": 0, "weirdness": 0, "offset": 0 }
+                    },
+                    {
+                        "biome": "minecraft:plains",
+                        "parameters": { "temperature": [-0.2, 0.5], "humidity": 0, "continentalness": 0, "erosion": 0, "depth": 0, "weirdness": 0, "offset": 0 }
+                    },
+                    {
+                        "biome": "minecraft:desert",
+                        "parameters": { "temperature": [0.5, 1], "humidity": 0, "continentalness": 0, "erosion": 0, "depth": 0, "weirdness": 0, "offset": 0 }
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let datapack = empty_datapack();
+        let biome_source = MultiNoiseBiomeSourceImpl::new(&datapack, &source).unwrap();
+        with_climate_sampler(&datapack, &router, |sampler| {
+            let mut biome_at = |y| {
+                let Holder::Reference(biome) =
+                    biome_source.get_noise_biome(QuartPos::new(0, y, 0), sampler)
+                else {
+                    unreachable!();
+                };
+                biome.to_string()
+            };
+            assert_eq!(biome_at(-25), "minecraft:snowy_plains");
+            assert_eq!(biome_at(0), "minecraft:plains");
+            assert_eq!(biome_at(15), "minecraft:desert");
+            // temperatures past the last region still pick the closest one
+            assert_eq!(biome_at(25), "minecraft:desert");
+        });
+    }
+}
@@ -0,0 +1,1056 @@
+//! The built-in parameter lists of `MultiNoiseBiomeSourceParameterList.Preset`.
+
+use crate::biomes::climate::{Parameter, ParameterPoint};
+use datapack::data::biome::Biome;
+use datapack::data::holder::Holder;
+use std::sync::OnceLock;
+use util::identifier::{Identifier, IdentifierBuf};
+
+pub type PresetEntries = Vec<(ParameterPoint, Holder<Biome>)>;
+
+type Consumer<'c> = &'c mut dyn FnMut(ParameterPoint, &'static str);
+
+/// Returns the biomes of a built-in multi noise preset, or `None` if there is no such preset.
+pub fn preset_entries(preset: &Identifier) -> Option<&'static PresetEntries> {
+    static NETHER: OnceLock<PresetEntries> = OnceLock::new();
+    static OVERWORLD: OnceLock<PresetEntries> = OnceLock::new();
+
+    if preset.namespace() != "minecraft" {
+        return None;
+    }
+    let (entries, init): (_, fn(Consumer)) = match preset.path() {
+        "nether" => (&NETHER, add_nether_biomes),
+        "overworld" => (&OVERWORLD, |consumer| {
+            OverworldBiomeBuilder::new().add_biomes(consumer)
+        }),
+        _ => return None,
+    };
+    Some(entries.get_or_init(|| {
+        let mut entries = Vec::new();
+        init(&mut |point, biome| {
+            let biome = IdentifierBuf::new(format!("minecraft:{biome}")).unwrap();
+            entries.push((point, Holder::Reference(biome)));
+        });
+        entries
+    }))
+}
+
+/// Equivalent of `Climate.parameters`.
+fn parameters(
+    temperature: Parameter,
+    humidity: Parameter,
+    continentalness: Parameter,
+    erosion: Parameter,
+    depth: Parameter,
+    weirdness: Parameter,
+    offset: f32,
+) -> ParameterPoint {
+    ParameterPoint {
+        temperature,
+        humidity,
+        continentalness,
+        erosion,
+        depth,
+        weirdness,
+        offset: Parameter::point(offset).min,
+    }
+}
+
+/// Equivalent of `Climate.Parameter.span(Parameter, Parameter)`, which takes the minimum of the
+/// first parameter and the maximum of the second.
+fn span(from: Parameter, to: Parameter) -> Parameter {
+    assert!(from.min <= to.max, "parameters are out of order");
+    Parameter {
+        min: from.min,
+        max: to.max,
+    }
+}
+
+fn add_nether_biomes(consumer: Consumer) {
+    let point = Parameter::point;
+    let mut add = |temperature, humidity, offset, biome| {
+        let zero = point(0.0);
+        consumer(
+            parameters(
+                point(temperature),
+                point(humidity),
+                zero,
+                zero,
+                zero,
+                zero,
+                offset,
+            ),
+            biome,
+        );
+    };
+    add(0.0, 0.0, 0.0, "nether_wastes");
+    add(0.0, -0.5, 0.0, "soul_sand_valley");
+    add(0.4, 0.0, 0.0, "crimson_forest");
+    add(0.0, 0.5, 0.375, "warped_forest");
+    add(-0.5, 0.0, 0.175, "basalt_deltas");
+}
+
+type BiomeTable = [[Option<&'static str>; 5]; 5];
+
+const OCEANS: [[&str; 5]; 2] = [
+    [
+        "deep_frozen_ocean",
+        "deep_cold_ocean",
+        "deep_ocean",
+        "deep_lukewarm_ocean",
+        "warm_ocean",
+    ],
+    [
+        "frozen_ocean",
+        "cold_ocean",
+        "ocean",
+        "lukewarm_ocean",
+        "warm_ocean",
+    ],
+];
+
+const MIDDLE_BIOMES: BiomeTable = [
+    [
+        Some("snowy_plains"),
+        Some("snowy_plains"),
+        Some("snowy_plains"),
+        Some("snowy_taiga"),
+        Some("taiga"),
+    ],
+    [
+        Some("plains"),
+        Some("plains"),
+        Some("forest"),
+        Some("taiga"),
+        Some("old_growth_spruce_taiga"),
+    ],
+    [
+        Some("flower_forest"),
+        Some("plains"),
+        Some("forest"),
+        Some("birch_forest"),
+        Some("dark_forest"),
+    ],
+    [
+        Some("savanna"),
+        Some("savanna"),
+        Some("forest"),
+        Some("jungle"),
+        Some("jungle"),
+    ],
+    [
+        Some("desert"),
+        Some("desert"),
+        Some("desert"),
+        Some("desert"),
+        Some("desert"),
+    ],
+];
+
+const MIDDLE_BIOMES_VARIANT: BiomeTable = [
+    [Some("ice_spikes"), None, Some("snowy_taiga"), None, None],
+    [None, None, None, None, Some("old_growth_pine_taiga")],
+    [
+        Some("sunflower_plains"),
+        None,
+        None,
+        Some("old_growth_birch_forest"),
+        None,
+    ],
+    [
+        None,
+        None,
+        Some("plains"),
+        Some("sparse_jungle"),
+        Some("bamboo_jungle"),
+    ],
+    [None, None, None, None, None],
+];
+
+const PLATEAU_BIOMES: BiomeTable = [
+    [
+        Some("snowy_plains"),
+        Some("snowy_plains"),
+        Some("snowy_plains"),
+        Some("snowy_taiga"),
+        Some("snowy_taiga"),
+    ],
+    [
+        Some("meadow"),
+        Some("meadow"),
+        Some("forest"),
+        Some("taiga"),
+        Some("old_growth_spruce_taiga"),
+    ],
+    [
+        Some("meadow"),
+        Some("meadow"),
+        Some("meadow"),
+        Some("meadow"),
+        Some("dark_forest"),
+    ],
+    [
+        Some("savanna_plateau"),
+        Some("savanna_plateau"),
+        Some("forest"),
+        Some("forest"),
+        Some("jungle"),
+    ],
+    [
+        Some("badlands"),
+        Some("badlands"),
+        Some("badlands"),
+        Some("wooded_badlands"),
+        Some("wooded_badlands"),
+    ],
+];
+
+const PLATEAU_BIOMES_VARIANT: BiomeTable = [
+    [Some("ice_spikes"), None, None, None, None],
+    [
+        Some("cherry_grove"),
+        None,
+        Some("meadow"),
+        Some("meadow"),
+        Some("old_growth_pine_taiga"),
+    ],
+    [
+        Some("cherry_grove"),
+        Some("cherry_grove"),
+        Some("forest"),
+        Some("birch_forest"),
+        None,
+    ],
+    [None, None, None, None, None],
+    [
+        Some("eroded_badlands"),
+        Some("eroded_badlands"),
+        None,
+        None,
+        None,
+    ],
+];
+
+const SHATTERED_BIOMES: BiomeTable = [
+    [
+        Some("windswept_gravelly_hills"),
+        Some("windswept_gravelly_hills"),
+        Some("windswept_hills"),
+        Some("windswept_forest"),
+        Some("windswept_forest"),
+    ],
+    [
+        Some("windswept_gravelly_hills"),
+        Some("windswept_gravelly_hills"),
+        Some("windswept_hills"),
+        Some("windswept_forest"),
+        Some("windswept_forest"),
+    ],
+    [
+        Some("windswept_hills"),
+        Some("windswept_hills"),
+        Some("windswept_hills"),
+        Some("windswept_forest"),
+        Some("windswept_forest"),
+    ],
+    [None, None, None, None, None],
+    [None, None, None, None, None],
+];
+
+/// A port of vanilla's `OverworldBiomeBuilder`. The order biomes are added in matters, since it
+/// affects how the search tree is built.
+struct OverworldBiomeBuilder {
+    full_range: Parameter,
+    temperatures: [Parameter; 5],
+    humidities: [Parameter; 5],
+    erosions: [Parameter; 7],
+    frozen_range: Parameter,
+    unfrozen_range: Parameter,
+    mushroom_fields_continentalness: Parameter,
+    deep_ocean_continentalness: Parameter,
+    ocean_continentalness: Parameter,
+    coast_continentalness: Parameter,
+    inland_continentalness: Parameter,
+    near_inland_continentalness: Parameter,
+    mid_inland_continentalness: Parameter,
+    far_inland_continentalness: Parameter,
+}
+
+impl OverworldBiomeBuilder {
+    fn new() -> Self {
+        let span = Parameter::span;
+        let temperatures = [
+            span(-1.0, -0.45),
+            span(-0.45, -0.15),
+            span(-0.15, 0.2),
+            span(0.2, 0.55),
+            span(0.55, 1.0),
+        ];
+        OverworldBiomeBuilder {
+            full_range: span(-1.0, 1.0),
+            temperatures,
+            humidities: [
+                span(-1.0, -0.35),
+                span(-0.35, -0.1),
+                span(-0.1, 0.1),
+                span(0.1, 0.3),
+                span(0.3, 1.0),
+            ],
+            erosions: [
+                span(-1.0, -0.78),
+                span(-0.78, -0.375),
+                span(-0.375, -0.2225),
+                span(-0.2225, 0.05),
+                span(0.05, 0.45),
+                span(0.45, 0.55),
+                span(0.55, 1.0),
+            ],
+            frozen_range: temperatures[0],
+            unfrozen_range: self::span(temperatures[1], temperatures[4]),
+            mushroom_fields_continentalness: span(-1.2, -1.05),
+            deep_ocean_continentalness: span(-1.05, -0.455),
+            ocean_continentalness: span(-0.455, -0.19),
+            coast_continentalness: span(-0.19, -0.11),
+            inland_continentalness: span(-0.11, 0.55),
+            near_inland_continentalness: span(-0.11, 0.03),
+            mid_inland_continentalness: span(0.03, 0.3),
+            far_inland_continentalness: span(0.3, 1.0),
+        }
+    }
+
+    fn add_biomes(&self, consumer: Consumer) {
+        self.add_off_coast_biomes(consumer);
+        self.add_inland_biomes(consumer);
+        self.add_underground_biomes(consumer);
+    }
+
+    fn add_off_coast_biomes(&self, consumer: Consumer) {
+        self.add_surface_biome(
+            consumer,
+            self.full_range,
+            self.full_range,
+            self.mushroom_fields_continentalness,
+            self.full_range,
+            self.full_range,
+            0.0,
+            "mushroom_fields",
+        );
+        for (i, &temperature) in self.temperatures.iter().enumerate() {
+            self.add_surface_biome(
+                consumer,
+                temperature,
+                self.full_range,
+                self.deep_ocean_continentalness,
+                self.full_range,
+                self.full_range,
+                0.0,
+                OCEANS[0][i],
+            );
+            self.add_surface_biome(
+                consumer,
+                temperature,
+                self.full_range,
+                self.ocean_continentalness,
+                self.full_range,
+                self.full_range,
+                0.0,
+                OCEANS[1][i],
+            );
+        }
+    }
+
+    fn add_inland_biomes(&self, consumer: Consumer) {
+        let span = Parameter::span;
+        self.add_mid_slice(consumer, span(-1.0, -0.93333334));
+        self.add_high_slice(consumer, span(-0.93333334, -0.7666667));
+        self.add_peaks(consumer, span(-0.7666667, -0.56666666));
+        self.add_high_slice(consumer, span(-0.56666666, -0.4));
+        self.add_mid_slice(consumer, span(-0.4, -0.26666668));
+        self.add_low_slice(consumer, span(-0.26666668, -0.05));
+        self.add_valleys(consumer, span(-0.05, 0.05));
+        self.add_low_slice(consumer, span(0.05, 0.26666668));
+        self.add_mid_slice(consumer, span(0.26666668, 0.4));
+        self.add_high_slice(consumer, span(0.4, 0.56666666));
+        self.add_peaks(consumer, span(0.56666666, 0.7666667));
+        self.add_high_slice(consumer, span(0.7666667, 0.93333334));
+        self.add_mid_slice(consumer, span(0.93333334, 1.0));
+    }
+
+    fn add_peaks(&self, consumer: Consumer, weirdness: Parameter) {
+        let e = &self.erosions;
+        let coast_to_far = span(self.coast_continentalness, self.far_inland_continentalness);
+        let coast_to_near = span(self.coast_continentalness, self.near_inland_continentalness);
+        let mid_to_far = span(
+            self.mid_inland_continentalness,
+            self.far_inland_continentalness,
+        );
+        for (i, &temperature) in self.temperatures.iter().enumerate() {
+            for (j, &humidity) in self.humidities.iter().enumerate() {
+                let middle = self.pick_middle_biome(i, j, weirdness);
+                let middle_or_badlands = self.pick_middle_biome_or_badlands_if_hot(i, j, weirdness);
+                let middle_or_badlands_or_slope =
+                    self.pick_middle_biome_or_badlands_if_hot_or_slope_if_cold(i, j, weirdness);
+                let plateau = self.pick_plateau_biome(i, j, weirdness);
+                let shattered = self.pick_shattered_biome(i, j, weirdness);
+                let shattered_or_savanna =
+                    self.maybe_pick_windswept_savanna_biome(i, j, weirdness, shattered);
+                let peak = self.pick_peak_biome(i, j, weirdness);
+                let mut add = |continentalness, erosion, biome| {
+                    self.add_surface_biome(
+                        consumer,
+                        temperature,
+                        humidity,
+                        continentalness,
+                        erosion,
+                        weirdness,
+                        0.0,
+                        biome,
+                    )
+                };
+                add(coast_to_far, e[0], peak);
+                add(coast_to_near, e[1], middle_or_badlands_or_slope);
+                add(mid_to_far, e[1], peak);
+                add(coast_to_near, span(e[2], e[3]), middle);
+                add(mid_to_far, e[2], plateau);
+                add(self.mid_inland_continentalness, e[3], middle_or_badlands);
+                add(self.far_inland_continentalness, e[3], plateau);
+                add(coast_to_far, e[4], middle);
+                add(coast_to_near, e[5], shattered_or_savanna);
+                add(mid_to_far, e[5], shattered);
+                add(coast_to_far, e[6], middle);
+            }
+        }
+    }
+
+    fn add_high_slice(&self, consumer: Consumer, weirdness: Parameter) {
+        let e = &self.erosions;
+        let coast_to_far = span(self.coast_continentalness, self.far_inland_continentalness);
+        let coast_to_near = span(self.coast_continentalness, self.near_inland_continentalness);
+        let mid_to_far = span(
+            self.mid_inland_continentalness,
+            self.far_inland_continentalness,
+        );
+        for (i, &temperature) in self.temperatures.iter().enumerate() {
+            for (j, &humidity) in self.humidities.iter().enumerate() {
+                let middle = self.pick_middle_biome(i, j, weirdness);
+                let middle_or_badlands = self.pick_middle_biome_or_badlands_if_hot(i, j, weirdness);
+                let middle_or_badlands_or_slope =
+                    self.pick_middle_biome_or_badlands_if_hot_or_slope_if_cold(i, j, weirdness);
+                let plateau = self.pick_plateau_biome(i, j, weirdness);
+                let shattered = self.pick_shattered_biome(i, j, weirdness);
+                let middle_or_savanna =
+                    self.maybe_pick_windswept_savanna_biome(i, j, weirdness, middle);
+                let slope = self.pick_slope_biome(i, j, weirdness);
+                let peak = self.pick_peak_biome(i, j, weirdness);
+                let mut add = |continentalness, erosion, biome| {
+                    self.add_surface_biome(
+                        consumer,
+                        temperature,
+                        humidity,
+                        continentalness,
+                        erosion,
+                        weirdness,
+                        0.0,
+                        biome,
+                    )
+                };
+                add(self.coast_continentalness, span(e[0], e[1]), middle);
+                add(self.near_inland_continentalness, e[0], slope);
+                add(mid_to_far, e[0], peak);
+                add(
+                    self.near_inland_continentalness,
+                    e[1],
+                    middle_or_badlands_or_slope,
+                );
+                add(mid_to_far, e[1], slope);
+                add(coast_to_near, span(e[2], e[3]), middle);
+                add(mid_to_far, e[2], plateau);
+                add(self.mid_inland_continentalness, e[3], middle_or_badlands);
+                add(self.far_inland_continentalness, e[3], plateau);
+                add(coast_to_far, e[4], middle);
+                add(coast_to_near, e[5], middle_or_savanna);
+                add(mid_to_far, e[5], shattered);
+                add(coast_to_far, e[6], middle);
+            }
+        }
+    }
+
+    fn add_mid_slice(&self, consumer: Consumer, weirdness: Parameter) {
+        let e = &self.erosions;
+        let t = &self.temperatures;
+        let coast_to_far = span(self.coast_continentalness, self.far_inland_continentalness);
+        let coast_to_near = span(self.coast_continentalness, self.near_inland_continentalness);
+        let near_to_far = span(
+            self.near_inland_continentalness,
+            self.far_inland_continentalness,
+        );
+        let near_to_mid = span(
+            self.near_inland_continentalness,
+            self.mid_inland_continentalness,
+        );
+        let mid_to_far = span(
+            self.mid_inland_continentalness,
+            self.far_inland_continentalness,
+        );
+        self.add_surface_biome(
+            consumer,
+            self.full_range,
+            self.full_range,
+            self.coast_continentalness,
+            span(e[0], e[2]),
+            weirdness,
+            0.0,
+            "stony_shore",
+        );
+        self.add_surface_biome(
+            consumer,
+            span(t[1], t[2]),
+            self.full_range,
+            near_to_far,
+            e[6],
+            weirdness,
+            0.0,
+            "swamp",
+        );
+        self.add_surface_biome(
+            consumer,
+            span(t[3], t[4]),
+            self.full_range,
+            near_to_far,
+            e[6],
+            weirdness,
+            0.0,
+            "mangrove_swamp",
+        );
+        for (i, &temperature) in self.temperatures.iter().enumerate() {
+            for (j, &humidity) in self.humidities.iter().enumerate() {
+                let middle = self.pick_middle_biome(i, j, weirdness);
+                let middle_or_badlands = self.pick_middle_biome_or_badlands_if_hot(i, j, weirdness);
+                let middle_or_badlands_or_slope =
+                    self.pick_middle_biome_or_badlands_if_hot_or_slope_if_cold(i, j, weirdness);
+                let shattered = self.pick_shattered_biome(i, j, weirdness);
+                let plateau = self.pick_plateau_biome(i, j, weirdness);
+                let beach = self.pick_beach_biome(i, j);
+                let middle_or_savanna =
+                    self.maybe_pick_windswept_savanna_biome(i, j, weirdness, middle);
+                let shattered_coast = self.pick_shattered_coast_biome(i, j, weirdness);
+                let slope = self.pick_slope_biome(i, j, weirdness);
+                let mut add = |continentalness, erosion, biome| {
+                    self.add_surface_biome(
+                        consumer,
+                        temperature,
+                        humidity,
+                        continentalness,
+                        erosion,
+                        weirdness,
+                        0.0,
+                        biome,
+                    )
+                };
+                add(near_to_far, e[0], slope);
+                add(near_to_mid, e[1], middle_or_badlands_or_slope);
+                add(
+                    self.far_inland_continentalness,
+                    e[1],
+                    if i == 0 { slope } else { plateau },
+                );
+                add(self.near_inland_continentalness, e[2], middle);
+                add(self.mid_inland_continentalness, e[2], middle_or_badlands);
+                add(self.far_inland_continentalness, e[2], plateau);
+                add(coast_to_near, e[3], middle);
+                add(mid_to_far, e[3], middle_or_badlands);
+                if weirdness.max < 0 {
+                    add(self.coast_continentalness, e[4], beach);
+                    add(near_to_far, e[4], middle);
+                } else {
+                    add(coast_to_far, e[4], middle);
+                }
+                add(self.coast_continentalness, e[5], shattered_coast);
+                add(self.near_inland_continentalness, e[5], middle_or_savanna);
+                add(mid_to_far, e[5], shattered);
+                if weirdness.max < 0 {
+                    add(self.coast_continentalness, e[6], beach);
+                } else {
+                    add(self.coast_continentalness, e[6], middle);
+                }
+                if i == 0 {
+                    add(near_to_far, e[6], middle);
+                }
+            }
+        }
+    }
+
+    fn add_low_slice(&self, consumer: Consumer, weirdness: Parameter) {
+        let e = &self.erosions;
+        let t = &self.temperatures;
+        let near_to_far = span(
+            self.near_inland_continentalness,
+            self.far_inland_continentalness,
+        );
+        let mid_to_far = span(
+            self.mid_inland_continentalness,
+            self.far_inland_continentalness,
+        );
+        self.add_surface_biome(
+            consumer,
+            self.full_range,
+            self.full_range,
+            self.coast_continentalness,
+            span(e[0], e[2]),
+            weirdness,
+            0.0,
+            "stony_shore",
+        );
+        self.add_surface_biome(
+            consumer,
+            span(t[1], t[2]),
+            self.full_range,
+            near_to_far,
+            e[6],
+            weirdness,
+            0.0,
+            "swamp",
+        );
+        self.add_surface_biome(
+            consumer,
+            span(t[3], t[4]),
+            self.full_range,
+            near_to_far,
+            e[6],
+            weirdness,
+            0.0,
+            "mangrove_swamp",
+        );
+        for (i, &temperature) in self.temperatures.iter().enumerate() {
+            for (j, &humidity) in self.humidities.iter().enumerate() {
+                let middle = self.pick_middle_biome(i, j, weirdness);
+                let middle_or_badlands = self.pick_middle_biome_or_badlands_if_hot(i, j, weirdness);
+                let middle_or_badlands_or_slope =
+                    self.pick_middle_biome_or_badlands_if_hot_or_slope_if_cold(i, j, weirdness);
+                let beach = self.pick_beach_biome(i, j);
+                let middle_or_savanna =
+                    self.maybe_pick_windswept_savanna_biome(i, j, weirdness, middle);
+                let shattered_coast = self.pick_shattered_coast_biome(i, j, weirdness);
+                let mut add = |continentalness, erosion, biome| {
+                    self.add_surface_biome(
+                        consumer,
+                        temperature,
+                        humidity,
+                        continentalness,
+                        erosion,
+                        weirdness,
+                        0.0,
+                        biome,
+                    )
+                };
+                add(
+                    self.near_inland_continentalness,
+                    span(e[0], e[1]),
+                    middle_or_badlands,
+                );
+                add(mid_to_far, span(e[0], e[1]), middle_or_badlands_or_slope);
+                add(self.near_inland_continentalness, span(e[2], e[3]), middle);
+                add(mid_to_far, span(e[2], e[3]), middle_or_badlands);
+                add(self.coast_continentalness, span(e[3], e[4]), beach);
+                add(near_to_far, e[4], middle);
+                add(self.coast_continentalness, e[5], shattered_coast);
+                add(self.near_inland_continentalness, e[5], middle_or_savanna);
+                add(mid_to_far, e[5], middle);
+                add(self.coast_continentalness, e[6], beach);
+                if i == 0 {
+                    add(near_to_far, e[6], middle);
+                }
+            }
+        }
+    }
+
+    fn add_valleys(&self, consumer: Consumer, weirdness: Parameter) {
+        let e = &self.erosions;
+        let t = &self.temperatures;
+        let coast_to_far = span(self.coast_continentalness, self.far_inland_continentalness);
+        let inland_to_far = span(self.inland_continentalness, self.far_inland_continentalness);
+        let mid_to_far = span(
+            self.mid_inland_continentalness,
+            self.far_inland_continentalness,
+        );
+        let mut add = |temperature, continentalness, erosion, biome| {
+            self.add_surface_biome(
+                consumer,
+                temperature,
+                self.full_range,
+                continentalness,
+                erosion,
+                weirdness,
+                0.0,
+                biome,
+            )
+        };
+        let coast_river = |river| {
+            if weirdness.max < 0 {
+                "stony_shore"
+            } else {
+                river
+            }
+        };
+        add(
+            self.frozen_range,
+            self.coast_continentalness,
+            span(e[0], e[1]),
+            coast_river("frozen_river"),
+        );
+        add(
+            self.unfrozen_range,
+            self.coast_continentalness,
+            span(e[0], e[1]),
+            coast_river("river"),
+        );
+        add(
+            self.frozen_range,
+            self.near_inland_continentalness,
+            span(e[0], e[1]),
+            "frozen_river",
+        );
+        add(
+            self.unfrozen_range,
+            self.near_inland_continentalness,
+            span(e[0], e[1]),
+            "river",
+        );
+        add(
+            self.frozen_range,
+            coast_to_far,
+            span(e[2], e[5]),
+            "frozen_river",
+        );
+        add(self.unfrozen_range, coast_to_far, span(e[2], e[5]), "river");
+        add(
+            self.frozen_range,
+            self.coast_continentalness,
+            e[6],
+            "frozen_river",
+        );
+        add(
+            self.unfrozen_range,
+            self.coast_continentalness,
+            e[6],
+            "river",
+        );
+        add(span(t[1], t[2]), inland_to_far, e[6], "swamp");
+        add(span(t[3], t[4]), inland_to_far, e[6], "mangrove_swamp");
+        add(self.frozen_range, inland_to_far, e[6], "frozen_river");
+        for (i, &temperature) in self.temperatures.iter().enumerate() {
+            for (j, &humidity) in self.humidities.iter().enumerate() {
+                let middle_or_badlands = self.pick_middle_biome_or_badlands_if_hot(i, j, weirdness);
+                self.add_surface_biome(
+                    consumer,
+                    temperature,
+                    humidity,
+                    mid_to_far,
+                    span(e[0], e[1]),
+                    weirdness,
+                    0.0,
+                    middle_or_badlands,
+                );
+            }
+        }
+    }
+
+    fn add_underground_biomes(&self, consumer: Consumer) {
+        let full = self.full_range;
+        let span = Parameter::span;
+        self.add_underground_biome(
+            consumer,
+            full,
+            full,
+            span(0.8, 1.0),
+            full,
+            full,
+            0.0,
+            "dripstone_caves",
+        );
+        self.add_underground_biome(
+            consumer,
+            full,
+            span(0.7, 1.0),
+            full,
+            full,
+            full,
+            0.0,
+            "lush_caves",
+        );
+        self.add_bottom_biome(
+            consumer,
+            full,
+            full,
+            full,
+            self::span(self.erosions[0], self.erosions[1]),
+            full,
+            0.0,
+            "deep_dark",
+        );
+    }
+
+    fn pick_middle_biome(&self, i: usize, j: usize, weirdness: Parameter) -> &'static str {
+        if weirdness.max < 0 {
+            MIDDLE_BIOMES[i][j].unwrap()
+        } else {
+            MIDDLE_BIOMES_VARIANT[i][j].or(MIDDLE_BIOMES[i][j]).unwrap()
+        }
+    }
+
+    fn pick_middle_biome_or_badlands_if_hot(
+        &self,
+        i: usize,
+        j: usize,
+        weirdness: Parameter,
+    ) -> &'static str {
+        if i == 4 {
+            self.pick_badlands_biome(j, weirdness)
+        } else {
+            self.pick_middle_biome(i, j, weirdness)
+        }
+    }
+
+    fn pick_middle_biome_or_badlands_if_hot_or_slope_if_cold(
+        &self,
+        i: usize,
+        j: usize,
+        weirdness: Parameter,
+    ) -> &'static str {
+        if i == 0 {
+            self.pick_slope_biome(i, j, weirdness)
+        } else {
+            self.pick_middle_biome_or_badlands_if_hot(i, j, weirdness)
+        }
+    }
+
+    fn maybe_pick_windswept_savanna_biome(
+        &self,
+        i: usize,
+        j: usize,
+        weirdness: Parameter,
+        biome: &'static str,
+    ) -> &'static str {
+        if i > 1 && j < 4 && weirdness.max >= 0 {
+            "windswept_savanna"
+        } else {
+            biome
+        }
+    }
+
+    fn pick_shattered_coast_biome(&self, i: usize, j: usize, weirdness: Parameter) -> &'static str {
+        let biome = if weirdness.max >= 0 {
+            self.pick_middle_biome(i, j, weirdness)
+        } else {
+            self.pick_beach_biome(i, j)
+        };
+        self.maybe_pick_windswept_savanna_biome(i, j, weirdness, biome)
+    }
+
+    fn pick_beach_biome(&self, i: usize, _j: usize) -> &'static str {
+        match i {
+            0 => "snowy_beach",
+            4 => "desert",
+            _ => "beach",
+        }
+    }
+
+    fn pick_badlands_biome(&self, j: usize, weirdness: Parameter) -> &'static str {
+        if j < 2 {
+            if weirdness.max < 0 {
+                "badlands"
+            } else {
+                "eroded_badlands"
+            }
+        } else if j < 3 {
+            "badlands"
+        } else {
+            "wooded_badlands"
+        }
+    }
+
+    fn pick_plateau_biome(&self, i: usize, j: usize, weirdness: Parameter) -> &'static str {
+        match PLATEAU_BIOMES_VARIANT[i][j] {
+            Some(variant) if weirdness.max >= 0 => variant,
+            _ => PLATEAU_BIOMES[i][j].unwrap(),
+        }
+    }
+
+    fn pick_peak_biome(&self, i: usize, j: usize, weirdness: Parameter) -> &'static str {
+        if i <= 2 {
+            if weirdness.max < 0 {
+                "jagged_peaks"
+            } else {
+                "frozen_peaks"
+            }
+        } else if i == 3 {
+            "stony_peaks"
+        } else {
+            self.pick_badlands_biome(j, weirdness)
+        }
+    }
+
+    fn pick_slope_biome(&self, i: usize, j: usize, weirdness: Parameter) -> &'static str {
+        if i >= 3 {
+            self.pick_plateau_biome(i, j, weirdness)
+        } else if j <= 1 {
+            "snowy_slopes"
+        } else {
+            "grove"
+        }
+    }
+
+    fn pick_shattered_biome(&self, i: usize, j: usize, weirdness: Parameter) -> &'static str {
+        SHATTERED_BIOMES[i][j].unwrap_or_else(|| self.pick_middle_biome(i, j, weirdness))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn add_surface_biome(
+        &self,
+        consumer: Consumer,
+        temperature: Parameter,
+        humidity: Parameter,
+        continentalness: Parameter,
+        erosion: Parameter,
+        weirdness: Parameter,
+        offset: f32,
+        biome: &'static str,
+    ) {
+        for depth in [0.0, 1.0] {
+            consumer(
+                parameters(
+                    temperature,
+                    humidity,
+                    continentalness,
+                    erosion,
+                    Parameter::point(depth),
+                    weirdness,
+                    offset,
+                ),
+                biome,
+            );
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn add_underground_biome(
+        &self,
+        consumer: Consumer,
+        temperature: Parameter,
+        humidity: Parameter,
+        continentalness: Parameter,
+        erosion: Parameter,
+        weirdness: Parameter,
+        offset: f32,
+        biome: &'static str,
+    ) {
+        consumer(
+            parameters(
+                temperature,
+                humidity,
+                continentalness,
+                erosion,
+                Parameter::span(0.2, 0.9),
+                weirdness,
+                offset,
+            ),
+            biome,
+        );
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn add_bottom_biome(
+        &self,
+        consumer: Consumer,
+        temperature: Parameter,
+        humidity: Parameter,
+        continentalness: Parameter,
+        erosion: Parameter,
+        weirdness: Parameter,
+        offset: f32,
+        biome: &'static str,
+    ) {
+        consumer(
+            parameters(
+                temperature,
+                humidity,
+                continentalness,
+                erosion,
+                Parameter::point(1.1),
+                weirdness,
+                offset,
+            ),
+            biome,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::biomes::climate::{ParameterList, TargetPoint};
+    use crate::biomes::presets::preset_entries;
+    use ahash::AHashSet;
+    use datapack::data::holder::Holder;
+    use util::identifier::Identifier;
+
+    fn biome_name(holder: &Holder<datapack::data::biome::Biome>) -> String {
+        match holder {
+            Holder::Reference(id) => id.to_string(),
+            Holder::Direct(_) => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_overworld() {
+        let entries = preset_entries(Identifier::new("overworld").unwrap()).unwrap();
+        let biomes: AHashSet<_> = entries.iter().map(|(_, biome)| biome_name(biome)).collect();
+        // every overworld biome except the void
+        assert_eq!(biomes.len(), 53);
+
+        let list = ParameterList::new(
+            entries
+                .iter()
+                .map(|(point, biome)| (*point, biome))
+                .collect(),
+        );
+        let biome_at = |temperature, humidity, continentalness, erosion, depth, weirdness| {
+            let target = TargetPoint::new(
+                temperature,
+                humidity,
+                continentalness,
+                erosion,
+                depth,
+                weirdness,
+            );
+            biome_name(list.find_value(target))
+        };
+        assert_eq!(
+            biome_at(0.0, 0.0, -1.1, 0.0, 0.0, 0.0),
+            "minecraft:mushroom_fields"
+        );
+        assert_eq!(
+            biome_at(0.6, 0.0, -0.3, 0.0, 0.0, 0.0),
+            "minecraft:warm_ocean"
+        );
+        assert_eq!(biome_at(0.0, 0.0, 0.2, 0.0, 0.0, 0.0), "minecraft:river");
+        assert_eq!(
+            biome_at(0.6, 0.0, 0.5, -0.3, 0.0, -0.1),
+            "minecraft:badlands"
+        );
+        assert_eq!(
+            biome_at(0.0, 0.0, 0.5, -0.9, 0.0, 0.6),
+            "minecraft:frozen_peaks"
+        );
+        assert_eq!(
+            biome_at(0.0, 0.0, 0.0, -0.9, 1.1, 0.0),
+            "minecraft:deep_dark"
+        );
+    }
+
+    #[test]
+    fn test_nether() {
+        let entries = preset_entries(Identifier::new("nether").unwrap()).unwrap();
+        assert_eq!(entries.len(), 5);
+        assert!(preset_entries(Identifier::new("the_end").unwrap()).is_none());
+    }
+}
@@ -0,0 +1,92 @@
+use crate::sealed::Sealed;
+use datapack::data::biome::{Biome, TemperatureModifier};
+use glam::IVec3;
+use runtime::noise::PerlinSimplexNoise;
+use runtime::random_source::LegacyRandomSource;
+use std::sync::OnceLock;
+
+/// The hardcoded noises of `Biome`, which don't depend on the world seed.
+struct BiomeNoises {
+    temperature: PerlinSimplexNoise,
+    frozen_temperature: PerlinSimplexNoise,
+    biome_info: PerlinSimplexNoise,
+}
+
+fn biome_noises() -> &'static BiomeNoises {
+    static NOISES: OnceLock<BiomeNoises> = OnceLock::new();
+    NOISES.get_or_init(|| BiomeNoises {
+        temperature: PerlinSimplexNoise::new(&mut LegacyRandomSource::new(1234), &[0]),
+        frozen_temperature: PerlinSimplexNoise::new(
+            &mut LegacyRandomSource::new(3456),
+            &[-2, -1, 0],
+        ),
+        biome_info: PerlinSimplexNoise::new(&mut LegacyRandomSource::new(2345), &[0]),
+    })
+}
+
+/// `Biome.BIOME_INFO_NOISE`, which noise based placements also count with.
+pub(crate) fn biome_info_noise() -> &'static PerlinSimplexNoise {
+    &biome_noises().biome_info
+}
+
+pub trait BiomeExt: Sealed {
+    /// The temperature at a block position, after the temperature modifier and the height
+    /// adjustment above y=80.
+    fn temperature(&self, pos: IVec3) -> f32;
+
+    fn warm_enough_to_rain(&self, pos: IVec3) -> bool {
+        self.temperature(pos) >= 0.15
+    }
+
+    fn cold_enough_to_snow(&self, pos: IVec3) -> bool {
+        !self.warm_enough_to_rain(pos)
+    }
+
+    fn should_melt_frozen_ocean_iceberg_slightly(&self, pos: IVec3) -> bool {
+        self.temperature(pos) > 0.1
+    }
+}
+
+impl Sealed for Biome {}
+
+impl BiomeExt for Biome {
+    fn temperature(&self, pos: IVec3) -> f32 {
+        let climate = &self.climate_settings;
+        let temperature = match climate.temperature_modifier {
+            TemperatureModifier::None => climate.temperature,
+            TemperatureModifier::Frozen => {
+                let noises = biome_noises();
+                let frozen = noises.frozen_temperature.get_value(
+                    pos.x as f64 * 0.05,
+                    pos.z as f64 * 0.05,
+                    false,
+                ) * 7.0;
+                let info =
+                    noises
+                        .biome_info
+                        .get_value(pos.x as f64 * 0.2, pos.z as f64 * 0.2, false);
+                if frozen + info < 0.3
+                    && noises
+                        .biome_info
+                        .get_value(pos.x as f64 * 0.09, pos.z as f64 * 0.09, false)
+                        < 0.8
+                {
+                    0.2
+                } else {
+                    climate.temperature
+                }
+            }
+        };
+
+        if pos.y > 80 {
+            let noise = (biome_noises().temperature.get_value(
+                (pos.x as f32 / 8.0) as f64,
+                (pos.z as f32 / 8.0) as f64,
+                false,
+            ) * 8.0) as f32;
+            temperature - (noise + pos.y as f32 - 80.0) * 0.05 / 40.0
+        } else {
+            temperature
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use crate::biomes::climate::ClimateSampler;
+use crate::biomes::{BiomeHolder, BiomeSourceImpl, QuartPos};
+use datapack::data::biome::Biome;
+use datapack::data::holder::Holder;
+use glam::IVec3;
+use util::identifier::IdentifierBuf;
+
+/// Picks the central end biome near the origin, and one of the outer end biomes by erosion
+/// elsewhere.
+#[derive(Debug)]
+pub struct TheEndBiomeSourceImpl {
+    end: BiomeHolder<'static>,
+    highlands: BiomeHolder<'static>,
+    midlands: BiomeHolder<'static>,
+    islands: BiomeHolder<'static>,
+    barrens: BiomeHolder<'static>,
+}
+
+impl TheEndBiomeSourceImpl {
+    pub fn new() -> Self {
+        let biome = |id: &str| BiomeHolder::reference(IdentifierBuf::new(id).unwrap());
+        TheEndBiomeSourceImpl {
+            end: biome("minecraft:the_end"),
+            highlands: biome("minecraft:end_highlands"),
+            midlands: biome("minecraft:end_midlands"),
+            islands: biome("minecraft:small_end_islands"),
+            barrens: biome("minecraft:end_barrens"),
+        }
+    }
+}
+
+impl Default for TheEndBiomeSourceImpl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BiomeSourceImpl for TheEndBiomeSourceImpl {
+    fn get_noise_biome(
+        &self,
+        pos: QuartPos,
+        climate_sampler: &mut ClimateSampler,
+    ) -> &Holder<Biome> {
+        let block = pos.to_block();
+        let section_x = (block.x >> 4) as i64;
+        let section_z = (block.z >> 4) as i64;
+        if section_x * section_x + section_z * section_z <= 4096 {
+            return &self.end;
+        }
+
+        // the erosion is sampled at the center of every other section
+        let sample_x = ((block.x >> 4) * 2 + 1) * 8;
+        let sample_z = ((block.z >> 4) * 2 + 1) * 8;
+        let erosion = climate_sampler.erosion(IVec3::new(sample_x, block.y, sample_z));
+        if erosion > 0.25 {
+            &self.highlands
+        } else if erosion >= -0.0625 {
+            &self.midlands
+        } else if erosion < -0.21875 {
+            &self.islands
+        } else {
+            &self.barrens
+        }
+    }
+
+    fn possible_biomes(&self) -> Vec<&Holder<Biome>> {
+        vec![
+            &self.end,
+            &self.highlands,
+            &self.midlands,
+            &self.islands,
+            &self.barrens,
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::biomes::the_end::TheEndBiomeSourceImpl;
+    use crate::biomes::{BiomeSourceImpl, QuartPos};
+    use crate::test_util::{empty_datapack, with_climate_sampler, zero_router};
+    use datapack::data::holder::Holder;
+    use datapack::data::noise::NoiseRouter;
+
+    #[test]
+    fn test_the_end() {
+        // erosion falls off with y, everything else is zero
+        let router = NoiseRouter {
+            erosion: serde_json::from_str(
+                r#"{ "type": "minecraft:y_clamped_gradient", "from_y": 0, "to_y": 100, "from_value": 1, "to_value": -1 }"#,
+            )
+            .unwrap(),
+            ..zero_router()
+        };
+
+        let biome_source = TheEndBiomeSourceImpl::new();
+        with_climate_sampler(&empty_datapack(), &router, |sampler| {
+            let mut biome_at = |x, y| {
+                let Holder::Reference(biome) =
+                    biome_source.get_noise_biome(QuartPos::new(x, y, 0), sampler)
+                else {
+                    unreachable!();
+                };
+                biome.to_string()
+            };
+            // within 64 sections of the origin, the erosion doesn't matter
+            assert_eq!(biome_at(0, 0), "minecraft:the_end");
+            assert_eq!(biome_at(256, 0), "minecraft:the_end");
+            assert_eq!(biome_at(260, 0), "minecraft:end_highlands");
+            assert_eq!(biome_at(260, 12), "minecraft:end_midlands");
+            assert_eq!(biome_at(-260, 14), "minecraft:end_barrens");
+            assert_eq!(biome_at(-260, 20), "minecraft:small_end_islands");
+        });
+    }
+}
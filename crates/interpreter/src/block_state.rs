@@ -0,0 +1,203 @@
+use crate::sealed::Sealed;
+use datapack::data::block_state::BlockState;
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+use util::identifier::{Identifier, IdentifierBuf};
+
+const AIR: &Identifier = Identifier::new_const("air");
+const CAVE_AIR: &Identifier = Identifier::new_const("cave_air");
+const VOID_AIR: &Identifier = Identifier::new_const("void_air");
+
+/// Blocks whose fluid state is never empty, even though they have no `waterlogged` property.
+const FLUID_BLOCKS: [&Identifier; 7] = [
+    Identifier::new_const("water"),
+    Identifier::new_const("lava"),
+    Identifier::new_const("bubble_column"),
+    Identifier::new_const("kelp"),
+    Identifier::new_const("kelp_plant"),
+    Identifier::new_const("seagrass"),
+    Identifier::new_const("tall_seagrass"),
+];
+
+/// Blocks that don't block motion, equivalent to those without vanilla's legacy solid flag, plus
+/// cobwebs and bamboo saplings.
+const NON_SOLID_BLOCKS: [&str; 81] = [
+    "air",
+    "cave_air",
+    "void_air",
+    "structure_void",
+    "light",
+    "water",
+    "lava",
+    "bubble_column",
+    "fire",
+    "soul_fire",
+    "nether_portal",
+    "end_portal",
+    "end_gateway",
+    "cobweb",
+    "bamboo_sapling",
+    "snow",
+    "powder_snow",
+    "short_grass",
+    "grass",
+    "fern",
+    "tall_grass",
+    "large_fern",
+    "dead_bush",
+    "seagrass",
+    "tall_seagrass",
+    "kelp",
+    "kelp_plant",
+    "sea_pickle",
+    "lily_pad",
+    "frogspawn",
+    "vine",
+    "glow_lichen",
+    "sculk_vein",
+    "hanging_roots",
+    "spore_blossom",
+    "azalea",
+    "flowering_azalea",
+    "big_dripleaf",
+    "big_dripleaf_stem",
+    "small_dripleaf",
+    "pink_petals",
+    "dandelion",
+    "poppy",
+    "allium",
+    "azure_bluet",
+    "oxeye_daisy",
+    "lily_of_the_valley",
+    "wither_rose",
+    "lilac",
+    "rose_bush",
+    "peony",
+    "pitcher_plant",
+    "pitcher_crop",
+    "torchflower_crop",
+    "mangrove_propagule",
+    "sugar_cane",
+    "sweet_berry_bush",
+    "chorus_plant",
+    "cocoa",
+    "wheat",
+    "carrots",
+    "potatoes",
+    "beetroots",
+    "melon_stem",
+    "pumpkin_stem",
+    "attached_melon_stem",
+    "attached_pumpkin_stem",
+    "nether_wart",
+    "crimson_roots",
+    "warped_roots",
+    "nether_sprouts",
+    "redstone_wire",
+    "repeater",
+    "comparator",
+    "tripwire",
+    "tripwire_hook",
+    "lever",
+    "ladder",
+    "scaffolding",
+    "end_rod",
+    "flower_pot",
+];
+
+/// Suffixes of whole families of blocks that don't block motion, like flowers and torches.
+const NON_SOLID_SUFFIXES: [&str; 16] = [
+    "_sapling",
+    "flower",
+    "_tulip",
+    "_orchid",
+    "_mushroom",
+    "_fungus",
+    "_vines",
+    "_vines_plant",
+    "_coral",
+    "_coral_fan",
+    "_coral_wall_fan",
+    "torch",
+    "rail",
+    "_button",
+    "_carpet",
+    "candle",
+];
+
+/// Returns the default state of a block without properties.
+pub fn simple_state(name: &str) -> BlockState {
+    BlockState {
+        name: IdentifierBuf::new(name).unwrap(),
+        properties: BTreeMap::new(),
+    }
+}
+
+/// The state of `minecraft:air`, returned for positions outside of a chunk.
+pub fn air() -> &'static BlockState {
+    static AIR_STATE: OnceLock<BlockState> = OnceLock::new();
+    AIR_STATE.get_or_init(|| simple_state("minecraft:air"))
+}
+
+/// The source state of `minecraft:lava`, which aquifers fill deep caves with.
+pub fn lava() -> &'static BlockState {
+    static LAVA_STATE: OnceLock<BlockState> = OnceLock::new();
+    LAVA_STATE.get_or_init(|| BlockState {
+        name: IdentifierBuf::new("minecraft:lava").unwrap(),
+        properties: BTreeMap::from([("level".to_owned(), "0".to_owned())]),
+    })
+}
+
+pub trait BlockStateExt: Sealed {
+    fn is(&self, block: &Identifier) -> bool;
+    fn is_air(&self) -> bool;
+    /// Whether the fluid state of this block is not empty, i.e. it's a fluid or waterlogged.
+    fn has_fluid(&self) -> bool;
+    /// Whether entities can't move through this block, equivalent to vanilla's
+    /// `BlockState.blocksMotion`. Since there is no block registry, this is an approximation that
+    /// covers the blocks placed during world generation.
+    fn blocks_motion(&self) -> bool;
+    fn is_leaves(&self) -> bool;
+}
+
+impl Sealed for BlockState {}
+
+impl BlockStateExt for BlockState {
+    #[inline]
+    fn is(&self, block: &Identifier) -> bool {
+        *self.name == *block
+    }
+
+    #[inline]
+    fn is_air(&self) -> bool {
+        self.is(AIR) || self.is(CAVE_AIR) || self.is(VOID_AIR)
+    }
+
+    fn has_fluid(&self) -> bool {
+        FLUID_BLOCKS.iter().any(|block| self.is(block))
+            || self
+                .properties
+                .get("waterlogged")
+                .is_some_and(|waterlogged| waterlogged == "true")
+    }
+
+    fn blocks_motion(&self) -> bool {
+        if self.name.namespace() != "minecraft" {
+            return true;
+        }
+        let path = self.name.path();
+        if path == "chain" {
+            // only a vertical chain is tall enough to be solid
+            return self.properties.get("axis").map_or(true, |axis| axis == "y");
+        }
+        !NON_SOLID_BLOCKS.contains(&path)
+            && !NON_SOLID_SUFFIXES
+                .iter()
+                .any(|suffix| path.ends_with(suffix))
+            && !path.starts_with("potted_")
+    }
+
+    fn is_leaves(&self) -> bool {
+        self.name.namespace() == "minecraft" && self.name.path().ends_with("_leaves")
+    }
+}
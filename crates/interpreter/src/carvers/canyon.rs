@@ -0,0 +1,137 @@
+use crate::carvers::{Carver, CARVER_RANGE};
+use crate::chunk::ChunkAccess;
+use crate::value_providers::{FloatProviderExt, HeightProviderExt};
+use datapack::data::carvers::CanyonCarverConfiguration;
+use datapack::DataPackResult;
+use glam::{DVec3, IVec2};
+use runtime::random_source::{LegacyRandomSource, RandomSource};
+use std::f32::consts::PI;
+use util::math;
+
+/// Carves the canyon starting in the `origin` chunk, equivalent to `CanyonWorldCarver.carve`.
+pub(super) fn carve<C: ChunkAccess>(
+    carver: &mut Carver<C>,
+    config: &CanyonCarverConfiguration,
+    random: &mut impl RandomSource,
+    origin: IVec2,
+) -> DataPackResult<bool> {
+    let max_distance = (CARVER_RANGE * 2 - 1) << 4;
+    let min_block: IVec2 = origin << 4;
+    let x = (min_block.x + random.next_u32(16) as i32) as f64;
+    let y = config
+        .base
+        .y
+        .sample(random, &carver.context.generation_context) as f64;
+    let z = (min_block.y + random.next_u32(16) as i32) as f64;
+    let yaw = random.next_f32() * (PI * 2.0);
+    let pitch = config.vertical_rotation.sample(random);
+    let y_scale = config.base.y_scale.sample(random) as f64;
+    let thickness = config.shape.thickness.sample(random);
+    let branch_count = (max_distance as f32 * config.shape.distance_factor.sample(random)) as i32;
+    let seed = random.next_u64();
+    do_carve(
+        carver,
+        config,
+        seed,
+        DVec3::new(x, y, z),
+        thickness,
+        yaw,
+        pitch,
+        branch_count,
+        y_scale,
+    )?;
+    Ok(true)
+}
+
+/// Carves a canyon along a path that wanders less than a cave tunnel and never splits, with a
+/// random width at every height. Equivalent to `CanyonWorldCarver.doCarve`.
+#[allow(clippy::too_many_arguments)]
+fn do_carve<C: ChunkAccess>(
+    carver: &mut Carver<C>,
+    config: &CanyonCarverConfiguration,
+    seed: u64,
+    mut pos: DVec3,
+    thickness: f32,
+    mut yaw: f32,
+    mut pitch: f32,
+    branch_count: i32,
+    horizontal_vertical_ratio: f64,
+) -> DataPackResult<()> {
+    let mut random = LegacyRandomSource::new(seed);
+    let width_factors = width_factors(carver.gen_depth(), config, &mut random);
+    let min_gen_y = carver.min_gen_y();
+    let skip = |dx: f64, dy: f64, dz: f64, y: i32| {
+        (dx * dx + dz * dz) * width_factors[(y - min_gen_y - 1) as usize] as f64 + dy * dy / 6.0
+            >= 1.0
+    };
+    let mut yaw_delta = 0.0f32;
+    let mut pitch_delta = 0.0f32;
+
+    for index in 0..branch_count {
+        let radius = 1.5 + (math::sin(index as f32 * PI / branch_count as f32) * thickness) as f64;
+        let horizontal_radius =
+            radius * config.shape.horizontal_radius_factor.sample(&mut random) as f64;
+        let vertical_radius = vertical_radius(
+            config,
+            &mut random,
+            radius * horizontal_vertical_ratio,
+            branch_count as f32,
+            index as f32,
+        );
+        let cos = math::cos(pitch);
+        let sin = math::sin(pitch);
+        pos.x += (math::cos(yaw) * cos) as f64;
+        pos.y += sin as f64;
+        pos.z += (math::sin(yaw) * cos) as f64;
+        pitch *= 0.7;
+        pitch += pitch_delta * 0.05;
+        yaw += yaw_delta * 0.05;
+        pitch_delta *= 0.8;
+        yaw_delta *= 0.5;
+        pitch_delta += (random.next_f32() - random.next_f32()) * random.next_f32() * 2.0;
+        yaw_delta += (random.next_f32() - random.next_f32()) * random.next_f32() * 4.0;
+
+        if random.next_u32(4) != 0 {
+            if !carver.can_reach(pos, index, branch_count, thickness) {
+                return Ok(());
+            }
+            carver.carve_ellipsoid(pos, horizontal_radius, vertical_radius, skip)?;
+        }
+    }
+    Ok(())
+}
+
+/// The squared width factor of the canyon at every height of the generator, which changes on
+/// average every `width_smoothness` blocks. Equivalent to `CanyonWorldCarver.initWidthFactors`.
+fn width_factors(
+    gen_depth: i32,
+    config: &CanyonCarverConfiguration,
+    random: &mut impl RandomSource,
+) -> Vec<f32> {
+    // vanilla fails on a smoothness of 0
+    let width_smoothness = (*config.shape.width_smoothness).max(1);
+    let mut factor = 1.0f32;
+    (0..gen_depth)
+        .map(|y| {
+            if y == 0 || random.next_u32(width_smoothness) == 0 {
+                factor = 1.0 + random.next_f32() * random.next_f32();
+            }
+            factor * factor
+        })
+        .collect()
+}
+
+/// Scales the vertical radius of the canyon, making it deeper towards its middle. Equivalent to
+/// `CanyonWorldCarver.updateVerticalRadius`.
+fn vertical_radius(
+    config: &CanyonCarverConfiguration,
+    random: &mut impl RandomSource,
+    vertical_radius: f64,
+    branch_count: f32,
+    branch_index: f32,
+) -> f64 {
+    let center = 1.0 - (0.5 - branch_index / branch_count).abs() * 2.0;
+    let factor = config.shape.vertical_radius_default_factor
+        + config.shape.vertical_radius_center_factor * center;
+    factor as f64 * vertical_radius * (random.next_f32() * 0.25 + 0.75) as f64
+}
@@ -0,0 +1,190 @@
+use crate::carvers::{Carver, CARVER_RANGE};
+use crate::chunk::ChunkAccess;
+use crate::value_providers::{FloatProviderExt, HeightProviderExt};
+use datapack::data::carvers::CaveCarverConfiguration;
+use datapack::DataPackResult;
+use glam::{DVec3, IVec2};
+use runtime::random_source::{LegacyRandomSource, RandomSource};
+use std::f32::consts::PI;
+use util::math;
+
+/// Carves the caves starting in the `origin` chunk, equivalent to `CaveWorldCarver.carve`. Nether
+/// caves are fewer, thicker and flatter.
+pub(super) fn carve<C: ChunkAccess>(
+    carver: &mut Carver<C>,
+    config: &CaveCarverConfiguration,
+    random: &mut impl RandomSource,
+    origin: IVec2,
+) -> DataPackResult<bool> {
+    let max_distance = (CARVER_RANGE * 2 - 1) << 4;
+    let cave_bound = if carver.nether { 10 } else { 15 };
+    let cave_count = random.next_u32(cave_bound) + 1;
+    let cave_count = random.next_u32(cave_count) + 1;
+    let cave_count = random.next_u32(cave_count);
+    let min_block: IVec2 = origin << 4;
+
+    for _ in 0..cave_count {
+        let x = (min_block.x + random.next_u32(16) as i32) as f64;
+        let y = config
+            .base
+            .y
+            .sample(random, &carver.context.generation_context) as f64;
+        let z = (min_block.y + random.next_u32(16) as i32) as f64;
+        let pos = DVec3::new(x, y, z);
+        let horizontal_radius_multiplier =
+            config.horizontal_radius_multiplier.sample(random) as f64;
+        let vertical_radius_multiplier = config.vertical_radius_multiplier.sample(random) as f64;
+        let floor_level = config.floor_level.sample(random) as f64;
+        let skip = move |dx: f64, dy: f64, dz: f64, _y: i32| {
+            dy <= floor_level || dx * dx + dy * dy + dz * dz >= 1.0
+        };
+
+        let mut tunnels = 1;
+        if random.next_u32(4) == 0 {
+            let y_scale = config.base.y_scale.sample(random) as f64;
+            let thickness = 1.0 + random.next_f32() * 6.0;
+            create_room(carver, pos, thickness, y_scale, skip)?;
+            tunnels += random.next_u32(4);
+        }
+
+        for _ in 0..tunnels {
+            let y_rot = random.next_f32() * (PI * 2.0);
+            let x_rot = (random.next_f32() - 0.5) / 4.0;
+            let thickness = thickness(carver.nether, random);
+            let branch_count = max_distance - random.next_u32(max_distance as u32 / 4) as i32;
+            let y_scale = if carver.nether { 5.0 } else { 1.0 };
+            create_tunnel(
+                carver,
+                random.next_u64(),
+                pos,
+                horizontal_radius_multiplier,
+                vertical_radius_multiplier,
+                Tunnel {
+                    thickness,
+                    y_rot,
+                    x_rot,
+                    branch_index: 0,
+                    branch_count,
+                    horizontal_vertical_ratio: y_scale,
+                },
+                skip,
+            )?;
+        }
+    }
+    Ok(true)
+}
+
+/// The shape of a tunnel and how far along its branches it starts.
+struct Tunnel {
+    thickness: f32,
+    y_rot: f32,
+    x_rot: f32,
+    branch_index: i32,
+    branch_count: i32,
+    horizontal_vertical_ratio: f64,
+}
+
+/// `CaveWorldCarver.getThickness`, or `NetherWorldCarver.getThickness` for nether caves.
+fn thickness(nether: bool, random: &mut impl RandomSource) -> f32 {
+    if nether {
+        return (random.next_f32() * 2.0 + random.next_f32()) * 2.0;
+    }
+    let mut thickness = random.next_f32() * 2.0 + random.next_f32();
+    if random.next_u32(10) == 0 {
+        thickness *= random.next_f32() * random.next_f32() * 3.0 + 1.0;
+    }
+    thickness
+}
+
+/// `CaveWorldCarver.createRoom`
+fn create_room<C: ChunkAccess>(
+    carver: &mut Carver<C>,
+    pos: DVec3,
+    radius: f32,
+    horizontal_vertical_ratio: f64,
+    skip: impl Fn(f64, f64, f64, i32) -> bool,
+) -> DataPackResult<bool> {
+    let horizontal_radius = 1.5 + (math::sin(PI / 2.0) * radius) as f64;
+    let vertical_radius = horizontal_radius * horizontal_vertical_ratio;
+    carver.carve_ellipsoid(pos + DVec3::X, horizontal_radius, vertical_radius, skip)
+}
+
+/// Carves a tunnel that wanders from `pos`, splitting into two tunnels once along the way.
+/// Equivalent to `CaveWorldCarver.createTunnel`.
+fn create_tunnel<C: ChunkAccess>(
+    carver: &mut Carver<C>,
+    seed: u64,
+    mut pos: DVec3,
+    horizontal_radius_multiplier: f64,
+    vertical_radius_multiplier: f64,
+    tunnel: Tunnel,
+    skip: impl Fn(f64, f64, f64, i32) -> bool + Copy,
+) -> DataPackResult<()> {
+    let Tunnel {
+        thickness,
+        mut y_rot,
+        mut x_rot,
+        branch_index,
+        branch_count,
+        horizontal_vertical_ratio,
+    } = tunnel;
+    let mut random = LegacyRandomSource::new(seed);
+    let split_point = random.next_u32(branch_count as u32 / 2) as i32 + branch_count / 4;
+    let steep = random.next_u32(6) == 0;
+    let mut y_rot_delta = 0.0f32;
+    let mut x_rot_delta = 0.0f32;
+
+    for index in branch_index..branch_count {
+        let horizontal_radius =
+            1.5 + (math::sin(PI * index as f32 / branch_count as f32) * thickness) as f64;
+        let vertical_radius = horizontal_radius * horizontal_vertical_ratio;
+        let cos = math::cos(x_rot);
+        pos.x += (math::cos(y_rot) * cos) as f64;
+        pos.y += math::sin(x_rot) as f64;
+        pos.z += (math::sin(y_rot) * cos) as f64;
+        x_rot *= if steep { 0.92 } else { 0.7 };
+        x_rot += x_rot_delta * 0.1;
+        y_rot += y_rot_delta * 0.1;
+        x_rot_delta *= 0.9;
+        y_rot_delta *= 0.75;
+        x_rot_delta += (random.next_f32() - random.next_f32()) * random.next_f32() * 2.0;
+        y_rot_delta += (random.next_f32() - random.next_f32()) * random.next_f32() * 4.0;
+
+        if index == split_point && thickness > 1.0 {
+            for y_rot_offset in [-PI / 2.0, PI / 2.0] {
+                let seed = random.next_u64();
+                let thickness = random.next_f32() * 0.5 + 0.5;
+                create_tunnel(
+                    carver,
+                    seed,
+                    pos,
+                    horizontal_radius_multiplier,
+                    vertical_radius_multiplier,
+                    Tunnel {
+                        thickness,
+                        y_rot: y_rot + y_rot_offset,
+                        x_rot: x_rot / 3.0,
+                        branch_index: index,
+                        branch_count,
+                        horizontal_vertical_ratio: 1.0,
+                    },
+                    skip,
+                )?;
+            }
+            return Ok(());
+        }
+
+        if random.next_u32(4) != 0 {
+            if !carver.can_reach(pos, index, branch_count, thickness) {
+                return Ok(());
+            }
+            carver.carve_ellipsoid(
+                pos,
+                horizontal_radius * horizontal_radius_multiplier,
+                vertical_radius * vertical_radius_multiplier,
+                skip,
+            )?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,728 @@
+mod canyon;
+mod cave;
+
+use crate::biomes::biome_manager::BiomeManager;
+use crate::biomes::climate::ClimateSampler;
+use crate::biomes::{BiomeSourceImpl, QuartPos};
+use crate::block_state::{self, BlockStateExt};
+use crate::chunk::carving_mask::CarvingMask;
+use crate::chunk::proto_chunk::ProtoChunk;
+use crate::chunk::ChunkAccess;
+use crate::noise_chunk::NoiseChunk;
+use crate::sealed::Sealed;
+use crate::surface::SurfaceSystem;
+use crate::world_generation_context::{VerticalAnchorExt, WorldGenerationContext};
+use ahash::AHashSet;
+use datapack::data::biome::Biome;
+use datapack::data::block_state::BlockState;
+use datapack::data::carvers::{CarverConfiguration, CarverDebugSettings, ConfiguredWorldCarver};
+use datapack::data::holder::Holder;
+use datapack::data::step::CarvingStep;
+use datapack::{DataPack, DataPackResult};
+use glam::{DVec3, IVec2, IVec3};
+use runtime::random_source::{LegacyRandomSource, RandomSource, WorldgenRandom};
+use util::identifier::Identifier;
+use util::math;
+
+const AIR: &Identifier = Identifier::new_const("air");
+const WATER: &Identifier = Identifier::new_const("water");
+const LAVA: &Identifier = Identifier::new_const("lava");
+const GRASS_BLOCK: &Identifier = Identifier::new_const("grass_block");
+const MYCELIUM: &Identifier = Identifier::new_const("mycelium");
+const DIRT: &Identifier = Identifier::new_const("dirt");
+
+/// The number of chunks in each direction from its start chunk that a carver may reach,
+/// `WorldCarver.getRange`.
+pub const CARVER_RANGE: i32 = 4;
+
+/// What carvers need from the generator of the chunk they carve, equivalent to vanilla's
+/// `CarvingContext`.
+pub struct CarvingContext<'c, 'f, 'a> {
+    datapack: &'a DataPack,
+    generation_context: WorldGenerationContext,
+    surface_system: &'c SurfaceSystem<'a>,
+    noise_chunk: &'c mut NoiseChunk<'f, 'a>,
+}
+
+impl<'c, 'f, 'a> CarvingContext<'c, 'f, 'a> {
+    /// Creates the context for carving a chunk. The aquifer of `noise_chunk` decides which fluid
+    /// fills the carved blocks.
+    pub fn new(
+        datapack: &'a DataPack,
+        generation_context: WorldGenerationContext,
+        surface_system: &'c SurfaceSystem<'a>,
+        noise_chunk: &'c mut NoiseChunk<'f, 'a>,
+    ) -> Self {
+        CarvingContext {
+            datapack,
+            generation_context,
+            surface_system,
+            noise_chunk,
+        }
+    }
+}
+
+pub trait ConfiguredWorldCarverExt: Sealed {
+    /// Whether the carver starts in a chunk, `ConfiguredWorldCarver.isStartChunk`.
+    fn is_start_chunk(&self, random: &mut impl RandomSource) -> bool;
+
+    /// Carves the part of the carver starting in the `origin` chunk that lies in `chunk`, skipping
+    /// the blocks already set in `mask` and setting the ones it carves. Equivalent to
+    /// `ConfiguredWorldCarver.carve`.
+    fn carve<'b>(
+        &self,
+        context: &mut CarvingContext,
+        chunk: &mut impl ChunkAccess,
+        biome_getter: &mut dyn FnMut(IVec3) -> &'b Holder<Biome>,
+        random: &mut impl RandomSource,
+        origin: IVec2,
+        mask: &mut CarvingMask,
+    ) -> DataPackResult<bool>;
+}
+
+impl Sealed for ConfiguredWorldCarver {}
+
+impl ConfiguredWorldCarverExt for ConfiguredWorldCarver {
+    fn is_start_chunk(&self, random: &mut impl RandomSource) -> bool {
+        random.next_f32() <= *base_config(self).probability.probability
+    }
+
+    fn carve<'b>(
+        &self,
+        context: &mut CarvingContext,
+        chunk: &mut impl ChunkAccess,
+        biome_getter: &mut dyn FnMut(IVec3) -> &'b Holder<Biome>,
+        random: &mut impl RandomSource,
+        origin: IVec2,
+        mask: &mut CarvingMask,
+    ) -> DataPackResult<bool> {
+        match self {
+            ConfiguredWorldCarver::Cave(config) => {
+                let mut carver =
+                    Carver::new(context, &config.base, false, chunk, biome_getter, mask)?;
+                cave::carve(&mut carver, config, random, origin)
+            }
+            ConfiguredWorldCarver::NetherCave(config) => {
+                let mut carver =
+                    Carver::new(context, &config.base, true, chunk, biome_getter, mask)?;
+                cave::carve(&mut carver, config, random, origin)
+            }
+            ConfiguredWorldCarver::Canyon(config) => {
+                let mut carver =
+                    Carver::new(context, &config.base, false, chunk, biome_getter, mask)?;
+                canyon::carve(&mut carver, config, random, origin)
+            }
+        }
+    }
+}
+
+/// Runs the carvers of a step on a chunk, equivalent to `NoiseBasedChunkGenerator.applyCarvers`.
+/// Every chunk within 8 chunks may start carvers reaching into this one, which are those of the
+/// biome at its corner. The carved blocks are recorded in the chunk's carving mask for the step.
+pub fn apply_carvers(
+    context: &mut CarvingContext,
+    chunk: &mut ProtoChunk,
+    seed: u64,
+    step: CarvingStep,
+    biome_source: &impl BiomeSourceImpl,
+    biome_manager: BiomeManager,
+    climate_sampler: &mut ClimateSampler,
+) -> DataPackResult<()> {
+    let datapack = context.datapack;
+    let chunk_pos = chunk.pos();
+    let mut mask = chunk.take_carving_mask(step);
+    let mut random = WorldgenRandom::new(LegacyRandomSource::new(0));
+
+    for dx in -8..=8 {
+        for dz in -8..=8 {
+            let origin = chunk_pos + IVec2::new(dx, dz);
+            let min_block: IVec2 = origin << 4;
+            let biome = biome_source.get_noise_biome(
+                QuartPos::new(min_block.x >> 2, 0, min_block.y >> 2),
+                climate_sampler,
+            );
+            for (index, carver) in biome_carvers(datapack, biome, step)?
+                .into_iter()
+                .enumerate()
+            {
+                random.set_large_feature_seed(seed.wrapping_add(index as u64), origin.x, origin.y);
+                if carver.is_start_chunk(&mut random) {
+                    carver.carve(
+                        context,
+                        chunk,
+                        &mut |pos| biome_manager.get_biome(biome_source, pos, climate_sampler),
+                        &mut random,
+                        origin,
+                        &mut mask,
+                    )?;
+                }
+            }
+        }
+    }
+
+    chunk.set_carving_mask(step, mask);
+    Ok(())
+}
+
+/// The carvers of a biome for a step in order, `BiomeGenerationSettings.getCarvers`.
+fn biome_carvers<'a>(
+    datapack: &'a DataPack,
+    biome: &'a Holder<Biome>,
+    step: CarvingStep,
+) -> DataPackResult<Vec<&'a ConfiguredWorldCarver>> {
+    match biome
+        .resolve(datapack)?
+        .generation_settings
+        .carvers
+        .get(&step)
+    {
+        Some(carvers) => carvers.resolve(datapack),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn base_config(carver: &ConfiguredWorldCarver) -> &CarverConfiguration {
+    match carver {
+        ConfiguredWorldCarver::Cave(config) | ConfiguredWorldCarver::NetherCave(config) => {
+            &config.base
+        }
+        ConfiguredWorldCarver::Canyon(config) => &config.base,
+    }
+}
+
+/// A carver carving a single chunk, holding the state vanilla's `WorldCarver` passes between its
+/// methods.
+struct Carver<'r, 'c, 'f, 'a, 'b, C> {
+    context: &'r mut CarvingContext<'c, 'f, 'a>,
+    chunk: &'r mut C,
+    biome_getter: &'r mut dyn FnMut(IVec3) -> &'b Holder<Biome>,
+    mask: &'r mut CarvingMask,
+    replaceable: AHashSet<&'r Identifier>,
+    lava_level: i32,
+    /// The debug settings, if debug mode is enabled.
+    debug_settings: Option<&'r CarverDebugSettings>,
+    /// Whether blocks are carved like `NetherWorldCarver.carveBlock`, ignoring the aquifer.
+    nether: bool,
+}
+
+impl<'r, 'c, 'f, 'a, 'b, C: ChunkAccess> Carver<'r, 'c, 'f, 'a, 'b, C> {
+    fn new(
+        context: &'r mut CarvingContext<'c, 'f, 'a>,
+        config: &'r CarverConfiguration,
+        nether: bool,
+        chunk: &'r mut C,
+        biome_getter: &'r mut dyn FnMut(IVec3) -> &'b Holder<Biome>,
+        mask: &'r mut CarvingMask,
+    ) -> DataPackResult<Self> {
+        let replaceable = config
+            .replaceable
+            .flatten(context.datapack)?
+            .into_iter()
+            .collect();
+        let lava_level = config.lava_level.resolve_y(&context.generation_context);
+        Ok(Carver {
+            context,
+            chunk,
+            biome_getter,
+            mask,
+            replaceable,
+            lava_level,
+            debug_settings: config
+                .debug_settings
+                .as_ref()
+                .filter(|settings| settings.debug_mode),
+            nether,
+        })
+    }
+
+    fn min_gen_y(&self) -> i32 {
+        self.context.generation_context.min_y
+    }
+
+    fn gen_depth(&self) -> i32 {
+        self.context.generation_context.height
+    }
+
+    /// Whether a carver at `pos` can still reach the chunk in the branches it has left,
+    /// `WorldCarver.canReach`.
+    fn can_reach(&self, pos: DVec3, branch_index: i32, branch_count: i32, width: f32) -> bool {
+        let middle: IVec2 = (self.chunk.pos() << 4) + 8;
+        let dx = pos.x - middle.x as f64;
+        let dz = pos.z - middle.y as f64;
+        let remaining = (branch_count - branch_index) as f64;
+        let max_distance = (width + 2.0 + 16.0) as f64;
+        dx * dx + dz * dz - remaining * remaining <= max_distance * max_distance
+    }
+
+    /// Carves the blocks of the chunk inside an ellipsoid, except where `skip` returns true for
+    /// the position relative to the radii and the block y. Returns whether any block was carved.
+    /// Equivalent to `WorldCarver.carveEllipsoid`.
+    fn carve_ellipsoid(
+        &mut self,
+        center: DVec3,
+        horizontal_radius: f64,
+        vertical_radius: f64,
+        skip: impl Fn(f64, f64, f64, i32) -> bool,
+    ) -> DataPackResult<bool> {
+        let min_block: IVec2 = self.chunk.pos() << 4;
+        let max_distance = 16.0 + horizontal_radius * 2.0;
+        if (center.x - (min_block.x + 8) as f64).abs() > max_distance
+            || (center.z - (min_block.y + 8) as f64).abs() > max_distance
+        {
+            return Ok(false);
+        }
+
+        let min_x = (math::floor(center.x - horizontal_radius) - min_block.x - 1).max(0);
+        let max_x = (math::floor(center.x + horizontal_radius) - min_block.x).min(15);
+        let min_y = (math::floor(center.y - vertical_radius) - 1).max(self.min_gen_y() + 1);
+        let max_y = (math::floor(center.y + vertical_radius) + 1)
+            .min(self.min_gen_y() + self.gen_depth() - 1 - 7);
+        let min_z = (math::floor(center.z - horizontal_radius) - min_block.y - 1).max(0);
+        let max_z = (math::floor(center.z + horizontal_radius) - min_block.y).min(15);
+
+        let mut carved = false;
+        for local_x in min_x..=max_x {
+            let x = min_block.x + local_x;
+            let dx = (x as f64 + 0.5 - center.x) / horizontal_radius;
+            for local_z in min_z..=max_z {
+                let z = min_block.y + local_z;
+                let dz = (z as f64 + 0.5 - center.z) / horizontal_radius;
+                if dx * dx + dz * dz >= 1.0 {
+                    continue;
+                }
+                let mut reached_surface = false;
+                for y in (min_y + 1..=max_y).rev() {
+                    let dy = (y as f64 - 0.5 - center.y) / vertical_radius;
+                    if skip(dx, dy, dz, y)
+                        || (self.mask.get(local_x, y, local_z) && self.debug_settings.is_none())
+                    {
+                        continue;
+                    }
+                    self.mask.set(local_x, y, local_z);
+                    carved |= self.carve_block(IVec3::new(x, y, z), &mut reached_surface)?;
+                }
+            }
+        }
+        Ok(carved)
+    }
+
+    /// Replaces a block with air or the fluid the aquifer places there, and replaces the dirt
+    /// below with the top material once the carver has carved through grass. Equivalent to
+    /// `WorldCarver.carveBlock`.
+    fn carve_block(&mut self, pos: IVec3, reached_surface: &mut bool) -> DataPackResult<bool> {
+        let state = self.chunk.block_state(pos);
+        if self.nether {
+            if !self.can_replace(state) {
+                return Ok(false);
+            }
+            let state = if pos.y <= self.min_gen_y() + 31 {
+                block_state::lava().clone()
+            } else {
+                block_state::simple_state("minecraft:cave_air")
+            };
+            self.chunk.set_block_state(pos, state);
+            return Ok(true);
+        }
+
+        if state.is(GRASS_BLOCK) || state.is(MYCELIUM) {
+            *reached_surface = true;
+        }
+        if !self.can_replace(state) && self.debug_settings.is_none() {
+            return Ok(false);
+        }
+        let Some(state) = self.carve_state(pos) else {
+            return Ok(false);
+        };
+        let has_fluid = state.has_fluid();
+        self.chunk.set_block_state(pos, state);
+        let schedule_fluid_update = self
+            .context
+            .noise_chunk
+            .aquifer()
+            .should_schedule_fluid_update();
+        if schedule_fluid_update && has_fluid {
+            self.chunk.mark_pos_for_postprocessing(pos);
+        }
+
+        let below = pos - IVec3::Y;
+        if *reached_surface && self.chunk.block_state(below).is(DIRT) {
+            let top_material = self.context.surface_system.top_material(
+                &*self.chunk,
+                self.biome_getter,
+                self.context.noise_chunk,
+                below,
+                has_fluid,
+            )?;
+            if let Some(state) = top_material {
+                let has_fluid = state.has_fluid();
+                self.chunk.set_block_state(below, state);
+                if has_fluid {
+                    self.chunk.mark_pos_for_postprocessing(below);
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    fn can_replace(&self, state: &BlockState) -> bool {
+        self.replaceable.contains(&*state.name)
+    }
+
+    /// The block a carved position is replaced with: lava up to the lava level, then whatever the
+    /// aquifer places there, or `None` if the aquifer keeps it solid. Equivalent to
+    /// `WorldCarver.getCarveState`.
+    fn carve_state(&mut self, pos: IVec3) -> Option<BlockState> {
+        if pos.y <= self.lava_level {
+            return Some(block_state::lava().clone());
+        }
+        let Some(state) = self.context.noise_chunk.compute_substance(pos, 0.0) else {
+            return self
+                .debug_settings
+                .map(|settings| settings.barrier_state.clone());
+        };
+        let Some(settings) = self.debug_settings else {
+            return Some(state.clone());
+        };
+        Some(if state.is(AIR) {
+            settings.air_state.clone()
+        } else if state.is(WATER) {
+            let mut state = settings.water_state.clone();
+            if let Some(waterlogged) = state.properties.get_mut("waterlogged") {
+                *waterlogged = "true".to_owned();
+            }
+            state
+        } else if state.is(LAVA) {
+            settings.lava_state.clone()
+        } else {
+            state.clone()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::biomes::biome_manager::BiomeManager;
+    use crate::biomes::climate::ClimateFunctions;
+    use crate::biomes::fixed::FixedBiomeSourceImpl;
+    use crate::block_state::BlockStateExt;
+    use crate::carvers::{apply_carvers, CarvingContext, ConfiguredWorldCarverExt};
+    use crate::chunk::carving_mask::CarvingMask;
+    use crate::chunk::proto_chunk::ProtoChunk;
+    use crate::chunk::ChunkAccess;
+    use crate::noise_chunk::{NoiseChunk, TerrainFunctions};
+    use crate::random_state::RandomState;
+    use crate::surface::SurfaceSystem;
+    use crate::test_util::{datapack, noise_files, noise_settings, SURFACE_NOISES};
+    use crate::world_generation_context::WorldGenerationContext;
+    use datapack::data::carvers::ConfiguredWorldCarver;
+    use datapack::data::holder::Holder;
+    use datapack::data::noise::NoiseGeneratorSettings;
+    use datapack::data::step::CarvingStep;
+    use glam::{IVec2, IVec3};
+    use runtime::random_source::LegacyRandomSource;
+    use std::collections::BTreeMap;
+    use util::identifier::IdentifierBuf;
+
+    const SURFACE_NOISE: &str = r#"{ "firstOctave": -6, "amplitudes": [1] }"#;
+
+    /// Stone up to y=63 and air above, without aquifers.
+    fn settings() -> NoiseGeneratorSettings {
+        let mut settings = noise_settings(0, 128, 0);
+        settings.noise_router.final_density = serde_json::from_str(
+            r#"{
+                "type": "minecraft:y_clamped_gradient", "from_y": 0, "to_y": 128, "from_value": 1, "to_value": -1
+            }"#,
+        )
+        .unwrap();
+        settings
+    }
+
+    const CAVE: &str = r#"
+        "horizontal_radius_multiplier": 1.5,
+        "vertical_radius_multiplier": 1,
+        "floor_level": -0.7
+    "#;
+
+    const CANYON: &str = r#"
+        "vertical_rotation": { "type": "minecraft:uniform", "min_inclusive": -0.125, "max_exclusive": 0.125 },
+        "shape": {
+            "distance_factor": { "type": "minecraft:uniform", "min_inclusive": 0.75, "max_exclusive": 1 },
+            "thickness": { "type": "minecraft:trapezoid", "min": 0, "max": 6, "plateau": 2 },
+            "width_smoothness": 3,
+            "horizontal_radius_factor": { "type": "minecraft:uniform", "min_inclusive": 0.75, "max_exclusive": 1 },
+            "vertical_radius_default_factor": 1,
+            "vertical_radius_center_factor": 0
+        }
+    "#;
+
+    fn carver(carver_type: &str, fields: &str, debug_settings: &str) -> ConfiguredWorldCarver {
+        serde_json::from_str(&format!(
+            r#"{{
+                "type": "{carver_type}",
+                "probability": 1,
+                "y": {{ "absolute": 40 }},
+                "yScale": 1.5,
+                "lava_level": {{ "absolute": 8 }},
+                "replaceable": ["minecraft:stone"],
+                {fields}
+                {debug_settings}
+            }}"#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_carve() {
+        let datapack = datapack(noise_files(&SURFACE_NOISES, SURFACE_NOISE));
+        let settings = settings();
+        let random_state = RandomState::from_settings(&datapack, &settings, 0);
+        let generation_context = WorldGenerationContext::from_settings(&settings.noise);
+        let surface_system =
+            SurfaceSystem::new(&datapack, &random_state, &settings, generation_context).unwrap();
+        let functions =
+            TerrainFunctions::new(&datapack, &random_state, &settings.noise_router).unwrap();
+        let chunk_pos = IVec2::new(2, -1);
+        let mut noise_chunk = NoiseChunk::new(
+            &settings,
+            &functions,
+            &random_state,
+            chunk_pos,
+            generation_context,
+        );
+        let terrain = ProtoChunk::from_terrain(&noise_chunk.fill());
+        let plains = Holder::Reference(IdentifierBuf::new("minecraft:plains").unwrap());
+
+        let mut carve = |carver: &ConfiguredWorldCarver, seed: u64, origin: IVec2| {
+            let mut context = CarvingContext::new(
+                &datapack,
+                generation_context,
+                &surface_system,
+                &mut noise_chunk,
+            );
+            let mut chunk = terrain.clone();
+            let mut mask = CarvingMask::new(0, 128);
+            let mut random = LegacyRandomSource::new(seed);
+            assert!(carver.is_start_chunk(&mut random));
+            carver
+                .carve(
+                    &mut context,
+                    &mut chunk,
+                    &mut |_| &plains,
+                    &mut random,
+                    origin,
+                    &mut mask,
+                )
+                .unwrap();
+            let mut carved = Vec::new();
+            for y in 0..128 {
+                for z in 0..16 {
+                    for x in 0..16 {
+                        let pos = IVec3::new((chunk_pos.x << 4) + x, y, (chunk_pos.y << 4) + z);
+                        let state = chunk.block_state(pos).name.path().to_owned();
+                        if mask.get(x, y, z) {
+                            carved.push((y, state));
+                        } else {
+                            assert_eq!(state, terrain.block_state(pos).name.path());
+                        }
+                    }
+                }
+            }
+            let positions: Vec<_> = mask.positions(chunk_pos).map(|pos| pos.y).collect();
+            assert_eq!(
+                positions,
+                carved.iter().map(|(y, _)| *y).collect::<Vec<_>>()
+            );
+            carved
+        };
+
+        // the number of carved blocks at each y, from vanilla's `CaveWorldCarver`
+        let counts = |carved: &[(i32, String)]| {
+            let mut counts = BTreeMap::new();
+            for (y, _) in carved {
+                *counts.entry(*y).or_insert(0) += 1;
+            }
+            counts.into_iter().collect::<Vec<_>>()
+        };
+        let seed = 6;
+        let carved = carve(&carver("minecraft:cave", CAVE, ""), seed, chunk_pos);
+        assert_eq!(
+            counts(&carved),
+            [
+                (37, 24),
+                (38, 43),
+                (39, 81),
+                (40, 142),
+                (41, 160),
+                (42, 141),
+                (43, 155),
+                (44, 156),
+                (45, 138),
+                (46, 107),
+                (47, 113),
+                (48, 96),
+                (49, 77),
+                (50, 45),
+            ]
+        );
+        // a cave started in another chunk only carves the part reaching this one
+        let reaching = carve(&carver("minecraft:cave", CAVE, ""), 157, IVec2::new(3, 3));
+        assert_eq!(counts(&reaching), [(30, 18), (31, 18), (32, 5)]);
+        for (y, state) in &carved {
+            match y {
+                ..=8 => assert_eq!(state, "lava"),
+                _ => assert_eq!(state, "air"),
+            }
+        }
+
+        // debug mode carves the same blocks, replacing the aquifer's air with the debug state
+        let debug_settings = r#", "debug_settings": {
+            "debug_mode": true, "air_state": { "Name": "minecraft:glass" }
+        }"#;
+        let debug_carved = carve(
+            &carver("minecraft:cave", CAVE, debug_settings),
+            seed,
+            chunk_pos,
+        );
+        assert_eq!(debug_carved.len(), carved.len());
+        for (y, state) in &debug_carved {
+            match y {
+                ..=8 => assert_eq!(state, "lava"),
+                _ => assert_eq!(state, "glass"),
+            }
+        }
+
+        let nether_carved = carve(&carver("minecraft:nether_cave", CAVE, ""), seed, chunk_pos);
+        assert!(!nether_carved.is_empty());
+        for (y, state) in &nether_carved {
+            match y {
+                ..=31 => assert_eq!(state, "lava"),
+                32..64 => assert_eq!(state, "cave_air"),
+                _ => assert_eq!(state, "air"),
+            }
+        }
+
+        let canyon_carved = carve(&carver("minecraft:canyon", CANYON, ""), seed, chunk_pos);
+        assert!(!canyon_carved.is_empty());
+        for (y, state) in &canyon_carved {
+            match y {
+                ..=8 => assert_eq!(state, "lava"),
+                _ => assert_eq!(state, "air"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_apply_carvers() {
+        let cave = format!(
+            r#"{{
+                "type": "minecraft:cave",
+                "probability": 0.2,
+                "y": {{ "absolute": 40 }},
+                "yScale": 1.5,
+                "lava_level": {{ "absolute": 8 }},
+                "replaceable": ["minecraft:stone"],
+                {CAVE}
+            }}"#
+        );
+        let plains = format!(
+            r##"{{
+                "has_precipitation": true, "temperature": 0.8, "downfall": 0.4,
+                "effects": {{
+                    "fog_color": 0, "water_color": 0, "water_fog_color": 0, "sky_color": 0
+                }},
+                "carvers": {{
+                    "air": "#minecraft:caves",
+                    "liquid": [{{
+                        "type": "minecraft:canyon",
+                        "probability": 0.05,
+                        "y": {{ "absolute": 40 }},
+                        "yScale": 3,
+                        "lava_level": {{ "absolute": 8 }},
+                        "replaceable": ["minecraft:stone"],
+                        {CANYON}
+                    }}]
+                }},
+                "features": [],
+                "spawners": {{}},
+                "spawn_costs": {{}}
+            }}"##
+        );
+        let datapack = datapack(
+            noise_files(&SURFACE_NOISES, SURFACE_NOISE)
+                .map(|(path, noise)| (path, noise.to_owned()))
+                .chain([
+                    ("worldgen/configured_carver/cave.json".to_owned(), cave),
+                    (
+                        "tags/worldgen/configured_carver/caves.json".to_owned(),
+                        r#"{ "values": ["minecraft:cave"] }"#.to_owned(),
+                    ),
+                    ("worldgen/biome/plains.json".to_owned(), plains),
+                ]),
+        );
+        let settings = settings();
+        let random_state = RandomState::from_settings(&datapack, &settings, 0);
+        let generation_context = WorldGenerationContext::from_settings(&settings.noise);
+        let surface_system =
+            SurfaceSystem::new(&datapack, &random_state, &settings, generation_context).unwrap();
+        let functions =
+            TerrainFunctions::new(&datapack, &random_state, &settings.noise_router).unwrap();
+        let climate =
+            ClimateFunctions::new(&datapack, &random_state, &settings.noise_router).unwrap();
+        let mut climate_sampler = climate.sampler();
+        let plains = Holder::Reference(IdentifierBuf::new("minecraft:plains").unwrap());
+        let biome_source = FixedBiomeSourceImpl::from_biome(&plains);
+        let seed = 0;
+        let chunk_pos = IVec2::new(2, -1);
+        let mut noise_chunk = NoiseChunk::new(
+            &settings,
+            &functions,
+            &random_state,
+            chunk_pos,
+            generation_context,
+        );
+        let terrain = ProtoChunk::from_terrain(&noise_chunk.fill());
+        let mut context = CarvingContext::new(
+            &datapack,
+            generation_context,
+            &surface_system,
+            &mut noise_chunk,
+        );
+
+        let mut chunk = terrain.clone();
+        assert!(chunk.carving_mask(CarvingStep::Air).is_none());
+        for step in [CarvingStep::Air, CarvingStep::Liquid] {
+            apply_carvers(
+                &mut context,
+                &mut chunk,
+                seed,
+                step,
+                &biome_source,
+                BiomeManager::new(seed),
+                &mut climate_sampler,
+            )
+            .unwrap();
+        }
+
+        let air_mask = chunk.carving_mask(CarvingStep::Air).unwrap();
+        let liquid_mask = chunk.carving_mask(CarvingStep::Liquid).unwrap();
+        let mut carved = [0, 0];
+        for y in 0..128 {
+            for z in 0..16 {
+                for x in 0..16 {
+                    let pos = IVec3::new((chunk_pos.x << 4) + x, y, (chunk_pos.y << 4) + z);
+                    let masks = [air_mask.get(x, y, z), liquid_mask.get(x, y, z)];
+                    // the liquid step can't carve the air it doesn't replace
+                    assert!(!(masks[0] && masks[1]));
+                    if masks[0] || masks[1] {
+                        assert!(chunk.block_state(pos).is_air() || y <= 8);
+                        carved[masks[1] as usize] += 1;
+                    } else {
+                        assert_eq!(chunk.block_state(pos), terrain.block_state(pos));
+                    }
+                }
+            }
+        }
+        assert!(carved[0] > 0);
+        assert!(carved[1] > 0);
+    }
+}
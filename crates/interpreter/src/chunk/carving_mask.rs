@@ -0,0 +1,67 @@
+use glam::{IVec2, IVec3};
+
+/// The blocks of a chunk that a carving step has carved, so that overlapping carvers don't carve
+/// them again. Equivalent to vanilla's `CarvingMask`.
+#[derive(Debug, Clone)]
+pub struct CarvingMask {
+    min_y: i32,
+    /// A bit per block, indexed by `(y - min_y) << 8 | z << 4 | x`.
+    bits: Vec<u64>,
+}
+
+impl CarvingMask {
+    pub fn new(min_y: i32, height: i32) -> Self {
+        CarvingMask {
+            min_y,
+            bits: vec![0; (16 * 16 * height as usize).div_ceil(64)],
+        }
+    }
+
+    /// Whether the block at chunk-local x and z and absolute y has been carved.
+    #[inline]
+    pub fn get(&self, x: i32, y: i32, z: i32) -> bool {
+        let index = self.index(x, y, z);
+        self.bits[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    #[inline]
+    pub fn set(&mut self, x: i32, y: i32, z: i32) {
+        let index = self.index(x, y, z);
+        self.bits[index / 64] |= 1 << (index % 64);
+    }
+
+    /// The carved blocks of the chunk at `chunk_pos`, ordered by y, then z, then x. This is what
+    /// the `carving_mask` placement modifier places features at. Equivalent to
+    /// `CarvingMask.stream`.
+    pub fn positions(&self, chunk_pos: IVec2) -> impl Iterator<Item = IVec3> + '_ {
+        let min_block: IVec2 = chunk_pos << 4;
+        let min_y = self.min_y;
+        self.bits
+            .iter()
+            .enumerate()
+            .flat_map(|(word_index, &word)| {
+                let mut word = word;
+                std::iter::from_fn(move || {
+                    if word == 0 {
+                        return None;
+                    }
+                    let bit = word.trailing_zeros() as usize;
+                    word &= word - 1;
+                    Some(word_index * 64 + bit)
+                })
+            })
+            .map(move |index| {
+                let index = index as i32;
+                IVec3::new(
+                    min_block.x + (index & 15),
+                    min_y + (index >> 8),
+                    min_block.y + (index >> 4 & 15),
+                )
+            })
+    }
+
+    #[inline]
+    fn index(&self, x: i32, y: i32, z: i32) -> usize {
+        ((x & 15) | (z & 15) << 4 | (y - self.min_y) << 8) as usize
+    }
+}
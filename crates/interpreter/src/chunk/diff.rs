@@ -0,0 +1,112 @@
+use crate::biomes::QuartPos;
+use crate::chunk::heightmap::HEIGHTMAP_TYPES;
+use crate::chunk::proto_chunk::ProtoChunk;
+use crate::chunk::ChunkAccess;
+use datapack::data::block_state::BlockState;
+use glam::{IVec2, IVec3};
+use util::heightmap_type::HeightmapType;
+use util::identifier::IdentifierBuf;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockDiff {
+    pub pos: IVec3,
+    pub ours: BlockState,
+    pub reference: BlockState,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BiomeDiff {
+    pub pos: QuartPos,
+    pub ours: IdentifierBuf,
+    pub reference: IdentifierBuf,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeightmapDiff {
+    pub heightmap: HeightmapType,
+    /// The chunk-local x and z of the column.
+    pub column: IVec2,
+    pub ours: i32,
+    pub reference: i32,
+}
+
+/// The differences between a generated chunk and a reference chunk, e.g. one generated by vanilla.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChunkDiff {
+    pub blocks: Vec<BlockDiff>,
+    pub biomes: Vec<BiomeDiff>,
+    pub heightmaps: Vec<HeightmapDiff>,
+}
+
+impl ChunkDiff {
+    /// Compares two chunks at the same position. Blocks are compared over the height of both
+    /// chunks, with air outside of a chunk, while biomes are only compared where both chunks have
+    /// them. Heightmaps are compared as heights, so chunks with different minimum ys only differ
+    /// where the heights do.
+    pub fn compare(ours: &ProtoChunk, reference: &ProtoChunk) -> Self {
+        let mut diff = ChunkDiff::default();
+        let min_block: IVec2 = ours.pos() << 4;
+
+        let min_y = ours.min_y().min(reference.min_y());
+        let max_y = (ours.min_y() + ours.height()).max(reference.min_y() + reference.height());
+        for y in min_y..max_y {
+            for z in 0..16 {
+                for x in 0..16 {
+                    let pos = IVec3::new(min_block.x + x, y, min_block.y + z);
+                    let ours = ours.block_state(pos);
+                    let reference = reference.block_state(pos);
+                    if ours != reference {
+                        diff.blocks.push(BlockDiff {
+                            pos,
+                            ours: ours.clone(),
+                            reference: reference.clone(),
+                        });
+                    }
+                }
+            }
+        }
+
+        let min_quart_y = ours.min_y().max(reference.min_y()) >> 2;
+        let max_quart_y =
+            (ours.min_y() + ours.height()).min(reference.min_y() + reference.height()) >> 2;
+        for y in min_quart_y..max_quart_y {
+            for z in 0..4 {
+                for x in 0..4 {
+                    let pos = QuartPos::new((min_block.x >> 2) + x, y, (min_block.y >> 2) + z);
+                    let ours = ours.biome(pos);
+                    let reference = reference.biome(pos);
+                    if ours != reference {
+                        diff.biomes.push(BiomeDiff {
+                            pos,
+                            ours: ours.clone(),
+                            reference: reference.clone(),
+                        });
+                    }
+                }
+            }
+        }
+
+        for heightmap in HEIGHTMAP_TYPES {
+            for z in 0..16 {
+                for x in 0..16 {
+                    let ours = ours.heightmap_height(heightmap, x, z);
+                    let reference = reference.heightmap_height(heightmap, x, z);
+                    if ours != reference {
+                        diff.heightmaps.push(HeightmapDiff {
+                            heightmap,
+                            column: IVec2::new(x, z),
+                            ours,
+                            reference,
+                        });
+                    }
+                }
+            }
+        }
+
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty() && self.biomes.is_empty() && self.heightmaps.is_empty()
+    }
+}
@@ -0,0 +1,133 @@
+use crate::block_state::BlockStateExt;
+use crate::sealed::Sealed;
+use datapack::data::block_state::BlockState;
+use util::heightmap_type::HeightmapType;
+
+/// All heightmap types, in the order chunks store them.
+pub const HEIGHTMAP_TYPES: [HeightmapType; 6] = [
+    HeightmapType::WorldSurfaceWg,
+    HeightmapType::WorldSurface,
+    HeightmapType::OceanFloorWg,
+    HeightmapType::OceanFloor,
+    HeightmapType::MotionBlocking,
+    HeightmapType::MotionBlockingNoLeaves,
+];
+
+pub trait HeightmapTypeExt: Sealed {
+    /// Whether a block counts towards this heightmap, equivalent to vanilla's
+    /// `Heightmap.Types.isOpaque`.
+    fn is_opaque(&self, state: &BlockState) -> bool;
+    /// The index of this type in [`HEIGHTMAP_TYPES`].
+    fn index(&self) -> usize;
+    /// The name of this type in saved chunks.
+    fn serialization_key(&self) -> &'static str;
+}
+
+impl Sealed for HeightmapType {}
+
+impl HeightmapTypeExt for HeightmapType {
+    fn is_opaque(&self, state: &BlockState) -> bool {
+        match self {
+            HeightmapType::WorldSurfaceWg | HeightmapType::WorldSurface => !state.is_air(),
+            HeightmapType::OceanFloorWg | HeightmapType::OceanFloor => state.blocks_motion(),
+            HeightmapType::MotionBlocking => state.blocks_motion() || state.has_fluid(),
+            HeightmapType::MotionBlockingNoLeaves => {
+                (state.blocks_motion() || state.has_fluid()) && !state.is_leaves()
+            }
+        }
+    }
+
+    fn index(&self) -> usize {
+        match self {
+            HeightmapType::WorldSurfaceWg => 0,
+            HeightmapType::WorldSurface => 1,
+            HeightmapType::OceanFloorWg => 2,
+            HeightmapType::OceanFloor => 3,
+            HeightmapType::MotionBlocking => 4,
+            HeightmapType::MotionBlockingNoLeaves => 5,
+        }
+    }
+
+    fn serialization_key(&self) -> &'static str {
+        match self {
+            HeightmapType::WorldSurfaceWg => "WORLD_SURFACE_WG",
+            HeightmapType::WorldSurface => "WORLD_SURFACE",
+            HeightmapType::OceanFloorWg => "OCEAN_FLOOR_WG",
+            HeightmapType::OceanFloor => "OCEAN_FLOOR",
+            HeightmapType::MotionBlocking => "MOTION_BLOCKING",
+            HeightmapType::MotionBlockingNoLeaves => "MOTION_BLOCKING_NO_LEAVES",
+        }
+    }
+}
+
+/// The lowest free y of every column in a chunk for one heightmap type, equivalent to vanilla's
+/// `Heightmap`.
+#[derive(Debug, Clone)]
+pub struct Heightmap {
+    kind: HeightmapType,
+    min_y: i32,
+    /// The first available y of each column, relative to `min_y`, indexed by `z * 16 + x`.
+    data: Box<[u16; 256]>,
+}
+
+impl Heightmap {
+    /// Creates a heightmap of a chunk without any opaque blocks.
+    pub fn new(kind: HeightmapType, min_y: i32) -> Self {
+        Heightmap {
+            kind,
+            min_y,
+            data: Box::new([0; 256]),
+        }
+    }
+
+    pub fn kind(&self) -> HeightmapType {
+        self.kind
+    }
+
+    /// Returns the y above the highest opaque block at chunk-local `x` and `z`, or the minimum y of
+    /// the chunk if there is none.
+    #[inline]
+    pub fn first_available(&self, x: i32, z: i32) -> i32 {
+        self.min_y + self.data[Self::index(x, z)] as i32
+    }
+
+    #[inline]
+    pub fn set_first_available(&mut self, x: i32, z: i32, y: i32) {
+        self.data[Self::index(x, z)] = (y - self.min_y) as u16;
+    }
+
+    /// Updates the column after the block at `y` was set to `state`, scanning down with
+    /// `block_at` if the highest opaque block was removed. Returns whether the height changed.
+    pub fn update<'s>(
+        &mut self,
+        x: i32,
+        y: i32,
+        z: i32,
+        state: &BlockState,
+        block_at: impl Fn(i32) -> &'s BlockState,
+    ) -> bool {
+        let first_available = self.first_available(x, z);
+        if y <= first_available - 2 {
+            return false;
+        }
+        if self.kind.is_opaque(state) {
+            if y >= first_available {
+                self.set_first_available(x, z, y + 1);
+                return true;
+            }
+        } else if first_available - 1 == y {
+            let top = (self.min_y..y)
+                .rev()
+                .find(|&y| self.kind.is_opaque(block_at(y)))
+                .map_or(self.min_y, |y| y + 1);
+            self.set_first_available(x, z, top);
+            return true;
+        }
+        false
+    }
+
+    #[inline]
+    fn index(x: i32, z: i32) -> usize {
+        ((z & 15) * 16 + (x & 15)) as usize
+    }
+}
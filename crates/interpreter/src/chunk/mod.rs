@@ -0,0 +1,38 @@
+pub mod carving_mask;
+pub mod diff;
+pub mod heightmap;
+pub mod proto_chunk;
+pub mod section;
+pub mod world_gen_region;
+
+use datapack::data::block_state::BlockState;
+use glam::{IVec2, IVec3};
+use util::heightmap_type::HeightmapType;
+
+/// A chunk that can be read and modified during world generation.
+pub trait ChunkAccess {
+    /// The position of this chunk, in chunk coordinates.
+    fn pos(&self) -> IVec2;
+
+    /// The lowest block y in this chunk.
+    fn min_y(&self) -> i32;
+
+    /// The number of blocks in this chunk vertically.
+    fn height(&self) -> i32;
+
+    /// Returns the y of the highest block matching the heightmap at the given chunk-local
+    /// coordinates, or one below [`min_y`](Self::min_y) if there is none. Equivalent to vanilla's
+    /// `ChunkAccess.getHeight`.
+    fn heightmap_height(&self, heightmap: HeightmapType, x: i32, z: i32) -> i32;
+
+    /// Returns the block state at a block position, or air outside of the chunk.
+    fn block_state(&self, pos: IVec3) -> &BlockState;
+
+    /// Sets the block state at a block position, updating the heightmaps. Positions outside of the
+    /// chunk are ignored.
+    fn set_block_state(&mut self, pos: IVec3, state: BlockState);
+
+    /// Marks a block to be updated when the chunk is loaded, such as a fluid that should start
+    /// flowing. Equivalent to vanilla's `ChunkAccess.markPosForPostprocessing`.
+    fn mark_pos_for_postprocessing(&mut self, pos: IVec3);
+}
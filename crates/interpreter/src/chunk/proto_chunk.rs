@@ -0,0 +1,319 @@
+use crate::biomes::QuartPos;
+use crate::block_state::{self, BlockStateExt};
+use crate::chunk::carving_mask::CarvingMask;
+use crate::chunk::heightmap::{Heightmap, HeightmapTypeExt, HEIGHTMAP_TYPES};
+use crate::chunk::section::ChunkSection;
+use crate::chunk::ChunkAccess;
+use crate::noise_chunk::TerrainBlocks;
+use ahash::AHashMap;
+use datapack::data::biome::Biome;
+use datapack::data::block_state::BlockState;
+use datapack::data::holder::Holder;
+use datapack::data::step::CarvingStep;
+use glam::{IVec2, IVec3};
+use util::heightmap_type::HeightmapType;
+use util::identifier::IdentifierBuf;
+
+/// A chunk that is being generated, storing its blocks in sections and keeping all of its
+/// heightmaps up to date. Equivalent to vanilla's `ProtoChunk`.
+#[derive(Debug, Clone)]
+pub struct ProtoChunk {
+    pos: IVec2,
+    min_y: i32,
+    height: i32,
+    sections: Vec<ChunkSection>,
+    heightmaps: [Heightmap; 6],
+    /// The blocks marked for post-processing in each section, packed by
+    /// [`pack_offset`](Self::pack_offset).
+    post_processing: Vec<Vec<u16>>,
+    carving_masks: AHashMap<CarvingStep, CarvingMask>,
+}
+
+impl ProtoChunk {
+    /// Creates a chunk filled with air. Like in vanilla, `min_y` and `height` must be multiples of
+    /// 16.
+    pub fn new(pos: IVec2, min_y: i32, height: i32) -> Self {
+        ProtoChunk {
+            pos,
+            min_y,
+            height,
+            sections: vec![ChunkSection::empty(); (height >> 4) as usize],
+            heightmaps: HEIGHTMAP_TYPES.map(|kind| Heightmap::new(kind, min_y)),
+            post_processing: vec![Vec::new(); (height >> 4) as usize],
+            carving_masks: AHashMap::new(),
+        }
+    }
+
+    /// Creates a chunk containing the terrain filled by a noise chunk, with its fluids marked for
+    /// post-processing.
+    pub fn from_terrain(terrain: &TerrainBlocks) -> Self {
+        let mut chunk = ProtoChunk::new(terrain.chunk_pos(), terrain.min_y(), terrain.height());
+        for y in chunk.min_y..chunk.min_y + chunk.height {
+            let section = &mut chunk.sections[((y - chunk.min_y) >> 4) as usize];
+            for z in 0..16 {
+                for x in 0..16 {
+                    let state = terrain.get(x, y, z);
+                    if state != block_state::air() {
+                        section.set(x, y, z, state.clone());
+                    }
+                }
+            }
+        }
+        for &pos in terrain.post_processing() {
+            chunk.mark_pos_for_postprocessing(pos);
+        }
+        chunk.prime_heightmaps();
+        chunk
+    }
+
+    pub fn sections(&self) -> &[ChunkSection] {
+        &self.sections
+    }
+
+    pub(crate) fn sections_mut(&mut self) -> &mut [ChunkSection] {
+        &mut self.sections
+    }
+
+    /// The section y of the lowest section.
+    pub fn min_section(&self) -> i32 {
+        self.min_y >> 4
+    }
+
+    /// Returns the biome at a quart position in this chunk, clamping the y to the chunk, like
+    /// vanilla's `ChunkAccess.getNoiseBiome`.
+    pub fn biome(&self, pos: QuartPos) -> &IdentifierBuf {
+        let min_quart_y = self.min_y >> 2;
+        let y = pos
+            .0
+            .y
+            .clamp(min_quart_y, min_quart_y + (self.height >> 2) - 1);
+        self.sections[((y - min_quart_y) >> 2) as usize].biome(QuartPos::new(pos.0.x, y, pos.0.z))
+    }
+
+    /// Sets the biome of every quart in this chunk to the one returned by `biome_at`, equivalent to
+    /// vanilla's `ChunkAccess.fillBiomesFromNoise`. Biomes that aren't registered can't be stored
+    /// and leave the quart unchanged.
+    pub fn fill_biomes<'b>(&mut self, mut biome_at: impl FnMut(QuartPos) -> &'b Holder<Biome>) {
+        let min_quart: IVec2 = self.pos << 2;
+        let min_quart_y = self.min_y >> 2;
+        for (index, section) in self.sections.iter_mut().enumerate() {
+            let section_quart_y = min_quart_y + index as i32 * 4;
+            for y in 0..4 {
+                for z in 0..4 {
+                    for x in 0..4 {
+                        let pos =
+                            QuartPos::new(min_quart.x + x, section_quart_y + y, min_quart.y + z);
+                        if let Holder::Reference(id) = biome_at(pos) {
+                            section.set_biome(QuartPos::new(x, y, z), id.clone());
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn heightmap(&self, kind: HeightmapType) -> &Heightmap {
+        &self.heightmaps[kind.index()]
+    }
+
+    pub(crate) fn heightmap_mut(&mut self, kind: HeightmapType) -> &mut Heightmap {
+        &mut self.heightmaps[kind.index()]
+    }
+
+    /// Recomputes all heightmaps from the blocks of the chunk, equivalent to vanilla's
+    /// `Heightmap.primeHeightmaps`.
+    pub fn prime_heightmaps(&mut self) {
+        for z in 0..16 {
+            for x in 0..16 {
+                for heightmap in &mut self.heightmaps {
+                    let kind = heightmap.kind();
+                    let top = (self.min_y..self.min_y + self.height)
+                        .rev()
+                        .find(|&y| {
+                            kind.is_opaque(section_block(
+                                &self.sections,
+                                self.min_y,
+                                IVec3::new(x, y, z),
+                            ))
+                        })
+                        .map_or(self.min_y, |y| y + 1);
+                    heightmap.set_first_available(x, z, top);
+                }
+            }
+        }
+    }
+
+    /// The packed section-local positions marked for post-processing, per section.
+    pub fn post_processing(&self) -> &[Vec<u16>] {
+        &self.post_processing
+    }
+
+    /// Marks a packed section-local position in the section at `section_index` for
+    /// post-processing, like vanilla's `ProtoChunk.addPackedPostProcess`.
+    pub fn add_packed_post_process(&mut self, packed: u16, section_index: usize) {
+        self.post_processing[section_index].push(packed);
+    }
+
+    /// Packs the section-local coordinates of a position, `ProtoChunk.packOffsetCoordinates`.
+    pub fn pack_offset(pos: IVec3) -> u16 {
+        ((pos.x & 15) | (pos.y & 15) << 4 | (pos.z & 15) << 8) as u16
+    }
+
+    /// The blocks carved by a carving step, or `None` if the step hasn't run on this chunk.
+    /// Equivalent to vanilla's `ProtoChunk.getCarvingMask`.
+    pub fn carving_mask(&self, step: CarvingStep) -> Option<&CarvingMask> {
+        self.carving_masks.get(&step)
+    }
+
+    pub fn set_carving_mask(&mut self, step: CarvingStep, mask: CarvingMask) {
+        self.carving_masks.insert(step, mask);
+    }
+
+    /// Removes the carving mask of a step so that it can be carved along with the chunk, creating
+    /// an empty one if there is none.
+    pub(crate) fn take_carving_mask(&mut self, step: CarvingStep) -> CarvingMask {
+        self.carving_masks
+            .remove(&step)
+            .unwrap_or_else(|| CarvingMask::new(self.min_y, self.height))
+    }
+
+    fn contains(&self, pos: IVec3) -> bool {
+        pos.x >> 4 == self.pos.x
+            && pos.z >> 4 == self.pos.y
+            && pos.y >= self.min_y
+            && pos.y < self.min_y + self.height
+    }
+}
+
+/// Returns the block at a position inside the chunk the sections belong to.
+#[inline]
+fn section_block(sections: &[ChunkSection], min_y: i32, pos: IVec3) -> &BlockState {
+    sections[((pos.y - min_y) >> 4) as usize].get(pos.x, pos.y, pos.z)
+}
+
+impl ChunkAccess for ProtoChunk {
+    fn pos(&self) -> IVec2 {
+        self.pos
+    }
+
+    fn min_y(&self) -> i32 {
+        self.min_y
+    }
+
+    fn height(&self) -> i32 {
+        self.height
+    }
+
+    fn heightmap_height(&self, heightmap: HeightmapType, x: i32, z: i32) -> i32 {
+        self.heightmap(heightmap).first_available(x, z) - 1
+    }
+
+    fn block_state(&self, pos: IVec3) -> &BlockState {
+        if self.contains(pos) {
+            section_block(&self.sections, self.min_y, pos)
+        } else {
+            block_state::air()
+        }
+    }
+
+    fn set_block_state(&mut self, pos: IVec3, state: BlockState) {
+        if !self.contains(pos) {
+            return;
+        }
+        let section = &mut self.sections[((pos.y - self.min_y) >> 4) as usize];
+        if section.is_empty() && state.is_air() {
+            return;
+        }
+        section.set(pos.x, pos.y, pos.z, state);
+
+        let state = section_block(&self.sections, self.min_y, pos);
+        for heightmap in &mut self.heightmaps {
+            heightmap.update(pos.x & 15, pos.y, pos.z & 15, state, |y| {
+                section_block(&self.sections, self.min_y, IVec3::new(pos.x, y, pos.z))
+            });
+        }
+    }
+
+    fn mark_pos_for_postprocessing(&mut self, pos: IVec3) {
+        if pos.y >= self.min_y && pos.y < self.min_y + self.height {
+            self.post_processing[((pos.y - self.min_y) >> 4) as usize].push(Self::pack_offset(pos));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::block_state;
+    use crate::chunk::heightmap::HEIGHTMAP_TYPES;
+    use crate::chunk::proto_chunk::ProtoChunk;
+    use crate::chunk::ChunkAccess;
+    use datapack::data::block_state::BlockState;
+    use glam::{IVec2, IVec3};
+    use std::collections::BTreeMap;
+    use util::identifier::IdentifierBuf;
+
+    fn heights(chunk: &ProtoChunk, x: i32, z: i32) -> [i32; 6] {
+        HEIGHTMAP_TYPES.map(|heightmap| chunk.heightmap_height(heightmap, x, z))
+    }
+
+    #[test]
+    fn test_heightmaps() {
+        let mut chunk = ProtoChunk::new(IVec2::new(-1, 2), -64, 128);
+        let x = -13;
+        let z = 37;
+        assert_eq!(heights(&chunk, x, z), [-65; 6]);
+
+        chunk.set_block_state(IVec3::new(x, -10, z), block_state::simple_state("stone"));
+        chunk.set_block_state(
+            IVec3::new(x, -5, z),
+            BlockState {
+                name: IdentifierBuf::new("water").unwrap(),
+                properties: BTreeMap::from([("level".to_owned(), "0".to_owned())]),
+            },
+        );
+        chunk.set_block_state(IVec3::new(x, 0, z), block_state::simple_state("oak_leaves"));
+        chunk.set_block_state(IVec3::new(x, 20, z), block_state::simple_state("poppy"));
+        assert_eq!(
+            chunk.block_state(IVec3::new(x, 0, z)).name.path(),
+            "oak_leaves"
+        );
+        assert_eq!(heights(&chunk, x, z), [20, 20, 0, 0, 0, -5]);
+        // other columns and chunks are unaffected
+        assert_eq!(heights(&chunk, x + 1, z), [-65; 6]);
+        chunk.set_block_state(
+            IVec3::new(x + 16, 10, z),
+            block_state::simple_state("stone"),
+        );
+        assert_eq!(
+            chunk.block_state(IVec3::new(x + 16, 10, z)),
+            block_state::air()
+        );
+
+        // removing the highest block scans down to the next one
+        chunk.set_block_state(IVec3::new(x, 20, z), block_state::air().clone());
+        assert_eq!(heights(&chunk, x, z), [0, 0, 0, 0, 0, -5]);
+        chunk.set_block_state(IVec3::new(x, 0, z), block_state::air().clone());
+        assert_eq!(heights(&chunk, x, z), [-5, -5, -10, -10, -5, -5]);
+
+        let mut primed = chunk.clone();
+        primed.prime_heightmaps();
+        assert_eq!(heights(&primed, x, z), heights(&chunk, x, z));
+    }
+
+    #[test]
+    fn test_non_solid_heightmaps() {
+        let mut chunk = ProtoChunk::new(IVec2::ZERO, 0, 32);
+        let chain = |axis: &str| BlockState {
+            name: IdentifierBuf::new("chain").unwrap(),
+            properties: BTreeMap::from([("axis".to_owned(), axis.to_owned())]),
+        };
+        chunk.set_block_state(IVec3::new(0, 5, 0), block_state::simple_state("stone"));
+        chunk.set_block_state(IVec3::new(0, 6, 0), chain("x"));
+        chunk.set_block_state(IVec3::new(0, 7, 0), block_state::simple_state("sculk_vein"));
+        // neither the horizontal chain nor the sculk vein block motion
+        assert_eq!(heights(&chunk, 0, 0), [7, 7, 5, 5, 5, 5]);
+
+        chunk.set_block_state(IVec3::new(0, 8, 0), chain("y"));
+        assert_eq!(heights(&chunk, 0, 0), [8, 8, 8, 8, 8, 8]);
+    }
+}
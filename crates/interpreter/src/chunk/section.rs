@@ -0,0 +1,219 @@
+use crate::biomes::QuartPos;
+use crate::block_state::{self, BlockStateExt};
+use datapack::data::block_state::BlockState;
+use util::identifier::IdentifierBuf;
+
+/// The number of blocks in a section.
+pub const SECTION_VOLUME: usize = 16 * 16 * 16;
+/// The number of biome quarts in a section.
+pub const SECTION_BIOME_VOLUME: usize = 4 * 4 * 4;
+
+/// Values stored as indices into a palette, equivalent to vanilla's `PalettedContainer`. The size
+/// of a container is `1 << (3 * BITS)`, i.e. `BITS` bits per axis.
+#[derive(Debug, Clone)]
+pub struct PalettedContainer<T, const BITS: u32> {
+    palette: Vec<T>,
+    /// The palette index of each value, indexed by `(y << 2 * BITS) | (z << BITS) | x`. Empty while
+    /// the palette has a single entry, in which case every value is that entry.
+    indices: Vec<u16>,
+}
+
+impl<T: Clone + PartialEq, const BITS: u32> PalettedContainer<T, BITS> {
+    pub const SIZE: usize = 1 << (3 * BITS);
+    const MASK: i32 = (1 << BITS) - 1;
+
+    /// Creates a container filled with `value`.
+    pub fn filled(value: T) -> Self {
+        PalettedContainer {
+            palette: vec![value],
+            indices: Vec::new(),
+        }
+    }
+
+    /// Creates a container from a palette and the index of every value into it, which must be of
+    /// length [`SIZE`](Self::SIZE).
+    pub fn from_packed(palette: Vec<T>, indices: Vec<u16>) -> Self {
+        debug_assert_eq!(indices.len(), Self::SIZE);
+        debug_assert!(indices
+            .iter()
+            .all(|&index| (index as usize) < palette.len()));
+        let indices = if palette.len() == 1 {
+            Vec::new()
+        } else {
+            indices
+        };
+        PalettedContainer { palette, indices }
+    }
+
+    /// The values in this container. This may contain values that are no longer used.
+    pub fn palette(&self) -> &[T] {
+        &self.palette
+    }
+
+    /// Returns the index into [`palette`](Self::palette) of the value at the given coordinates,
+    /// which are taken modulo the size of the container.
+    #[inline]
+    pub fn palette_index(&self, x: i32, y: i32, z: i32) -> usize {
+        if self.indices.is_empty() {
+            0
+        } else {
+            self.indices[Self::index(x, y, z)] as usize
+        }
+    }
+
+    #[inline]
+    pub fn get(&self, x: i32, y: i32, z: i32) -> &T {
+        &self.palette[self.palette_index(x, y, z)]
+    }
+
+    /// Sets the value at the given coordinates, returning the previous value.
+    pub fn set(&mut self, x: i32, y: i32, z: i32, value: T) -> T {
+        let old_index = self.palette_index(x, y, z);
+        if self.palette[old_index] == value {
+            return value;
+        }
+        let new_index = match self.palette.iter().position(|entry| *entry == value) {
+            Some(index) => index,
+            None => {
+                self.palette.push(value);
+                self.palette.len() - 1
+            }
+        };
+        if self.indices.is_empty() {
+            self.indices = vec![0; Self::SIZE];
+        }
+        self.indices[Self::index(x, y, z)] = new_index as u16;
+        self.palette[old_index].clone()
+    }
+
+    /// Returns the palette without unused entries, in the order they first occur, and the index of
+    /// every value into it. This is how vanilla's `PalettedContainer.pack` stores containers.
+    pub fn packed(&self) -> (Vec<&T>, Vec<u16>) {
+        if self.indices.is_empty() {
+            return (vec![&self.palette[0]], vec![0; Self::SIZE]);
+        }
+        let mut remapped = vec![u16::MAX; self.palette.len()];
+        let mut palette = Vec::new();
+        let indices = self
+            .indices
+            .iter()
+            .map(|&index| {
+                let new_index = &mut remapped[index as usize];
+                if *new_index == u16::MAX {
+                    *new_index = palette.len() as u16;
+                    palette.push(&self.palette[index as usize]);
+                }
+                *new_index
+            })
+            .collect();
+        (palette, indices)
+    }
+
+    #[inline]
+    fn index(x: i32, y: i32, z: i32) -> usize {
+        (((y & Self::MASK) << (2 * BITS)) | ((z & Self::MASK) << BITS) | (x & Self::MASK)) as usize
+    }
+}
+
+/// A 16x16x16 section of a chunk with its blocks and 4x4x4 biomes, equivalent to vanilla's
+/// `LevelChunkSection`. Biomes are stored by id, since only registered biomes can be saved.
+#[derive(Debug, Clone)]
+pub struct ChunkSection {
+    states: PalettedContainer<BlockState, 4>,
+    biomes: PalettedContainer<IdentifierBuf, 2>,
+    non_air_count: u16,
+}
+
+impl ChunkSection {
+    /// Creates a section filled with `state`, in the plains biome.
+    pub fn filled(state: BlockState) -> Self {
+        let non_air_count = if state.is_air() {
+            0
+        } else {
+            SECTION_VOLUME as u16
+        };
+        ChunkSection {
+            states: PalettedContainer::filled(state),
+            biomes: PalettedContainer::filled(IdentifierBuf::new("minecraft:plains").unwrap()),
+            non_air_count,
+        }
+    }
+
+    pub fn new(
+        states: PalettedContainer<BlockState, 4>,
+        biomes: PalettedContainer<IdentifierBuf, 2>,
+    ) -> Self {
+        let non_air_count = if states.indices.is_empty() {
+            if states.palette[0].is_air() {
+                0
+            } else {
+                SECTION_VOLUME
+            }
+        } else {
+            states
+                .indices
+                .iter()
+                .filter(|&&index| !states.palette[index as usize].is_air())
+                .count()
+        };
+        ChunkSection {
+            states,
+            biomes,
+            non_air_count: non_air_count as u16,
+        }
+    }
+
+    /// Creates a section filled with air, in the plains biome.
+    pub fn empty() -> Self {
+        Self::filled(block_state::air().clone())
+    }
+
+    /// Whether the section only contains air.
+    pub fn is_empty(&self) -> bool {
+        self.non_air_count == 0
+    }
+
+    pub fn states(&self) -> &PalettedContainer<BlockState, 4> {
+        &self.states
+    }
+
+    pub fn biomes(&self) -> &PalettedContainer<IdentifierBuf, 2> {
+        &self.biomes
+    }
+
+    /// Returns the block at section-local coordinates.
+    #[inline]
+    pub fn get(&self, x: i32, y: i32, z: i32) -> &BlockState {
+        self.states.get(x, y, z)
+    }
+
+    /// Sets the block at section-local coordinates, returning the previous state.
+    pub fn set(&mut self, x: i32, y: i32, z: i32, state: BlockState) -> BlockState {
+        let is_air = state.is_air();
+        let old_state = self.states.set(x, y, z, state);
+        if !old_state.is_air() {
+            self.non_air_count -= 1;
+        }
+        if !is_air {
+            self.non_air_count += 1;
+        }
+        old_state
+    }
+
+    /// Returns the biome at section-local quart coordinates.
+    #[inline]
+    pub fn biome(&self, pos: QuartPos) -> &IdentifierBuf {
+        self.biomes.get(pos.0.x, pos.0.y, pos.0.z)
+    }
+
+    /// Sets the biome at section-local quart coordinates.
+    pub fn set_biome(&mut self, pos: QuartPos, biome: IdentifierBuf) {
+        self.biomes.set(pos.0.x, pos.0.y, pos.0.z, biome);
+    }
+}
+
+impl Default for ChunkSection {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
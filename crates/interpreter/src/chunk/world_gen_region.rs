@@ -0,0 +1,128 @@
+use crate::biomes::biome_manager::BiomeManager;
+use crate::biomes::QuartPos;
+use crate::block_state;
+use crate::chunk::proto_chunk::ProtoChunk;
+use crate::chunk::ChunkAccess;
+use datapack::data::biome::Biome;
+use datapack::data::block_state::BlockState;
+use datapack::data::holder::Holder;
+use datapack::{DataPack, DataPackResult};
+use glam::{IVec2, IVec3};
+use util::heightmap_type::HeightmapType;
+
+/// The chunks around a chunk being decorated, which its features can read and modify. Equivalent
+/// to vanilla's `WorldGenRegion` with a write radius of 1.
+#[derive(Debug)]
+pub struct WorldGenRegion<'c> {
+    center: IVec2,
+    /// The chunks from `center - 1` to `center + 1`, indexed by `(dz + 1) * 3 + dx + 1`.
+    chunks: Vec<&'c mut ProtoChunk>,
+    biome_manager: BiomeManager,
+}
+
+impl<'c> WorldGenRegion<'c> {
+    /// Creates the region around `center` from the chunks next to it. Chunks further away are
+    /// ignored. Panics if any of the 9 chunks is missing.
+    pub fn new(
+        center: IVec2,
+        chunks: impl IntoIterator<Item = &'c mut ProtoChunk>,
+        biome_manager: BiomeManager,
+    ) -> Self {
+        let mut slots: Vec<Option<&'c mut ProtoChunk>> = (0..9).map(|_| None).collect();
+        for chunk in chunks {
+            if let Some(index) = Self::index(center, chunk.pos()) {
+                slots[index] = Some(chunk);
+            }
+        }
+        let chunks = slots
+            .into_iter()
+            .map(|chunk| chunk.expect("missing chunk around the center of the region"))
+            .collect();
+        WorldGenRegion {
+            center,
+            chunks,
+            biome_manager,
+        }
+    }
+
+    fn index(center: IVec2, chunk_pos: IVec2) -> Option<usize> {
+        let offset = chunk_pos - center;
+        if offset.x.abs() > 1 || offset.y.abs() > 1 {
+            return None;
+        }
+        Some(((offset.y + 1) * 3 + offset.x + 1) as usize)
+    }
+
+    /// The position of the chunk being decorated.
+    pub fn center(&self) -> IVec2 {
+        self.center
+    }
+
+    pub fn center_chunk(&self) -> &ProtoChunk {
+        self.chunks[4]
+    }
+
+    pub fn chunks(&self) -> impl Iterator<Item = &ProtoChunk> {
+        self.chunks.iter().map(|chunk| &**chunk)
+    }
+
+    pub fn chunk(&self, chunk_pos: IVec2) -> Option<&ProtoChunk> {
+        Self::index(self.center, chunk_pos).map(|index| &*self.chunks[index])
+    }
+
+    pub fn min_y(&self) -> i32 {
+        self.center_chunk().min_y()
+    }
+
+    pub fn height(&self) -> i32 {
+        self.center_chunk().height()
+    }
+
+    /// Returns the block state at a block position, or air outside of the region.
+    pub fn block_state(&self, pos: IVec3) -> &BlockState {
+        match self.chunk(IVec2::new(pos.x >> 4, pos.z >> 4)) {
+            Some(chunk) => chunk.block_state(pos),
+            None => block_state::air(),
+        }
+    }
+
+    /// Whether a block position is in the region, `WorldGenRegion.ensureCanWrite`.
+    pub fn ensure_can_write(&self, pos: IVec3) -> bool {
+        Self::index(self.center, IVec2::new(pos.x >> 4, pos.z >> 4)).is_some()
+    }
+
+    /// Sets the block state at a block position, returning `false` if it's outside of the region.
+    pub fn set_block_state(&mut self, pos: IVec3, state: BlockState) -> bool {
+        let Some(index) = Self::index(self.center, IVec2::new(pos.x >> 4, pos.z >> 4)) else {
+            return false;
+        };
+        self.chunks[index].set_block_state(pos, state);
+        true
+    }
+
+    /// The y above the highest block matching the heightmap at a block column, equivalent to
+    /// `WorldGenRegion.getHeight`. Vanilla fails for columns outside of the region, here this is
+    /// the bottom of the world, which placement modifiers treat as having no surface.
+    pub fn first_available(&self, heightmap: HeightmapType, x: i32, z: i32) -> i32 {
+        match self.chunk(IVec2::new(x >> 4, z >> 4)) {
+            Some(chunk) => chunk.heightmap_height(heightmap, x & 15, z & 15) + 1,
+            None => self.min_y(),
+        }
+    }
+
+    /// The biome at a block position, picked from the biomes stored in the chunks. Biomes outside
+    /// of the region come from `uncached_biome`, which samples the biome source like vanilla's
+    /// `getUncachedNoiseBiome`. Equivalent to `WorldGenRegion.getBiome`.
+    pub fn biome<'a>(
+        &self,
+        datapack: &'a DataPack,
+        pos: IVec3,
+        uncached_biome: &mut dyn FnMut(QuartPos) -> &'a Holder<Biome>,
+    ) -> DataPackResult<&'a Biome> {
+        let quart = self.biome_manager.zoom(pos);
+        match self.chunk(IVec2::new(quart.0.x >> 2, quart.0.z >> 2)) {
+            Some(chunk) => Holder::<Biome>::resolve_reference(datapack, chunk.biome(quart)),
+            None => uncached_biome(quart).resolve(datapack),
+        }
+    }
+}
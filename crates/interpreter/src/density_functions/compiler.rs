@@ -0,0 +1,457 @@
+use crate::density_functions::graph::{
+    CacheKind, CompiledSpline, CompiledSplinePoint, DensityFunctionGraph, Node, NodeId,
+};
+use crate::density_functions::{
+    mul_constant_bounds, spaghetti_rarity_2d, spaghetti_rarity_3d, BinaryKind, DensityFunctionExt,
+    MappedKind,
+};
+use crate::random_state::RandomState;
+use ahash::AHashMap;
+use datapack::data::density_function::{
+    CubicSpline, DensityFunction, NoiseParameters, RarityValueMapper,
+};
+use datapack::data::holder::Holder;
+use datapack::{DataPack, DataPackResult};
+use runtime::noise::NormalNoise;
+use util::identifier::IdentifierBuf;
+
+/// Lowers density function trees into a [`DensityFunctionGraph`]. Several functions can be
+/// compiled into the same graph, in which case they share any common nodes. Functions are
+/// deduplicated by identity, so two references to the same registered function (and so also its
+/// caches) share a node, like in vanilla.
+pub struct DensityFunctionCompiler<'a> {
+    datapack: &'a DataPack,
+    random_state: &'a RandomState<'a>,
+    nodes: Vec<Node<'a>>,
+    bounds: Vec<(f64, f64)>,
+    slot_count: usize,
+    compiled: AHashMap<*const DensityFunction, NodeId>,
+    names: Vec<Option<IdentifierBuf>>,
+}
+
+impl<'a> DensityFunctionCompiler<'a> {
+    pub fn new(datapack: &'a DataPack, random_state: &'a RandomState<'a>) -> Self {
+        DensityFunctionCompiler {
+            datapack,
+            random_state,
+            nodes: Vec::new(),
+            bounds: Vec::new(),
+            slot_count: 0,
+            compiled: AHashMap::new(),
+            names: Vec::new(),
+        }
+    }
+
+    pub fn compile(&mut self, function: &'a Holder<DensityFunction>) -> DataPackResult<NodeId> {
+        let id = self.compile_function(function.resolve(self.datapack)?)?;
+        if let Holder::Reference(name) = function {
+            self.names[id.0].get_or_insert_with(|| name.clone());
+        }
+        Ok(id)
+    }
+
+    pub fn finish(self) -> DensityFunctionGraph<'a> {
+        DensityFunctionGraph {
+            nodes: self.nodes,
+            bounds: self.bounds,
+            slot_count: self.slot_count,
+            names: self.names,
+        }
+    }
+
+    fn push(&mut self, node: Node<'a>, bounds: (f64, f64)) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(node);
+        self.bounds.push(bounds);
+        self.names.push(None);
+        id
+    }
+
+    fn bounds_of(&self, id: NodeId) -> (f64, f64) {
+        self.bounds[id.0]
+    }
+
+    fn noise(&self, noise: &Holder<NoiseParameters>) -> DataPackResult<&'a NormalNoise> {
+        self.random_state.get_or_create_noise(noise)
+    }
+
+    fn cache(
+        &mut self,
+        kind: CacheKind,
+        argument: &'a Holder<DensityFunction>,
+    ) -> DataPackResult<(Node<'a>, (f64, f64))> {
+        let argument = self.compile(argument)?;
+        let slot = self.slot_count;
+        self.slot_count += 1;
+        Ok((
+            Node::Cache {
+                kind,
+                argument,
+                slot,
+            },
+            self.bounds_of(argument),
+        ))
+    }
+
+    fn mapped(
+        &mut self,
+        kind: MappedKind,
+        argument: &'a Holder<DensityFunction>,
+    ) -> DataPackResult<(Node<'a>, (f64, f64))> {
+        let argument = self.compile(argument)?;
+        Ok((
+            Node::Mapped { kind, argument },
+            kind.bounds(self.bounds_of(argument)),
+        ))
+    }
+
+    fn binary(
+        &mut self,
+        kind: BinaryKind,
+        argument1: &'a Holder<DensityFunction>,
+        argument2: &'a Holder<DensityFunction>,
+    ) -> DataPackResult<(Node<'a>, (f64, f64))> {
+        if matches!(kind, BinaryKind::Add | BinaryKind::Mul) {
+            // only direct constants count, references are never treated as constants
+            let constant = match (argument1, argument2) {
+                (Holder::Direct(DensityFunction::Constant(constant)), input)
+                | (input, Holder::Direct(DensityFunction::Constant(constant))) => {
+                    Some((*constant.argument, input))
+                }
+                _ => None,
+            };
+            if let Some((argument, input)) = constant {
+                let input = self.compile(input)?;
+                let (min, max) = self.bounds_of(input);
+                let bounds = if kind == BinaryKind::Add {
+                    (min + argument, max + argument)
+                } else {
+                    mul_constant_bounds((min, max), argument)
+                };
+                return Ok((
+                    Node::MulOrAdd {
+                        kind,
+                        input,
+                        argument,
+                    },
+                    bounds,
+                ));
+            }
+        }
+
+        let argument1 = self.compile(argument1)?;
+        let argument2 = self.compile(argument2)?;
+        Ok((
+            Node::Binary {
+                kind,
+                argument1,
+                argument2,
+            },
+            kind.bounds(self.bounds_of(argument1), self.bounds_of(argument2)),
+        ))
+    }
+
+    fn spline(&mut self, spline: &'a CubicSpline) -> DataPackResult<CompiledSpline> {
+        Ok(match spline {
+            CubicSpline::Constant(value) => CompiledSpline::Constant(*value),
+            CubicSpline::Multipoint { coordinate, points } => CompiledSpline::Multipoint {
+                coordinate: self.compile(coordinate)?,
+                points: points
+                    .iter()
+                    .map(|point| {
+                        Ok(CompiledSplinePoint {
+                            location: point.location,
+                            value: self.spline(&point.value)?,
+                            derivative: point.derivative,
+                        })
+                    })
+                    .collect::<DataPackResult<_>>()?,
+            },
+        })
+    }
+
+    fn compile_function(&mut self, function: &'a DensityFunction) -> DataPackResult<NodeId> {
+        let key = function as *const DensityFunction;
+        if let Some(&id) = self.compiled.get(&key) {
+            return Ok(id);
+        }
+
+        let (node, bounds) = match function {
+            DensityFunction::BlendAlpha(_) => (Node::BlendAlpha, function.bounds(self.datapack)?),
+            DensityFunction::BlendOffset(_) => (Node::BlendOffset, function.bounds(self.datapack)?),
+            DensityFunction::Beardifier(_) => (Node::Beardifier, function.bounds(self.datapack)?),
+            DensityFunction::OldBlendedNoise(function) => {
+                let noise = self.random_state.get_or_create_blended_noise(function)?;
+                (
+                    Node::OldBlendedNoise(noise),
+                    (noise.min_value(), noise.max_value()),
+                )
+            }
+            DensityFunction::Interpolated(function) => {
+                self.cache(CacheKind::Interpolated, &function.argument)?
+            }
+            DensityFunction::FlatCache(function) => {
+                self.cache(CacheKind::FlatCache, &function.argument)?
+            }
+            DensityFunction::Cache2d(function) => {
+                self.cache(CacheKind::Cache2d, &function.argument)?
+            }
+            DensityFunction::CacheOnce(function) => {
+                self.cache(CacheKind::CacheOnce, &function.argument)?
+            }
+            DensityFunction::CacheAllInCell(function) => {
+                self.cache(CacheKind::CacheAllInCell, &function.argument)?
+            }
+            DensityFunction::Noise(function) => {
+                let noise = self.noise(&function.noise)?;
+                (
+                    Node::Noise {
+                        noise,
+                        xz_scale: function.xz_scale,
+                        y_scale: function.y_scale,
+                    },
+                    (-noise.max_value(), noise.max_value()),
+                )
+            }
+            DensityFunction::EndIslands(_) => (
+                Node::EndIslands(self.random_state.end_islands()),
+                function.bounds(self.datapack)?,
+            ),
+            DensityFunction::WeirdScaledSampler(function) => {
+                let input = self.compile(&function.input)?;
+                let noise = self.noise(&function.noise)?;
+                let (rarity, max_rarity): (fn(f64) -> f64, f64) = match function.rarity_value_mapper
+                {
+                    RarityValueMapper::Type1 => (spaghetti_rarity_3d, 2.0),
+                    RarityValueMapper::Type2 => (spaghetti_rarity_2d, 3.0),
+                };
+                (
+                    Node::WeirdScaledSampler {
+                        input,
+                        noise,
+                        rarity,
+                    },
+                    (0.0, max_rarity * noise.max_value()),
+                )
+            }
+            DensityFunction::ShiftedNoise(function) => {
+                let shift_x = self.compile(&function.shift_x)?;
+                let shift_y = self.compile(&function.shift_y)?;
+                let shift_z = self.compile(&function.shift_z)?;
+                let noise = self.noise(&function.noise)?;
+                (
+                    Node::ShiftedNoise {
+                        shift_x,
+                        shift_y,
+                        shift_z,
+                        xz_scale: function.xz_scale,
+                        y_scale: function.y_scale,
+                        noise,
+                    },
+                    (-noise.max_value(), noise.max_value()),
+                )
+            }
+            DensityFunction::RangeChoice(function) => {
+                let input = self.compile(&function.input)?;
+                let when_in_range = self.compile(&function.when_in_range)?;
+                let when_out_of_range = self.compile(&function.when_out_of_range)?;
+                let (in_range_min, in_range_max) = self.bounds_of(when_in_range);
+                let (out_of_range_min, out_of_range_max) = self.bounds_of(when_out_of_range);
+                (
+                    Node::RangeChoice {
+                        input,
+                        min_inclusive: *function.min_inclusive,
+                        max_exclusive: *function.max_exclusive,
+                        when_in_range,
+                        when_out_of_range,
+                    },
+                    (
+                        in_range_min.min(out_of_range_min),
+                        in_range_max.max(out_of_range_max),
+                    ),
+                )
+            }
+            DensityFunction::ShiftA(function) => {
+                let noise = self.noise(&function.argument)?;
+                let max = noise.max_value() * 4.0;
+                (Node::ShiftA(noise), (-max, max))
+            }
+            DensityFunction::ShiftB(function) => {
+                let noise = self.noise(&function.argument)?;
+                let max = noise.max_value() * 4.0;
+                (Node::ShiftB(noise), (-max, max))
+            }
+            DensityFunction::Shift(function) => {
+                let noise = self.noise(&function.argument)?;
+                let max = noise.max_value() * 4.0;
+                (Node::Shift(noise), (-max, max))
+            }
+            DensityFunction::BlendDensity(blend_density) => (
+                Node::BlendDensity(self.compile(&blend_density.argument)?),
+                function.bounds(self.datapack)?,
+            ),
+            DensityFunction::Clamp(clamp) => (
+                Node::Clamp {
+                    input: self.compile_function(&clamp.input)?,
+                    min: *clamp.min,
+                    max: *clamp.max,
+                },
+                function.bounds(self.datapack)?,
+            ),
+            DensityFunction::Abs(function) => self.mapped(MappedKind::Abs, &function.argument)?,
+            DensityFunction::Square(function) => {
+                self.mapped(MappedKind::Square, &function.argument)?
+            }
+            DensityFunction::Cube(function) => self.mapped(MappedKind::Cube, &function.argument)?,
+            DensityFunction::HalfNegative(function) => {
+                self.mapped(MappedKind::HalfNegative, &function.argument)?
+            }
+            DensityFunction::QuarterNegative(function) => {
+                self.mapped(MappedKind::QuarterNegative, &function.argument)?
+            }
+            DensityFunction::Squeeze(function) => {
+                self.mapped(MappedKind::Squeeze, &function.argument)?
+            }
+            DensityFunction::Add(function) => {
+                self.binary(BinaryKind::Add, &function.argument1, &function.argument2)?
+            }
+            DensityFunction::Mul(function) => {
+                self.binary(BinaryKind::Mul, &function.argument1, &function.argument2)?
+            }
+            DensityFunction::Min(function) => {
+                self.binary(BinaryKind::Min, &function.argument1, &function.argument2)?
+            }
+            DensityFunction::Max(function) => {
+                self.binary(BinaryKind::Max, &function.argument1, &function.argument2)?
+            }
+            DensityFunction::Spline(spline) => (
+                Node::Spline(Box::new(self.spline(&spline.spline)?)),
+                function.bounds(self.datapack)?,
+            ),
+            DensityFunction::Constant(constant) => (
+                Node::Constant(*constant.argument),
+                function.bounds(self.datapack)?,
+            ),
+            DensityFunction::YClampedGradient(gradient) => (
+                Node::YClampedGradient {
+                    from_y: *gradient.from_y,
+                    to_y: *gradient.to_y,
+                    from_value: *gradient.from_value,
+                    to_value: *gradient.to_value,
+                },
+                function.bounds(self.datapack)?,
+            ),
+        };
+
+        let id = self.push(node, bounds);
+        self.compiled.insert(key, id);
+        Ok(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::density_functions::compiler::DensityFunctionCompiler;
+    use crate::density_functions::evaluator::PointInterpreter;
+    use crate::density_functions::graph::{CellSize, GraphEvaluator, Node};
+    use crate::random_state::RandomState;
+    use datapack::data::density_function::DensityFunction;
+    use datapack::data::holder::Holder;
+    use datapack::DataPack;
+    use glam::IVec3;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_compile() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let worldgen = dir.join("data/minecraft/worldgen");
+        fs::create_dir_all(worldgen.join("noise")).unwrap();
+        fs::create_dir_all(worldgen.join("density_function")).unwrap();
+        fs::write(
+            worldgen.join("noise/test.json"),
+            r#"{ "firstOctave": -4, "amplitudes": [1, 0, 0.5] }"#,
+        )
+        .unwrap();
+        fs::write(
+            worldgen.join("density_function/shared.json"),
+            r#"{
+                "type": "minecraft:flat_cache",
+                "argument": { "type": "minecraft:noise", "noise": "minecraft:test", "xz_scale": 1, "y_scale": 0 }
+            }"#,
+        )
+        .unwrap();
+        let function: Holder<DensityFunction> = serde_json::from_str(
+            r#"{
+                "type": "minecraft:add",
+                "argument1": "minecraft:shared",
+                "argument2": {
+                    "type": "minecraft:min",
+                    "argument1": "minecraft:shared",
+                    "argument2": { "type": "minecraft:y_clamped_gradient", "from_y": 0, "to_y": 64, "from_value": -1, "to_value": 1 }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let datapack = DataPack::new(dir).unwrap();
+        let random_state = RandomState::new(&datapack, 42, false);
+        let mut compiler = DensityFunctionCompiler::new(&datapack, &random_state);
+        let root = compiler.compile(&function).unwrap();
+        let graph = compiler.finish();
+        let caches = graph
+            .nodes()
+            .iter()
+            .filter(|node| matches!(node, Node::Cache { .. }))
+            .count();
+        assert_eq!(caches, 1);
+        assert_eq!(graph.slot_count(), 1);
+
+        let mut evaluator = GraphEvaluator::new(&graph);
+        for x in (-40..40).step_by(7) {
+            for y in (-10..80).step_by(9) {
+                let pos = IVec3::new(x, y, 13);
+                let expected = PointInterpreter::new(&datapack, &random_state, pos)
+                    .compute(&function)
+                    .unwrap();
+                assert_eq!(evaluator.compute(root, pos), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_fill_cell() {
+        let function: Holder<DensityFunction> = serde_json::from_str(
+            r#"{
+                "type": "minecraft:interpolated",
+                "argument": {
+                    "type": "minecraft:square",
+                    "argument": { "type": "minecraft:y_clamped_gradient", "from_y": 0, "to_y": 16, "from_value": 0, "to_value": 2 }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let temp_dir = TempDir::new().unwrap();
+        let datapack = DataPack::new(temp_dir.path()).unwrap();
+        let random_state = RandomState::new(&datapack, 0, false);
+        let mut compiler = DensityFunctionCompiler::new(&datapack, &random_state);
+        let root = compiler.compile(&function).unwrap();
+        let graph = compiler.finish();
+
+        let size = CellSize {
+            width: 4,
+            height: 8,
+        };
+        let mut output = vec![0.0; size.volume()];
+        let mut evaluator = GraphEvaluator::new(&graph);
+        evaluator.fill_cell(root, IVec3::new(0, 8, 0), size, &mut output);
+        // the corners are (y = 8) => 1 and (y = 16) => 4, the top of the cell comes first
+        assert_eq!(output[0], 1.0 + 3.0 * 7.0 / 8.0);
+        assert_eq!(output[size.volume() - 1], 1.0);
+        assert!(output[..16].iter().all(|&value| value == output[0]));
+        // outside a cell, the argument is computed directly
+        assert_eq!(evaluator.compute(root, IVec3::new(0, 12, 0)), 2.25);
+    }
+}
@@ -0,0 +1,499 @@
+use crate::density_functions::spline::CubicSplineExt;
+use crate::density_functions::{
+    spaghetti_rarity_2d, spaghetti_rarity_3d, ContextProvider, DensityFunctionExt, Interpreter,
+    MappedKind,
+};
+use datapack::data::density_function::{
+    AbsFunction, AddFunction, BeardifierFunction, BlendAlphaFunction, BlendDensityFunction,
+    BlendOffsetFunction, BlendedNoiseFunction, Cache2dFunction, CacheAllInCellFunction,
+    CacheOnceFunction, ClampFunction, ConstantFunction, CubeFunction, DensityFunction,
+    EndIslandsFunction, FlatCacheFunction, HalfNegativeFunction, InterpolatedFunction, MaxFunction,
+    MinFunction, MulFunction, NoiseFunction, NoiseParameters, QuarterNegativeFunction,
+    RangeChoiceFunction, RarityValueMapper, ShiftAFunction, ShiftBFunction, ShiftFunction,
+    ShiftedNoiseFunction, SplineFunction, SquareFunction, SqueezeFunction,
+    WeirdScaledSamplerFunction, YClampedGradientFunction,
+};
+use datapack::data::holder::Holder;
+use datapack::{DataPack, DataPackResult};
+use glam::IVec3;
+use util::math;
+
+/// The seeded noise state that density functions sample from. This is the equivalent of
+/// vanilla's `RandomState` after the noise router has been wired up to it.
+pub trait NoiseContext {
+    /// Samples the normal noise created for `noise` at the given (already scaled) coordinates.
+    fn sample_noise(
+        &self,
+        noise: &Holder<NoiseParameters>,
+        x: f64,
+        y: f64,
+        z: f64,
+    ) -> DataPackResult<f64>;
+    fn sample_old_blended_noise(
+        &self,
+        function: &BlendedNoiseFunction,
+        pos: IVec3,
+    ) -> DataPackResult<f64>;
+    fn sample_end_islands(&self, pos: IVec3) -> DataPackResult<f64>;
+}
+
+/// Evaluates density functions at a single block position, without any of the caching that
+/// happens during chunk generation. Cache and interpolation markers are transparent here.
+pub struct PointInterpreter<'a, N> {
+    datapack: &'a DataPack,
+    noise_context: &'a N,
+    pos: IVec3,
+}
+
+impl<'a, N> PointInterpreter<'a, N>
+where
+    N: NoiseContext,
+{
+    pub fn new(datapack: &'a DataPack, noise_context: &'a N, pos: IVec3) -> Self {
+        PointInterpreter {
+            datapack,
+            noise_context,
+            pos,
+        }
+    }
+
+    pub fn pos(&self) -> IVec3 {
+        self.pos
+    }
+
+    pub fn compute(&self, function: &Holder<DensityFunction>) -> DataPackResult<f64> {
+        function.compute(self)
+    }
+
+    fn is_constant(function: &Holder<DensityFunction>) -> bool {
+        // references are never treated as constants, even if they resolve to one
+        matches!(function, Holder::Direct(DensityFunction::Constant(_)))
+    }
+
+    fn sample_shift(
+        &self,
+        noise: &Holder<NoiseParameters>,
+        x: f64,
+        y: f64,
+        z: f64,
+    ) -> DataPackResult<f64> {
+        Ok(self
+            .noise_context
+            .sample_noise(noise, x * 0.25, y * 0.25, z * 0.25)?
+            * 4.0)
+    }
+}
+
+impl<N> Interpreter for PointInterpreter<'_, N>
+where
+    N: NoiseContext,
+{
+    fn datapack(&self) -> &DataPack {
+        self.datapack
+    }
+
+    fn handle_blend_alpha(&self, _function: &BlendAlphaFunction) -> DataPackResult<f64> {
+        // there is no blending with old chunks
+        Ok(1.0)
+    }
+
+    fn handle_blend_offset(&self, _function: &BlendOffsetFunction) -> DataPackResult<f64> {
+        Ok(0.0)
+    }
+
+    fn handle_beardifier(&self, _function: &BeardifierFunction) -> DataPackResult<f64> {
+        Ok(0.0)
+    }
+
+    fn handle_old_blended_noise(&self, function: &BlendedNoiseFunction) -> DataPackResult<f64> {
+        self.noise_context
+            .sample_old_blended_noise(function, self.pos)
+    }
+
+    fn handle_interpolated(&self, function: &InterpolatedFunction) -> DataPackResult<f64> {
+        self.compute(&function.argument)
+    }
+
+    fn handle_flat_cache(&self, function: &FlatCacheFunction) -> DataPackResult<f64> {
+        self.compute(&function.argument)
+    }
+
+    fn handle_cache_2d(&self, function: &Cache2dFunction) -> DataPackResult<f64> {
+        self.compute(&function.argument)
+    }
+
+    fn handle_cache_once(&self, function: &CacheOnceFunction) -> DataPackResult<f64> {
+        self.compute(&function.argument)
+    }
+
+    fn handle_cache_all_in_cell(&self, function: &CacheAllInCellFunction) -> DataPackResult<f64> {
+        self.compute(&function.argument)
+    }
+
+    fn handle_noise(&self, function: &NoiseFunction) -> DataPackResult<f64> {
+        self.noise_context.sample_noise(
+            &function.noise,
+            self.pos.x as f64 * function.xz_scale,
+            self.pos.y as f64 * function.y_scale,
+            self.pos.z as f64 * function.xz_scale,
+        )
+    }
+
+    fn handle_end_islands(&self, _function: &EndIslandsFunction) -> DataPackResult<f64> {
+        self.noise_context.sample_end_islands(self.pos)
+    }
+
+    fn handle_weird_scaled_sampler(
+        &self,
+        function: &WeirdScaledSamplerFunction,
+    ) -> DataPackResult<f64> {
+        let input = self.compute(&function.input)?;
+        let rarity = match function.rarity_value_mapper {
+            RarityValueMapper::Type1 => spaghetti_rarity_3d(input),
+            RarityValueMapper::Type2 => spaghetti_rarity_2d(input),
+        };
+        let noise = self.noise_context.sample_noise(
+            &function.noise,
+            self.pos.x as f64 / rarity,
+            self.pos.y as f64 / rarity,
+            self.pos.z as f64 / rarity,
+        )?;
+        Ok(rarity * noise.abs())
+    }
+
+    fn handle_shifted_noise(&self, function: &ShiftedNoiseFunction) -> DataPackResult<f64> {
+        let x = self.pos.x as f64 * function.xz_scale + self.compute(&function.shift_x)?;
+        let y = self.pos.y as f64 * function.y_scale + self.compute(&function.shift_y)?;
+        let z = self.pos.z as f64 * function.xz_scale + self.compute(&function.shift_z)?;
+        self.noise_context.sample_noise(&function.noise, x, y, z)
+    }
+
+    fn handle_range_choice(&self, function: &RangeChoiceFunction) -> DataPackResult<f64> {
+        let input = self.compute(&function.input)?;
+        if input >= *function.min_inclusive && input < *function.max_exclusive {
+            self.compute(&function.when_in_range)
+        } else {
+            self.compute(&function.when_out_of_range)
+        }
+    }
+
+    fn handle_shift_a(&self, function: &ShiftAFunction) -> DataPackResult<f64> {
+        self.sample_shift(
+            &function.argument,
+            self.pos.x as f64,
+            0.0,
+            self.pos.z as f64,
+        )
+    }
+
+    fn handle_shift_b(&self, function: &ShiftBFunction) -> DataPackResult<f64> {
+        self.sample_shift(
+            &function.argument,
+            self.pos.z as f64,
+            self.pos.x as f64,
+            0.0,
+        )
+    }
+
+    fn handle_shift(&self, function: &ShiftFunction) -> DataPackResult<f64> {
+        self.sample_shift(
+            &function.argument,
+            self.pos.x as f64,
+            self.pos.y as f64,
+            self.pos.z as f64,
+        )
+    }
+
+    fn handle_blend_density(&self, function: &BlendDensityFunction) -> DataPackResult<f64> {
+        // without a blender the density passes through unchanged
+        self.compute(&function.argument)
+    }
+
+    fn handle_clamp(&self, function: &ClampFunction) -> DataPackResult<f64> {
+        Ok(math::clamp(
+            function.input.compute(self)?,
+            *function.min,
+            *function.max,
+        ))
+    }
+
+    fn handle_abs(&self, function: &AbsFunction) -> DataPackResult<f64> {
+        Ok(MappedKind::Abs.apply(self.compute(&function.argument)?))
+    }
+
+    fn handle_square(&self, function: &SquareFunction) -> DataPackResult<f64> {
+        Ok(MappedKind::Square.apply(self.compute(&function.argument)?))
+    }
+
+    fn handle_cube(&self, function: &CubeFunction) -> DataPackResult<f64> {
+        Ok(MappedKind::Cube.apply(self.compute(&function.argument)?))
+    }
+
+    fn handle_half_negative(&self, function: &HalfNegativeFunction) -> DataPackResult<f64> {
+        Ok(MappedKind::HalfNegative.apply(self.compute(&function.argument)?))
+    }
+
+    fn handle_quarter_negative(&self, function: &QuarterNegativeFunction) -> DataPackResult<f64> {
+        Ok(MappedKind::QuarterNegative.apply(self.compute(&function.argument)?))
+    }
+
+    fn handle_squeeze(&self, function: &SqueezeFunction) -> DataPackResult<f64> {
+        Ok(MappedKind::Squeeze.apply(self.compute(&function.argument)?))
+    }
+
+    fn handle_add(&self, function: &AddFunction) -> DataPackResult<f64> {
+        Ok(self.compute(&function.argument1)? + self.compute(&function.argument2)?)
+    }
+
+    fn handle_mul(&self, function: &MulFunction) -> DataPackResult<f64> {
+        // vanilla turns multiplication by a constant into a plain multiplication, otherwise it
+        // skips the second argument if the first is zero. The difference is visible in the sign
+        // of zero results.
+        if Self::is_constant(&function.argument1) || Self::is_constant(&function.argument2) {
+            return Ok(self.compute(&function.argument1)? * self.compute(&function.argument2)?);
+        }
+        let value = self.compute(&function.argument1)?;
+        if value == 0.0 {
+            Ok(0.0)
+        } else {
+            Ok(value * self.compute(&function.argument2)?)
+        }
+    }
+
+    fn handle_min(&self, function: &MinFunction) -> DataPackResult<f64> {
+        Ok(self
+            .compute(&function.argument1)?
+            .min(self.compute(&function.argument2)?))
+    }
+
+    fn handle_max(&self, function: &MaxFunction) -> DataPackResult<f64> {
+        Ok(self
+            .compute(&function.argument1)?
+            .max(self.compute(&function.argument2)?))
+    }
+
+    fn handle_spline(&self, function: &SplineFunction) -> DataPackResult<f64> {
+        Ok(function
+            .spline
+            .apply(&mut |coordinate| self.compute(coordinate))? as f64)
+    }
+
+    fn handle_constant(&self, function: &ConstantFunction) -> DataPackResult<f64> {
+        Ok(*function.argument)
+    }
+
+    fn handle_y_clamped_gradient(
+        &self,
+        function: &YClampedGradientFunction,
+    ) -> DataPackResult<f64> {
+        Ok(math::clamped_map(
+            self.pos.y as f64,
+            *function.from_y as f64,
+            *function.to_y as f64,
+            *function.from_value,
+            *function.to_value,
+        ))
+    }
+}
+
+/// Evaluates density functions over a list of block positions, one [`PointInterpreter`] per
+/// position.
+pub struct PositionsContextProvider<'a, N> {
+    datapack: &'a DataPack,
+    noise_context: &'a N,
+    positions: &'a [IVec3],
+}
+
+impl<'a, N> PositionsContextProvider<'a, N>
+where
+    N: NoiseContext,
+{
+    pub fn new(datapack: &'a DataPack, noise_context: &'a N, positions: &'a [IVec3]) -> Self {
+        PositionsContextProvider {
+            datapack,
+            noise_context,
+            positions,
+        }
+    }
+
+    pub fn positions(&self) -> &'a [IVec3] {
+        self.positions
+    }
+}
+
+impl<'a, N> ContextProvider for PositionsContextProvider<'a, N>
+where
+    N: NoiseContext,
+{
+    type Interpreter<'s>
+        = PointInterpreter<'a, N>
+    where
+        Self: 's;
+
+    fn for_index(&self, index: usize) -> PointInterpreter<'a, N> {
+        PointInterpreter::new(self.datapack, self.noise_context, self.positions[index])
+    }
+
+    fn fill_all_directly<F>(&self, output: &mut [f64], function: &F) -> DataPackResult<()>
+    where
+        F: DensityFunctionExt + ?Sized,
+    {
+        assert_eq!(
+            output.len(),
+            self.positions.len(),
+            "output and positions must have the same length"
+        );
+        for (value, &pos) in output.iter_mut().zip(self.positions) {
+            *value = function.compute(&PointInterpreter::new(
+                self.datapack,
+                self.noise_context,
+                pos,
+            ))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::density_functions::evaluator::{
+        NoiseContext, PointInterpreter, PositionsContextProvider,
+    };
+    use crate::density_functions::DensityFunctionExt;
+    use datapack::data::density_function::{
+        BlendedNoiseFunction, DensityFunction, NoiseParameters,
+    };
+    use datapack::data::holder::Holder;
+    use datapack::{DataPack, DataPackResult};
+    use glam::IVec3;
+    use tempfile::TempDir;
+
+    /// A "noise" which is just the sum of its coordinates, so results can be checked by hand.
+    struct LinearNoise;
+
+    impl NoiseContext for LinearNoise {
+        fn sample_noise(
+            &self,
+            _noise: &Holder<NoiseParameters>,
+            x: f64,
+            y: f64,
+            z: f64,
+        ) -> DataPackResult<f64> {
+            Ok(x + y + z)
+        }
+
+        fn sample_old_blended_noise(
+            &self,
+            _function: &BlendedNoiseFunction,
+            _pos: IVec3,
+        ) -> DataPackResult<f64> {
+            Ok(0.0)
+        }
+
+        fn sample_end_islands(&self, _pos: IVec3) -> DataPackResult<f64> {
+            Ok(0.0)
+        }
+    }
+
+    fn compute(json: &str, pos: IVec3) -> f64 {
+        let temp_dir = TempDir::new().unwrap();
+        let datapack = DataPack::new(temp_dir.path()).unwrap();
+        let function: Holder<DensityFunction> = serde_json::from_str(json).unwrap();
+        PointInterpreter::new(&datapack, &LinearNoise, pos)
+            .compute(&function)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let json = r#"{
+            "type": "minecraft:clamp",
+            "input": {
+                "type": "minecraft:add",
+                "argument1": { "type": "minecraft:mul", "argument1": 3, "argument2": -2 },
+                "argument2": { "type": "minecraft:squeeze", "argument": 0.5 }
+            },
+            "min": -5.5,
+            "max": 0
+        }"#;
+        assert_eq!(compute(json, IVec3::ZERO), -5.5);
+
+        let json = r#"{
+            "type": "minecraft:range_choice",
+            "input": { "type": "minecraft:half_negative", "argument": -1 },
+            "min_inclusive": -0.5,
+            "max_exclusive": 0,
+            "when_in_range": 1,
+            "when_out_of_range": 2
+        }"#;
+        assert_eq!(compute(json, IVec3::ZERO), 1.0);
+    }
+
+    #[test]
+    fn test_y_clamped_gradient() {
+        let json = r#"{
+            "type": "minecraft:y_clamped_gradient",
+            "from_y": -64,
+            "to_y": 320,
+            "from_value": 1.5,
+            "to_value": -1.5
+        }"#;
+        assert_eq!(compute(json, IVec3::new(0, -100, 0)), 1.5);
+        assert_eq!(compute(json, IVec3::new(0, 128, 0)), 0.0);
+        assert_eq!(compute(json, IVec3::new(0, 400, 0)), -1.5);
+    }
+
+    #[test]
+    fn test_noise_coordinates() {
+        let json = r#"{
+            "type": "minecraft:shifted_noise",
+            "noise": "minecraft:temperature",
+            "xz_scale": 0.25,
+            "y_scale": 0,
+            "shift_x": { "type": "minecraft:shift_a", "argument": "minecraft:offset" },
+            "shift_y": 0,
+            "shift_z": { "type": "minecraft:shift_b", "argument": "minecraft:offset" }
+        }"#;
+        // shift_a samples (x, 0, z) and shift_b samples (z, x, 0), so both shifts are x + z
+        assert_eq!(compute(json, IVec3::new(8, 5, 4)), 3.0 + 12.0 + 12.0);
+    }
+
+    #[test]
+    fn test_spline() {
+        let json = r#"{
+            "type": "minecraft:spline",
+            "spline": {
+                "coordinate": { "type": "minecraft:y_clamped_gradient", "from_y": 0, "to_y": 10, "from_value": -2, "to_value": 3 },
+                "points": [
+                    { "location": -1, "value": 0, "derivative": 0 },
+                    { "location": 1, "value": 1, "derivative": 2 }
+                ]
+            }
+        }"#;
+        // before the first point, the spline is extended by the (zero) derivative
+        assert_eq!(compute(json, IVec3::new(0, 0, 0)), 0.0);
+        // halfway between the points, the steep derivative at the end pulls the curve down
+        assert_eq!(compute(json, IVec3::new(0, 4, 0)), 0.0);
+        // after the last point, the spline is extended linearly
+        assert_eq!(compute(json, IVec3::new(0, 10, 0)), 5.0);
+    }
+
+    #[test]
+    fn test_fill_array() {
+        let temp_dir = TempDir::new().unwrap();
+        let datapack = DataPack::new(temp_dir.path()).unwrap();
+        let function: Holder<DensityFunction> = serde_json::from_str(
+            r#"{ "type": "minecraft:noise", "noise": "minecraft:test", "xz_scale": 2, "y_scale": 1 }"#,
+        )
+        .unwrap();
+        let positions = [IVec3::new(1, 2, 3), IVec3::new(-4, 0, 4), IVec3::ZERO];
+        let mut output = [0.0; 3];
+        function
+            .fill_array(
+                &mut output,
+                &PositionsContextProvider::new(&datapack, &LinearNoise, &positions),
+            )
+            .unwrap();
+        assert_eq!(output, [10.0, 0.0, 0.0]);
+    }
+}
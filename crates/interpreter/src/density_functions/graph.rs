@@ -0,0 +1,636 @@
+use crate::density_functions::spline::apply_multipoint;
+use crate::density_functions::{BinaryKind, MappedKind};
+use datapack::data::noise::NoiseSettings;
+use glam::{IVec2, IVec3};
+use runtime::noise::{BlendedNoise, EndIslands, NormalNoise};
+use std::convert::Infallible;
+use util::identifier::{Identifier, IdentifierBuf};
+use util::math;
+
+/// The index of a node in a [`DensityFunctionGraph`]. Nodes only ever refer to nodes with a lower
+/// index.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(pub usize);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CacheKind {
+    Interpolated,
+    FlatCache,
+    Cache2d,
+    CacheOnce,
+    CacheAllInCell,
+}
+
+impl CacheKind {
+    /// Whether the cached value only depends on the x and z coordinates.
+    #[inline]
+    pub fn is_2d(self) -> bool {
+        matches!(self, CacheKind::FlatCache | CacheKind::Cache2d)
+    }
+}
+
+/// A lowered density function, with all references resolved and noises wired up to a
+/// [`RandomState`](crate::random_state::RandomState).
+#[derive(Debug)]
+pub enum Node<'a> {
+    Constant(f64),
+    BlendAlpha,
+    BlendOffset,
+    Beardifier,
+    OldBlendedNoise(&'a BlendedNoise),
+    Cache {
+        kind: CacheKind,
+        argument: NodeId,
+        slot: usize,
+    },
+    Noise {
+        noise: &'a NormalNoise,
+        xz_scale: f64,
+        y_scale: f64,
+    },
+    EndIslands(&'a EndIslands),
+    WeirdScaledSampler {
+        input: NodeId,
+        noise: &'a NormalNoise,
+        rarity: fn(f64) -> f64,
+    },
+    ShiftedNoise {
+        shift_x: NodeId,
+        shift_y: NodeId,
+        shift_z: NodeId,
+        xz_scale: f64,
+        y_scale: f64,
+        noise: &'a NormalNoise,
+    },
+    RangeChoice {
+        input: NodeId,
+        min_inclusive: f64,
+        max_exclusive: f64,
+        when_in_range: NodeId,
+        when_out_of_range: NodeId,
+    },
+    ShiftA(&'a NormalNoise),
+    ShiftB(&'a NormalNoise),
+    Shift(&'a NormalNoise),
+    BlendDensity(NodeId),
+    Clamp {
+        input: NodeId,
+        min: f64,
+        max: f64,
+    },
+    Mapped {
+        kind: MappedKind,
+        argument: NodeId,
+    },
+    Binary {
+        kind: BinaryKind,
+        argument1: NodeId,
+        argument2: NodeId,
+    },
+    /// An add or multiply where one of the arguments was a constant.
+    MulOrAdd {
+        kind: BinaryKind,
+        input: NodeId,
+        argument: f64,
+    },
+    Spline(Box<CompiledSpline>),
+    YClampedGradient {
+        from_y: i32,
+        to_y: i32,
+        from_value: f64,
+        to_value: f64,
+    },
+}
+
+macro_rules! visit_children {
+    ($node:expr, $f:ident, $visit_spline:ident) => {
+        match $node {
+            Node::Cache { argument, .. }
+            | Node::BlendDensity(argument)
+            | Node::Mapped { argument, .. }
+            | Node::MulOrAdd {
+                input: argument, ..
+            }
+            | Node::WeirdScaledSampler {
+                input: argument, ..
+            }
+            | Node::Clamp {
+                input: argument, ..
+            } => $f(argument),
+            Node::ShiftedNoise {
+                shift_x,
+                shift_y,
+                shift_z,
+                ..
+            } => {
+                $f(shift_x);
+                $f(shift_y);
+                $f(shift_z);
+            }
+            Node::RangeChoice {
+                input,
+                when_in_range,
+                when_out_of_range,
+                ..
+            } => {
+                $f(input);
+                $f(when_in_range);
+                $f(when_out_of_range);
+            }
+            Node::Binary {
+                argument1,
+                argument2,
+                ..
+            } => {
+                $f(argument1);
+                $f(argument2);
+            }
+            Node::Spline(spline) => spline.$visit_spline(&mut $f),
+            _ => {}
+        }
+    };
+}
+
+impl Node<'_> {
+    /// Calls `f` with each node this node directly depends on.
+    pub fn for_each_child(&self, mut f: impl FnMut(NodeId)) {
+        let mut f = |child: &NodeId| f(*child);
+        visit_children!(self, f, for_each_coordinate);
+    }
+
+    pub(crate) fn for_each_child_mut(&mut self, mut f: impl FnMut(&mut NodeId)) {
+        visit_children!(self, f, for_each_coordinate_mut);
+    }
+}
+
+#[derive(Debug)]
+pub enum CompiledSpline {
+    Constant(f32),
+    Multipoint {
+        coordinate: NodeId,
+        points: Vec<CompiledSplinePoint>,
+    },
+}
+
+impl CompiledSpline {
+    fn for_each_coordinate(&self, f: &mut impl FnMut(&NodeId)) {
+        if let CompiledSpline::Multipoint { coordinate, points } = self {
+            f(coordinate);
+            for point in points {
+                point.value.for_each_coordinate(f);
+            }
+        }
+    }
+
+    fn for_each_coordinate_mut(&mut self, f: &mut impl FnMut(&mut NodeId)) {
+        if let CompiledSpline::Multipoint { coordinate, points } = self {
+            f(coordinate);
+            for point in points {
+                point.value.for_each_coordinate_mut(f);
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CompiledSplinePoint {
+    pub location: f32,
+    pub value: CompiledSpline,
+    pub derivative: f32,
+}
+
+/// A flat array of density function nodes, created by the
+/// [`DensityFunctionCompiler`](crate::density_functions::compiler::DensityFunctionCompiler).
+#[derive(Debug)]
+pub struct DensityFunctionGraph<'a> {
+    pub(crate) nodes: Vec<Node<'a>>,
+    pub(crate) bounds: Vec<(f64, f64)>,
+    pub(crate) slot_count: usize,
+    pub(crate) names: Vec<Option<IdentifierBuf>>,
+}
+
+impl<'a> DensityFunctionGraph<'a> {
+    #[inline]
+    pub fn nodes(&self) -> &[Node<'a>] {
+        &self.nodes
+    }
+
+    #[inline]
+    pub fn node(&self, id: NodeId) -> &Node<'a> {
+        &self.nodes[id.0]
+    }
+
+    /// The registered density function this node was compiled from, if any.
+    #[inline]
+    pub fn name(&self, id: NodeId) -> Option<&Identifier> {
+        self.names[id.0].as_deref()
+    }
+
+    #[inline]
+    pub fn bounds(&self, id: NodeId) -> (f64, f64) {
+        self.bounds[id.0]
+    }
+
+    #[inline]
+    pub fn min_value(&self, id: NodeId) -> f64 {
+        self.bounds[id.0].0
+    }
+
+    #[inline]
+    pub fn max_value(&self, id: NodeId) -> f64 {
+        self.bounds[id.0].1
+    }
+
+    /// The number of cache slots used by the cache nodes in this graph.
+    #[inline]
+    pub fn slot_count(&self) -> usize {
+        self.slot_count
+    }
+}
+
+/// The size of a noise cell in blocks. During chunk generation, interpolated functions are only
+/// computed at the corners of each cell and trilinearly interpolated in between.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CellSize {
+    pub width: i32,
+    pub height: i32,
+}
+
+impl CellSize {
+    pub fn from_settings(settings: &NoiseSettings) -> Self {
+        CellSize {
+            width: (*settings.size_horizontal as i32) << 2,
+            height: (*settings.size_vertical as i32) << 2,
+        }
+    }
+
+    /// The number of blocks in a cell.
+    #[inline]
+    pub fn volume(self) -> usize {
+        (self.width * self.width * self.height) as usize
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct CacheSlot {
+    pos: Option<IVec3>,
+    interpolating: bool,
+    value: f64,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct CellContext {
+    origin: IVec3,
+    size: CellSize,
+    /// Cleared while computing the corners of an interpolated node, so nested interpolation
+    /// markers are transparent.
+    interpolating: bool,
+}
+
+/// Evaluates a [`DensityFunctionGraph`] one position at a time, or a whole noise cell at once.
+/// Each cache node remembers the last value it computed, keyed by position, or by column for the
+/// 2D caches.
+#[derive(Debug)]
+pub struct GraphEvaluator<'g, 'a> {
+    graph: &'g DensityFunctionGraph<'a>,
+    slots: Vec<CacheSlot>,
+    /// The corner values of each interpolated node, keyed by the origin of their cell.
+    corners: Vec<Option<(IVec3, [f64; 8])>>,
+    cell: Option<CellContext>,
+    /// The blocks in which flat caches sample quart positions even outside of a cell, like within
+    /// the area of a `NoiseChunk`.
+    flat_cache_area: Option<(IVec2, IVec2)>,
+}
+
+impl<'g, 'a> GraphEvaluator<'g, 'a> {
+    pub fn new(graph: &'g DensityFunctionGraph<'a>) -> Self {
+        GraphEvaluator {
+            graph,
+            slots: vec![
+                CacheSlot {
+                    pos: None,
+                    interpolating: false,
+                    value: 0.0,
+                };
+                graph.slot_count
+            ],
+            corners: vec![None; graph.slot_count],
+            cell: None,
+            flat_cache_area: None,
+        }
+    }
+
+    #[inline]
+    pub fn graph(&self) -> &'g DensityFunctionGraph<'a> {
+        self.graph
+    }
+
+    pub fn invalidate_caches(&mut self) {
+        for slot in &mut self.slots {
+            slot.pos = None;
+        }
+        self.corners.fill(None);
+    }
+
+    /// Computes `id` at each of `positions`, without any interpolation.
+    pub fn fill_array(&mut self, id: NodeId, positions: &[IVec3], output: &mut [f64]) {
+        assert_eq!(
+            output.len(),
+            positions.len(),
+            "output and positions must have the same length"
+        );
+        for (value, &pos) in output.iter_mut().zip(positions) {
+            *value = self.compute(id, pos);
+        }
+    }
+
+    /// Fills `output` with the values of `id` for every block of the cell starting at `origin`, in
+    /// vanilla's order: from the top of the cell down, then along x, then along z. Interpolated
+    /// nodes are interpolated between the corners of the cell and flat caches are sampled at quart
+    /// positions, like they are during chunk generation.
+    pub fn fill_cell(&mut self, id: NodeId, origin: IVec3, size: CellSize, output: &mut [f64]) {
+        assert_eq!(output.len(), size.volume(), "output must cover the cell");
+        let previous = self.cell;
+        self.enter_cell(origin, size);
+        let mut index = 0;
+        for y in (0..size.height).rev() {
+            for x in 0..size.width {
+                for z in 0..size.width {
+                    output[index] = self.compute(id, origin + IVec3::new(x, y, z));
+                    index += 1;
+                }
+            }
+        }
+        self.cell = previous;
+    }
+
+    /// Starts filling the cell at `origin`. Until [`exit_cell`](Self::exit_cell) is called,
+    /// [`compute`](Self::compute) interpolates and caches like [`fill_cell`](Self::fill_cell)
+    /// does, so the positions computed should be in the cell.
+    pub fn enter_cell(&mut self, origin: IVec3, size: CellSize) {
+        self.cell = Some(CellContext {
+            origin,
+            size,
+            interpolating: true,
+        });
+    }
+
+    pub fn exit_cell(&mut self) {
+        self.cell = None;
+    }
+
+    /// Makes flat caches sample quart positions for the columns of a chunk, including the quarts
+    /// on its positive edges, even outside of a cell. This matches vanilla, where flat caches
+    /// always use the values `NoiseChunk` filled at quart positions if they have one.
+    pub fn set_flat_cache_chunk(&mut self, chunk_pos: IVec2) {
+        let min = chunk_pos << 4;
+        self.flat_cache_area = Some((min, min + 20));
+    }
+
+    /// Computes `id` at `pos` outside of the current cell, like vanilla does for a
+    /// `SinglePointContext` while filling a noise chunk.
+    pub fn compute_point(&mut self, id: NodeId, pos: IVec3) -> f64 {
+        let cell = self.cell.take();
+        let value = self.compute(id, pos);
+        self.cell = cell;
+        value
+    }
+
+    fn in_flat_cache_area(&self, pos: IVec3) -> bool {
+        self.flat_cache_area.is_some_and(|(min, max)| {
+            pos.x >= min.x && pos.x < max.x && pos.z >= min.y && pos.z < max.y
+        })
+    }
+
+    fn interpolating(&self) -> bool {
+        self.cell.is_some_and(|cell| cell.interpolating)
+    }
+
+    fn compute_cached(
+        &mut self,
+        kind: CacheKind,
+        argument: NodeId,
+        slot: usize,
+        pos: IVec3,
+    ) -> f64 {
+        let interpolating = self.interpolating();
+        let cached = self.slots[slot];
+        if let Some(cached_pos) = cached.pos {
+            let hit = if kind.is_2d() {
+                cached_pos.x == pos.x && cached_pos.z == pos.z
+            } else {
+                cached_pos == pos
+            };
+            if hit && cached.interpolating == interpolating {
+                return cached.value;
+            }
+        }
+        let value = self.compute(argument, pos);
+        self.slots[slot] = CacheSlot {
+            pos: Some(pos),
+            interpolating,
+            value,
+        };
+        value
+    }
+
+    fn interpolate(&mut self, argument: NodeId, slot: usize, cell: CellContext, pos: IVec3) -> f64 {
+        let corners = match self.corners[slot] {
+            Some((origin, corners)) if origin == cell.origin => corners,
+            _ => {
+                self.cell = Some(CellContext {
+                    interpolating: false,
+                    ..cell
+                });
+                let mut corners = [0.0; 8];
+                for (index, corner) in corners.iter_mut().enumerate() {
+                    let offset = IVec3::new(
+                        (index >> 2) as i32 * cell.size.width,
+                        (index >> 1 & 1) as i32 * cell.size.height,
+                        (index & 1) as i32 * cell.size.width,
+                    );
+                    *corner = self.compute(argument, cell.origin + offset);
+                }
+                self.cell = Some(cell);
+                self.corners[slot] = Some((cell.origin, corners));
+                corners
+            }
+        };
+
+        let local = pos - cell.origin;
+        let delta_x = local.x as f64 / cell.size.width as f64;
+        let delta_y = local.y as f64 / cell.size.height as f64;
+        let delta_z = local.z as f64 / cell.size.width as f64;
+        // vanilla interpolates along y first, then x, then z, which rounds differently to lerp3
+        let x0z0 = math::lerp(delta_y, corners[0b000], corners[0b010]);
+        let x1z0 = math::lerp(delta_y, corners[0b100], corners[0b110]);
+        let x0z1 = math::lerp(delta_y, corners[0b001], corners[0b011]);
+        let x1z1 = math::lerp(delta_y, corners[0b101], corners[0b111]);
+        let z0 = math::lerp(delta_x, x0z0, x1z0);
+        let z1 = math::lerp(delta_x, x0z1, x1z1);
+        math::lerp(delta_z, z0, z1)
+    }
+
+    pub fn compute(&mut self, id: NodeId, pos: IVec3) -> f64 {
+        let graph = self.graph;
+        match graph.node(id) {
+            Node::Constant(value) => *value,
+            // there is no blending with old chunks
+            Node::BlendAlpha => 1.0,
+            Node::BlendOffset => 0.0,
+            Node::Beardifier => 0.0,
+            Node::OldBlendedNoise(noise) => noise.compute(pos),
+            &Node::Cache {
+                kind,
+                argument,
+                slot,
+            } => match (kind, self.cell) {
+                (CacheKind::Interpolated, Some(cell)) if cell.interpolating => {
+                    self.interpolate(argument, slot, cell, pos)
+                }
+                (CacheKind::FlatCache, cell) if cell.is_some() || self.in_flat_cache_area(pos) => {
+                    // in a chunk, flat caches are filled at the quart position below, at y = 0,
+                    // outside any cell
+                    self.cell = None;
+                    let quart = IVec3::new(pos.x & !3, 0, pos.z & !3);
+                    let value = self.compute_cached(kind, argument, slot, quart);
+                    self.cell = cell;
+                    value
+                }
+                _ => self.compute_cached(kind, argument, slot, pos),
+            },
+            &Node::Noise {
+                noise,
+                xz_scale,
+                y_scale,
+            } => noise.get_value(
+                pos.x as f64 * xz_scale,
+                pos.y as f64 * y_scale,
+                pos.z as f64 * xz_scale,
+            ),
+            Node::EndIslands(end_islands) => end_islands.compute(pos),
+            &Node::WeirdScaledSampler {
+                input,
+                noise,
+                rarity,
+            } => {
+                let rarity = rarity(self.compute(input, pos));
+                rarity
+                    * noise
+                        .get_value(
+                            pos.x as f64 / rarity,
+                            pos.y as f64 / rarity,
+                            pos.z as f64 / rarity,
+                        )
+                        .abs()
+            }
+            &Node::ShiftedNoise {
+                shift_x,
+                shift_y,
+                shift_z,
+                xz_scale,
+                y_scale,
+                noise,
+            } => {
+                let x = pos.x as f64 * xz_scale + self.compute(shift_x, pos);
+                let y = pos.y as f64 * y_scale + self.compute(shift_y, pos);
+                let z = pos.z as f64 * xz_scale + self.compute(shift_z, pos);
+                noise.get_value(x, y, z)
+            }
+            &Node::RangeChoice {
+                input,
+                min_inclusive,
+                max_exclusive,
+                when_in_range,
+                when_out_of_range,
+            } => {
+                let input = self.compute(input, pos);
+                if input >= min_inclusive && input < max_exclusive {
+                    self.compute(when_in_range, pos)
+                } else {
+                    self.compute(when_out_of_range, pos)
+                }
+            }
+            Node::ShiftA(noise) => sample_shift(noise, pos.x as f64, 0.0, pos.z as f64),
+            Node::ShiftB(noise) => sample_shift(noise, pos.z as f64, pos.x as f64, 0.0),
+            Node::Shift(noise) => sample_shift(noise, pos.x as f64, pos.y as f64, pos.z as f64),
+            // without a blender the density passes through unchanged
+            &Node::BlendDensity(argument) => self.compute(argument, pos),
+            &Node::Clamp { input, min, max } => math::clamp(self.compute(input, pos), min, max),
+            &Node::Mapped { kind, argument } => kind.apply(self.compute(argument, pos)),
+            &Node::Binary {
+                kind,
+                argument1,
+                argument2,
+            } => {
+                let value = self.compute(argument1, pos);
+                match kind {
+                    BinaryKind::Add => value + self.compute(argument2, pos),
+                    BinaryKind::Mul => {
+                        if value == 0.0 {
+                            0.0
+                        } else {
+                            value * self.compute(argument2, pos)
+                        }
+                    }
+                    BinaryKind::Min => {
+                        if value < graph.min_value(argument2) {
+                            value
+                        } else {
+                            value.min(self.compute(argument2, pos))
+                        }
+                    }
+                    BinaryKind::Max => {
+                        if value > graph.max_value(argument2) {
+                            value
+                        } else {
+                            value.max(self.compute(argument2, pos))
+                        }
+                    }
+                }
+            }
+            &Node::MulOrAdd {
+                kind,
+                input,
+                argument,
+            } => {
+                let value = self.compute(input, pos);
+                match kind {
+                    BinaryKind::Mul => value * argument,
+                    _ => value + argument,
+                }
+            }
+            Node::Spline(spline) => self.apply_spline(spline, pos) as f64,
+            &Node::YClampedGradient {
+                from_y,
+                to_y,
+                from_value,
+                to_value,
+            } => math::clamped_map(
+                pos.y as f64,
+                from_y as f64,
+                to_y as f64,
+                from_value,
+                to_value,
+            ),
+        }
+    }
+
+    fn apply_spline(&mut self, spline: &CompiledSpline, pos: IVec3) -> f32 {
+        match spline {
+            CompiledSpline::Constant(value) => *value,
+            CompiledSpline::Multipoint { coordinate, points } => {
+                let point = self.compute(*coordinate, pos) as f32;
+                let Ok(value) = apply_multipoint(point, points, |point| {
+                    Ok::<_, Infallible>(self.apply_spline(&point.value, pos))
+                });
+                value
+            }
+        }
+    }
+}
+
+#[inline]
+fn sample_shift(noise: &NormalNoise, x: f64, y: f64, z: f64) -> f64 {
+    noise.get_value(x * 0.25, y * 0.25, z * 0.25) * 4.0
+}
@@ -1,21 +1,167 @@
+pub mod compiler;
+pub mod evaluator;
+pub mod graph;
+pub mod optimizer;
+pub mod spline;
+
+use crate::density_functions::spline::CubicSplineExt;
 use crate::sealed::Sealed;
 use datapack::data::density_function::{
     AbsFunction, AddFunction, BeardifierFunction, BlendAlphaFunction, BlendDensityFunction,
     BlendOffsetFunction, BlendedNoiseFunction, Cache2dFunction, CacheAllInCellFunction,
     CacheOnceFunction, ClampFunction, ConstantFunction, CubeFunction, DensityFunction,
     EndIslandsFunction, FlatCacheFunction, HalfNegativeFunction, InterpolatedFunction, MaxFunction,
-    MinFunction, MulFunction, NoiseFunction, QuarterNegativeFunction, RangeChoiceFunction,
-    ShiftAFunction, ShiftBFunction, ShiftFunction, ShiftedNoiseFunction, SplineFunction,
-    SquareFunction, SqueezeFunction, WeirdScaledSamplerFunction, YClampedGradientFunction,
+    MinFunction, MulFunction, NoiseFunction, NoiseParameters, QuarterNegativeFunction,
+    RangeChoiceFunction, RarityValueMapper, ShiftAFunction, ShiftBFunction, ShiftFunction,
+    ShiftedNoiseFunction, SplineFunction, SquareFunction, SqueezeFunction,
+    WeirdScaledSamplerFunction, YClampedGradientFunction,
 };
-use datapack::DataPackResult;
+use datapack::data::holder::Holder;
+use datapack::{DataPack, DataPackResult};
+use util::math;
 
 pub trait DensityFunctionExt: Sealed {
     fn compute<I>(&self, interpreter: &I) -> DataPackResult<f64>
     where
         I: Interpreter;
-    fn min_value(&self) -> DataPackResult<f64>;
-    fn max_value(&self) -> DataPackResult<f64>;
+
+    /// Returns the `(min, max)` range of values this function can produce. These match the bounds
+    /// vanilla computes once the noise router has been wired up, including their quirks, since
+    /// vanilla uses them to skip evaluating some arguments.
+    fn bounds(&self, datapack: &DataPack) -> DataPackResult<(f64, f64)>;
+
+    fn min_value(&self, datapack: &DataPack) -> DataPackResult<f64> {
+        Ok(self.bounds(datapack)?.0)
+    }
+
+    fn max_value(&self, datapack: &DataPack) -> DataPackResult<f64> {
+        Ok(self.bounds(datapack)?.1)
+    }
+
+    /// Computes this function for every position of `provider`, like vanilla's `fillArray`.
+    /// `output` must have one element per position.
+    fn fill_array<P>(&self, output: &mut [f64], provider: &P) -> DataPackResult<()>
+    where
+        P: ContextProvider,
+    {
+        provider.fill_all_directly(output, self)
+    }
+}
+
+/// Hands out an [`Interpreter`] for each index of a batch of positions, like vanilla's
+/// `ContextProvider`.
+pub trait ContextProvider {
+    type Interpreter<'s>: Interpreter
+    where
+        Self: 's;
+
+    fn for_index(&self, index: usize) -> Self::Interpreter<'_>;
+
+    fn fill_all_directly<F>(&self, output: &mut [f64], function: &F) -> DataPackResult<()>
+    where
+        F: DensityFunctionExt + ?Sized,
+    {
+        for (index, value) in output.iter_mut().enumerate() {
+            *value = function.compute(&self.for_index(index))?;
+        }
+        Ok(())
+    }
+}
+
+impl Sealed for DensityFunction {}
+
+impl DensityFunctionExt for DensityFunction {
+    fn compute<I>(&self, interpreter: &I) -> DataPackResult<f64>
+    where
+        I: Interpreter,
+    {
+        match self {
+            DensityFunction::BlendAlpha(function) => function.compute(interpreter),
+            DensityFunction::BlendOffset(function) => function.compute(interpreter),
+            DensityFunction::Beardifier(function) => function.compute(interpreter),
+            DensityFunction::OldBlendedNoise(function) => function.compute(interpreter),
+            DensityFunction::Interpolated(function) => function.compute(interpreter),
+            DensityFunction::FlatCache(function) => function.compute(interpreter),
+            DensityFunction::Cache2d(function) => function.compute(interpreter),
+            DensityFunction::CacheOnce(function) => function.compute(interpreter),
+            DensityFunction::CacheAllInCell(function) => function.compute(interpreter),
+            DensityFunction::Noise(function) => function.compute(interpreter),
+            DensityFunction::EndIslands(function) => function.compute(interpreter),
+            DensityFunction::WeirdScaledSampler(function) => function.compute(interpreter),
+            DensityFunction::ShiftedNoise(function) => function.compute(interpreter),
+            DensityFunction::RangeChoice(function) => function.compute(interpreter),
+            DensityFunction::ShiftA(function) => function.compute(interpreter),
+            DensityFunction::ShiftB(function) => function.compute(interpreter),
+            DensityFunction::Shift(function) => function.compute(interpreter),
+            DensityFunction::BlendDensity(function) => function.compute(interpreter),
+            DensityFunction::Clamp(function) => function.compute(interpreter),
+            DensityFunction::Abs(function) => function.compute(interpreter),
+            DensityFunction::Square(function) => function.compute(interpreter),
+            DensityFunction::Cube(function) => function.compute(interpreter),
+            DensityFunction::HalfNegative(function) => function.compute(interpreter),
+            DensityFunction::QuarterNegative(function) => function.compute(interpreter),
+            DensityFunction::Squeeze(function) => function.compute(interpreter),
+            DensityFunction::Add(function) => function.compute(interpreter),
+            DensityFunction::Mul(function) => function.compute(interpreter),
+            DensityFunction::Min(function) => function.compute(interpreter),
+            DensityFunction::Max(function) => function.compute(interpreter),
+            DensityFunction::Spline(function) => function.compute(interpreter),
+            DensityFunction::Constant(function) => function.compute(interpreter),
+            DensityFunction::YClampedGradient(function) => function.compute(interpreter),
+        }
+    }
+
+    fn bounds(&self, datapack: &DataPack) -> DataPackResult<(f64, f64)> {
+        match self {
+            DensityFunction::BlendAlpha(function) => function.bounds(datapack),
+            DensityFunction::BlendOffset(function) => function.bounds(datapack),
+            DensityFunction::Beardifier(function) => function.bounds(datapack),
+            DensityFunction::OldBlendedNoise(function) => function.bounds(datapack),
+            DensityFunction::Interpolated(function) => function.bounds(datapack),
+            DensityFunction::FlatCache(function) => function.bounds(datapack),
+            DensityFunction::Cache2d(function) => function.bounds(datapack),
+            DensityFunction::CacheOnce(function) => function.bounds(datapack),
+            DensityFunction::CacheAllInCell(function) => function.bounds(datapack),
+            DensityFunction::Noise(function) => function.bounds(datapack),
+            DensityFunction::EndIslands(function) => function.bounds(datapack),
+            DensityFunction::WeirdScaledSampler(function) => function.bounds(datapack),
+            DensityFunction::ShiftedNoise(function) => function.bounds(datapack),
+            DensityFunction::RangeChoice(function) => function.bounds(datapack),
+            DensityFunction::ShiftA(function) => function.bounds(datapack),
+            DensityFunction::ShiftB(function) => function.bounds(datapack),
+            DensityFunction::Shift(function) => function.bounds(datapack),
+            DensityFunction::BlendDensity(function) => function.bounds(datapack),
+            DensityFunction::Clamp(function) => function.bounds(datapack),
+            DensityFunction::Abs(function) => function.bounds(datapack),
+            DensityFunction::Square(function) => function.bounds(datapack),
+            DensityFunction::Cube(function) => function.bounds(datapack),
+            DensityFunction::HalfNegative(function) => function.bounds(datapack),
+            DensityFunction::QuarterNegative(function) => function.bounds(datapack),
+            DensityFunction::Squeeze(function) => function.bounds(datapack),
+            DensityFunction::Add(function) => function.bounds(datapack),
+            DensityFunction::Mul(function) => function.bounds(datapack),
+            DensityFunction::Min(function) => function.bounds(datapack),
+            DensityFunction::Max(function) => function.bounds(datapack),
+            DensityFunction::Spline(function) => function.bounds(datapack),
+            DensityFunction::Constant(function) => function.bounds(datapack),
+            DensityFunction::YClampedGradient(function) => function.bounds(datapack),
+        }
+    }
+}
+
+impl Sealed for Holder<DensityFunction> {}
+
+impl DensityFunctionExt for Holder<DensityFunction> {
+    fn compute<I>(&self, interpreter: &I) -> DataPackResult<f64>
+    where
+        I: Interpreter,
+    {
+        self.resolve(interpreter.datapack())?.compute(interpreter)
+    }
+
+    fn bounds(&self, datapack: &DataPack) -> DataPackResult<(f64, f64)> {
+        self.resolve(datapack)?.bounds(datapack)
+    }
 }
 
 macro_rules! define_marker_ext {
@@ -33,12 +179,8 @@ macro_rules! define_marker_ext {
                     interpreter.$inter_fn(self)
                 }
 
-                fn min_value(&self) -> DataPackResult<f64> {
-                    Ok($value)
-                }
-
-                fn max_value(&self) -> DataPackResult<f64> {
-                    Ok($value)
+                fn bounds(&self, _datapack: &DataPack) -> DataPackResult<(f64, f64)> {
+                    Ok(($value, $value))
                 }
             }
         )*
@@ -66,17 +208,478 @@ macro_rules! define_wrapper_ext {
                     interpreter.$inter_fn(self)
                 }
 
-                fn min_value(&self) -> DataPackResult<f64> {
-                    todo!()
+                fn bounds(&self, datapack: &DataPack) -> DataPackResult<(f64, f64)> {
+                    self.argument.bounds(datapack)
                 }
             }
         )*
     };
 }
 
-// BlendedNoise
+define_wrapper_ext! {
+    InterpolatedFunction handle_interpolated;
+    FlatCacheFunction handle_flat_cache;
+    Cache2dFunction handle_cache_2d;
+    CacheOnceFunction handle_cache_once;
+    CacheAllInCellFunction handle_cache_all_in_cell;
+}
+
+macro_rules! define_mapped_ext {
+    (
+        $($ty:ident $inter_fn:ident $kind:ident);*$(;)?
+    ) => {
+        $(
+            impl Sealed for $ty {}
+
+            impl DensityFunctionExt for $ty {
+                fn compute<I>(&self, interpreter: &I) -> DataPackResult<f64>
+                where
+                    I: Interpreter
+                {
+                    interpreter.$inter_fn(self)
+                }
+
+                fn bounds(&self, datapack: &DataPack) -> DataPackResult<(f64, f64)> {
+                    Ok(MappedKind::$kind.bounds(self.argument.bounds(datapack)?))
+                }
+            }
+        )*
+    };
+}
+
+define_mapped_ext! {
+    AbsFunction handle_abs Abs;
+    SquareFunction handle_square Square;
+    CubeFunction handle_cube Cube;
+    HalfNegativeFunction handle_half_negative HalfNegative;
+    QuarterNegativeFunction handle_quarter_negative QuarterNegative;
+    SqueezeFunction handle_squeeze Squeeze;
+}
+
+impl Sealed for BlendedNoiseFunction {}
+
+impl DensityFunctionExt for BlendedNoiseFunction {
+    fn compute<I>(&self, interpreter: &I) -> DataPackResult<f64>
+    where
+        I: Interpreter,
+    {
+        interpreter.handle_old_blended_noise(self)
+    }
+
+    fn bounds(&self, _datapack: &DataPack) -> DataPackResult<(f64, f64)> {
+        // the min limit noise has 16 octaves of amplitude 1, and its "broken" max value is used
+        let max = perlin_edge_value(&[1.0; 16], 684.412 * *self.y_scale + 2.0);
+        Ok((-max, max))
+    }
+}
+
+impl Sealed for NoiseFunction {}
+
+impl DensityFunctionExt for NoiseFunction {
+    fn compute<I>(&self, interpreter: &I) -> DataPackResult<f64>
+    where
+        I: Interpreter,
+    {
+        interpreter.handle_noise(self)
+    }
+
+    fn bounds(&self, datapack: &DataPack) -> DataPackResult<(f64, f64)> {
+        let max = noise_max_value(&self.noise, datapack)?;
+        Ok((-max, max))
+    }
+}
+
+impl Sealed for EndIslandsFunction {}
+
+impl DensityFunctionExt for EndIslandsFunction {
+    fn compute<I>(&self, interpreter: &I) -> DataPackResult<f64>
+    where
+        I: Interpreter,
+    {
+        interpreter.handle_end_islands(self)
+    }
+
+    fn bounds(&self, _datapack: &DataPack) -> DataPackResult<(f64, f64)> {
+        Ok((-0.84375, 0.5625))
+    }
+}
+
+impl Sealed for WeirdScaledSamplerFunction {}
+
+impl DensityFunctionExt for WeirdScaledSamplerFunction {
+    fn compute<I>(&self, interpreter: &I) -> DataPackResult<f64>
+    where
+        I: Interpreter,
+    {
+        interpreter.handle_weird_scaled_sampler(self)
+    }
+
+    fn bounds(&self, datapack: &DataPack) -> DataPackResult<(f64, f64)> {
+        let max_rarity = match self.rarity_value_mapper {
+            RarityValueMapper::Type1 => 2.0,
+            RarityValueMapper::Type2 => 3.0,
+        };
+        Ok((0.0, max_rarity * noise_max_value(&self.noise, datapack)?))
+    }
+}
+
+impl Sealed for ShiftedNoiseFunction {}
+
+impl DensityFunctionExt for ShiftedNoiseFunction {
+    fn compute<I>(&self, interpreter: &I) -> DataPackResult<f64>
+    where
+        I: Interpreter,
+    {
+        interpreter.handle_shifted_noise(self)
+    }
+
+    fn bounds(&self, datapack: &DataPack) -> DataPackResult<(f64, f64)> {
+        let max = noise_max_value(&self.noise, datapack)?;
+        Ok((-max, max))
+    }
+}
+
+impl Sealed for RangeChoiceFunction {}
+
+impl DensityFunctionExt for RangeChoiceFunction {
+    fn compute<I>(&self, interpreter: &I) -> DataPackResult<f64>
+    where
+        I: Interpreter,
+    {
+        interpreter.handle_range_choice(self)
+    }
+
+    fn bounds(&self, datapack: &DataPack) -> DataPackResult<(f64, f64)> {
+        let (in_range_min, in_range_max) = self.when_in_range.bounds(datapack)?;
+        let (out_of_range_min, out_of_range_max) = self.when_out_of_range.bounds(datapack)?;
+        Ok((
+            in_range_min.min(out_of_range_min),
+            in_range_max.max(out_of_range_max),
+        ))
+    }
+}
+
+macro_rules! define_shift_ext {
+    (
+        $($ty:ident $inter_fn:ident);*$(;)?
+    ) => {
+        $(
+            impl Sealed for $ty {}
+
+            impl DensityFunctionExt for $ty {
+                fn compute<I>(&self, interpreter: &I) -> DataPackResult<f64>
+                where
+                    I: Interpreter
+                {
+                    interpreter.$inter_fn(self)
+                }
+
+                fn bounds(&self, datapack: &DataPack) -> DataPackResult<(f64, f64)> {
+                    let max = noise_max_value(&self.argument, datapack)? * 4.0;
+                    Ok((-max, max))
+                }
+            }
+        )*
+    };
+}
+
+define_shift_ext! {
+    ShiftAFunction handle_shift_a;
+    ShiftBFunction handle_shift_b;
+    ShiftFunction handle_shift;
+}
+
+impl Sealed for BlendDensityFunction {}
+
+impl DensityFunctionExt for BlendDensityFunction {
+    fn compute<I>(&self, interpreter: &I) -> DataPackResult<f64>
+    where
+        I: Interpreter,
+    {
+        interpreter.handle_blend_density(self)
+    }
+
+    fn bounds(&self, _datapack: &DataPack) -> DataPackResult<(f64, f64)> {
+        Ok((f64::NEG_INFINITY, f64::INFINITY))
+    }
+}
+
+impl Sealed for ClampFunction {}
+
+impl DensityFunctionExt for ClampFunction {
+    fn compute<I>(&self, interpreter: &I) -> DataPackResult<f64>
+    where
+        I: Interpreter,
+    {
+        interpreter.handle_clamp(self)
+    }
+
+    fn bounds(&self, _datapack: &DataPack) -> DataPackResult<(f64, f64)> {
+        Ok((*self.min, *self.max))
+    }
+}
+
+macro_rules! define_binary_ext {
+    (
+        $($ty:ident $inter_fn:ident $kind:ident);*$(;)?
+    ) => {
+        $(
+            impl Sealed for $ty {}
+
+            impl DensityFunctionExt for $ty {
+                fn compute<I>(&self, interpreter: &I) -> DataPackResult<f64>
+                where
+                    I: Interpreter
+                {
+                    interpreter.$inter_fn(self)
+                }
+
+                fn bounds(&self, datapack: &DataPack) -> DataPackResult<(f64, f64)> {
+                    Ok(BinaryKind::$kind.bounds(
+                        self.argument1.bounds(datapack)?,
+                        self.argument2.bounds(datapack)?,
+                    ))
+                }
+            }
+        )*
+    };
+}
+
+define_binary_ext! {
+    AddFunction handle_add Add;
+    MinFunction handle_min Min;
+    MaxFunction handle_max Max;
+}
+
+impl Sealed for MulFunction {}
+
+impl DensityFunctionExt for MulFunction {
+    fn compute<I>(&self, interpreter: &I) -> DataPackResult<f64>
+    where
+        I: Interpreter,
+    {
+        interpreter.handle_mul(self)
+    }
+
+    fn bounds(&self, datapack: &DataPack) -> DataPackResult<(f64, f64)> {
+        let constant_mul = match (&*self.argument1, &*self.argument2) {
+            (Holder::Direct(DensityFunction::Constant(constant)), input)
+            | (input, Holder::Direct(DensityFunction::Constant(constant))) => {
+                Some((*constant.argument, input))
+            }
+            _ => None,
+        };
+        if let Some((factor, input)) = constant_mul {
+            return Ok(mul_constant_bounds(input.bounds(datapack)?, factor));
+        }
+        Ok(BinaryKind::Mul.bounds(
+            self.argument1.bounds(datapack)?,
+            self.argument2.bounds(datapack)?,
+        ))
+    }
+}
+
+impl Sealed for SplineFunction {}
+
+impl DensityFunctionExt for SplineFunction {
+    fn compute<I>(&self, interpreter: &I) -> DataPackResult<f64>
+    where
+        I: Interpreter,
+    {
+        interpreter.handle_spline(self)
+    }
+
+    fn bounds(&self, datapack: &DataPack) -> DataPackResult<(f64, f64)> {
+        let (min, max) = self.spline.bounds(datapack)?;
+        Ok((min as f64, max as f64))
+    }
+}
+
+impl Sealed for ConstantFunction {}
+
+impl DensityFunctionExt for ConstantFunction {
+    fn compute<I>(&self, interpreter: &I) -> DataPackResult<f64>
+    where
+        I: Interpreter,
+    {
+        interpreter.handle_constant(self)
+    }
+
+    fn bounds(&self, _datapack: &DataPack) -> DataPackResult<(f64, f64)> {
+        Ok((*self.argument, *self.argument))
+    }
+}
+
+impl Sealed for YClampedGradientFunction {}
+
+impl DensityFunctionExt for YClampedGradientFunction {
+    fn compute<I>(&self, interpreter: &I) -> DataPackResult<f64>
+    where
+        I: Interpreter,
+    {
+        interpreter.handle_y_clamped_gradient(self)
+    }
+
+    fn bounds(&self, _datapack: &DataPack) -> DataPackResult<(f64, f64)> {
+        Ok((
+            self.from_value.min(*self.to_value),
+            self.from_value.max(*self.to_value),
+        ))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MappedKind {
+    Abs,
+    Square,
+    Cube,
+    HalfNegative,
+    QuarterNegative,
+    Squeeze,
+}
+
+impl MappedKind {
+    #[inline]
+    pub fn apply(self, value: f64) -> f64 {
+        match self {
+            MappedKind::Abs => value.abs(),
+            MappedKind::Square => value * value,
+            MappedKind::Cube => value * value * value,
+            MappedKind::HalfNegative => {
+                if value > 0.0 {
+                    value
+                } else {
+                    value * 0.5
+                }
+            }
+            MappedKind::QuarterNegative => {
+                if value > 0.0 {
+                    value
+                } else {
+                    value * 0.25
+                }
+            }
+            MappedKind::Squeeze => {
+                let value = math::clamp(value, -1.0, 1.0);
+                value / 2.0 - value * value * value / 24.0
+            }
+        }
+    }
+
+    pub fn bounds(self, (min, max): (f64, f64)) -> (f64, f64) {
+        let transformed_min = self.apply(min);
+        let transformed_max = self.apply(max);
+        match self {
+            // abs and square are the only mapped functions that aren't monotonic. Vanilla clamps
+            // the *untransformed* input minimum to zero here, which is wrong but has to be kept.
+            MappedKind::Abs | MappedKind::Square => {
+                (min.max(0.0), transformed_min.max(transformed_max))
+            }
+            _ => (transformed_min, transformed_max),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BinaryKind {
+    Add,
+    Mul,
+    Min,
+    Max,
+}
+
+impl BinaryKind {
+    /// The bounds vanilla gives to a two argument function. For multiplication this is an
+    /// estimate which isn't always a superset of the real range.
+    pub fn bounds(self, (min1, max1): (f64, f64), (min2, max2): (f64, f64)) -> (f64, f64) {
+        match self {
+            BinaryKind::Add => (min1 + min2, max1 + max2),
+            BinaryKind::Mul => {
+                if min1 > 0.0 && min2 > 0.0 {
+                    (min1 * min2, max1 * max2)
+                } else if max1 < 0.0 && max2 < 0.0 {
+                    (max1 * max2, min1 * min2)
+                } else {
+                    (
+                        (min1 * max2).min(max1 * min2),
+                        (min1 * min2).max(max1 * max2),
+                    )
+                }
+            }
+            BinaryKind::Min => (min1.min(min2), max1.min(max2)),
+            BinaryKind::Max => (min1.max(min2), max1.max(max2)),
+        }
+    }
+}
+
+/// Multiplication by a constant gets exact bounds.
+pub fn mul_constant_bounds((min, max): (f64, f64), factor: f64) -> (f64, f64) {
+    if factor >= 0.0 {
+        (min * factor, max * factor)
+    } else {
+        (max * factor, min * factor)
+    }
+}
+
+pub(crate) fn spaghetti_rarity_2d(value: f64) -> f64 {
+    if value < -0.75 {
+        0.5
+    } else if value < -0.5 {
+        0.75
+    } else if value < 0.5 {
+        1.0
+    } else if value < 0.75 {
+        2.0
+    } else {
+        3.0
+    }
+}
+
+pub(crate) fn spaghetti_rarity_3d(value: f64) -> f64 {
+    if value < -0.5 {
+        0.75
+    } else if value < 0.0 {
+        1.0
+    } else if value < 0.5 {
+        1.5
+    } else {
+        2.0
+    }
+}
+
+/// Equivalent of `PerlinNoise.edgeValue`, for a perlin noise with the given amplitudes.
+fn perlin_edge_value(amplitudes: &[f64], value: f64) -> f64 {
+    let octaves = amplitudes.len() as i32;
+    let mut value_factor = 2.0f64.powi(octaves - 1) / (2.0f64.powi(octaves) - 1.0);
+    let mut result = 0.0;
+    for &amplitude in amplitudes {
+        // octaves with an amplitude of zero are never created, so don't contribute
+        if amplitude != 0.0 {
+            result += amplitude * value * value_factor;
+        }
+        value_factor /= 2.0;
+    }
+    result
+}
+
+/// Equivalent of `NormalNoise.maxValue` for a noise created from the given parameters.
+fn noise_max_value(noise: &Holder<NoiseParameters>, datapack: &DataPack) -> DataPackResult<f64> {
+    let amplitudes = &noise.resolve(datapack)?.amplitudes;
+    let (Some(first), Some(last)) = (
+        amplitudes.iter().position(|&amplitude| amplitude != 0.0),
+        amplitudes.iter().rposition(|&amplitude| amplitude != 0.0),
+    ) else {
+        return Ok(0.0);
+    };
+    let expected_deviation = 0.1 * (1.0 + 1.0 / (last - first + 1) as f64);
+    let value_factor = 0.16666666666666666 / expected_deviation;
+    // both of the underlying perlin noises have the same parameters
+    let perlin_max = perlin_edge_value(amplitudes, 2.0);
+    Ok((perlin_max + perlin_max) * value_factor)
+}
 
 pub trait Interpreter {
+    fn datapack(&self) -> &DataPack;
     fn handle_blend_alpha(&self, function: &BlendAlphaFunction) -> DataPackResult<f64>;
     fn handle_blend_offset(&self, function: &BlendOffsetFunction) -> DataPackResult<f64>;
     fn handle_beardifier(&self, function: &BeardifierFunction) -> DataPackResult<f64>;
@@ -114,3 +717,72 @@ pub trait Interpreter {
     fn handle_y_clamped_gradient(&self, function: &YClampedGradientFunction)
         -> DataPackResult<f64>;
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::density_functions::DensityFunctionExt;
+    use datapack::data::density_function::DensityFunction;
+    use datapack::data::holder::Holder;
+    use datapack::DataPack;
+    use tempfile::TempDir;
+
+    fn bounds(json: &str) -> (f64, f64) {
+        let temp_dir = TempDir::new().unwrap();
+        let datapack = DataPack::new(temp_dir.path()).unwrap();
+        let function: Holder<DensityFunction> = serde_json::from_str(json).unwrap();
+        function.bounds(&datapack).unwrap()
+    }
+
+    fn gradient(from_value: f64, to_value: f64) -> String {
+        format!(
+            r#"{{ "type": "minecraft:y_clamped_gradient", "from_y": 0, "to_y": 10, "from_value": {from_value}, "to_value": {to_value} }}"#
+        )
+    }
+
+    #[test]
+    fn test_mapped_bounds() {
+        let json = format!(
+            r#"{{ "type": "minecraft:abs", "argument": {} }}"#,
+            gradient(-3.0, -1.0)
+        );
+        assert_eq!(bounds(&json), (0.0, 3.0));
+        let json = format!(
+            r#"{{ "type": "minecraft:half_negative", "argument": {} }}"#,
+            gradient(-3.0, 1.0)
+        );
+        assert_eq!(bounds(&json), (-1.5, 1.0));
+    }
+
+    #[test]
+    fn test_mul_bounds() {
+        let json = format!(
+            r#"{{ "type": "minecraft:mul", "argument1": {}, "argument2": {} }}"#,
+            gradient(-2.0, 3.0),
+            gradient(-1.0, 4.0)
+        );
+        assert_eq!(bounds(&json), (-8.0, 12.0));
+        let json = format!(
+            r#"{{ "type": "minecraft:mul", "argument1": -2, "argument2": {} }}"#,
+            gradient(-2.0, 3.0)
+        );
+        assert_eq!(bounds(&json), (-6.0, 4.0));
+    }
+
+    #[test]
+    fn test_spline_bounds() {
+        let json = format!(
+            r#"{{
+                "type": "minecraft:spline",
+                "spline": {{
+                    "coordinate": {},
+                    "points": [
+                        {{ "location": -1, "value": 0, "derivative": 0 }},
+                        {{ "location": 1, "value": 1, "derivative": 2 }}
+                    ]
+                }}
+            }}"#,
+            gradient(-2.0, 3.0)
+        );
+        assert_eq!(bounds(&json), (-0.75, 5.0));
+    }
+}
@@ -0,0 +1,764 @@
+use crate::density_functions::graph::{CacheKind, DensityFunctionGraph, Node, NodeId};
+use crate::density_functions::{BinaryKind, MappedKind};
+use std::fmt::{Display, Formatter};
+use util::identifier::IdentifierBuf;
+use util::math;
+
+/// A single simplification made by [`optimize`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Optimization {
+    /// The closest registered density function containing the simplified node, if any.
+    pub function: Option<IdentifierBuf>,
+    pub kind: OptimizationKind,
+}
+
+impl Display for Optimization {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.function {
+            Some(function) => write!(f, "{function}: ")?,
+            None => write!(f, "<inline>: ")?,
+        }
+        match self.kind {
+            OptimizationKind::FoldedConstant(value) => write!(f, "folded to constant {value}"),
+            OptimizationKind::RemovedIdentity => write!(f, "removed identity operation"),
+            OptimizationKind::RemovedClamp => write!(f, "removed clamp of an input in range"),
+            OptimizationKind::PrunedRangeChoice { in_range: true } => {
+                write!(f, "range choice input is always in range")
+            }
+            OptimizationKind::PrunedRangeChoice { in_range: false } => {
+                write!(f, "range choice input is never in range")
+            }
+            OptimizationKind::PrunedMinMax => write!(f, "one side of min or max always wins"),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum OptimizationKind {
+    /// A node which only depends on constants was replaced by its value.
+    FoldedConstant(f64),
+    /// An addition of zero or a multiplication by one was replaced by its input.
+    RemovedIdentity,
+    /// A clamp whose input is always inside the clamped range was replaced by its input.
+    RemovedClamp,
+    /// A range choice whose input is always in or always out of range was replaced by the branch
+    /// that is always taken.
+    PrunedRangeChoice { in_range: bool },
+    /// A min or max whose arguments never overlap was replaced by the argument that is always
+    /// picked.
+    PrunedMinMax,
+}
+
+enum Simplified {
+    Constant(f64),
+    Alias(NodeId),
+}
+
+/// The values a node can compute. Unlike the vanilla bounds stored in the graph, which are only
+/// estimates for some functions, these are guaranteed to contain every computed value, so they can
+/// be used to prove branches unreachable. Only finite ranges are tracked.
+#[derive(Debug, Copy, Clone)]
+struct Interval {
+    min: f64,
+    max: f64,
+    /// Whether the node can compute `-0.0`, which adding `0.0` turns into `0.0`.
+    negative_zero: bool,
+}
+
+impl Interval {
+    fn new(min: f64, max: f64, negative_zero: bool) -> Option<Interval> {
+        (min.is_finite() && max.is_finite()).then_some(Interval {
+            min,
+            max,
+            negative_zero,
+        })
+    }
+
+    /// An interval which can compute `-0.0` whenever it contains zero.
+    fn containing_zero(min: f64, max: f64) -> Option<Interval> {
+        Self::new(min, max, min <= 0.0 && max >= 0.0)
+    }
+
+    fn constant(value: f64) -> Option<Interval> {
+        Self::new(value, value, is_negative_zero(value))
+    }
+
+    #[inline]
+    fn is_within(&self, min: f64, max: f64) -> bool {
+        self.min >= min && self.max <= max
+    }
+}
+
+#[inline]
+fn is_negative_zero(value: f64) -> bool {
+    value == 0.0 && value.is_sign_negative()
+}
+
+/// Simplifies `graph`, folding constants, dropping identity operations and removing branches which
+/// are provably unreachable, then drops any nodes no longer reachable from `roots`. `roots` are
+/// updated to point into the returned graph.
+///
+/// Simplifications never change the computed values, down to the sign of zero. In particular,
+/// chains of additions and multiplications with constants are not reassociated, since that changes
+/// rounding. Since vanilla's bounds of a multiplication aren't always a superset of its values,
+/// branches are only pruned based on [intervals](Interval) computed alongside the pass.
+pub fn optimize<'a>(
+    graph: DensityFunctionGraph<'a>,
+    roots: &mut [NodeId],
+) -> (DensityFunctionGraph<'a>, Vec<Optimization>) {
+    let owners = owners(&graph);
+    let DensityFunctionGraph {
+        nodes: old_nodes,
+        bounds: old_bounds,
+        slot_count,
+        names: old_names,
+    } = graph;
+
+    let mut optimized = DensityFunctionGraph {
+        nodes: Vec::with_capacity(old_nodes.len()),
+        bounds: Vec::with_capacity(old_nodes.len()),
+        slot_count,
+        names: Vec::with_capacity(old_nodes.len()),
+    };
+    let mut intervals = Vec::with_capacity(old_nodes.len());
+    let mut forward = Vec::with_capacity(old_nodes.len());
+    let mut optimizations = Vec::new();
+    for (index, mut node) in old_nodes.into_iter().enumerate() {
+        node.for_each_child_mut(|child| *child = forward[child.0]);
+        let name = old_names[index].clone();
+        let simplified = simplify(&optimized, &intervals, &node).filter(|(simplified, _)| {
+            keeps_bounds(&optimized, &intervals, simplified, old_bounds[index])
+        });
+        let id = match simplified {
+            Some((simplified, kind)) => {
+                optimizations.push(Optimization {
+                    function: owners[index].clone(),
+                    kind,
+                });
+                match simplified {
+                    Simplified::Alias(id) => id,
+                    Simplified::Constant(value) => {
+                        intervals.push(Interval::constant(value));
+                        push(&mut optimized, Node::Constant(value), (value, value), name)
+                    }
+                }
+            }
+            None => {
+                intervals.push(interval(&optimized, &intervals, &node));
+                push(&mut optimized, node, old_bounds[index], name)
+            }
+        };
+        forward.push(id);
+    }
+    for root in roots.iter_mut() {
+        *root = forward[root.0];
+    }
+
+    (remove_unreachable(optimized, roots), optimizations)
+}
+
+fn push<'a>(
+    graph: &mut DensityFunctionGraph<'a>,
+    node: Node<'a>,
+    bounds: (f64, f64),
+    name: Option<IdentifierBuf>,
+) -> NodeId {
+    let id = NodeId(graph.nodes.len());
+    graph.nodes.push(node);
+    graph.bounds.push(bounds);
+    graph.names.push(name);
+    id
+}
+
+/// Finds the closest named ancestor of each node, preferring the parent with the highest index.
+fn owners(graph: &DensityFunctionGraph) -> Vec<Option<IdentifierBuf>> {
+    let mut owners = graph.names.clone();
+    for index in (0..graph.nodes.len()).rev() {
+        let Some(owner) = owners[index].clone() else {
+            continue;
+        };
+        graph.nodes[index].for_each_child(|child| {
+            owners[child.0].get_or_insert_with(|| owner.clone());
+        });
+    }
+    owners
+}
+
+/// Computes the interval of a node whose children are already in `graph`.
+fn interval(
+    graph: &DensityFunctionGraph,
+    intervals: &[Option<Interval>],
+    node: &Node,
+) -> Option<Interval> {
+    let interval = |id: NodeId| intervals[id.0];
+    match *node {
+        Node::Constant(value) => Interval::constant(value),
+        // interpolation can round slightly outside of the corner values
+        Node::Cache {
+            kind: CacheKind::Interpolated,
+            ..
+        } => None,
+        Node::Cache { argument, .. } | Node::BlendDensity(argument) => interval(argument),
+        Node::Clamp { input, min, max } => {
+            let input = interval(input)?;
+            Interval::new(
+                math::clamp(input.min, min, max),
+                math::clamp(input.max, min, max),
+                input.negative_zero || is_negative_zero(min) || is_negative_zero(max),
+            )
+        }
+        Node::Mapped { kind, argument } => {
+            let Interval { min, max, .. } = interval(argument)?;
+            match kind {
+                MappedKind::Abs | MappedKind::Square => {
+                    let (mapped_min, mapped_max) = (kind.apply(min), kind.apply(max));
+                    let lowest = if min <= 0.0 && max >= 0.0 {
+                        0.0
+                    } else {
+                        mapped_min.min(mapped_max)
+                    };
+                    Interval::new(lowest, mapped_min.max(mapped_max), false)
+                }
+                MappedKind::Squeeze => Interval::containing_zero(-0.5, 0.5),
+                // the rest are monotonic
+                _ => Interval::containing_zero(kind.apply(min), kind.apply(max)),
+            }
+        }
+        Node::MulOrAdd {
+            kind: BinaryKind::Mul,
+            input,
+            argument,
+        } => {
+            let input = interval(input)?;
+            let (a, b) = (input.min * argument, input.max * argument);
+            let interval = Interval::containing_zero(a.min(b), a.max(b))?;
+            // a product can only round to zero when the argument is small
+            Some(Interval {
+                negative_zero: if argument >= 1.0 {
+                    input.negative_zero
+                } else {
+                    interval.negative_zero
+                },
+                ..interval
+            })
+        }
+        Node::MulOrAdd {
+            input, argument, ..
+        } => {
+            let input = interval(input)?;
+            Interval::new(
+                input.min + argument,
+                input.max + argument,
+                input.negative_zero && is_negative_zero(argument),
+            )
+        }
+        Node::Binary {
+            kind,
+            argument1,
+            argument2,
+        } => {
+            let interval1 = interval(argument1)?;
+            let interval2 = interval(argument2)?;
+            match kind {
+                BinaryKind::Add => Interval::new(
+                    interval1.min + interval2.min,
+                    interval1.max + interval2.max,
+                    interval1.negative_zero && interval2.negative_zero,
+                ),
+                BinaryKind::Mul => {
+                    let products = [
+                        interval1.min * interval2.min,
+                        interval1.min * interval2.max,
+                        interval1.max * interval2.min,
+                        interval1.max * interval2.max,
+                    ];
+                    Interval::containing_zero(
+                        products.into_iter().fold(f64::INFINITY, f64::min),
+                        products.into_iter().fold(f64::NEG_INFINITY, f64::max),
+                    )
+                }
+                // the first argument is returned as is when it's below the vanilla bounds of the
+                // second, so the second only limits the result when it's within them
+                BinaryKind::Min => {
+                    let (min2, max2) = graph.bounds(argument2);
+                    let max = if interval2.is_within(min2, max2) {
+                        interval1.max.min(interval2.max)
+                    } else {
+                        interval1.max
+                    };
+                    Interval::containing_zero(interval1.min.min(interval2.min), max)
+                }
+                BinaryKind::Max => {
+                    let (min2, max2) = graph.bounds(argument2);
+                    let min = if interval2.is_within(min2, max2) {
+                        interval1.min.max(interval2.min)
+                    } else {
+                        interval1.min
+                    };
+                    Interval::containing_zero(min, interval1.max.max(interval2.max))
+                }
+            }
+        }
+        Node::RangeChoice {
+            when_in_range,
+            when_out_of_range,
+            ..
+        } => {
+            let in_range = interval(when_in_range)?;
+            let out_of_range = interval(when_out_of_range)?;
+            Interval::new(
+                in_range.min.min(out_of_range.min),
+                in_range.max.max(out_of_range.max),
+                in_range.negative_zero || out_of_range.negative_zero,
+            )
+        }
+        Node::YClampedGradient {
+            from_y,
+            to_y,
+            from_value,
+            to_value,
+        } if from_y != to_y => {
+            // between the ends, the value is lerped as `from_value + delta * (to_value - from_value)`
+            let ends = [from_value, to_value, from_value + (to_value - from_value)];
+            Interval::new(
+                ends.into_iter().fold(f64::INFINITY, f64::min),
+                ends.into_iter().fold(f64::NEG_INFINITY, f64::max),
+                is_negative_zero(from_value) || is_negative_zero(to_value),
+            )
+        }
+        _ => None,
+    }
+}
+
+/// Whether replacing a node with the vanilla bounds `bounds` keeps the min and max nodes using it
+/// computing the same values. These skip their second argument based on its bounds, so the
+/// replacement must either have the same bounds or stay within both its own and the old ones.
+fn keeps_bounds(
+    graph: &DensityFunctionGraph,
+    intervals: &[Option<Interval>],
+    simplified: &Simplified,
+    bounds: (f64, f64),
+) -> bool {
+    let (new_bounds, interval) = match *simplified {
+        Simplified::Constant(value) => ((value, value), Interval::constant(value)),
+        Simplified::Alias(id) => (graph.bounds(id), intervals[id.0]),
+    };
+    new_bounds == bounds
+        || interval.is_some_and(|interval| {
+            interval.is_within(new_bounds.0, new_bounds.1) && interval.is_within(bounds.0, bounds.1)
+        })
+}
+
+fn simplify(
+    graph: &DensityFunctionGraph,
+    intervals: &[Option<Interval>],
+    node: &Node,
+) -> Option<(Simplified, OptimizationKind)> {
+    let constant = |id: NodeId| match graph.node(id) {
+        Node::Constant(value) => Some(*value),
+        _ => None,
+    };
+    let interval = |id: NodeId| intervals[id.0];
+    let fold = |value: f64| {
+        Some((
+            Simplified::Constant(value),
+            OptimizationKind::FoldedConstant(value),
+        ))
+    };
+    let alias = |id: NodeId, kind: OptimizationKind| Some((Simplified::Alias(id), kind));
+
+    match *node {
+        // caching or interpolating a constant gives back the same constant, except that
+        // interpolating turns -0.0 into 0.0 inside of cells only
+        Node::Cache {
+            kind: CacheKind::Interpolated,
+            argument,
+            ..
+        } => match constant(argument)? {
+            value if value.is_finite() && !is_negative_zero(value) => fold(value),
+            _ => None,
+        },
+        Node::Cache { argument, .. } | Node::BlendDensity(argument) => fold(constant(argument)?),
+        Node::Mapped { kind, argument } => fold(kind.apply(constant(argument)?)),
+        Node::Clamp {
+            input: input_id,
+            min,
+            max,
+        } => {
+            if let Some(value) = constant(input_id) {
+                return fold(math::clamp(value, min, max));
+            }
+            let input = interval(input_id)?;
+            // the clamp could pick either zero if the input can reach a maximum of zero
+            if input.is_within(min, max) && (input.max < max || max != 0.0) {
+                alias(input_id, OptimizationKind::RemovedClamp)
+            } else {
+                None
+            }
+        }
+        Node::RangeChoice {
+            input,
+            min_inclusive,
+            max_exclusive,
+            when_in_range,
+            when_out_of_range,
+        } => {
+            let Interval {
+                min: input_min,
+                max: input_max,
+                ..
+            } = interval(input)?;
+            if input_min >= min_inclusive && input_max < max_exclusive {
+                alias(
+                    when_in_range,
+                    OptimizationKind::PrunedRangeChoice { in_range: true },
+                )
+            } else if input_max < min_inclusive || input_min >= max_exclusive {
+                alias(
+                    when_out_of_range,
+                    OptimizationKind::PrunedRangeChoice { in_range: false },
+                )
+            } else {
+                None
+            }
+        }
+        Node::MulOrAdd {
+            kind,
+            input,
+            argument,
+        } => {
+            if let Some(value) = constant(input) {
+                return fold(match kind {
+                    BinaryKind::Mul => value * argument,
+                    _ => value + argument,
+                });
+            }
+            match kind {
+                BinaryKind::Add if argument == 0.0 && adds_zero(interval(input), argument) => {
+                    alias(input, OptimizationKind::RemovedIdentity)
+                }
+                BinaryKind::Mul if argument == 1.0 => {
+                    alias(input, OptimizationKind::RemovedIdentity)
+                }
+                _ => None,
+            }
+        }
+        Node::Binary {
+            kind,
+            argument1,
+            argument2,
+        } => simplify_binary(graph, intervals, kind, argument1, argument2),
+        _ => None,
+    }
+}
+
+/// Whether adding `zero` to values in `interval` gives back the same values. Adding `-0.0` never
+/// changes a value, but adding `0.0` to `-0.0` gives `0.0`.
+fn adds_zero(interval: Option<Interval>, zero: f64) -> bool {
+    is_negative_zero(zero) || never_negative_zero(interval)
+}
+
+#[inline]
+fn never_negative_zero(interval: Option<Interval>) -> bool {
+    interval.is_some_and(|interval| !interval.negative_zero)
+}
+
+fn simplify_binary(
+    graph: &DensityFunctionGraph,
+    intervals: &[Option<Interval>],
+    kind: BinaryKind,
+    argument1: NodeId,
+    argument2: NodeId,
+) -> Option<(Simplified, OptimizationKind)> {
+    let constant = |id: NodeId| match graph.node(id) {
+        Node::Constant(value) => Some(*value),
+        _ => None,
+    };
+    let interval1 = intervals[argument1.0];
+    let interval2 = intervals[argument2.0];
+    // the evaluator returns the first argument of a min or max as is when it's beyond the vanilla
+    // bounds of the second, so the second can only win when the first never is
+    let (bound_min2, bound_max2) = graph.bounds(argument2);
+    let apart = |lower: Option<Interval>, upper: Option<Interval>| {
+        lower
+            .zip(upper)
+            .is_some_and(|(lower, upper)| lower.max < upper.min)
+    };
+
+    let simplified = match (kind, constant(argument1), constant(argument2)) {
+        (BinaryKind::Add, Some(value1), Some(value2)) => Simplified::Constant(value1 + value2),
+        // the second argument is never computed when the first is zero
+        (BinaryKind::Mul, Some(0.0), _) => Simplified::Constant(0.0),
+        (BinaryKind::Mul, Some(value1), Some(value2)) => Simplified::Constant(value1 * value2),
+        (BinaryKind::Add, Some(zero @ 0.0), _) if adds_zero(interval2, zero) => {
+            return Some((
+                Simplified::Alias(argument2),
+                OptimizationKind::RemovedIdentity,
+            ));
+        }
+        (BinaryKind::Mul, Some(1.0), _) => {
+            return Some((
+                Simplified::Alias(argument2),
+                OptimizationKind::RemovedIdentity,
+            ));
+        }
+        (BinaryKind::Add, _, Some(zero @ 0.0)) if adds_zero(interval1, zero) => {
+            return Some((
+                Simplified::Alias(argument1),
+                OptimizationKind::RemovedIdentity,
+            ));
+        }
+        // a first argument of -0.0 is short-circuited to 0.0 by the multiplication
+        (BinaryKind::Mul, _, Some(1.0)) if never_negative_zero(interval1) => {
+            return Some((
+                Simplified::Alias(argument1),
+                OptimizationKind::RemovedIdentity,
+            ));
+        }
+        (BinaryKind::Min, Some(value1), Some(value2)) => Simplified::Constant(value1.min(value2)),
+        (BinaryKind::Max, Some(value1), Some(value2)) => Simplified::Constant(value1.max(value2)),
+        (BinaryKind::Min, _, _) if apart(interval1, interval2) => Simplified::Alias(argument1),
+        (BinaryKind::Min, _, _)
+            if apart(interval2, interval1)
+                && interval1.is_some_and(|interval1| interval1.min >= bound_min2) =>
+        {
+            Simplified::Alias(argument2)
+        }
+        (BinaryKind::Max, _, _) if apart(interval2, interval1) => Simplified::Alias(argument1),
+        (BinaryKind::Max, _, _)
+            if apart(interval1, interval2)
+                && interval1.is_some_and(|interval1| interval1.max <= bound_max2) =>
+        {
+            Simplified::Alias(argument2)
+        }
+        _ => return None,
+    };
+    Some(match simplified {
+        Simplified::Constant(value) => (simplified, OptimizationKind::FoldedConstant(value)),
+        Simplified::Alias(_) => (simplified, OptimizationKind::PrunedMinMax),
+    })
+}
+
+/// Compacts `graph` down to the nodes reachable from `roots`, renumbering nodes and cache slots.
+fn remove_unreachable<'a>(
+    graph: DensityFunctionGraph<'a>,
+    roots: &mut [NodeId],
+) -> DensityFunctionGraph<'a> {
+    let mut reachable = vec![false; graph.nodes.len()];
+    for root in roots.iter() {
+        reachable[root.0] = true;
+    }
+    for index in (0..graph.nodes.len()).rev() {
+        if reachable[index] {
+            graph.nodes[index].for_each_child(|child| reachable[child.0] = true);
+        }
+    }
+
+    let mut compacted = DensityFunctionGraph {
+        nodes: Vec::new(),
+        bounds: Vec::new(),
+        slot_count: 0,
+        names: Vec::new(),
+    };
+    let mut forward = vec![NodeId(usize::MAX); graph.nodes.len()];
+    let nodes = graph.nodes.into_iter().zip(graph.bounds).zip(graph.names);
+    for (index, ((mut node, bounds), name)) in nodes.enumerate() {
+        if !reachable[index] {
+            continue;
+        }
+        node.for_each_child_mut(|child| *child = forward[child.0]);
+        if let Node::Cache { slot, .. } = &mut node {
+            *slot = compacted.slot_count;
+            compacted.slot_count += 1;
+        }
+        forward[index] = push(&mut compacted, node, bounds, name);
+    }
+    for root in roots.iter_mut() {
+        *root = forward[root.0];
+    }
+    compacted
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::density_functions::compiler::DensityFunctionCompiler;
+    use crate::density_functions::graph::{GraphEvaluator, Node};
+    use crate::density_functions::optimizer::{optimize, OptimizationKind};
+    use crate::random_state::RandomState;
+    use datapack::data::density_function::DensityFunction;
+    use datapack::data::holder::Holder;
+    use datapack::DataPack;
+    use glam::IVec3;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_optimize() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let density_functions = dir.join("data/minecraft/worldgen/density_function");
+        fs::create_dir_all(&density_functions).unwrap();
+        fs::write(
+            density_functions.join("folded.json"),
+            r#"{ "type": "minecraft:mul", "argument1": 2, "argument2": { "type": "minecraft:square", "argument": 3 } }"#,
+        )
+        .unwrap();
+        let pruned: Holder<DensityFunction> = serde_json::from_str(
+            r#"{
+                "type": "minecraft:min",
+                "argument1": {
+                    "type": "minecraft:range_choice",
+                    "input": {
+                        "type": "minecraft:clamp",
+                        "input": {
+                            "type": "minecraft:add",
+                            "argument1": { "type": "minecraft:mul", "argument1": 2, "argument2": "minecraft:gradient" },
+                            "argument2": 0
+                        },
+                        "min": -5,
+                        "max": 5
+                    },
+                    "min_inclusive": -10,
+                    "max_exclusive": 10,
+                    "when_in_range": "minecraft:gradient",
+                    "when_out_of_range": 1
+                },
+                "argument2": 10
+            }"#,
+        )
+        .unwrap();
+        fs::write(
+            density_functions.join("gradient.json"),
+            r#"{ "type": "minecraft:y_clamped_gradient", "from_y": 0, "to_y": 16, "from_value": -1, "to_value": 1 }"#,
+        )
+        .unwrap();
+        let folded = Holder::Reference("folded".try_into().unwrap());
+
+        let datapack = DataPack::new(dir).unwrap();
+        let random_state = RandomState::new(&datapack, 0, false);
+        let mut compiler = DensityFunctionCompiler::new(&datapack, &random_state);
+        let mut roots = [
+            compiler.compile(&folded).unwrap(),
+            compiler.compile(&pruned).unwrap(),
+        ];
+        let graph = compiler.finish();
+        let original_roots = roots;
+        let (optimized, optimizations) = optimize(graph, &mut roots);
+
+        let kinds: Vec<_> = optimizations
+            .iter()
+            .map(|optimization| optimization.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                OptimizationKind::FoldedConstant(9.0),
+                OptimizationKind::FoldedConstant(18.0),
+                OptimizationKind::RemovedIdentity,
+                OptimizationKind::RemovedClamp,
+                OptimizationKind::PrunedRangeChoice { in_range: true },
+                OptimizationKind::PrunedMinMax,
+            ]
+        );
+        assert_eq!(optimizations[0].to_string(), "folded: folded to constant 9");
+        assert!(matches!(optimized.node(roots[0]), Node::Constant(18.0)));
+        assert!(matches!(
+            optimized.node(roots[1]),
+            Node::YClampedGradient { .. }
+        ));
+        assert_eq!(optimized.nodes().len(), 2);
+
+        // the optimized graph computes the same values as the original one
+        let mut compiler = DensityFunctionCompiler::new(&datapack, &random_state);
+        compiler.compile(&folded).unwrap();
+        compiler.compile(&pruned).unwrap();
+        let graph = compiler.finish();
+        let mut original = GraphEvaluator::new(&graph);
+        let mut evaluator = GraphEvaluator::new(&optimized);
+        for y in -4..20 {
+            let pos = IVec3::new(0, y, 0);
+            for (&root, &original_root) in roots.iter().zip(&original_roots) {
+                assert_eq!(
+                    evaluator.compute(root, pos),
+                    original.compute(original_root, pos)
+                );
+            }
+        }
+    }
+
+    /// Optimizes `json`, checking that the result computes the same values as the original down to
+    /// the sign of zero. Returns the optimizations made and the value at `y`.
+    fn optimize_function(json: &str, y: i32) -> (Vec<OptimizationKind>, f64) {
+        let temp_dir = TempDir::new().unwrap();
+        let datapack = DataPack::new(temp_dir.path()).unwrap();
+        let random_state = RandomState::new(&datapack, 0, false);
+        let function: Holder<DensityFunction> = serde_json::from_str(json).unwrap();
+        let compile = || {
+            let mut compiler = DensityFunctionCompiler::new(&datapack, &random_state);
+            let root = compiler.compile(&function).unwrap();
+            (compiler.finish(), root)
+        };
+        let (original, original_root) = compile();
+        let (graph, root) = compile();
+        let mut roots = [root];
+        let (optimized, optimizations) = optimize(graph, &mut roots);
+
+        let mut original = GraphEvaluator::new(&original);
+        let mut evaluator = GraphEvaluator::new(&optimized);
+        for y in -4..14 {
+            let pos = IVec3::new(0, y, 0);
+            assert_eq!(
+                evaluator.compute(roots[0], pos).to_bits(),
+                original.compute(original_root, pos).to_bits()
+            );
+        }
+        let kinds = optimizations
+            .iter()
+            .map(|optimization| optimization.kind)
+            .collect();
+        (kinds, evaluator.compute(roots[0], IVec3::new(0, y, 0)))
+    }
+
+    #[test]
+    fn test_mixed_sign_mul() {
+        // vanilla's bounds of this product don't contain the -1 it computes at y=0, so they can't
+        // prove the clamp unnecessary
+        let (kinds, value) = optimize_function(
+            r#"{
+                "type": "minecraft:clamp",
+                "input": {
+                    "type": "minecraft:mul",
+                    "argument1": { "type": "minecraft:y_clamped_gradient", "from_y": 0, "to_y": 10, "from_value": 1, "to_value": 2 },
+                    "argument2": { "type": "minecraft:y_clamped_gradient", "from_y": 0, "to_y": 10, "from_value": -1, "to_value": -3 }
+                },
+                "min": -10,
+                "max": -2
+            }"#,
+            0,
+        );
+        assert!(kinds.is_empty());
+        assert_eq!(value, -2.0);
+    }
+
+    #[test]
+    fn test_negative_zero() {
+        let gradient = r#"{ "type": "minecraft:y_clamped_gradient", "from_y": 0, "to_y": 10, "from_value": -0.0, "to_value": 1 }"#;
+        let add = |argument: &str| {
+            optimize_function(
+                &format!(
+                    r#"{{ "type": "minecraft:add", "argument1": {gradient}, "argument2": {argument} }}"#
+                ),
+                -1,
+            )
+        };
+
+        // adding 0 turns the -0.0 below the gradient into 0.0
+        let (kinds, value) = add("0");
+        assert!(kinds.is_empty());
+        assert!(value.is_sign_positive());
+        // but adding -0.0 doesn't
+        let (kinds, value) = add("-0.0");
+        assert_eq!(kinds, [OptimizationKind::RemovedIdentity]);
+        assert!(value.is_sign_negative());
+    }
+}
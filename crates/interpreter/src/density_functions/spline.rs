@@ -0,0 +1,261 @@
+use crate::density_functions::graph::CompiledSplinePoint;
+use crate::density_functions::DensityFunctionExt;
+use crate::sealed::Sealed;
+use datapack::data::density_function::{CubicSpline, DensityFunction, SplinePoint};
+use datapack::data::holder::Holder;
+use datapack::{DataPack, DataPackResult};
+use util::math;
+
+pub trait CubicSplineExt: Sealed {
+    /// Evaluates the spline like vanilla's `CubicSpline.Multipoint.apply`, using `coordinate` to
+    /// compute the coordinate functions of this spline and any nested splines.
+    fn apply<E>(
+        &self,
+        coordinate: &mut impl FnMut(&Holder<DensityFunction>) -> Result<f64, E>,
+    ) -> Result<f32, E>;
+
+    /// Returns the `(min, max)` range of values this spline can produce, computed like in
+    /// `CubicSpline.Multipoint.create`.
+    fn bounds(&self, datapack: &DataPack) -> DataPackResult<(f32, f32)>;
+
+    fn min_value(&self, datapack: &DataPack) -> DataPackResult<f32> {
+        Ok(self.bounds(datapack)?.0)
+    }
+
+    fn max_value(&self, datapack: &DataPack) -> DataPackResult<f32> {
+        Ok(self.bounds(datapack)?.1)
+    }
+}
+
+impl Sealed for CubicSpline {}
+
+impl CubicSplineExt for CubicSpline {
+    fn apply<E>(
+        &self,
+        coordinate_fn: &mut impl FnMut(&Holder<DensityFunction>) -> Result<f64, E>,
+    ) -> Result<f32, E> {
+        match self {
+            CubicSpline::Constant(value) => Ok(*value),
+            CubicSpline::Multipoint { coordinate, points } => {
+                let point = coordinate_fn(coordinate)? as f32;
+                apply_multipoint(point, points, |point| point.value.apply(coordinate_fn))
+            }
+        }
+    }
+
+    fn bounds(&self, datapack: &DataPack) -> DataPackResult<(f32, f32)> {
+        let (coordinate, points) = match self {
+            CubicSpline::Constant(value) => return Ok((*value, *value)),
+            CubicSpline::Multipoint { coordinate, points } => (coordinate, points),
+        };
+
+        let value_bounds = points
+            .iter()
+            .map(|point| point.value.bounds(datapack))
+            .collect::<DataPackResult<Vec<_>>>()?;
+        let (coordinate_min, coordinate_max) = coordinate.bounds(datapack)?;
+        let (coordinate_min, coordinate_max) = (coordinate_min as f32, coordinate_max as f32);
+
+        let mut min = f32::INFINITY;
+        let mut max = f32::NEG_INFINITY;
+        let mut extend = |coordinate: f32, index: usize| {
+            let point = &points[index];
+            let (value_min, value_max) = value_bounds[index];
+            let extended_min =
+                linear_extend(coordinate, point.location, point.derivative, value_min);
+            let extended_max =
+                linear_extend(coordinate, point.location, point.derivative, value_max);
+            min = min.min(extended_min.min(extended_max));
+            max = max.max(extended_min.max(extended_max));
+        };
+        let last = points.len() - 1;
+        if coordinate_min < points[0].location {
+            extend(coordinate_min, 0);
+        }
+        if coordinate_max > points[last].location {
+            extend(coordinate_max, last);
+        }
+
+        for &(value_min, value_max) in &value_bounds {
+            min = min.min(value_min);
+            max = max.max(value_max);
+        }
+
+        for index in 0..last {
+            let from = &points[index];
+            let to = &points[index + 1];
+            if from.derivative == 0.0 && to.derivative == 0.0 {
+                continue;
+            }
+            let width = to.location - from.location;
+            let (from_min, from_max) = value_bounds[index];
+            let (to_min, to_max) = value_bounds[index + 1];
+            let from_slope = from.derivative * width;
+            let to_slope = to.derivative * width;
+            let lowest_slope = (from_slope - to_max + from_min).min(-to_slope + to_min - from_max);
+            let highest_slope = (from_slope - to_min + from_max).max(-to_slope + to_max - from_min);
+            min = min.min(from_min.min(to_min) + 0.25 * lowest_slope);
+            max = max.max(from_max.max(to_max) + 0.25 * highest_slope);
+        }
+
+        Ok((min, max))
+    }
+}
+
+/// The location and derivative of a spline point, shared by the datapack and compiled splines.
+pub(crate) trait SplinePointData {
+    fn location(&self) -> f32;
+    fn derivative(&self) -> f32;
+}
+
+impl SplinePointData for SplinePoint {
+    fn location(&self) -> f32 {
+        self.location
+    }
+
+    fn derivative(&self) -> f32 {
+        self.derivative
+    }
+}
+
+impl SplinePointData for CompiledSplinePoint {
+    fn location(&self) -> f32 {
+        self.location
+    }
+
+    fn derivative(&self) -> f32 {
+        self.derivative
+    }
+}
+
+/// Interpolates between the spline `points` around `point`, computing the value of a point with
+/// `value`. Outside the first and last points, the spline is extended linearly along their
+/// derivative.
+pub(crate) fn apply_multipoint<P, E>(
+    point: f32,
+    points: &[P],
+    mut value: impl FnMut(&P) -> Result<f32, E>,
+) -> Result<f32, E>
+where
+    P: SplinePointData,
+{
+    let last = points.len() - 1;
+    let start = math::binary_search(0, points.len() as i32, |i| {
+        point < points[i as usize].location()
+    }) - 1;
+
+    if start < 0 {
+        let first = &points[0];
+        return Ok(linear_extend(
+            point,
+            first.location(),
+            first.derivative(),
+            value(first)?,
+        ));
+    }
+    let start = start as usize;
+    if start == last {
+        let last = &points[last];
+        return Ok(linear_extend(
+            point,
+            last.location(),
+            last.derivative(),
+            value(last)?,
+        ));
+    }
+
+    let from = &points[start];
+    let to = &points[start + 1];
+    let width = to.location() - from.location();
+    let t = (point - from.location()) / width;
+    let from_value = value(from)?;
+    let to_value = value(to)?;
+    let from_slope = from.derivative() * width - (to_value - from_value);
+    let to_slope = -to.derivative() * width + (to_value - from_value);
+    Ok(math::lerp(t, from_value, to_value) + t * (1.0 - t) * math::lerp(t, from_slope, to_slope))
+}
+
+fn linear_extend(point: f32, location: f32, derivative: f32, value: f32) -> f32 {
+    if derivative == 0.0 {
+        value
+    } else {
+        value + derivative * (point - location)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::density_functions::spline::CubicSplineExt;
+    use datapack::data::density_function::{CubicSpline, DensityFunction};
+    use datapack::data::holder::Holder;
+    use datapack::DataPack;
+    use std::convert::Infallible;
+    use tempfile::TempDir;
+
+    /// A spline over `-1..1` whose second point is a nested spline over a second coordinate.
+    const NESTED: &str = r#"{
+        "coordinate": { "type": "minecraft:y_clamped_gradient", "from_y": 0, "to_y": 10, "from_value": -2, "to_value": 2 },
+        "points": [
+            { "location": -1, "value": -1, "derivative": 0.5 },
+            {
+                "location": 1,
+                "value": {
+                    "coordinate": { "type": "minecraft:y_clamped_gradient", "from_y": 0, "to_y": 10, "from_value": 0, "to_value": 1 },
+                    "points": [
+                        { "location": 0, "value": 0, "derivative": 0 },
+                        { "location": 1, "value": 2, "derivative": 0 }
+                    ]
+                },
+                "derivative": 1
+            }
+        ]
+    }"#;
+
+    /// Applies the nested spline with both coordinates set to the given values.
+    fn apply(spline: &CubicSpline, outer: f64, inner: f64) -> f32 {
+        let mut coordinate = |function: &Holder<DensityFunction>| {
+            let Holder::Direct(DensityFunction::YClampedGradient(gradient)) = function else {
+                unreachable!();
+            };
+            Ok::<_, Infallible>(if *gradient.from_value < 0.0 {
+                outer
+            } else {
+                inner
+            })
+        };
+        spline.apply(&mut coordinate).unwrap()
+    }
+
+    #[test]
+    fn test_apply() {
+        let spline: CubicSpline = serde_json::from_str(NESTED).unwrap();
+        // at the points themselves, the spline takes the point values
+        assert_eq!(apply(&spline, -1.0, 0.0), -1.0);
+        assert_eq!(apply(&spline, 1.0, 1.0), 2.0);
+        // the nested spline is a smoothstep between 0 and 2
+        assert_eq!(apply(&spline, 1.0, 0.5), 1.0);
+        // outside the points, the values are extended linearly along the derivatives
+        assert_eq!(apply(&spline, -3.0, 0.0), -2.0);
+        assert_eq!(apply(&spline, 2.0, 1.0), 3.0);
+        // halfway, the value is the average plus the hermite terms
+        assert_eq!(apply(&spline, 0.0, 1.0), 0.375);
+    }
+
+    #[test]
+    fn test_bounds() {
+        let temp_dir = TempDir::new().unwrap();
+        let datapack = DataPack::new(temp_dir.path()).unwrap();
+        let spline: CubicSpline = serde_json::from_str(NESTED).unwrap();
+        let (min, max) = spline.bounds(&datapack).unwrap();
+        assert_eq!((min, max), (-1.5, 3.0));
+        for outer in [-2.0, -1.5, -1.0, -0.5, 0.0, 0.5, 1.0, 1.5, 2.0] {
+            for inner in [0.0, 0.25, 0.5, 0.75, 1.0] {
+                let value = apply(&spline, outer, inner);
+                assert!(
+                    value >= min && value <= max,
+                    "{value} at ({outer}, {inner})"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use ahash::AHashMap;
+use datapack::data::biome::Biome;
+use datapack::data::feature::PlacedFeature;
+use datapack::data::holder::Holder;
+use datapack::{DataPack, DataPackError, DataPackResult};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ptr;
+
+/// The features of a decoration step in the order they are placed, equivalent to vanilla's
+/// `FeatureSorter.StepFeatureData`.
+#[derive(Debug)]
+pub struct StepFeatureData<'a> {
+    features: Vec<&'a PlacedFeature>,
+    /// The index of every feature, keyed by its address like vanilla's identity lookup.
+    indices: AHashMap<*const PlacedFeature, usize>,
+}
+
+impl<'a> StepFeatureData<'a> {
+    fn new(features: Vec<&'a PlacedFeature>) -> Self {
+        let indices = features
+            .iter()
+            .enumerate()
+            .map(|(index, &feature)| (ptr::from_ref(feature), index))
+            .collect();
+        StepFeatureData { features, indices }
+    }
+
+    pub fn features(&self) -> &[&'a PlacedFeature] {
+        &self.features
+    }
+
+    /// The index of a feature in this step, which its random is seeded with.
+    pub fn index_of(&self, feature: &PlacedFeature) -> Option<usize> {
+        self.indices.get(&ptr::from_ref(feature)).copied()
+    }
+}
+
+/// A feature in a decoration step, ordered by step and then by the order features were first
+/// seen in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct FeatureData {
+    step: usize,
+    index: usize,
+}
+
+/// Orders the features of every decoration step so that the features of each biome are placed in
+/// the order the biome lists them, equivalent to `FeatureSorter.buildFeaturesPerStep`. Fails if
+/// two biomes list features in conflicting orders.
+pub fn build_features_per_step<'a>(
+    datapack: &'a DataPack,
+    biomes: &[&'a Holder<Biome>],
+) -> DataPackResult<Vec<StepFeatureData<'a>>> {
+    let mut indices = AHashMap::<*const PlacedFeature, usize>::new();
+    let mut features = Vec::<&PlacedFeature>::new();
+    let mut graph = BTreeMap::<FeatureData, BTreeSet<FeatureData>>::new();
+    let mut step_count = 0;
+
+    for biome in biomes {
+        let steps = &biome.resolve(datapack)?.generation_settings.features;
+        step_count = step_count.max(steps.len());
+        let mut biome_features = Vec::new();
+        for (step, step_features) in steps.iter().enumerate() {
+            for feature in step_features {
+                let feature = feature.resolve(datapack)?;
+                let index = *indices.entry(ptr::from_ref(feature)).or_insert_with(|| {
+                    features.push(feature);
+                    features.len() - 1
+                });
+                biome_features.push(FeatureData { step, index });
+            }
+        }
+        for (i, &feature) in biome_features.iter().enumerate() {
+            let successors = graph.entry(feature).or_default();
+            if let Some(&next) = biome_features.get(i + 1) {
+                successors.insert(next);
+            }
+        }
+    }
+
+    let mut non_cyclical = BTreeSet::new();
+    let mut path = BTreeSet::new();
+    let mut sorted = Vec::new();
+    for &feature in graph.keys() {
+        if !non_cyclical.contains(&feature)
+            && depth_first_search(&graph, &mut non_cyclical, &mut path, &mut sorted, feature)
+        {
+            return Err(DataPackError::FeatureOrderCycle);
+        }
+    }
+    sorted.reverse();
+
+    Ok((0..step_count)
+        .map(|step| {
+            StepFeatureData::new(
+                sorted
+                    .iter()
+                    .filter(|feature| feature.step == step)
+                    .map(|feature| features[feature.index])
+                    .collect(),
+            )
+        })
+        .collect())
+}
+
+/// Visits the successors of `node` before adding it to `sorted`, returning `true` if it's part of
+/// a cycle. Equivalent to vanilla's `Graph.depthFirstSearch`.
+fn depth_first_search(
+    graph: &BTreeMap<FeatureData, BTreeSet<FeatureData>>,
+    non_cyclical: &mut BTreeSet<FeatureData>,
+    path: &mut BTreeSet<FeatureData>,
+    sorted: &mut Vec<FeatureData>,
+    node: FeatureData,
+) -> bool {
+    if non_cyclical.contains(&node) {
+        return false;
+    }
+    if !path.insert(node) {
+        return true;
+    }
+    for &successor in graph.get(&node).into_iter().flatten() {
+        if depth_first_search(graph, non_cyclical, path, sorted, successor) {
+            return true;
+        }
+    }
+    path.remove(&node);
+    non_cyclical.insert(node);
+    sorted.push(node);
+    false
+}
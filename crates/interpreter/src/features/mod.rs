@@ -0,0 +1,493 @@
+pub mod feature_sorter;
+pub mod placement;
+
+use crate::biomes::climate::ClimateSampler;
+use crate::biomes::{BiomeSourceImpl, QuartPos};
+use crate::block_state::BlockStateExt;
+use crate::chunk::world_gen_region::WorldGenRegion;
+use crate::chunk::ChunkAccess;
+use crate::features::feature_sorter::StepFeatureData;
+use crate::features::placement::PlacedFeatureExt;
+use crate::sealed::Sealed;
+use crate::world_generation_context::WorldGenerationContext;
+use ahash::AHashSet;
+use datapack::data::biome::Biome;
+use datapack::data::feature::configured_feature::{ConfiguredFeature, LayerConfiguration};
+use datapack::data::holder::Holder;
+use datapack::{DataPack, DataPackError, DataPackResult};
+use glam::{IVec2, IVec3};
+use runtime::random_source::{RandomSource, WorldgenRandom, XoroshiroRandomSource};
+use std::collections::BTreeSet;
+use std::fmt::Debug;
+
+/// What features and their placement modifiers place into, equivalent to vanilla's
+/// `PlacementContext` without the top feature.
+pub struct FeatureContext<'r, 'c, 'a> {
+    datapack: &'a DataPack,
+    generation_context: WorldGenerationContext,
+    region: &'r mut WorldGenRegion<'c>,
+    /// Samples the biome source for biomes outside of the region.
+    uncached_biome: &'r mut dyn FnMut(QuartPos) -> &'a Holder<Biome>,
+}
+
+impl<'r, 'c, 'a> FeatureContext<'r, 'c, 'a> {
+    pub fn new(
+        datapack: &'a DataPack,
+        generation_context: WorldGenerationContext,
+        region: &'r mut WorldGenRegion<'c>,
+        uncached_biome: &'r mut dyn FnMut(QuartPos) -> &'a Holder<Biome>,
+    ) -> Self {
+        FeatureContext {
+            datapack,
+            generation_context,
+            region,
+            uncached_biome,
+        }
+    }
+
+    pub fn region(&self) -> &WorldGenRegion<'c> {
+        self.region
+    }
+}
+
+pub trait ConfiguredFeatureExt: Sealed {
+    /// Places the feature at `origin`, returning whether it placed anything. Equivalent to
+    /// `ConfiguredFeature.place`. Fails for the features that need block behaviour, which isn't
+    /// modelled yet.
+    fn place(
+        &self,
+        context: &mut FeatureContext,
+        random: &mut impl RandomSource,
+        origin: IVec3,
+    ) -> DataPackResult<bool>;
+}
+
+impl Sealed for ConfiguredFeature {}
+
+impl ConfiguredFeatureExt for ConfiguredFeature {
+    fn place(
+        &self,
+        context: &mut FeatureContext,
+        random: &mut impl RandomSource,
+        origin: IVec3,
+    ) -> DataPackResult<bool> {
+        if !context.region.ensure_can_write(origin) {
+            return Ok(false);
+        }
+        match self {
+            ConfiguredFeature::NoOp(_) => Ok(true),
+            ConfiguredFeature::FillLayer(config) => Ok(fill_layer(context.region, config, origin)),
+            ConfiguredFeature::RandomSelector(config) => {
+                for feature in &config.features {
+                    if random.next_f32() < *feature.chance {
+                        return feature.feature.place(context, random, origin);
+                    }
+                }
+                config.placed_feature.place(context, random, origin)
+            }
+            ConfiguredFeature::SimpleRandomSelector(config) => {
+                let features = config.features.resolve(context.datapack)?;
+                // vanilla fails to pick from an empty list
+                if features.is_empty() {
+                    return Ok(false);
+                }
+                let index = random.next_u32(features.len() as u32) as usize;
+                features[index].place(context, random, origin)
+            }
+            ConfiguredFeature::RandomBooleanSelector(config) => {
+                let feature = if random.next_bool() {
+                    &config.feature_true
+                } else {
+                    &config.feature_false
+                };
+                feature.place(context, random, origin)
+            }
+            // the other features need block behaviour, which isn't modelled yet
+            _ => Err(DataPackError::UnsupportedFeature(variant_name(self))),
+        }
+    }
+}
+
+/// The name of the variant of a feature or placement modifier, for errors about it.
+pub(crate) fn variant_name(value: &impl Debug) -> String {
+    let debug = format!("{value:?}");
+    let end = debug.find(['(', ' ', '{']).unwrap_or(debug.len());
+    debug[..end].to_owned()
+}
+
+/// Fills the air of a layer of the 16x16 blocks from `origin`, `FillLayerFeature.place`.
+fn fill_layer(region: &mut WorldGenRegion, config: &LayerConfiguration, origin: IVec3) -> bool {
+    let y = region.min_y() + *config.height as i32;
+    for x in 0..16 {
+        for z in 0..16 {
+            let pos = IVec3::new(origin.x + x, y, origin.z + z);
+            if region.block_state(pos).is_air() {
+                region.set_block_state(pos, config.state.clone());
+            }
+        }
+    }
+    true
+}
+
+/// Places the features of biomes in chunks, holding what vanilla's `ChunkGenerator` computes once
+/// per dimension for it.
+pub struct FeatureDecorator<'a> {
+    datapack: &'a DataPack,
+    biome_source: &'a dyn BiomeSourceImpl,
+    generation_context: WorldGenerationContext,
+    possible_biomes: Vec<&'a Holder<Biome>>,
+    features_per_step: Vec<StepFeatureData<'a>>,
+}
+
+impl<'a> FeatureDecorator<'a> {
+    /// Orders the features of every biome the biome source can return. Fails if the biomes list
+    /// their features in conflicting orders.
+    pub fn new(
+        datapack: &'a DataPack,
+        biome_source: &'a impl BiomeSourceImpl,
+        generation_context: WorldGenerationContext,
+    ) -> DataPackResult<Self> {
+        let possible_biomes = biome_source.possible_biomes();
+        let features_per_step =
+            feature_sorter::build_features_per_step(datapack, &possible_biomes)?;
+        Ok(FeatureDecorator {
+            datapack,
+            biome_source,
+            generation_context,
+            possible_biomes,
+            features_per_step,
+        })
+    }
+
+    pub fn features_per_step(&self) -> &[StepFeatureData<'a>] {
+        &self.features_per_step
+    }
+
+    /// Places the features of the biomes in the chunks of the region into its center chunk, step
+    /// by step. Equivalent to `ChunkGenerator.applyBiomeDecoration`, except that structures aren't
+    /// placed yet. Fails if any of the features or their placement modifiers isn't supported.
+    pub fn apply_biome_decoration(
+        &self,
+        region: &mut WorldGenRegion,
+        seed: u64,
+        climate_sampler: &mut ClimateSampler,
+    ) -> DataPackResult<()> {
+        let center = region.center();
+        let origin = IVec3::new(
+            center.x << 4,
+            region.center_chunk().min_section() << 4,
+            center.y << 4,
+        );
+        let mut random = WorldgenRandom::new(XoroshiroRandomSource::new(0));
+        let decoration_seed = random.set_decoration_seed(seed, origin.x, origin.z);
+
+        let mut region_biomes = AHashSet::new();
+        for chunk in region.chunks() {
+            let min_quart: IVec2 = chunk.pos() << 2;
+            let min_quart_y = chunk.min_y() >> 2;
+            for y in min_quart_y..min_quart_y + (chunk.height() >> 2) {
+                for z in 0..4 {
+                    for x in 0..4 {
+                        region_biomes.insert(chunk.biome(QuartPos::new(
+                            min_quart.x + x,
+                            y,
+                            min_quart.y + z,
+                        )));
+                    }
+                }
+            }
+        }
+        let biomes = self
+            .possible_biomes
+            .iter()
+            .filter(|biome| matches!(biome, Holder::Reference(id) if region_biomes.contains(id)))
+            .map(|biome| biome.resolve(self.datapack))
+            .collect::<DataPackResult<Vec<_>>>()?;
+
+        let mut uncached_biome = |pos| self.biome_source.get_noise_biome(pos, climate_sampler);
+        let mut context = FeatureContext::new(
+            self.datapack,
+            self.generation_context,
+            region,
+            &mut uncached_biome,
+        );
+        for (step, step_features) in self.features_per_step.iter().enumerate() {
+            let mut indices = BTreeSet::new();
+            for biome in &biomes {
+                let Some(features) = biome.generation_settings.features.get(step) else {
+                    continue;
+                };
+                for feature in features {
+                    if let Some(index) = step_features.index_of(feature.resolve(self.datapack)?) {
+                        indices.insert(index);
+                    }
+                }
+            }
+            for index in indices {
+                random.set_feature_seed(decoration_seed, index, step);
+                step_features.features()[index].place_with_biome_check(
+                    &mut context,
+                    &mut random,
+                    origin,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::biomes::biome_manager::BiomeManager;
+    use crate::biomes::checkerboard::CheckerboardColumnBiomeSourceImpl;
+    use crate::chunk::proto_chunk::ProtoChunk;
+    use crate::chunk::world_gen_region::WorldGenRegion;
+    use crate::features::feature_sorter::build_features_per_step;
+    use crate::features::placement::PlacedFeatureExt;
+    use crate::features::{FeatureContext, FeatureDecorator};
+    use crate::test_util::{datapack, empty_datapack, with_climate_sampler, zero_router};
+    use crate::world_generation_context::WorldGenerationContext;
+    use datapack::data::biome::Biome;
+    use datapack::data::biome_source::CheckerboardColumnBiomeSource;
+    use datapack::data::feature::PlacedFeature;
+    use datapack::data::holder::Holder;
+    use datapack::{DataPack, DataPackError};
+    use glam::{IVec2, IVec3};
+    use runtime::random_source::XoroshiroRandomSource;
+    use util::heightmap_type::HeightmapType;
+    use util::identifier::IdentifierBuf;
+
+    fn biome_json(features: &str) -> String {
+        format!(
+            r#"{{
+                "has_precipitation": true, "temperature": 0.8, "downfall": 0.4,
+                "effects": {{ "fog_color": 0, "water_color": 0, "water_fog_color": 0, "sky_color": 0 }},
+                "carvers": {{}},
+                "features": {features},
+                "spawners": {{}},
+                "spawn_costs": {{}}
+            }}"#
+        )
+    }
+
+    fn chunks_around(center: IVec2, biome: &Holder<Biome>) -> Vec<ProtoChunk> {
+        (-1..=1)
+            .flat_map(|dz| (-1..=1).map(move |dx| center + IVec2::new(dx, dz)))
+            .map(|pos| {
+                let mut chunk = ProtoChunk::new(pos, 0, 32);
+                chunk.fill_biomes(|_| biome);
+                chunk
+            })
+            .collect()
+    }
+
+    fn feature_ids(features: &[&PlacedFeature], datapack: &DataPack) -> Vec<String> {
+        features
+            .iter()
+            .map(|&feature| {
+                (1..=4)
+                    .map(|index| format!("f{index}"))
+                    .find(|name| {
+                        let id = IdentifierBuf::new(format!("minecraft:{name}")).unwrap();
+                        std::ptr::eq(
+                            Holder::<PlacedFeature>::resolve_reference(datapack, &id).unwrap(),
+                            feature,
+                        )
+                    })
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_features_per_step() {
+        let datapack = datapack((1..=4).map(|index| {
+            (
+                format!("worldgen/placed_feature/f{index}.json"),
+                r#"{ "feature": { "type": "minecraft:no_op" }, "placement": [] }"#,
+            )
+        }));
+        let biome = |features: &str| -> Holder<Biome> {
+            serde_json::from_str(&biome_json(features)).unwrap()
+        };
+
+        // f3 is first seen after f2, but the second biome places it before f1
+        let first = biome(r#"[["minecraft:f2", "minecraft:f1"], [], ["minecraft:f4"]]"#);
+        let second = biome(r#"[["minecraft:f3", "minecraft:f1"]]"#);
+        let steps = build_features_per_step(&datapack, &[&first, &second]).unwrap();
+        assert_eq!(steps.len(), 3);
+        assert_eq!(
+            feature_ids(steps[0].features(), &datapack),
+            ["f3", "f2", "f1"]
+        );
+        assert!(steps[1].features().is_empty());
+        assert_eq!(feature_ids(steps[2].features(), &datapack), ["f4"]);
+        let f1 = steps[0].features()[2];
+        assert_eq!(steps[0].index_of(f1), Some(2));
+        assert_eq!(steps[2].index_of(f1), None);
+
+        let cycle = biome(r#"[["minecraft:f1", "minecraft:f2"]]"#);
+        assert!(matches!(
+            build_features_per_step(&datapack, &[&first, &cycle]),
+            Err(DataPackError::FeatureOrderCycle)
+        ));
+    }
+
+    #[test]
+    fn test_apply_biome_decoration() {
+        let layer = |height: i32, block: &str, placement: &str| {
+            format!(
+                r#"{{
+                    "feature": {{
+                        "type": "minecraft:fill_layer",
+                        "height": {height},
+                        "state": {{ "Name": "minecraft:{block}" }}
+                    }},
+                    "placement": [{placement}]
+                }}"#
+            )
+        };
+        let datapack = datapack([
+            (
+                "worldgen/placed_feature/stone_layer.json",
+                layer(5, "stone", ""),
+            ),
+            (
+                "worldgen/placed_feature/glass_layer.json",
+                layer(
+                    7,
+                    "glass",
+                    r#"{ "type": "minecraft:in_square_placement" }, { "type": "minecraft:biome_filter" }"#,
+                ),
+            ),
+            (
+                "worldgen/placed_feature/dirt_layer.json",
+                layer(9, "dirt", ""),
+            ),
+            (
+                "worldgen/biome/plains.json",
+                biome_json(r#"[[], ["minecraft:glass_layer", "minecraft:stone_layer"]]"#),
+            ),
+            (
+                "worldgen/biome/desert.json",
+                biome_json(r#"[[], ["minecraft:dirt_layer", "minecraft:glass_layer"]]"#),
+            ),
+        ]);
+        let source: CheckerboardColumnBiomeSource = serde_json::from_str(
+            r#"{ "biomes": ["minecraft:plains", "minecraft:desert"], "scale": 0 }"#,
+        )
+        .unwrap();
+        let biome_source = CheckerboardColumnBiomeSourceImpl::new(&datapack, &source).unwrap();
+        let generation_context = WorldGenerationContext {
+            min_y: 0,
+            height: 32,
+        };
+        let decorator =
+            FeatureDecorator::new(&datapack, &biome_source, generation_context).unwrap();
+        assert!(decorator.features_per_step()[0].features().is_empty());
+        assert_eq!(decorator.features_per_step()[1].features().len(), 3);
+
+        // only plains is in the region, so the dirt layer of the desert isn't placed
+        let seed = 7;
+        let center = IVec2::new(3, -2);
+        let plains = Holder::Reference(IdentifierBuf::new("minecraft:plains").unwrap());
+        let mut chunks = chunks_around(center, &plains);
+        let mut region = WorldGenRegion::new(center, &mut chunks, BiomeManager::new(seed));
+        with_climate_sampler(&datapack, &zero_router(), |sampler| {
+            decorator.apply_biome_decoration(&mut region, seed, sampler)
+        })
+        .unwrap();
+
+        // the glass layer is the feature with index 1 in step 1, and vanilla's in_square placement
+        // offsets it by this with seed 7 in chunk (3, -2)
+        let offset = IVec2::new(3, 8);
+        let min_block = center * 16;
+        for x in -16..32 {
+            for z in -16..32 {
+                let block = |y: i32| {
+                    let pos = IVec3::new(min_block.x + x, y, min_block.y + z);
+                    region.block_state(pos).name.path().to_owned()
+                };
+                let in_center = (0..16).contains(&x) && (0..16).contains(&z);
+                let in_glass = (offset.x..offset.x + 16).contains(&x)
+                    && (offset.y..offset.y + 16).contains(&z);
+                assert_eq!(block(5), if in_center { "stone" } else { "air" });
+                assert_eq!(block(7), if in_glass { "glass" } else { "air" });
+                assert_eq!(block(9), "air");
+            }
+        }
+    }
+
+    #[test]
+    fn test_unsupported_features() {
+        let datapack = empty_datapack();
+        let plains = Holder::Reference(IdentifierBuf::new("minecraft:plains").unwrap());
+        let mut chunks = chunks_around(IVec2::ZERO, &plains);
+        let mut region = WorldGenRegion::new(IVec2::ZERO, &mut chunks, BiomeManager::new(0));
+        let generation_context = WorldGenerationContext {
+            min_y: 0,
+            height: 32,
+        };
+        let mut uncached_biome = |_| &plains;
+        let mut context = FeatureContext::new(
+            &datapack,
+            generation_context,
+            &mut region,
+            &mut uncached_biome,
+        );
+        let mut random = XoroshiroRandomSource::new(0);
+
+        let feature: PlacedFeature = serde_json::from_str(
+            r#"{ "feature": { "type": "minecraft:ice_spike" }, "placement": [] }"#,
+        )
+        .unwrap();
+        let result = feature.place(&mut context, &mut random, IVec3::new(0, 16, 0));
+        assert!(
+            matches!(result, Err(DataPackError::UnsupportedFeature(name)) if name == "IceSpike")
+        );
+
+        let feature: PlacedFeature = serde_json::from_str(
+            r#"{
+                "feature": { "type": "minecraft:no_op" },
+                "placement": [{
+                    "type": "minecraft:block_predicate_filter",
+                    "predicate": { "type": "minecraft:true" }
+                }]
+            }"#,
+        )
+        .unwrap();
+        let result = feature.place(&mut context, &mut random, IVec3::new(0, 16, 0));
+        assert!(matches!(
+            result,
+            Err(DataPackError::UnsupportedPlacementModifier(name)) if name == "BlockPredicateFilter"
+        ));
+    }
+
+    #[test]
+    fn test_region_outside() {
+        let datapack = datapack([("worldgen/biome/plains.json", biome_json("[]"))]);
+        let plains_id = IdentifierBuf::new("minecraft:plains").unwrap();
+        let plains = Holder::Reference(plains_id.clone());
+        let desert: Holder<Biome> = serde_json::from_str(&biome_json("[]")).unwrap();
+        let mut chunks = chunks_around(IVec2::ZERO, &plains);
+        let region = WorldGenRegion::new(IVec2::ZERO, &mut chunks, BiomeManager::new(0));
+
+        // biomes outside of the region come from the biome source
+        let mut uncached_biome = |_| &desert;
+        let mut biome = |pos| region.biome(&datapack, pos, &mut uncached_biome).unwrap();
+        let plains = Holder::<Biome>::resolve_reference(&datapack, &plains_id).unwrap();
+        let desert = desert.resolve(&datapack).unwrap();
+        assert!(std::ptr::eq(biome(IVec3::new(-8, 16, 20)), plains));
+        assert!(std::ptr::eq(biome(IVec3::new(-40, 16, 8)), desert));
+        assert!(std::ptr::eq(biome(IVec3::new(8, 16, 100)), desert));
+
+        // outside of the region, there are no blocks and no surface
+        let pos = IVec3::new(100, 16, 8);
+        assert!(!region.ensure_can_write(pos));
+        assert_eq!(region.block_state(pos).name.path(), "air");
+        assert_eq!(
+            region.first_available(HeightmapType::WorldSurface, 100, 8),
+            0
+        );
+    }
+}
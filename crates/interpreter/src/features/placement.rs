@@ -0,0 +1,249 @@
+use crate::biomes::temperature;
+use crate::block_state::BlockStateExt;
+use crate::chunk::world_gen_region::WorldGenRegion;
+use crate::features::{variant_name, ConfiguredFeatureExt, FeatureContext};
+use crate::sealed::Sealed;
+use crate::value_providers::HeightProviderExt;
+use datapack::data::feature::configured_feature::ConfiguredFeature;
+use datapack::data::feature::placement_modifier::PlacementModifier;
+use datapack::data::feature::PlacedFeature;
+use datapack::{DataPackError, DataPackResult};
+use glam::{IVec2, IVec3};
+use runtime::random_source::RandomSource;
+use std::ptr;
+use util::heightmap_type::HeightmapType;
+use util::identifier::Identifier;
+
+const WATER: &Identifier = Identifier::new_const("water");
+const LAVA: &Identifier = Identifier::new_const("lava");
+const BEDROCK: &Identifier = Identifier::new_const("bedrock");
+
+pub trait PlacedFeatureExt: Sealed {
+    /// Places the feature at every position its placement modifiers produce from `origin`,
+    /// returning whether any of them placed anything. Equivalent to `PlacedFeature.place`.
+    fn place(
+        &self,
+        context: &mut FeatureContext,
+        random: &mut impl RandomSource,
+        origin: IVec3,
+    ) -> DataPackResult<bool>;
+
+    /// Like [`place`](Self::place), for a feature placed by a biome, which its biome filters
+    /// check for. Equivalent to `PlacedFeature.placeWithBiomeCheck`.
+    fn place_with_biome_check(
+        &self,
+        context: &mut FeatureContext,
+        random: &mut impl RandomSource,
+        origin: IVec3,
+    ) -> DataPackResult<bool>;
+}
+
+impl Sealed for PlacedFeature {}
+
+impl PlacedFeatureExt for PlacedFeature {
+    fn place(
+        &self,
+        context: &mut FeatureContext,
+        random: &mut impl RandomSource,
+        origin: IVec3,
+    ) -> DataPackResult<bool> {
+        let feature = self.feature.resolve(context.datapack)?;
+        place_at_positions(&self.placement, feature, context, None, random, origin)
+    }
+
+    fn place_with_biome_check(
+        &self,
+        context: &mut FeatureContext,
+        random: &mut impl RandomSource,
+        origin: IVec3,
+    ) -> DataPackResult<bool> {
+        let feature = self.feature.resolve(context.datapack)?;
+        place_at_positions(
+            &self.placement,
+            feature,
+            context,
+            Some(self),
+            random,
+            origin,
+        )
+    }
+}
+
+/// Applies the first modifier to `pos` and the rest to each of its positions in turn, placing the
+/// feature once no modifiers are left. Like the streams of vanilla's
+/// `PlacedFeature.placeWithContext`, every position is placed before the next one is produced.
+fn place_at_positions(
+    modifiers: &[PlacementModifier],
+    feature: &ConfiguredFeature,
+    context: &mut FeatureContext,
+    top_feature: Option<&PlacedFeature>,
+    random: &mut impl RandomSource,
+    pos: IVec3,
+) -> DataPackResult<bool> {
+    let Some((modifier, modifiers)) = modifiers.split_first() else {
+        return feature.place(context, random, pos);
+    };
+    let mut placed = false;
+    for pos in positions(modifier, context, top_feature, random, pos)? {
+        placed |= place_at_positions(modifiers, feature, context, top_feature, random, pos)?;
+    }
+    Ok(placed)
+}
+
+/// The positions a placement modifier turns `pos` into, `PlacementModifier.getPositions`.
+/// Modifiers testing blocks with block predicates need block behaviour, which isn't modelled yet,
+/// so they fail.
+fn positions(
+    modifier: &PlacementModifier,
+    context: &mut FeatureContext,
+    top_feature: Option<&PlacedFeature>,
+    random: &mut impl RandomSource,
+    pos: IVec3,
+) -> DataPackResult<Vec<IVec3>> {
+    let region = &*context.region;
+    let filter = |keep: bool| if keep { vec![pos] } else { Vec::new() };
+    Ok(match modifier {
+        PlacementModifier::BiomeFilter(_) => filter(biome_has_feature(context, top_feature, pos)?),
+        PlacementModifier::BlockPredicateFilter(_)
+        | PlacementModifier::EnvironmentScanPlacement(_) => {
+            return Err(DataPackError::UnsupportedPlacementModifier(variant_name(
+                modifier,
+            )));
+        }
+        PlacementModifier::CarvingMaskPlacement(modifier) => {
+            let chunk_pos = IVec2::new(pos.x >> 4, pos.z >> 4);
+            region
+                .chunk(chunk_pos)
+                .and_then(|chunk| chunk.carving_mask(modifier.step))
+                .map_or_else(Vec::new, |mask| mask.positions(chunk_pos).collect())
+        }
+        PlacementModifier::CountOnEveryLayerPlacement(modifier) => {
+            count_on_every_layer(region, *modifier.count, random, pos)
+        }
+        PlacementModifier::CountPlacement(modifier) => vec![pos; *modifier.count as usize],
+        PlacementModifier::FixedPlacement(modifier) => modifier
+            .positions
+            .iter()
+            .copied()
+            .filter(|fixed| fixed.x >> 4 == pos.x >> 4 && fixed.z >> 4 == pos.z >> 4)
+            .collect(),
+        PlacementModifier::HeightmapPlacement(modifier) => {
+            let y = region.first_available(modifier.heightmap, pos.x, pos.z);
+            if y > region.min_y() {
+                vec![pos.with_y(y)]
+            } else {
+                Vec::new()
+            }
+        }
+        PlacementModifier::HeightRangePlacement(modifier) => {
+            vec![pos.with_y(modifier.height.sample(random, &context.generation_context))]
+        }
+        PlacementModifier::InSquarePlacement(_) => {
+            let x = random.next_u32(16) as i32 + pos.x;
+            let z = random.next_u32(16) as i32 + pos.z;
+            vec![IVec3::new(x, pos.y, z)]
+        }
+        PlacementModifier::NoiseBasedCountPlacement(modifier) => {
+            let noise = temperature::biome_info_noise().get_value(
+                pos.x as f64 / modifier.noise_factor,
+                pos.z as f64 / modifier.noise_factor,
+                false,
+            );
+            let count = ((noise + *modifier.noise_offset) * modifier.noise_to_count_ratio as f64)
+                .ceil() as i32;
+            vec![pos; count.max(0) as usize]
+        }
+        PlacementModifier::RandomOffsetPlacement(modifier) => {
+            let xz_spread = *modifier.xz_spread;
+            vec![pos + IVec3::new(xz_spread, *modifier.y_spread, xz_spread)]
+        }
+        PlacementModifier::RarityFilter(modifier) => {
+            filter(random.next_f32() < 1.0 / *modifier.chance as f32)
+        }
+        PlacementModifier::SurfaceRelativeThresholdFilter(modifier) => {
+            let height = region.first_available(modifier.heightmap, pos.x, pos.z) as i64;
+            let y = pos.y as i64;
+            filter(
+                height + modifier.min_inclusive as i64 <= y
+                    && y <= height + modifier.max_inclusive as i64,
+            )
+        }
+        PlacementModifier::SurfaceWaterDepthFilter(modifier) => {
+            let ocean_floor = region.first_available(HeightmapType::OceanFloor, pos.x, pos.z);
+            let surface = region.first_available(HeightmapType::WorldSurface, pos.x, pos.z);
+            filter(surface - ocean_floor <= modifier.max_water_depth)
+        }
+    })
+}
+
+/// Whether the biome at `pos` places the top feature, `BiomeFilter.shouldPlace`. Vanilla fails
+/// when there is no top feature, here nothing is placed.
+fn biome_has_feature(
+    context: &mut FeatureContext,
+    top_feature: Option<&PlacedFeature>,
+    pos: IVec3,
+) -> DataPackResult<bool> {
+    let Some(top_feature) = top_feature else {
+        return Ok(false);
+    };
+    let biome = context
+        .region
+        .biome(context.datapack, pos, &mut *context.uncached_biome)?;
+    for feature in biome.generation_settings.features.iter().flatten() {
+        if ptr::eq(feature.resolve(context.datapack)?, top_feature) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// `CountOnEveryLayerPlacement.getPositions`, which places `count` features on each layer of
+/// ground from the top down, until a layer has no ground left.
+fn count_on_every_layer(
+    region: &WorldGenRegion,
+    count: i32,
+    random: &mut impl RandomSource,
+    pos: IVec3,
+) -> Vec<IVec3> {
+    let mut positions = Vec::new();
+    let mut layer = 0;
+    loop {
+        let mut found = false;
+        for _ in 0..count {
+            let x = random.next_u32(16) as i32 + pos.x;
+            let z = random.next_u32(16) as i32 + pos.z;
+            let top = region.first_available(HeightmapType::MotionBlocking, x, z);
+            if let Some(y) = find_on_ground_y(region, x, top, z, layer) {
+                positions.push(IVec3::new(x, y, z));
+                found = true;
+            }
+        }
+        if !found {
+            return positions;
+        }
+        layer += 1;
+    }
+}
+
+/// The y above the `layer`th ground below `y`, counting from the top,
+/// `CountOnEveryLayerPlacement.findOnGroundYPosition`.
+fn find_on_ground_y(region: &WorldGenRegion, x: i32, y: i32, z: i32, layer: i32) -> Option<i32> {
+    let is_empty = |y: i32| {
+        let state = region.block_state(IVec3::new(x, y, z));
+        state.is_air() || state.is(WATER) || state.is(LAVA)
+    };
+    let mut layers = 0;
+    let mut above_empty = is_empty(y);
+    for y in (region.min_y() + 1..=y).rev() {
+        let below = region.block_state(IVec3::new(x, y - 1, z));
+        let below_empty = is_empty(y - 1);
+        if !below_empty && above_empty && !below.is(BEDROCK) {
+            if layers == layer {
+                return Some(y);
+            }
+            layers += 1;
+        }
+        above_empty = below_empty;
+    }
+    None
+}
@@ -1,5 +1,19 @@
-mod biomes;
-mod density_functions;
+pub mod anvil;
+pub mod aquifer;
+pub mod biomes;
+pub mod block_state;
+pub mod carvers;
+pub mod chunk;
+pub mod density_functions;
+pub mod features;
+pub mod noise_chunk;
+pub mod ore_veinifier;
+pub mod random_state;
+pub mod surface;
+#[cfg(test)]
+mod test_util;
+pub mod value_providers;
+pub mod world_generation_context;
 
 mod sealed {
     pub trait Sealed {}
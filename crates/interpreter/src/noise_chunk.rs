@@ -0,0 +1,399 @@
+use crate::aquifer::{Aquifer, AquiferFunctions};
+use crate::block_state;
+use crate::block_state::BlockStateExt;
+use crate::density_functions::compiler::DensityFunctionCompiler;
+use crate::density_functions::graph::{CellSize, DensityFunctionGraph, GraphEvaluator, NodeId};
+use crate::density_functions::optimizer;
+use crate::ore_veinifier::{OreVeinFunctions, OreVeinifier};
+use crate::random_state::RandomState;
+use crate::surface::PreliminarySurface;
+use crate::world_generation_context::WorldGenerationContext;
+use ahash::AHashMap;
+use datapack::data::block_state::BlockState;
+use datapack::data::noise::{NoiseGeneratorSettings, NoiseRouter};
+use datapack::{DataPack, DataPackResult};
+use glam::{IVec2, IVec3};
+
+/// The density above which `NoiseChunk.computePreliminarySurfaceLevel` considers a block solid.
+const PRELIMINARY_SURFACE_DENSITY: f64 = 0.390625;
+
+/// The noise router functions used to fill chunks with terrain, compiled into a single graph that
+/// is shared between chunks.
+#[derive(Debug)]
+pub struct TerrainFunctions<'a> {
+    graph: DensityFunctionGraph<'a>,
+    final_density: NodeId,
+    initial_density_without_jaggedness: NodeId,
+    aquifer: AquiferFunctions,
+    ore_veins: OreVeinFunctions,
+}
+
+impl<'a> TerrainFunctions<'a> {
+    pub fn new(
+        datapack: &'a DataPack,
+        random_state: &'a RandomState<'a>,
+        router: &'a NoiseRouter,
+    ) -> DataPackResult<Self> {
+        let mut compiler = DensityFunctionCompiler::new(datapack, random_state);
+        let final_density = compiler.compile(&router.final_density)?;
+        let initial_density = compiler.compile(&router.initial_density_without_jaggedness)?;
+        let aquifer = AquiferFunctions::compile(&mut compiler, router)?;
+        let ore_veins = OreVeinFunctions::compile(&mut compiler, router)?;
+
+        let mut roots = [
+            final_density,
+            initial_density,
+            aquifer.barrier,
+            aquifer.fluid_level_floodedness,
+            aquifer.fluid_level_spread,
+            aquifer.lava,
+            aquifer.erosion,
+            aquifer.depth,
+            ore_veins.vein_toggle,
+            ore_veins.vein_ridged,
+            ore_veins.vein_gap,
+        ];
+        let (graph, _) = optimizer::optimize(compiler.finish(), &mut roots);
+        let [final_density, initial_density, barrier, fluid_level_floodedness, fluid_level_spread, lava, erosion, depth, vein_toggle, vein_ridged, vein_gap] =
+            roots;
+        Ok(TerrainFunctions {
+            graph,
+            final_density,
+            initial_density_without_jaggedness: initial_density,
+            aquifer: AquiferFunctions {
+                barrier,
+                fluid_level_floodedness,
+                fluid_level_spread,
+                lava,
+                erosion,
+                depth,
+            },
+            ore_veins: OreVeinFunctions {
+                vein_toggle,
+                vein_ridged,
+                vein_gap,
+            },
+        })
+    }
+
+    pub fn graph(&self) -> &DensityFunctionGraph<'a> {
+        &self.graph
+    }
+}
+
+/// Fills a chunk with terrain from the final density of a noise router, equivalent to vanilla's
+/// `NoiseChunk` and `NoiseBasedChunkGenerator.doFill`.
+pub struct NoiseChunk<'f, 'a> {
+    settings: &'a NoiseGeneratorSettings,
+    functions: &'f TerrainFunctions<'a>,
+    chunk_pos: IVec2,
+    min_y: i32,
+    height: i32,
+    cell_size: CellSize,
+    evaluator: GraphEvaluator<'f, 'a>,
+    preliminary_surface: PreliminarySurfaceLevels<'f, 'a>,
+    aquifer: Aquifer<'a>,
+    ore_veinifier: Option<OreVeinifier>,
+}
+
+impl<'f, 'a> NoiseChunk<'f, 'a> {
+    pub fn new(
+        settings: &'a NoiseGeneratorSettings,
+        functions: &'f TerrainFunctions<'a>,
+        random_state: &RandomState,
+        chunk_pos: IVec2,
+        context: WorldGenerationContext,
+    ) -> Self {
+        let cell_size = CellSize::from_settings(&settings.noise);
+        let mut evaluator = GraphEvaluator::new(&functions.graph);
+        evaluator.set_flat_cache_chunk(chunk_pos);
+        let mut preliminary_evaluator = GraphEvaluator::new(&functions.graph);
+        preliminary_evaluator.set_flat_cache_chunk(chunk_pos);
+
+        NoiseChunk {
+            settings,
+            functions,
+            chunk_pos,
+            min_y: context.min_y,
+            height: context.height,
+            cell_size,
+            evaluator,
+            preliminary_surface: PreliminarySurfaceLevels {
+                evaluator: preliminary_evaluator,
+                initial_density: functions.initial_density_without_jaggedness,
+                min_y: context.min_y,
+                height: context.height,
+                cell_height: cell_size.height,
+                levels: AHashMap::new(),
+            },
+            aquifer: Aquifer::new(
+                settings,
+                functions.aquifer,
+                random_state.aquifer_random(),
+                chunk_pos,
+                context.min_y,
+                context.height,
+            ),
+            ore_veinifier: settings
+                .ore_veins_enabled
+                .then(|| OreVeinifier::new(functions.ore_veins, random_state.ore_random().clone())),
+        }
+    }
+
+    pub fn chunk_pos(&self) -> IVec2 {
+        self.chunk_pos
+    }
+
+    pub fn aquifer(&self) -> &Aquifer<'a> {
+        &self.aquifer
+    }
+
+    /// Returns the fluid or air the aquifer places at `pos` given the density there, or `None` if
+    /// the block stays solid. This is computed outside of any cell, like carvers do.
+    pub fn compute_substance(&mut self, pos: IVec3, density: f64) -> Option<&'a BlockState> {
+        self.aquifer.compute_substance(
+            &mut self.evaluator,
+            &mut self.preliminary_surface,
+            pos,
+            density,
+        )
+    }
+
+    /// Computes the terrain of the whole chunk, one noise cell at a time, equivalent to
+    /// `NoiseBasedChunkGenerator.doFill`. Fluids placed where the aquifer schedules a fluid update
+    /// are recorded for post-processing.
+    pub fn fill(&mut self) -> TerrainBlocks<'a> {
+        let CellSize {
+            width: cell_width,
+            height: cell_height,
+        } = self.cell_size;
+        let cell_count_xz = 16 / cell_width;
+        let min_cell_y = self.min_y.div_euclid(cell_height);
+        let cell_count_y = self.height / cell_height;
+        let min_block: IVec2 = self.chunk_pos << 4;
+        let mut terrain = TerrainBlocks {
+            chunk_pos: self.chunk_pos,
+            min_y: self.min_y,
+            height: self.height,
+            blocks: vec![block_state::air(); (16 * 16 * self.height) as usize],
+            post_processing: Vec::new(),
+        };
+
+        for cell_x in 0..cell_count_xz {
+            for cell_z in 0..cell_count_xz {
+                for cell_y in (0..cell_count_y).rev() {
+                    let origin = IVec3::new(
+                        min_block.x + cell_x * cell_width,
+                        (min_cell_y + cell_y) * cell_height,
+                        min_block.y + cell_z * cell_width,
+                    );
+                    self.evaluator.enter_cell(origin, self.cell_size);
+                    for y in (0..cell_height).rev() {
+                        for x in 0..cell_width {
+                            for z in 0..cell_width {
+                                let pos = origin + IVec3::new(x, y, z);
+                                let state = self.compute_block_state(pos);
+                                if let Some(index) = terrain.index(pos.x & 15, pos.y, pos.z & 15) {
+                                    terrain.blocks[index] = state;
+                                    if self.aquifer.should_schedule_fluid_update()
+                                        && state.has_fluid()
+                                    {
+                                        terrain.post_processing.push(pos);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    self.evaluator.exit_cell();
+                }
+            }
+        }
+
+        terrain
+    }
+
+    /// The block at a position in the current cell: a fluid or air from the aquifer, an ore vein,
+    /// or the default block, equivalent to `NoiseChunk.getInterpolatedState`.
+    fn compute_block_state(&mut self, pos: IVec3) -> &'a BlockState {
+        let density = self.evaluator.compute(self.functions.final_density, pos);
+        self.aquifer
+            .compute_substance(
+                &mut self.evaluator,
+                &mut self.preliminary_surface,
+                pos,
+                density,
+            )
+            .or_else(|| {
+                self.ore_veinifier
+                    .as_ref()?
+                    .compute(&mut self.evaluator, pos)
+            })
+            .unwrap_or(&self.settings.default_block)
+    }
+}
+
+impl PreliminarySurface for NoiseChunk<'_, '_> {
+    fn preliminary_surface_level(&mut self, x: i32, z: i32) -> i32 {
+        self.preliminary_surface.preliminary_surface_level(x, z)
+    }
+}
+
+/// The preliminary surface levels of a noise chunk, cached per quart column.
+struct PreliminarySurfaceLevels<'f, 'a> {
+    evaluator: GraphEvaluator<'f, 'a>,
+    initial_density: NodeId,
+    min_y: i32,
+    height: i32,
+    cell_height: i32,
+    levels: AHashMap<IVec2, i32>,
+}
+
+impl PreliminarySurface for PreliminarySurfaceLevels<'_, '_> {
+    fn preliminary_surface_level(&mut self, x: i32, z: i32) -> i32 {
+        let column = IVec2::new(x & !3, z & !3);
+        if let Some(&level) = self.levels.get(&column) {
+            return level;
+        }
+        let level = (self.min_y..=self.min_y + self.height)
+            .rev()
+            .step_by(self.cell_height as usize)
+            .find(|&y| {
+                self.evaluator
+                    .compute(self.initial_density, IVec3::new(column.x, y, column.y))
+                    > PRELIMINARY_SURFACE_DENSITY
+            })
+            .unwrap_or(i32::MAX);
+        self.levels.insert(column, level);
+        level
+    }
+}
+
+/// The blocks of a chunk filled by a [`NoiseChunk`].
+#[derive(Debug, Clone)]
+pub struct TerrainBlocks<'a> {
+    chunk_pos: IVec2,
+    min_y: i32,
+    height: i32,
+    blocks: Vec<&'a BlockState>,
+    /// The fluids to mark for post-processing, in the order they were placed.
+    post_processing: Vec<IVec3>,
+}
+
+impl<'a> TerrainBlocks<'a> {
+    pub fn chunk_pos(&self) -> IVec2 {
+        self.chunk_pos
+    }
+
+    pub fn min_y(&self) -> i32 {
+        self.min_y
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    #[inline]
+    fn index(&self, x: i32, y: i32, z: i32) -> Option<usize> {
+        let y = y - self.min_y;
+        (y >= 0 && y < self.height).then(|| ((y * 16 + z) * 16 + x) as usize)
+    }
+
+    pub fn post_processing(&self) -> &[IVec3] {
+        &self.post_processing
+    }
+
+    /// Returns the block at chunk-local `x` and `z` and the absolute `y`, or air outside of the
+    /// filled height.
+    pub fn get(&self, x: i32, y: i32, z: i32) -> &'a BlockState {
+        match self.index(x & 15, y, z & 15) {
+            Some(index) => self.blocks[index],
+            None => block_state::air(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chunk::proto_chunk::ProtoChunk;
+    use crate::noise_chunk::{NoiseChunk, TerrainFunctions};
+    use crate::random_state::RandomState;
+    use crate::surface::PreliminarySurface;
+    use crate::test_util::{empty_datapack, noise_settings};
+    use crate::world_generation_context::WorldGenerationContext;
+    use datapack::data::noise::NoiseGeneratorSettings;
+    use glam::IVec2;
+
+    const GRADIENT: &str = r#"{
+        "type": "minecraft:y_clamped_gradient", "from_y": 0, "to_y": 128, "from_value": 1, "to_value": -1
+    }"#;
+
+    /// Flat terrain up to y=64 with the sea up to y=80.
+    fn settings() -> NoiseGeneratorSettings {
+        let mut settings = noise_settings(0, 128, 80);
+        let router = &mut settings.noise_router;
+        router.initial_density_without_jaggedness = serde_json::from_str(GRADIENT).unwrap();
+        router.final_density = serde_json::from_str(&format!(
+            r#"{{ "type": "minecraft:interpolated", "argument": {GRADIENT} }}"#
+        ))
+        .unwrap();
+        settings
+    }
+
+    #[test]
+    fn test_fill() {
+        let datapack = empty_datapack();
+        let settings = settings();
+        let random_state = RandomState::from_settings(&datapack, &settings, 0);
+        let functions =
+            TerrainFunctions::new(&datapack, &random_state, &settings.noise_router).unwrap();
+        let mut noise_chunk = NoiseChunk::new(
+            &settings,
+            &functions,
+            &random_state,
+            IVec2::new(3, -2),
+            WorldGenerationContext::from_settings(&settings.noise),
+        );
+        let terrain = noise_chunk.fill();
+
+        let block = |x, y, z| terrain.get(x, y, z).name.path();
+        for (x, z) in [(0, 0), (7, 3), (15, 15)] {
+            assert_eq!(block(x, 0, z), "stone");
+            assert_eq!(block(x, 63, z), "stone");
+            assert_eq!(block(x, 64, z), "water");
+            assert_eq!(block(x, 79, z), "water");
+            assert_eq!(block(x, 80, z), "air");
+            assert_eq!(block(x, 127, z), "air");
+        }
+        // the highest cell corner with a density above 0.390625
+        assert_eq!(noise_chunk.preliminary_surface_level(48, -32), 32);
+    }
+
+    #[test]
+    fn test_fill_post_processing() {
+        let datapack = empty_datapack();
+        let mut settings = settings();
+        settings.noise_router.fluid_level_floodedness = serde_json::from_str("1").unwrap();
+        settings.aquifers_enabled = true;
+        let random_state = RandomState::from_settings(&datapack, &settings, 0);
+        let functions =
+            TerrainFunctions::new(&datapack, &random_state, &settings.noise_router).unwrap();
+        let mut noise_chunk = NoiseChunk::new(
+            &settings,
+            &functions,
+            &random_state,
+            IVec2::new(3, -2),
+            WorldGenerationContext::from_settings(&settings.noise),
+        );
+        let terrain = noise_chunk.fill();
+
+        // fluids between aquifers are marked for post-processing
+        let marked = terrain.post_processing();
+        assert!(!marked.is_empty());
+        for pos in marked {
+            assert_eq!(terrain.get(pos.x, pos.y, pos.z).name.path(), "water");
+        }
+        let chunk = ProtoChunk::from_terrain(&terrain);
+        let section_marked: usize = chunk.post_processing().iter().map(Vec::len).sum();
+        assert_eq!(section_marked, marked.len());
+    }
+}
@@ -0,0 +1,228 @@
+use crate::block_state;
+use crate::density_functions::compiler::DensityFunctionCompiler;
+use crate::density_functions::graph::{GraphEvaluator, NodeId};
+use datapack::data::block_state::BlockState;
+use datapack::data::noise::NoiseRouter;
+use datapack::DataPackResult;
+use glam::IVec3;
+use runtime::random_source::{AnyPositionalRandomFactory, PositionalRandomFactory, RandomSource};
+use std::sync::OnceLock;
+use util::math;
+
+const VEININESS_THRESHOLD: f32 = 0.4;
+const EDGE_ROUNDOFF_BEGIN: i32 = 20;
+const MAX_EDGE_ROUNDOFF: f64 = 0.2;
+const VEIN_SOLIDNESS: f32 = 0.7;
+const MIN_RICHNESS: f32 = 0.1;
+const MAX_RICHNESS: f32 = 0.3;
+const MAX_RICHNESS_THRESHOLD: f32 = 0.6;
+const CHANCE_OF_RAW_ORE_BLOCK: f32 = 0.02;
+const SKIP_ORE_IF_GAP_NOISE_IS_BELOW: f32 = -0.3;
+
+/// A kind of large ore vein, equivalent to `OreVeinifier.VeinType`.
+#[derive(Debug)]
+pub struct VeinType {
+    pub ore: BlockState,
+    pub raw_ore_block: BlockState,
+    pub filler: BlockState,
+    pub min_y: i32,
+    pub max_y: i32,
+}
+
+impl VeinType {
+    fn new(ore: &str, raw_ore_block: &str, filler: &str, min_y: i32, max_y: i32) -> Self {
+        VeinType {
+            ore: block_state::simple_state(ore),
+            raw_ore_block: block_state::simple_state(raw_ore_block),
+            filler: block_state::simple_state(filler),
+            min_y,
+            max_y,
+        }
+    }
+
+    /// Copper veins in granite, generated where the vein toggle is positive.
+    pub fn copper() -> &'static VeinType {
+        static COPPER: OnceLock<VeinType> = OnceLock::new();
+        COPPER.get_or_init(|| {
+            VeinType::new(
+                "minecraft:copper_ore",
+                "minecraft:raw_copper_block",
+                "minecraft:granite",
+                0,
+                50,
+            )
+        })
+    }
+
+    /// Iron veins in tuff, generated where the vein toggle is negative.
+    pub fn iron() -> &'static VeinType {
+        static IRON: OnceLock<VeinType> = OnceLock::new();
+        IRON.get_or_init(|| {
+            VeinType::new(
+                "minecraft:deepslate_iron_ore",
+                "minecraft:raw_iron_block",
+                "minecraft:tuff",
+                -60,
+                -8,
+            )
+        })
+    }
+}
+
+/// The vein functions of a noise router, compiled into a shared graph.
+#[derive(Debug, Copy, Clone)]
+pub struct OreVeinFunctions {
+    pub vein_toggle: NodeId,
+    pub vein_ridged: NodeId,
+    pub vein_gap: NodeId,
+}
+
+impl OreVeinFunctions {
+    pub fn compile<'a>(
+        compiler: &mut DensityFunctionCompiler<'a>,
+        router: &'a NoiseRouter,
+    ) -> DataPackResult<Self> {
+        Ok(OreVeinFunctions {
+            vein_toggle: compiler.compile(&router.vein_toggle)?,
+            vein_ridged: compiler.compile(&router.vein_ridged)?,
+            vein_gap: compiler.compile(&router.vein_gap)?,
+        })
+    }
+}
+
+/// Places the large copper and iron veins into solid terrain, equivalent to vanilla's
+/// `OreVeinifier`.
+#[derive(Debug, Clone)]
+pub struct OreVeinifier {
+    functions: OreVeinFunctions,
+    random: AnyPositionalRandomFactory,
+}
+
+impl OreVeinifier {
+    /// Creates an ore veinifier using `random`, which should be the
+    /// [`ore_random`](crate::random_state::RandomState::ore_random) of the world.
+    pub fn new(functions: OreVeinFunctions, random: AnyPositionalRandomFactory) -> Self {
+        OreVeinifier { functions, random }
+    }
+
+    /// Returns the vein block at `pos`, or `None` if there is no vein there. The functions are
+    /// computed with `evaluator`, which must be evaluating the graph they were compiled into.
+    pub fn compute(
+        &self,
+        evaluator: &mut GraphEvaluator,
+        pos: IVec3,
+    ) -> Option<&'static BlockState> {
+        let toggle = evaluator.compute(self.functions.vein_toggle, pos);
+        let vein_type = if toggle > 0.0 {
+            VeinType::copper()
+        } else {
+            VeinType::iron()
+        };
+        let veininess = toggle.abs();
+        let distance_to_top = vein_type.max_y - pos.y;
+        let distance_to_bottom = pos.y - vein_type.min_y;
+        if distance_to_bottom < 0 || distance_to_top < 0 {
+            return None;
+        }
+        let edge_roundoff = math::clamped_map(
+            distance_to_top.min(distance_to_bottom) as f64,
+            0.0,
+            EDGE_ROUNDOFF_BEGIN as f64,
+            -MAX_EDGE_ROUNDOFF,
+            0.0,
+        );
+        if veininess + edge_roundoff < VEININESS_THRESHOLD as f64 {
+            return None;
+        }
+
+        let mut random = self.random.at(pos);
+        if random.next_f32() > VEIN_SOLIDNESS {
+            return None;
+        }
+        if evaluator.compute(self.functions.vein_ridged, pos) >= 0.0 {
+            return None;
+        }
+
+        let richness = math::clamped_map(
+            veininess,
+            VEININESS_THRESHOLD as f64,
+            MAX_RICHNESS_THRESHOLD as f64,
+            MIN_RICHNESS as f64,
+            MAX_RICHNESS as f64,
+        );
+        if (random.next_f32() as f64) < richness
+            && evaluator.compute(self.functions.vein_gap, pos)
+                > SKIP_ORE_IF_GAP_NOISE_IS_BELOW as f64
+        {
+            Some(if random.next_f32() < CHANCE_OF_RAW_ORE_BLOCK {
+                &vein_type.raw_ore_block
+            } else {
+                &vein_type.ore
+            })
+        } else {
+            Some(&vein_type.filler)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::density_functions::compiler::DensityFunctionCompiler;
+    use crate::density_functions::graph::GraphEvaluator;
+    use crate::ore_veinifier::{OreVeinFunctions, OreVeinifier};
+    use crate::random_state::RandomState;
+    use crate::test_util::{empty_datapack, zero_router};
+    use datapack::data::noise::NoiseRouter;
+    use glam::IVec3;
+
+    /// Computes the vein blocks along a line of x at the given y, with constant vein functions.
+    fn veins(toggle: f64, ridged: f64, y: i32) -> Vec<Option<String>> {
+        let router = NoiseRouter {
+            vein_toggle: serde_json::from_str(&toggle.to_string()).unwrap(),
+            vein_ridged: serde_json::from_str(&ridged.to_string()).unwrap(),
+            ..zero_router()
+        };
+        let datapack = empty_datapack();
+        let random_state = RandomState::new(&datapack, 0, false);
+        let mut compiler = DensityFunctionCompiler::new(&datapack, &random_state);
+        let functions = OreVeinFunctions::compile(&mut compiler, &router).unwrap();
+        let graph = compiler.finish();
+        let mut evaluator = GraphEvaluator::new(&graph);
+        let veinifier = OreVeinifier::new(functions, random_state.ore_random().clone());
+        (0..256)
+            .map(|x| {
+                veinifier
+                    .compute(&mut evaluator, IVec3::new(x, y, 0))
+                    .map(|state| state.name.path().to_owned())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_veins() {
+        // copper veins are mostly granite, with some ore
+        let copper = veins(0.5, -1.0, 25);
+        let count = |veins: &[Option<String>], block: &str| {
+            veins
+                .iter()
+                .filter(|vein| vein.as_deref() == Some(block))
+                .count()
+        };
+        assert!(copper.iter().flatten().all(|block| {
+            ["granite", "copper_ore", "raw_copper_block"].contains(&block.as_str())
+        }));
+        assert!(count(&copper, "granite") > count(&copper, "copper_ore"));
+        assert!(count(&copper, "copper_ore") > 0);
+        // and leave some gaps
+        assert!(copper.contains(&None));
+
+        let iron = veins(-0.5, -1.0, -30);
+        assert!(count(&iron, "tuff") > count(&iron, "deepslate_iron_ore"));
+        assert!(count(&iron, "deepslate_iron_ore") > 0);
+
+        // outside of the vein height range, or where the ridges are positive, there are no veins
+        assert!(veins(0.5, -1.0, 51).iter().all(Option::is_none));
+        assert!(veins(-0.5, -1.0, 0).iter().all(Option::is_none));
+        assert!(veins(0.5, 1.0, 25).iter().all(Option::is_none));
+    }
+}
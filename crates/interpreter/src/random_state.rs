@@ -0,0 +1,236 @@
+use crate::density_functions::evaluator::NoiseContext;
+use datapack::data::density_function::{BlendedNoiseFunction, NoiseParameters};
+use datapack::data::holder::Holder;
+use datapack::data::noise::NoiseGeneratorSettings;
+use datapack::{DataPack, DataPackError, DataPackResult};
+use glam::IVec3;
+use runtime::noise::{self, BlendedNoise, EndIslands, NormalNoise};
+use runtime::random_source::{
+    AnyPositionalRandomFactory, LegacyRandomSource, PositionalRandomFactory, RandomSource,
+};
+use std::sync::OnceLock;
+use util::add_only_map::AddOnlyMap;
+use util::identifier::{Identifier, IdentifierBuf};
+
+const TEMPERATURE: &Identifier = Identifier::new_const("temperature");
+const VEGETATION: &Identifier = Identifier::new_const("vegetation");
+const OFFSET: &Identifier = Identifier::new_const("offset");
+
+/// The noises of a world, seeded from the world seed. Noises are created lazily on first use.
+pub struct RandomState<'a> {
+    datapack: &'a DataPack,
+    seed: u64,
+    legacy_random_source: bool,
+    random: AnyPositionalRandomFactory,
+    aquifer_random: AnyPositionalRandomFactory,
+    ore_random: AnyPositionalRandomFactory,
+    noises: AddOnlyMap<IdentifierBuf, NormalNoise>,
+    blended_noises: AddOnlyMap<[u64; 5], BlendedNoise>,
+    end_islands: OnceLock<EndIslands>,
+}
+
+impl<'a> RandomState<'a> {
+    pub fn new(datapack: &'a DataPack, seed: u64, legacy_random_source: bool) -> RandomState<'a> {
+        let random = AnyPositionalRandomFactory::new(seed, legacy_random_source);
+        let fork = |name: &str| random.create_from_hash(random.hash(name)).fork_positional();
+        RandomState {
+            datapack,
+            seed,
+            legacy_random_source,
+            aquifer_random: fork("minecraft:aquifer"),
+            ore_random: fork("minecraft:ore"),
+            random,
+            noises: AddOnlyMap::default(),
+            blended_noises: AddOnlyMap::default(),
+            end_islands: OnceLock::new(),
+        }
+    }
+
+    pub fn from_settings(
+        datapack: &'a DataPack,
+        settings: &NoiseGeneratorSettings,
+        seed: u64,
+    ) -> RandomState<'a> {
+        Self::new(datapack, seed, settings.legacy_random_source)
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The positional random factory seeded from the world seed, equivalent to
+    /// `RandomState.random`.
+    pub fn random(&self) -> &AnyPositionalRandomFactory {
+        &self.random
+    }
+
+    /// The positional random factory of aquifers, equivalent to `RandomState.aquiferRandom`.
+    pub fn aquifer_random(&self) -> &AnyPositionalRandomFactory {
+        &self.aquifer_random
+    }
+
+    /// The positional random factory of ore veins, equivalent to `RandomState.oreRandom`.
+    pub fn ore_random(&self) -> &AnyPositionalRandomFactory {
+        &self.ore_random
+    }
+
+    /// Equivalent of `RandomState.getOrCreateRandomFactory`.
+    pub fn get_or_create_random_factory(&self, id: &Identifier) -> AnyPositionalRandomFactory {
+        self.random
+            .create_from_hash(self.random.hash(id))
+            .fork_positional()
+    }
+
+    pub fn get_or_create_noise(
+        &self,
+        noise: &Holder<NoiseParameters>,
+    ) -> DataPackResult<&NormalNoise> {
+        let Holder::Reference(id) = noise else {
+            // vanilla needs the registry key to seed the noise
+            return Err(DataPackError::UnregisteredNoise);
+        };
+        if let Some(noise) = self.noises.get(id) {
+            return Ok(noise);
+        }
+        self.noises
+            .get_or_try_insert(id.clone(), || self.create_noise(id, noise))
+    }
+
+    fn create_noise(
+        &self,
+        id: &Identifier,
+        noise: &Holder<NoiseParameters>,
+    ) -> DataPackResult<NormalNoise> {
+        if self.legacy_random_source {
+            // legacy worlds keep their pre-1.18 climate noises, and have no offset noise
+            if id == TEMPERATURE || id == VEGETATION {
+                let seed = if id == TEMPERATURE {
+                    self.seed
+                } else {
+                    self.seed.wrapping_add(1)
+                };
+                return Ok(NormalNoise::create_legacy_nether_biome(
+                    &mut LegacyRandomSource::new(seed),
+                    &NoiseParameters {
+                        first_octave: -7,
+                        amplitudes: vec![1.0, 1.0],
+                    },
+                ));
+            }
+            if id == OFFSET {
+                return Ok(NormalNoise::create(
+                    &mut self.random.create_from_hash_of(id),
+                    &NoiseParameters {
+                        first_octave: 0,
+                        amplitudes: vec![0.0],
+                    },
+                ));
+            }
+        }
+
+        let parameters = noise.resolve(self.datapack)?;
+        Ok(noise::instantiate(&self.random, id, parameters))
+    }
+
+    pub fn get_or_create_blended_noise(
+        &self,
+        function: &BlendedNoiseFunction,
+    ) -> DataPackResult<&BlendedNoise> {
+        let key = [
+            function.xz_scale.to_bits(),
+            function.y_scale.to_bits(),
+            function.xz_factor.to_bits(),
+            function.y_factor.to_bits(),
+            function.smear_scale_multiplier.to_bits(),
+        ];
+        self.blended_noises.get_or_try_insert(key, || {
+            Ok(if self.legacy_random_source {
+                BlendedNoise::new(&mut LegacyRandomSource::new(self.seed), function)
+            } else {
+                BlendedNoise::new(
+                    &mut self.random.create_from_hash_of("minecraft:terrain"),
+                    function,
+                )
+            })
+        })
+    }
+
+    pub fn end_islands(&self) -> &EndIslands {
+        self.end_islands.get_or_init(|| EndIslands::new(self.seed))
+    }
+}
+
+impl NoiseContext for RandomState<'_> {
+    fn sample_noise(
+        &self,
+        noise: &Holder<NoiseParameters>,
+        x: f64,
+        y: f64,
+        z: f64,
+    ) -> DataPackResult<f64> {
+        Ok(self.get_or_create_noise(noise)?.get_value(x, y, z))
+    }
+
+    fn sample_old_blended_noise(
+        &self,
+        function: &BlendedNoiseFunction,
+        pos: IVec3,
+    ) -> DataPackResult<f64> {
+        Ok(self.get_or_create_blended_noise(function)?.compute(pos))
+    }
+
+    fn sample_end_islands(&self, pos: IVec3) -> DataPackResult<f64> {
+        Ok(self.end_islands().compute(pos))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::density_functions::evaluator::PointInterpreter;
+    use crate::random_state::RandomState;
+    use datapack::data::density_function::DensityFunction;
+    use datapack::data::holder::Holder;
+    use datapack::{DataPack, DataPackError};
+    use glam::IVec3;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_end_islands() {
+        let temp_dir = TempDir::new().unwrap();
+        let datapack = DataPack::new(temp_dir.path()).unwrap();
+        let random_state = RandomState::new(&datapack, 12345, true);
+        let function: Holder<DensityFunction> =
+            serde_json::from_str(r#"{ "type": "minecraft:end_islands" }"#).unwrap();
+        let compute = |x, z| {
+            PointInterpreter::new(&datapack, &random_state, IVec3::new(x, 64, z))
+                .compute(&function)
+                .unwrap()
+        };
+        // the main island is the same in every world
+        assert_eq!(compute(0, 0), 0.5625);
+        assert_eq!(compute(2000, 0), -0.84375);
+        for x in (1000..3000).step_by(40) {
+            let value = compute(x, 1000);
+            assert!((-0.84375..=0.5625).contains(&value), "{value} out of range");
+        }
+    }
+
+    #[test]
+    fn test_inline_noise() {
+        let temp_dir = TempDir::new().unwrap();
+        let datapack = DataPack::new(temp_dir.path()).unwrap();
+        let random_state = RandomState::new(&datapack, 0, false);
+        let function: Holder<DensityFunction> = serde_json::from_str(
+            r#"{
+                "type": "minecraft:noise",
+                "noise": { "firstOctave": -3, "amplitudes": [1, 1] },
+                "xz_scale": 1,
+                "y_scale": 1
+            }"#,
+        )
+        .unwrap();
+        let result =
+            PointInterpreter::new(&datapack, &random_state, IVec3::ZERO).compute(&function);
+        assert!(matches!(result, Err(DataPackError::UnregisteredNoise)));
+    }
+}
@@ -0,0 +1,63 @@
+use crate::block_state;
+use datapack::data::block_state::BlockState;
+use runtime::random_source::RandomSource;
+
+/// The number of blocks after which the clay bands repeat vertically.
+pub(crate) const CLAY_BANDS_LENGTH: usize = 192;
+
+/// Generates the terracotta stripes of badlands, equivalent to `SurfaceSystem.generateBands`.
+pub(crate) fn generate_bands(random: &mut impl RandomSource) -> Vec<BlockState> {
+    let terracotta = block_state::simple_state("minecraft:terracotta");
+    let mut bands = vec![terracotta; CLAY_BANDS_LENGTH];
+
+    let mut index = 0;
+    while index < bands.len() {
+        index += random.next_u32(5) as usize + 1;
+        if index >= bands.len() {
+            break;
+        }
+        bands[index] = block_state::simple_state("minecraft:orange_terracotta");
+        index += 1;
+    }
+
+    make_bands(random, &mut bands, 1, "minecraft:yellow_terracotta");
+    make_bands(random, &mut bands, 2, "minecraft:brown_terracotta");
+    make_bands(random, &mut bands, 1, "minecraft:red_terracotta");
+
+    let white = block_state::simple_state("minecraft:white_terracotta");
+    let light_gray = block_state::simple_state("minecraft:light_gray_terracotta");
+    let count = random.next_i32_between_inclusive(9, 15);
+    let mut index = 0;
+    for _ in 0..count {
+        if index >= bands.len() {
+            break;
+        }
+        bands[index] = white.clone();
+        if index > 1 && random.next_bool() {
+            bands[index - 1] = light_gray.clone();
+        }
+        if index + 1 < bands.len() && random.next_bool() {
+            bands[index + 1] = light_gray.clone();
+        }
+        index += random.next_u32(16) as usize + 4;
+    }
+
+    bands
+}
+
+fn make_bands(
+    random: &mut impl RandomSource,
+    bands: &mut [BlockState],
+    min_size: u32,
+    block: &str,
+) {
+    let state = block_state::simple_state(block);
+    let count = random.next_i32_between_inclusive(6, 15);
+    for _ in 0..count {
+        let size = (min_size + random.next_u32(3)) as usize;
+        let start = random.next_u32(bands.len() as u32) as usize;
+        for band in bands.iter_mut().skip(start).take(size) {
+            *band = state.clone();
+        }
+    }
+}
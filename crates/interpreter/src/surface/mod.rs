@@ -0,0 +1,535 @@
+mod clay_bands;
+mod rules;
+
+use crate::biomes::temperature::BiomeExt;
+use crate::block_state::{self, BlockStateExt};
+use crate::chunk::ChunkAccess;
+use crate::random_state::RandomState;
+use crate::surface::rules::{SurfaceContext, SurfaceRule};
+use crate::world_generation_context::WorldGenerationContext;
+use datapack::data::biome::Biome;
+use datapack::data::block_state::BlockState;
+use datapack::data::holder::Holder;
+use datapack::data::noise::NoiseGeneratorSettings;
+use datapack::{DataPack, DataPackResult};
+use glam::IVec3;
+use runtime::noise::NormalNoise;
+use runtime::random_source::{AnyPositionalRandomFactory, PositionalRandomFactory, RandomSource};
+use util::heightmap_type::HeightmapType;
+use util::identifier::{Identifier, IdentifierBuf};
+use util::math;
+
+const ERODED_BADLANDS: &Identifier = Identifier::new_const("eroded_badlands");
+const FROZEN_OCEAN: &Identifier = Identifier::new_const("frozen_ocean");
+const DEEP_FROZEN_OCEAN: &Identifier = Identifier::new_const("deep_frozen_ocean");
+const WATER: &Identifier = Identifier::new_const("water");
+
+/// `DimensionType.WAY_BELOW_MIN_Y`
+const WAY_BELOW_MIN_Y: i32 = -32512;
+
+/// Provides the preliminary surface level of a noise chunk, which is an estimate of the terrain
+/// height before caves and aquifers, used by the `above_preliminary_surface` condition.
+pub trait PreliminarySurface {
+    fn preliminary_surface_level(&mut self, x: i32, z: i32) -> i32;
+}
+
+impl<F> PreliminarySurface for F
+where
+    F: FnMut(i32, i32) -> i32,
+{
+    fn preliminary_surface_level(&mut self, x: i32, z: i32) -> i32 {
+        self(x, z)
+    }
+}
+
+/// Replaces the stone of generated terrain with surface blocks, using the surface rule of a noise
+/// generator. Equivalent to vanilla's `SurfaceSystem`.
+pub struct SurfaceSystem<'a> {
+    datapack: &'a DataPack,
+    default_block: &'a BlockState,
+    sea_level: i32,
+    legacy_random_source: bool,
+    rule: SurfaceRule<'a>,
+    noise_random: AnyPositionalRandomFactory,
+    clay_bands: Vec<BlockState>,
+    clay_bands_offset_noise: &'a NormalNoise,
+    surface_noise: &'a NormalNoise,
+    surface_secondary_noise: &'a NormalNoise,
+    badlands_pillar_noise: &'a NormalNoise,
+    badlands_pillar_roof_noise: &'a NormalNoise,
+    badlands_surface_noise: &'a NormalNoise,
+    iceberg_pillar_noise: &'a NormalNoise,
+    iceberg_pillar_roof_noise: &'a NormalNoise,
+    iceberg_surface_noise: &'a NormalNoise,
+}
+
+impl<'a> SurfaceSystem<'a> {
+    pub fn new(
+        datapack: &'a DataPack,
+        random_state: &'a RandomState,
+        settings: &'a NoiseGeneratorSettings,
+        context: WorldGenerationContext,
+    ) -> DataPackResult<Self> {
+        let noise = |name: &str| {
+            random_state.get_or_create_noise(&Holder::Reference(IdentifierBuf::new(name).unwrap()))
+        };
+        let noise_random = random_state.random().clone();
+        Ok(SurfaceSystem {
+            datapack,
+            default_block: &settings.default_block,
+            sea_level: settings.sea_level,
+            legacy_random_source: settings.legacy_random_source,
+            rule: SurfaceRule::compile(&settings.surface_rule, random_state, &context)?,
+            clay_bands: clay_bands::generate_bands(
+                &mut noise_random.create_from_hash_of("minecraft:clay_bands"),
+            ),
+            clay_bands_offset_noise: noise("minecraft:clay_bands_offset")?,
+            noise_random,
+            surface_noise: noise("minecraft:surface")?,
+            surface_secondary_noise: noise("minecraft:surface_secondary")?,
+            badlands_pillar_noise: noise("minecraft:badlands_pillar")?,
+            badlands_pillar_roof_noise: noise("minecraft:badlands_pillar_roof")?,
+            badlands_surface_noise: noise("minecraft:badlands_surface")?,
+            iceberg_pillar_noise: noise("minecraft:iceberg_pillar")?,
+            iceberg_pillar_roof_noise: noise("minecraft:iceberg_pillar_roof")?,
+            iceberg_surface_noise: noise("minecraft:iceberg_surface")?,
+        })
+    }
+
+    pub fn default_block(&self) -> &'a BlockState {
+        self.default_block
+    }
+
+    pub fn sea_level(&self) -> i32 {
+        self.sea_level
+    }
+
+    /// Applies the surface rule to every column of `chunk`, equivalent to vanilla's
+    /// `SurfaceSystem.buildSurface`. The chunk must be filled with terrain, and `biome_getter`
+    /// must return the biome at a block position, like `BiomeManager.getBiome`.
+    pub fn build_surface<'b>(
+        &self,
+        chunk: &mut impl ChunkAccess,
+        biome_getter: &mut dyn FnMut(IVec3) -> &'b Holder<Biome>,
+        preliminary_surface: &mut dyn PreliminarySurface,
+    ) -> DataPackResult<()> {
+        let chunk_pos = chunk.pos();
+        let min_x = chunk_pos.x << 4;
+        let min_z = chunk_pos.y << 4;
+        let min_y = chunk.min_y();
+        let mut context = SurfaceContext::new(self, biome_getter, preliminary_surface);
+
+        for local_x in 0..16 {
+            for local_z in 0..16 {
+                let x = min_x + local_x;
+                let z = min_z + local_z;
+                let height =
+                    chunk.heightmap_height(HeightmapType::WorldSurfaceWg, local_x, local_z) + 1;
+                let biome_y = if self.legacy_random_source { 0 } else { height };
+                let biome = context.biome_at(IVec3::new(x, biome_y, z));
+                if is_biome(biome, ERODED_BADLANDS) {
+                    self.eroded_badlands_extension(chunk, x, z, height);
+                }
+
+                let top =
+                    chunk.heightmap_height(HeightmapType::WorldSurfaceWg, local_x, local_z) + 1;
+                context.update_xz(x, z);
+                let mut stone_depth_above = 0;
+                let mut water_height = i32::MIN;
+                let mut bottom_of_stone = i32::MAX;
+                for y in (min_y..=top).rev() {
+                    let pos = IVec3::new(x, y, z);
+                    let state = chunk.block_state(pos);
+                    if state.is_air() {
+                        stone_depth_above = 0;
+                        water_height = i32::MIN;
+                        continue;
+                    }
+                    if state.has_fluid() {
+                        if water_height == i32::MIN {
+                            water_height = y + 1;
+                        }
+                        continue;
+                    }
+
+                    if bottom_of_stone >= y {
+                        bottom_of_stone = WAY_BELOW_MIN_Y;
+                        for below in (min_y - 1..y).rev() {
+                            if !is_stone(chunk.block_state(IVec3::new(x, below, z))) {
+                                bottom_of_stone = below + 1;
+                                break;
+                            }
+                        }
+                    }
+
+                    stone_depth_above += 1;
+                    let stone_depth_below = y - bottom_of_stone + 1;
+                    context.update_y(stone_depth_above, stone_depth_below, water_height, y);
+                    if state != self.default_block {
+                        continue;
+                    }
+                    if let Some(new_state) = self.rule.try_apply(&mut context, chunk)? {
+                        chunk.set_block_state(pos, new_state.clone());
+                    }
+                }
+
+                if is_biome(biome, FROZEN_OCEAN) || is_biome(biome, DEEP_FROZEN_OCEAN) {
+                    let min_surface_level = context.min_surface_level();
+                    self.frozen_ocean_extension(
+                        min_surface_level,
+                        biome.resolve(self.datapack)?,
+                        chunk,
+                        x,
+                        z,
+                        height,
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the block the surface rule places on top of the terrain at `pos`, as if it were
+    /// the top block of its column. Carvers use this to replace the dirt they uncover. Equivalent
+    /// to vanilla's `SurfaceSystem.topMaterial`.
+    pub fn top_material<'b>(
+        &self,
+        chunk: &impl ChunkAccess,
+        biome_getter: &mut dyn FnMut(IVec3) -> &'b Holder<Biome>,
+        preliminary_surface: &mut dyn PreliminarySurface,
+        pos: IVec3,
+        under_fluid: bool,
+    ) -> DataPackResult<Option<BlockState>> {
+        let mut context = SurfaceContext::new(self, biome_getter, preliminary_surface);
+        context.update_xz(pos.x, pos.z);
+        let water_height = if under_fluid { pos.y + 1 } else { i32::MIN };
+        context.update_y(1, 1, water_height, pos.y);
+        Ok(self.rule.try_apply(&mut context, chunk)?.cloned())
+    }
+
+    /// The number of blocks of surface material at a column, `SurfaceSystem.getSurfaceDepth`.
+    fn surface_depth(&self, x: i32, z: i32) -> i32 {
+        let noise = self.surface_noise.get_value(x as f64, 0.0, z as f64);
+        let random = self.noise_random.at(IVec3::new(x, 0, z)).next_f64();
+        (noise * 2.75 + 3.0 + random * 0.25) as i32
+    }
+
+    fn surface_secondary(&self, x: i32, z: i32) -> f64 {
+        self.surface_secondary_noise
+            .get_value(x as f64, 0.0, z as f64)
+    }
+
+    /// The terracotta band at a position, equivalent to `SurfaceSystem.getBand`.
+    fn band(&self, x: i32, y: i32, z: i32) -> &BlockState {
+        let offset = math::lround(
+            self.clay_bands_offset_noise
+                .get_value(x as f64, 0.0, z as f64)
+                * 4.0,
+        ) as i32;
+        &self.clay_bands[(y + offset).rem_euclid(self.clay_bands.len() as i32) as usize]
+    }
+
+    /// Raises the hoodoo pillars of eroded badlands out of the air above the terrain.
+    fn eroded_badlands_extension(&self, chunk: &mut impl ChunkAccess, x: i32, z: i32, height: i32) {
+        let (x_f64, z_f64) = (x as f64, z as f64);
+        let pillar = (self.badlands_surface_noise.get_value(x_f64, 0.0, z_f64) * 8.25)
+            .abs()
+            .min(
+                self.badlands_pillar_noise
+                    .get_value(x_f64 * 0.2, 0.0, z_f64 * 0.2)
+                    * 15.0,
+            );
+        if pillar <= 0.0 {
+            return;
+        }
+
+        let roof = (self
+            .badlands_pillar_roof_noise
+            .get_value(x_f64 * 0.75, 0.0, z_f64 * 0.75)
+            * 1.5)
+            .abs();
+        let top = math::floor(64.0 + (pillar * pillar * 2.5).min((roof * 50.0).ceil() + 24.0));
+        if height > top {
+            return;
+        }
+
+        let min_y = chunk.min_y();
+        for y in (min_y..=top).rev() {
+            let state = chunk.block_state(IVec3::new(x, y, z));
+            if state.is(&self.default_block.name) {
+                break;
+            }
+            if state.is(WATER) {
+                return;
+            }
+        }
+        for y in (min_y..=top).rev() {
+            let pos = IVec3::new(x, y, z);
+            if !chunk.block_state(pos).is_air() {
+                break;
+            }
+            chunk.set_block_state(pos, self.default_block.clone());
+        }
+    }
+
+    /// Builds the packed ice and snow icebergs of frozen oceans.
+    fn frozen_ocean_extension(
+        &self,
+        min_surface_level: i32,
+        biome: &Biome,
+        chunk: &mut impl ChunkAccess,
+        x: i32,
+        z: i32,
+        height: i32,
+    ) {
+        let (x_f64, z_f64) = (x as f64, z as f64);
+        let pillar = (self.iceberg_surface_noise.get_value(x_f64, 0.0, z_f64) * 8.25)
+            .abs()
+            .min(
+                self.iceberg_pillar_noise
+                    .get_value(x_f64 * 1.28, 0.0, z_f64 * 1.28)
+                    * 15.0,
+            );
+        if pillar <= 1.8 {
+            return;
+        }
+
+        let roof = (self
+            .iceberg_pillar_roof_noise
+            .get_value(x_f64 * 1.17, 0.0, z_f64 * 1.17)
+            * 1.5)
+            .abs();
+        let mut top = (pillar * pillar * 1.2).min((roof * 40.0).ceil() + 14.0);
+        if biome.should_melt_frozen_ocean_iceberg_slightly(IVec3::new(x, 63, z)) {
+            top -= 2.0;
+        }
+        let bottom;
+        if top > 2.0 {
+            bottom = self.sea_level as f64 - top - 7.0;
+            top += self.sea_level as f64;
+        } else {
+            top = 0.0;
+            bottom = 0.0;
+        }
+
+        let mut random = self.noise_random.at(IVec3::new(x, 0, z));
+        let max_snow_blocks = 2 + random.next_u32(4) as i32;
+        let min_snow_y = self.sea_level + 18 + random.next_u32(10) as i32;
+        let mut snow_blocks = 0;
+        let snow_block = block_state::simple_state("minecraft:snow_block");
+        let packed_ice = block_state::simple_state("minecraft:packed_ice");
+        for y in (min_surface_level..=height.max(top as i32 + 1)).rev() {
+            let pos = IVec3::new(x, y, z);
+            let state = chunk.block_state(pos);
+            let replace = (state.is_air() && y < top as i32 && random.next_f64() > 0.01)
+                || (state.is(WATER)
+                    && y > bottom as i32
+                    && y < self.sea_level
+                    && bottom != 0.0
+                    && random.next_f64() > 0.15);
+            if !replace {
+                continue;
+            }
+            if snow_blocks <= max_snow_blocks && y > min_snow_y {
+                chunk.set_block_state(pos, snow_block.clone());
+                snow_blocks += 1;
+            } else {
+                chunk.set_block_state(pos, packed_ice.clone());
+            }
+        }
+    }
+}
+
+fn is_biome(biome: &Holder<Biome>, id: &Identifier) -> bool {
+    matches!(biome, Holder::Reference(biome_id) if **biome_id == *id)
+}
+
+fn is_stone(state: &BlockState) -> bool {
+    !state.is_air() && !state.has_fluid()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::block_state::{self, BlockStateExt};
+    use crate::chunk::ChunkAccess;
+    use crate::random_state::RandomState;
+    use crate::surface::SurfaceSystem;
+    use crate::test_util::{datapack, noise_files, noise_settings, SURFACE_NOISES};
+    use crate::world_generation_context::WorldGenerationContext;
+    use datapack::data::block_state::BlockState;
+    use datapack::data::holder::Holder;
+    use glam::{IVec2, IVec3};
+    use util::heightmap_type::HeightmapType;
+    use util::identifier::IdentifierBuf;
+
+    /// Grass and dirt on the surface, with deepslate at the bottom.
+    const SURFACE_RULE: &str = r#"{
+        "type": "minecraft:sequence",
+        "sequence": [
+            {
+                "type": "minecraft:condition",
+                "if_true": { "type": "minecraft:stone_depth", "offset": 0, "add_surface_depth": false, "secondary_depth_range": 0, "surface_type": "floor" },
+                "then_run": {
+                    "type": "minecraft:condition",
+                    "if_true": { "type": "minecraft:water", "offset": 0, "surface_depth_multiplier": 0, "add_stone_depth": false },
+                    "then_run": { "type": "minecraft:block", "result_state": { "Name": "minecraft:grass_block" } }
+                }
+            },
+            {
+                "type": "minecraft:condition",
+                "if_true": { "type": "minecraft:stone_depth", "offset": 2, "add_surface_depth": false, "secondary_depth_range": 0, "surface_type": "floor" },
+                "then_run": { "type": "minecraft:block", "result_state": { "Name": "minecraft:dirt" } }
+            },
+            {
+                "type": "minecraft:condition",
+                "if_true": {
+                    "type": "minecraft:not",
+                    "invert": { "type": "minecraft:y_above", "anchor": { "absolute": 4 }, "surface_depth_multiplier": 0, "add_stone_depth": false }
+                },
+                "then_run": { "type": "minecraft:block", "result_state": { "Name": "minecraft:deepslate" } }
+            }
+        ]
+    }"#;
+
+    /// A 32 block tall chunk at the origin, without heightmap tracking beyond the world surface.
+    struct TestChunk {
+        blocks: Vec<BlockState>,
+    }
+
+    impl TestChunk {
+        fn index(pos: IVec3) -> Option<usize> {
+            ((0..16).contains(&pos.x) && (0..32).contains(&pos.y) && (0..16).contains(&pos.z))
+                .then(|| ((pos.y * 16 + pos.z) * 16 + pos.x) as usize)
+        }
+    }
+
+    impl ChunkAccess for TestChunk {
+        fn pos(&self) -> IVec2 {
+            IVec2::ZERO
+        }
+
+        fn min_y(&self) -> i32 {
+            0
+        }
+
+        fn height(&self) -> i32 {
+            32
+        }
+
+        fn heightmap_height(&self, _heightmap: HeightmapType, x: i32, z: i32) -> i32 {
+            (0..32)
+                .rev()
+                .find(|&y| !self.block_state(IVec3::new(x, y, z)).is_air())
+                .unwrap_or(-1)
+        }
+
+        fn block_state(&self, pos: IVec3) -> &BlockState {
+            Self::index(pos).map_or(block_state::air(), |index| &self.blocks[index])
+        }
+
+        fn set_block_state(&mut self, pos: IVec3, state: BlockState) {
+            if let Some(index) = Self::index(pos) {
+                self.blocks[index] = state;
+            }
+        }
+
+        fn mark_pos_for_postprocessing(&mut self, _pos: IVec3) {}
+    }
+
+    #[test]
+    fn test_build_surface() {
+        let datapack = datapack(noise_files(
+            &SURFACE_NOISES,
+            r#"{ "firstOctave": -6, "amplitudes": [1, 1, 1] }"#,
+        ));
+        let mut settings = noise_settings(0, 32, 12);
+        settings.surface_rule = serde_json::from_str(SURFACE_RULE).unwrap();
+        let random_state = RandomState::from_settings(&datapack, &settings, 0);
+        let surface_system = SurfaceSystem::new(
+            &datapack,
+            &random_state,
+            &settings,
+            WorldGenerationContext::from_settings(&settings.noise),
+        )
+        .unwrap();
+
+        // stone up to y=20 for x >= 8, else stone up to y=10 below water up to y=12
+        let stone = block_state::simple_state("minecraft:stone");
+        let water = block_state::simple_state("minecraft:water");
+        let mut chunk = TestChunk {
+            blocks: vec![block_state::air().clone(); 16 * 16 * 32],
+        };
+        for x in 0..16 {
+            for z in 0..16 {
+                for y in 0..=20 {
+                    let state = match (x >= 8, y) {
+                        (true, _) | (false, ..=10) => &stone,
+                        (false, ..=12) => &water,
+                        _ => continue,
+                    };
+                    chunk.set_block_state(IVec3::new(x, y, z), state.clone());
+                }
+            }
+        }
+
+        let plains = Holder::Reference(IdentifierBuf::new("minecraft:plains").unwrap());
+        surface_system
+            .build_surface(&mut chunk, &mut |_| &plains, &mut |_, _| 0)
+            .unwrap();
+
+        let column = |x: i32| {
+            (0..=20)
+                .rev()
+                .map(|y| {
+                    chunk
+                        .block_state(IVec3::new(x, y, 3))
+                        .name
+                        .path()
+                        .to_owned()
+                })
+                .collect::<Vec<_>>()
+        };
+        let mut land = vec!["grass_block", "dirt", "dirt"];
+        land.extend(["stone"; 14]);
+        land.extend(["deepslate"; 4]);
+        assert_eq!(column(12), land);
+        let mut underwater = vec!["air"; 8];
+        underwater.extend(["water", "water", "dirt", "dirt", "dirt"]);
+        underwater.extend(["stone"; 4]);
+        underwater.extend(["deepslate"; 4]);
+        assert_eq!(column(2), underwater);
+    }
+
+    #[test]
+    fn test_clay_bands() {
+        let datapack = datapack(noise_files(
+            &SURFACE_NOISES,
+            r#"{ "firstOctave": -8, "amplitudes": [1] }"#,
+        ));
+        let settings = noise_settings(0, 32, 12);
+        let random_state = RandomState::from_settings(&datapack, &settings, 42);
+        let surface_system = SurfaceSystem::new(
+            &datapack,
+            &random_state,
+            &settings,
+            WorldGenerationContext::from_settings(&settings.noise),
+        )
+        .unwrap();
+
+        // the expected bands are from vanilla's `SurfaceSystem`, by the first letter of the color
+        let initial = |state: &BlockState| state.name.path().chars().next().unwrap();
+        let bands: String = surface_system.clay_bands.iter().map(initial).collect();
+        assert_eq!(bands, "wttobwlottblwltttotbbrotttotttlwttottotrototttorrrwotttototttotttttlwlttottotottottylwoytttotwrrtyttwtttttotttotlwttotttototowltottolwlolwlbbbbbbbottorottlwtbrrbtttotttyyytttbbbtototrrtottotot");
+        let column = |x, z| {
+            (60..70)
+                .map(|y| initial(surface_system.band(x, y, z)))
+                .collect::<String>()
+        };
+        assert_eq!(column(0, 0), "ttotttttlw");
+        assert_eq!(column(100, -50), "totttttlwl");
+        assert_eq!(column(-700, 321), "otttttlwlt");
+        assert_eq!(column(2500, 2500), "totttttlwl");
+        assert_eq!(column(-31, 4000), "tttotttttl");
+    }
+}
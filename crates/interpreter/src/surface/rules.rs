@@ -0,0 +1,413 @@
+use crate::biomes::temperature::BiomeExt;
+use crate::chunk::ChunkAccess;
+use crate::random_state::RandomState;
+use crate::surface::{PreliminarySurface, SurfaceSystem};
+use crate::world_generation_context::{VerticalAnchorExt, WorldGenerationContext};
+use datapack::data::biome::Biome;
+use datapack::data::block_state::BlockState;
+use datapack::data::feature::CaveSurface;
+use datapack::data::holder::Holder;
+use datapack::data::surface_rules::{SurfaceRuleSource, SurfaceRulesConditionSource};
+use datapack::DataPackResult;
+use glam::{IVec2, IVec3};
+use runtime::noise::NormalNoise;
+use runtime::random_source::{AnyPositionalRandomFactory, PositionalRandomFactory, RandomSource};
+use util::heightmap_type::HeightmapType;
+use util::identifier::IdentifierBuf;
+use util::math;
+
+/// A surface rule with its noises, random factories and vertical anchors resolved.
+pub(crate) enum SurfaceRule<'a> {
+    Bandlands,
+    Block(&'a BlockState),
+    Sequence(Vec<SurfaceRule<'a>>),
+    Test(Box<SurfaceCondition<'a>>, Box<SurfaceRule<'a>>),
+}
+
+pub(crate) enum SurfaceCondition<'a> {
+    Biome(&'a [IdentifierBuf]),
+    NoiseThreshold {
+        noise: &'a NormalNoise,
+        min_threshold: f64,
+        max_threshold: f64,
+    },
+    VerticalGradient {
+        random: AnyPositionalRandomFactory,
+        true_at_and_below: i32,
+        false_at_and_above: i32,
+    },
+    YAbove {
+        anchor_y: i32,
+        surface_depth_multiplier: i32,
+        add_stone_depth: bool,
+    },
+    Water {
+        offset: i32,
+        surface_depth_multiplier: i32,
+        add_stone_depth: bool,
+    },
+    Temperature,
+    Steep,
+    Not(Box<SurfaceCondition<'a>>),
+    Hole,
+    AbovePreliminarySurface,
+    StoneDepth {
+        offset: i32,
+        add_surface_depth: bool,
+        secondary_depth_range: i32,
+        ceiling: bool,
+    },
+}
+
+impl<'a> SurfaceRule<'a> {
+    pub(crate) fn compile(
+        source: &'a SurfaceRuleSource,
+        random_state: &'a RandomState,
+        context: &WorldGenerationContext,
+    ) -> DataPackResult<Self> {
+        Ok(match source {
+            SurfaceRuleSource::Bandlands(_) => SurfaceRule::Bandlands,
+            SurfaceRuleSource::Block(block) => SurfaceRule::Block(&block.result_state),
+            SurfaceRuleSource::Sequence(sequence) => SurfaceRule::Sequence(
+                sequence
+                    .sequence
+                    .iter()
+                    .map(|rule| SurfaceRule::compile(rule, random_state, context))
+                    .collect::<DataPackResult<_>>()?,
+            ),
+            SurfaceRuleSource::Condition(test) => SurfaceRule::Test(
+                Box::new(SurfaceCondition::compile(
+                    &test.if_true,
+                    random_state,
+                    context,
+                )?),
+                Box::new(SurfaceRule::compile(&test.then_run, random_state, context)?),
+            ),
+        })
+    }
+
+    /// Returns the block state this rule places at the current position of `context`, or `None`
+    /// if the block should be left as is.
+    pub(crate) fn try_apply<'s>(
+        &'s self,
+        context: &mut SurfaceContext<'s, '_>,
+        chunk: &impl ChunkAccess,
+    ) -> DataPackResult<Option<&'s BlockState>> {
+        match self {
+            SurfaceRule::Bandlands => Ok(Some(context.system.band(
+                context.block_x,
+                context.block_y,
+                context.block_z,
+            ))),
+            SurfaceRule::Block(state) => Ok(Some(state)),
+            SurfaceRule::Sequence(sequence) => {
+                for rule in sequence {
+                    if let Some(state) = rule.try_apply(context, chunk)? {
+                        return Ok(Some(state));
+                    }
+                }
+                Ok(None)
+            }
+            SurfaceRule::Test(condition, rule) => {
+                if condition.test(context, chunk)? {
+                    rule.try_apply(context, chunk)
+                } else {
+                    Ok(None)
+                }
+            }
+        }
+    }
+}
+
+impl<'a> SurfaceCondition<'a> {
+    fn compile(
+        source: &'a SurfaceRulesConditionSource,
+        random_state: &'a RandomState,
+        context: &WorldGenerationContext,
+    ) -> DataPackResult<Self> {
+        Ok(match source {
+            SurfaceRulesConditionSource::Biome(biome) => SurfaceCondition::Biome(&biome.biome_is),
+            SurfaceRulesConditionSource::NoiseThreshold(threshold) => {
+                SurfaceCondition::NoiseThreshold {
+                    noise: random_state
+                        .get_or_create_noise(&Holder::Reference(threshold.noise.clone()))?,
+                    min_threshold: threshold.min_threshold,
+                    max_threshold: threshold.max_threshold,
+                }
+            }
+            SurfaceRulesConditionSource::VerticalGradient(gradient) => {
+                SurfaceCondition::VerticalGradient {
+                    random: random_state.get_or_create_random_factory(&gradient.random_name),
+                    true_at_and_below: gradient.true_at_and_below.resolve_y(context),
+                    false_at_and_above: gradient.false_at_and_above.resolve_y(context),
+                }
+            }
+            SurfaceRulesConditionSource::YAbove(y_above) => SurfaceCondition::YAbove {
+                anchor_y: y_above.anchor.resolve_y(context),
+                surface_depth_multiplier: *y_above.surface_depth_multiplier,
+                add_stone_depth: y_above.add_stone_depth,
+            },
+            SurfaceRulesConditionSource::Water(water) => SurfaceCondition::Water {
+                offset: water.offset,
+                surface_depth_multiplier: *water.surface_depth_multiplier,
+                add_stone_depth: water.add_stone_depth,
+            },
+            SurfaceRulesConditionSource::Temperature(_) => SurfaceCondition::Temperature,
+            SurfaceRulesConditionSource::Steep(_) => SurfaceCondition::Steep,
+            SurfaceRulesConditionSource::Not(not) => SurfaceCondition::Not(Box::new(
+                SurfaceCondition::compile(&not.invert, random_state, context)?,
+            )),
+            SurfaceRulesConditionSource::Hole(_) => SurfaceCondition::Hole,
+            SurfaceRulesConditionSource::AbovePreliminarySurface(_) => {
+                SurfaceCondition::AbovePreliminarySurface
+            }
+            SurfaceRulesConditionSource::StoneDepth(stone_depth) => SurfaceCondition::StoneDepth {
+                offset: stone_depth.offset,
+                add_surface_depth: stone_depth.add_surface_depth,
+                secondary_depth_range: stone_depth.secondary_depth_range,
+                ceiling: matches!(stone_depth.surface_type, CaveSurface::Ceiling),
+            },
+        })
+    }
+
+    fn test(
+        &self,
+        context: &mut SurfaceContext<'_, '_>,
+        chunk: &impl ChunkAccess,
+    ) -> DataPackResult<bool> {
+        Ok(match self {
+            SurfaceCondition::Biome(biomes) => match context.biome() {
+                Holder::Reference(id) => biomes.contains(id),
+                Holder::Direct(_) => false,
+            },
+            SurfaceCondition::NoiseThreshold {
+                noise,
+                min_threshold,
+                max_threshold,
+            } => {
+                let value = noise.get_value(context.block_x as f64, 0.0, context.block_z as f64);
+                value >= *min_threshold && value <= *max_threshold
+            }
+            SurfaceCondition::VerticalGradient {
+                random,
+                true_at_and_below,
+                false_at_and_above,
+            } => {
+                let y = context.block_y;
+                if y <= *true_at_and_below {
+                    true
+                } else if y >= *false_at_and_above {
+                    false
+                } else {
+                    let chance = math::map(
+                        y as f64,
+                        *true_at_and_below as f64,
+                        *false_at_and_above as f64,
+                        1.0,
+                        0.0,
+                    );
+                    let mut random = random.at(IVec3::new(context.block_x, y, context.block_z));
+                    (random.next_f32() as f64) < chance
+                }
+            }
+            SurfaceCondition::YAbove {
+                anchor_y,
+                surface_depth_multiplier,
+                add_stone_depth,
+            } => {
+                let stone_depth = if *add_stone_depth {
+                    context.stone_depth_above
+                } else {
+                    0
+                };
+                context.block_y + stone_depth
+                    >= anchor_y + context.surface_depth * surface_depth_multiplier
+            }
+            SurfaceCondition::Water {
+                offset,
+                surface_depth_multiplier,
+                add_stone_depth,
+            } => {
+                let stone_depth = if *add_stone_depth {
+                    context.stone_depth_above
+                } else {
+                    0
+                };
+                context.water_height == i32::MIN
+                    || context.block_y + stone_depth
+                        >= context.water_height
+                            + offset
+                            + context.surface_depth * surface_depth_multiplier
+            }
+            SurfaceCondition::Temperature => {
+                let pos = IVec3::new(context.block_x, context.block_y, context.block_z);
+                context
+                    .biome()
+                    .resolve(context.system.datapack)?
+                    .cold_enough_to_snow(pos)
+            }
+            SurfaceCondition::Steep => {
+                let x = context.block_x & 15;
+                let z = context.block_z & 15;
+                let height = |x, z| chunk.heightmap_height(HeightmapType::WorldSurfaceWg, x, z);
+                height(x, (z + 1).min(15)) >= height(x, (z - 1).max(0)) + 4
+                    || height((x - 1).max(0), z) >= height((x + 1).min(15), z) + 4
+            }
+            SurfaceCondition::Not(condition) => !condition.test(context, chunk)?,
+            SurfaceCondition::Hole => context.surface_depth <= 0,
+            SurfaceCondition::AbovePreliminarySurface => {
+                context.block_y >= context.min_surface_level()
+            }
+            SurfaceCondition::StoneDepth {
+                offset,
+                add_surface_depth,
+                secondary_depth_range,
+                ceiling,
+            } => {
+                let stone_depth = if *ceiling {
+                    context.stone_depth_below
+                } else {
+                    context.stone_depth_above
+                };
+                let surface_depth = if *add_surface_depth {
+                    context.surface_depth
+                } else {
+                    0
+                };
+                let secondary_depth = if *secondary_depth_range == 0 {
+                    0
+                } else {
+                    math::map(
+                        context.surface_secondary(),
+                        -1.0,
+                        1.0,
+                        0.0,
+                        *secondary_depth_range as f64,
+                    ) as i32
+                };
+                stone_depth <= 1 + offset + surface_depth + secondary_depth
+            }
+        })
+    }
+}
+
+/// The position and column state the surface rule is evaluated at, equivalent to vanilla's
+/// `SurfaceRules.Context`.
+pub(crate) struct SurfaceContext<'s, 'b> {
+    system: &'s SurfaceSystem<'s>,
+    biome_getter: &'s mut dyn FnMut(IVec3) -> &'b Holder<Biome>,
+    preliminary_surface: &'s mut dyn PreliminarySurface,
+    block_x: i32,
+    block_z: i32,
+    surface_depth: i32,
+    surface_secondary: Option<f64>,
+    min_surface_level: Option<i32>,
+    preliminary_surface_cell: Option<IVec2>,
+    preliminary_surface_cache: [i32; 4],
+    block_y: i32,
+    water_height: i32,
+    stone_depth_below: i32,
+    stone_depth_above: i32,
+    biome: Option<&'b Holder<Biome>>,
+}
+
+impl<'s, 'b> SurfaceContext<'s, 'b> {
+    pub(crate) fn new(
+        system: &'s SurfaceSystem<'s>,
+        biome_getter: &'s mut dyn FnMut(IVec3) -> &'b Holder<Biome>,
+        preliminary_surface: &'s mut dyn PreliminarySurface,
+    ) -> Self {
+        SurfaceContext {
+            system,
+            biome_getter,
+            preliminary_surface,
+            block_x: 0,
+            block_z: 0,
+            surface_depth: 0,
+            surface_secondary: None,
+            min_surface_level: None,
+            preliminary_surface_cell: None,
+            preliminary_surface_cache: [0; 4],
+            block_y: 0,
+            water_height: 0,
+            stone_depth_below: 0,
+            stone_depth_above: 0,
+            biome: None,
+        }
+    }
+
+    pub(crate) fn update_xz(&mut self, x: i32, z: i32) {
+        self.block_x = x;
+        self.block_z = z;
+        self.surface_depth = self.system.surface_depth(x, z);
+        self.surface_secondary = None;
+        self.min_surface_level = None;
+        self.biome = None;
+    }
+
+    pub(crate) fn update_y(
+        &mut self,
+        stone_depth_above: i32,
+        stone_depth_below: i32,
+        water_height: i32,
+        y: i32,
+    ) {
+        self.block_y = y;
+        self.water_height = water_height;
+        self.stone_depth_below = stone_depth_below;
+        self.stone_depth_above = stone_depth_above;
+        self.biome = None;
+    }
+
+    pub(crate) fn biome_at(&mut self, pos: IVec3) -> &'b Holder<Biome> {
+        (self.biome_getter)(pos)
+    }
+
+    /// The biome at the current position, looked up at most once per position.
+    fn biome(&mut self) -> &'b Holder<Biome> {
+        if let Some(biome) = self.biome {
+            return biome;
+        }
+        let biome = self.biome_at(IVec3::new(self.block_x, self.block_y, self.block_z));
+        self.biome = Some(biome);
+        biome
+    }
+
+    fn surface_secondary(&mut self) -> f64 {
+        *self
+            .surface_secondary
+            .get_or_insert_with(|| self.system.surface_secondary(self.block_x, self.block_z))
+    }
+
+    /// The preliminary surface level interpolated between the corners of the current chunk,
+    /// lowered by 8 blocks minus the surface depth.
+    pub(crate) fn min_surface_level(&mut self) -> i32 {
+        if let Some(min_surface_level) = self.min_surface_level {
+            return min_surface_level;
+        }
+
+        let cell = IVec2::new(self.block_x >> 4, self.block_z >> 4);
+        if self.preliminary_surface_cell != Some(cell) {
+            self.preliminary_surface_cell = Some(cell);
+            for (index, corner) in [(0, 0), (1, 0), (0, 1), (1, 1)].into_iter().enumerate() {
+                let corner: IVec2 = (cell + IVec2::from(corner)) << 4;
+                self.preliminary_surface_cache[index] = self
+                    .preliminary_surface
+                    .preliminary_surface_level(corner.x, corner.y);
+            }
+        }
+
+        let [x0z0, x1z0, x0z1, x1z1] = self.preliminary_surface_cache.map(|level| level as f64);
+        let level = math::floor(math::lerp2(
+            ((self.block_x & 15) as f32 / 16.0) as f64,
+            ((self.block_z & 15) as f32 / 16.0) as f64,
+            x0z0,
+            x1z0,
+            x0z1,
+            x1z1,
+        ));
+        let min_surface_level = level + self.surface_depth - 8;
+        self.min_surface_level = Some(min_surface_level);
+        min_surface_level
+    }
+}
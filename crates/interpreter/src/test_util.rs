@@ -0,0 +1,114 @@
+//! Fixtures shared by the tests of this crate.
+
+use crate::biomes::climate::{ClimateFunctions, ClimateSampler};
+use crate::random_state::RandomState;
+use datapack::data::noise::{NoiseGeneratorSettings, NoiseRouter};
+use datapack::DataPack;
+use std::fs;
+use std::ops::Deref;
+use std::path::Path;
+use tempfile::TempDir;
+
+const ZERO_ROUTER: &str = r#"{
+    "barrier": 0, "fluid_level_floodedness": 0, "fluid_level_spread": 0, "lava": 0,
+    "temperature": 0, "vegetation": 0, "continents": 0, "erosion": 0, "depth": 0, "ridges": 0,
+    "initial_density_without_jaggedness": 0, "final_density": 0,
+    "vein_toggle": 0, "vein_ridged": 0, "vein_gap": 0
+}"#;
+
+/// A noise router where every density function is the constant 0. Tests override the fields they
+/// need with struct update syntax.
+pub fn zero_router() -> NoiseRouter {
+    serde_json::from_str(ZERO_ROUTER).unwrap()
+}
+
+/// Noise generator settings for `height` blocks from `min_y`, filled with stone and with water below
+/// `sea_level`. The router is [`zero_router`], the surface rule is empty and aquifers and ore veins
+/// are disabled, so tests set the fields they need on top of these.
+pub fn noise_settings(min_y: i32, height: u32, sea_level: i32) -> NoiseGeneratorSettings {
+    serde_json::from_str(&format!(
+        r#"{{
+            "noise": {{ "min_y": {min_y}, "height": {height}, "size_horizontal": 1, "size_vertical": 2 }},
+            "default_block": {{ "Name": "minecraft:stone" }},
+            "default_fluid": {{ "Name": "minecraft:water", "Properties": {{ "level": "0" }} }},
+            "noise_router": {ZERO_ROUTER},
+            "surface_rule": {{ "type": "minecraft:sequence", "sequence": [] }},
+            "spawn_target": [],
+            "sea_level": {sea_level},
+            "disable_mob_generation": false,
+            "aquifers_enabled": false,
+            "ore_veins_enabled": false,
+            "legacy_random_source": false
+        }}"#
+    ))
+    .unwrap()
+}
+
+/// The noises a [`SurfaceSystem`](crate::surface::SurfaceSystem) samples.
+pub const SURFACE_NOISES: [&str; 9] = [
+    "surface",
+    "clay_bands_offset",
+    "surface_secondary",
+    "badlands_pillar",
+    "badlands_pillar_roof",
+    "badlands_surface",
+    "iceberg_pillar",
+    "iceberg_pillar_roof",
+    "iceberg_surface",
+];
+
+/// The [`datapack`] files defining each of `noises` with the same `parameters`.
+pub fn noise_files<'a>(
+    noises: &'a [&str],
+    parameters: &'a str,
+) -> impl Iterator<Item = (String, &'a str)> + 'a {
+    noises
+        .iter()
+        .map(move |noise| (format!("worldgen/noise/{noise}.json"), parameters))
+}
+
+/// A datapack read from a temporary directory, which is removed when this is dropped.
+pub struct TestDataPack {
+    datapack: DataPack,
+    _dir: TempDir,
+}
+
+impl Deref for TestDataPack {
+    type Target = DataPack;
+
+    fn deref(&self) -> &DataPack {
+        &self.datapack
+    }
+}
+
+/// Writes `files` into a new datapack, by their path relative to the `minecraft` namespace.
+pub fn datapack<P: AsRef<Path>, C: AsRef<[u8]>>(
+    files: impl IntoIterator<Item = (P, C)>,
+) -> TestDataPack {
+    let dir = TempDir::new().unwrap();
+    let namespace = dir.path().join("data/minecraft");
+    for (path, contents) in files {
+        let path = namespace.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+    TestDataPack {
+        datapack: DataPack::new(dir.path()).unwrap(),
+        _dir: dir,
+    }
+}
+
+pub fn empty_datapack() -> TestDataPack {
+    datapack::<&str, &str>([])
+}
+
+/// Runs `f` with a sampler of the climate functions of `router`, with a random state seeded with 0.
+pub fn with_climate_sampler<T>(
+    datapack: &DataPack,
+    router: &NoiseRouter,
+    f: impl FnOnce(&mut ClimateSampler) -> T,
+) -> T {
+    let random_state = RandomState::new(datapack, 0, false);
+    let climate = ClimateFunctions::new(datapack, &random_state, router).unwrap();
+    f(&mut climate.sampler())
+}
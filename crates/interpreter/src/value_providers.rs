@@ -0,0 +1,142 @@
+use crate::sealed::Sealed;
+use crate::world_generation_context::{VerticalAnchorExt, WorldGenerationContext};
+use datapack::data::carvers::AnchorOrHeightProvider;
+use datapack::data::height_provider::HeightProvider;
+use datapack::data::value_provider::FloatProvider;
+use datapack::data::SimpleWeightedListEntry;
+use runtime::random_source::RandomSource;
+use util::math;
+
+pub trait FloatProviderExt: Sealed {
+    fn sample(&self, random: &mut impl RandomSource) -> f32;
+}
+
+impl Sealed for FloatProvider {}
+
+impl FloatProviderExt for FloatProvider {
+    fn sample(&self, random: &mut impl RandomSource) -> f32 {
+        match self {
+            FloatProvider::Constant(provider) => provider.value,
+            FloatProvider::Uniform(provider) => {
+                random.next_f32() * (provider.max_exclusive - provider.min_inclusive)
+                    + provider.min_inclusive
+            }
+            FloatProvider::ClampedNormal(provider) => math::clamp(
+                provider.mean + random.next_gaussian() as f32 * provider.deviation,
+                provider.min,
+                provider.max,
+            ),
+            FloatProvider::Trapezoid(provider) => {
+                let range = provider.max - provider.min;
+                let half_slope = (range - provider.plateau) / 2.0;
+                let rest = range - half_slope;
+                provider.min + random.next_f32() * rest + random.next_f32() * half_slope
+            }
+        }
+    }
+}
+
+pub trait HeightProviderExt: Sealed {
+    fn sample(&self, random: &mut impl RandomSource, context: &WorldGenerationContext) -> i32;
+}
+
+impl Sealed for HeightProvider {}
+
+impl HeightProviderExt for HeightProvider {
+    fn sample(&self, random: &mut impl RandomSource, context: &WorldGenerationContext) -> i32 {
+        match self {
+            HeightProvider::ConstantHeight(provider) => provider.0.resolve_y(context),
+            HeightProvider::UniformHeight(provider) => {
+                let min = provider.min_inclusive.resolve_y(context);
+                let max = provider.max_inclusive.resolve_y(context);
+                if min > max {
+                    return min;
+                }
+                random.next_i32_between_inclusive(min, max)
+            }
+            HeightProvider::BasedToBottomHeight(provider) => {
+                let min = provider.min_inclusive.resolve_y(context);
+                let max = provider.max_inclusive.resolve_y(context);
+                let inner = *provider.inner as i32;
+                let range = max - min - inner + 1;
+                if range <= 0 {
+                    return min;
+                }
+                let range = random.next_u32(range as u32) as i32;
+                random.next_u32((range + inner) as u32) as i32 + min
+            }
+            HeightProvider::VeryBiasedToBottomHeight(provider) => {
+                let min = provider.min_inclusive.resolve_y(context);
+                let max = provider.max_inclusive.resolve_y(context);
+                let inner = *provider.inner as i32;
+                if max - min - inner < 0 {
+                    return min;
+                }
+                let upper = next_i32_clamped(random, min + inner, max);
+                let lower = next_i32_clamped(random, min, upper - 1);
+                next_i32_clamped(random, min, lower - 1 + inner)
+            }
+            HeightProvider::TrapezoidHeight(provider) => {
+                let min = provider.min_inclusive.resolve_y(context);
+                let max = provider.max_inclusive.resolve_y(context);
+                if min > max {
+                    return min;
+                }
+                let range = max - min;
+                if provider.plateau >= range {
+                    return random.next_i32_between_inclusive(min, max);
+                }
+                let half_slope = (range - provider.plateau) / 2;
+                let rest = range - half_slope;
+                min + random.next_i32_between_inclusive(0, rest)
+                    + random.next_i32_between_inclusive(0, half_slope)
+            }
+            // vanilla fails to sample an empty list, here it's the bottom of the world
+            HeightProvider::WeightedListHeight(provider) => {
+                weighted_value(random, &provider.distribution)
+                    .map_or(context.min_y, |provider| provider.sample(random, context))
+            }
+        }
+    }
+}
+
+impl Sealed for AnchorOrHeightProvider {}
+
+impl HeightProviderExt for AnchorOrHeightProvider {
+    fn sample(&self, random: &mut impl RandomSource, context: &WorldGenerationContext) -> i32 {
+        match self {
+            AnchorOrHeightProvider::Anchor(anchor) => anchor.resolve_y(context),
+            AnchorOrHeightProvider::HeightProvider(provider) => provider.sample(random, context),
+        }
+    }
+}
+
+/// `Mth.nextInt`, which returns `min` if the range is empty.
+fn next_i32_clamped(random: &mut impl RandomSource, min: i32, max: i32) -> i32 {
+    if min >= max {
+        min
+    } else {
+        random.next_i32_between_inclusive(min, max)
+    }
+}
+
+/// Picks a random entry of a weighted list, equivalent to
+/// `SimpleWeightedRandomList.getRandomValue`. Returns `None` if the total weight is zero.
+pub fn weighted_value<'e, T>(
+    random: &mut impl RandomSource,
+    entries: &'e [SimpleWeightedListEntry<T>],
+) -> Option<&'e T> {
+    let total_weight: u32 = entries.iter().map(|entry| *entry.weight).sum();
+    if total_weight == 0 {
+        return None;
+    }
+    let mut index = random.next_u32(total_weight);
+    entries.iter().find_map(|entry| {
+        if index < *entry.weight {
+            Some(&entry.data)
+        } else {
+            index -= *entry.weight;
+            None
+        }
+    })
+}
@@ -0,0 +1,45 @@
+use crate::sealed::Sealed;
+use datapack::data::feature::VerticalAnchor;
+use datapack::data::noise::NoiseSettings;
+
+/// The vertical range a chunk generator generates in, used to resolve [`VerticalAnchor`]s.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct WorldGenerationContext {
+    pub min_y: i32,
+    pub height: i32,
+}
+
+impl WorldGenerationContext {
+    /// Creates the context of a noise generator in a level of the given height range, equivalent
+    /// to vanilla's `WorldGenerationContext` constructor.
+    pub fn new(settings: &NoiseSettings, level_min_y: i32, level_height: i32) -> Self {
+        WorldGenerationContext {
+            min_y: level_min_y.max(*settings.min_y),
+            height: level_height.min(*settings.height as i32),
+        }
+    }
+
+    /// Creates the context of a noise generator in a level with the same height range.
+    pub fn from_settings(settings: &NoiseSettings) -> Self {
+        WorldGenerationContext {
+            min_y: *settings.min_y,
+            height: *settings.height as i32,
+        }
+    }
+}
+
+pub trait VerticalAnchorExt: Sealed {
+    fn resolve_y(&self, context: &WorldGenerationContext) -> i32;
+}
+
+impl Sealed for VerticalAnchor {}
+
+impl VerticalAnchorExt for VerticalAnchor {
+    fn resolve_y(&self, context: &WorldGenerationContext) -> i32 {
+        match self {
+            VerticalAnchor::Absolute(y) => **y,
+            VerticalAnchor::AboveBottom(offset) => context.min_y + **offset,
+            VerticalAnchor::BelowTop(offset) => context.height - 1 + context.min_y - **offset,
+        }
+    }
+}
@@ -1 +1,2 @@
+pub mod noise;
 pub mod random_source;
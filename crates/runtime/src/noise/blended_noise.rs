@@ -0,0 +1,173 @@
+use crate::noise::{ImprovedNoise, PerlinNoise};
+use crate::random_source::{RandomSource, XoroshiroRandomSource};
+use datapack::data::density_function::BlendedNoiseFunction;
+use glam::IVec3;
+use util::math;
+
+/// The terrain noise from before 1.18, which is still used as the base of the overworld and nether
+/// terrain shape.
+#[derive(Debug)]
+pub struct BlendedNoise {
+    min_limit_noise: PerlinNoise,
+    max_limit_noise: PerlinNoise,
+    main_noise: PerlinNoise,
+    xz_factor: f64,
+    y_factor: f64,
+    smear_scale_multiplier: f64,
+    xz_multiplier: f64,
+    y_multiplier: f64,
+    max_value: f64,
+}
+
+impl BlendedNoise {
+    /// Vanilla seeds this with `LegacyRandomSource::new(seed)` if the noise settings use the
+    /// legacy random source, and from the hash of `minecraft:terrain` otherwise.
+    pub fn new(random: &mut impl RandomSource, function: &BlendedNoiseFunction) -> BlendedNoise {
+        let min_limit_noise = PerlinNoise::create_legacy_for_blended_noise(random, -15..=0);
+        let max_limit_noise = PerlinNoise::create_legacy_for_blended_noise(random, -15..=0);
+        let main_noise = PerlinNoise::create_legacy_for_blended_noise(random, -7..=0);
+        let xz_multiplier = 684.412 * *function.xz_scale;
+        let y_multiplier = 684.412 * *function.y_scale;
+        let max_value = min_limit_noise.max_broken_value(y_multiplier);
+        BlendedNoise {
+            min_limit_noise,
+            max_limit_noise,
+            main_noise,
+            xz_factor: *function.xz_factor,
+            y_factor: *function.y_factor,
+            smear_scale_multiplier: *function.smear_scale_multiplier,
+            xz_multiplier,
+            y_multiplier,
+            max_value,
+        }
+    }
+
+    /// Equivalent to vanilla's `BlendedNoise.createUnseeded`, which is what the density function
+    /// codec creates before the random state replaces it with a seeded one.
+    pub fn create_unseeded(function: &BlendedNoiseFunction) -> BlendedNoise {
+        Self::new(&mut XoroshiroRandomSource::new(0), function)
+    }
+
+    pub fn compute(&self, pos: IVec3) -> f64 {
+        let x = pos.x as f64 * self.xz_multiplier;
+        let y = pos.y as f64 * self.y_multiplier;
+        let z = pos.z as f64 * self.xz_multiplier;
+        let main_x = x / self.xz_factor;
+        let main_y = y / self.y_factor;
+        let main_z = z / self.xz_factor;
+        let smear = self.y_multiplier * self.smear_scale_multiplier;
+        let main_smear = smear / self.y_factor;
+
+        let mut main_value = 0.0;
+        let mut factor = 1.0;
+        for octave in 0..8 {
+            if let Some(noise) = self.main_noise.get_octave_noise(octave) {
+                main_value += noise.noise_with_y_scale(
+                    PerlinNoise::wrap(main_x * factor),
+                    PerlinNoise::wrap(main_y * factor),
+                    PerlinNoise::wrap(main_z * factor),
+                    main_smear * factor,
+                    main_y * factor,
+                ) / factor;
+            }
+            factor /= 2.0;
+        }
+
+        let delta = (main_value / 10.0 + 1.0) / 2.0;
+        let use_max_only = delta >= 1.0;
+        let use_min_only = delta <= 0.0;
+        let mut min_value = 0.0;
+        let mut max_value = 0.0;
+        let mut factor = 1.0;
+        for octave in 0..16 {
+            let sample = |noise: &ImprovedNoise| {
+                noise.noise_with_y_scale(
+                    PerlinNoise::wrap(x * factor),
+                    PerlinNoise::wrap(y * factor),
+                    PerlinNoise::wrap(z * factor),
+                    smear * factor,
+                    y * factor,
+                ) / factor
+            };
+            if !use_max_only {
+                if let Some(noise) = self.min_limit_noise.get_octave_noise(octave) {
+                    min_value += sample(noise);
+                }
+            }
+            if !use_min_only {
+                if let Some(noise) = self.max_limit_noise.get_octave_noise(octave) {
+                    max_value += sample(noise);
+                }
+            }
+            factor /= 2.0;
+        }
+
+        math::clamped_lerp(min_value / 512.0, max_value / 512.0, delta) / 128.0
+    }
+
+    #[inline]
+    pub fn min_value(&self) -> f64 {
+        -self.max_value
+    }
+
+    #[inline]
+    pub fn max_value(&self) -> f64 {
+        self.max_value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::noise::BlendedNoise;
+    use crate::random_source::LegacyRandomSource;
+    use datapack::data::density_function::BlendedNoiseFunction;
+    use glam::IVec3;
+    use util::ranged::Ranged;
+
+    fn function(xz_scale: f64, y_scale: f64, smear_scale_multiplier: f64) -> BlendedNoiseFunction {
+        BlendedNoiseFunction {
+            xz_scale: Ranged::new(xz_scale).unwrap(),
+            y_scale: Ranged::new(y_scale).unwrap(),
+            xz_factor: Ranged::new(80.0).unwrap(),
+            y_factor: Ranged::new(160.0).unwrap(),
+            smear_scale_multiplier: Ranged::new(smear_scale_multiplier).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_blended_noise() {
+        // the expected values are from vanilla's `BlendedNoise`
+        let positions = [
+            IVec3::new(0, 0, 0),
+            IVec3::new(8, 64, -8),
+            IVec3::new(1000, -40, 3000),
+            IVec3::new(-123, 250, 77),
+        ];
+        let compute = |noise: &BlendedNoise| positions.map(|pos| noise.compute(pos));
+
+        let legacy = BlendedNoise::new(
+            &mut LegacyRandomSource::new(42),
+            &function(0.25, 0.125, 8.0),
+        );
+        assert_eq!(
+            compute(&legacy),
+            [
+                0.17498762833457276,
+                0.022624479780880738,
+                -0.5228910469618135,
+                0.27919646269990017,
+            ]
+        );
+
+        let unseeded = BlendedNoise::create_unseeded(&function(1.0, 1.0, 8.0));
+        assert_eq!(
+            compute(&unseeded),
+            [
+                0.05283727086562935,
+                -0.10312383292779255,
+                0.38846541982132965,
+                0.08616257090825427,
+            ]
+        );
+    }
+}
@@ -0,0 +1,62 @@
+use crate::noise::SimplexNoise;
+use crate::random_source::{LegacyRandomSource, RandomSource};
+use glam::IVec3;
+use util::math;
+
+/// The density of the End's outer islands, as sampled by the `minecraft:end_islands` density
+/// function.
+#[derive(Debug)]
+pub struct EndIslands {
+    island_noise: SimplexNoise,
+}
+
+impl EndIslands {
+    const ISLAND_THRESHOLD: f32 = -0.9;
+
+    pub fn new(seed: u64) -> EndIslands {
+        let mut random = LegacyRandomSource::new(seed);
+        random.consume_count(17292);
+        EndIslands {
+            island_noise: SimplexNoise::new(&mut random),
+        }
+    }
+
+    pub fn compute(&self, pos: IVec3) -> f64 {
+        (self.get_height_value(pos.x / 8, pos.z / 8) as f64 - 8.0) / 128.0
+    }
+
+    /// Takes coordinates in units of 8 blocks.
+    pub fn get_height_value(&self, x: i32, z: i32) -> f32 {
+        let chunk_x = x / 2;
+        let chunk_z = z / 2;
+        let offset_x = x % 2;
+        let offset_z = z % 2;
+        let distance_sq = x.wrapping_mul(x).wrapping_add(z.wrapping_mul(z));
+        let mut height = 100.0 - (distance_sq as f32).sqrt() * 8.0;
+        height = math::clamp(height, -100.0, 80.0);
+
+        for dx in -12..=12 {
+            for dz in -12..=12 {
+                let island_x = (chunk_x + dx) as i64;
+                let island_z = (chunk_z + dz) as i64;
+                if island_x * island_x + island_z * island_z > 4096
+                    && self
+                        .island_noise
+                        .get_value_2d(island_x as f64, island_z as f64)
+                        < Self::ISLAND_THRESHOLD as f64
+                {
+                    let size = ((island_x as f32).abs() * 3439.0 + (island_z as f32).abs() * 147.0)
+                        % 13.0
+                        + 9.0;
+                    let distance_x = (offset_x - dx * 2) as f32;
+                    let distance_z = (offset_z - dz * 2) as f32;
+                    let island_height =
+                        100.0 - (distance_x * distance_x + distance_z * distance_z).sqrt() * size;
+                    height = height.max(math::clamp(island_height, -100.0, 80.0));
+                }
+            }
+        }
+
+        height
+    }
+}
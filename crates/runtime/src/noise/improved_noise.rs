@@ -0,0 +1,156 @@
+use crate::noise::{dot, GRADIENT};
+use crate::random_source::RandomSource;
+use util::math;
+
+#[derive(Debug)]
+pub struct ImprovedNoise {
+    pub xo: f64,
+    pub yo: f64,
+    pub zo: f64,
+    p: [u8; 256],
+}
+
+impl ImprovedNoise {
+    pub fn new(random: &mut impl RandomSource) -> ImprovedNoise {
+        let xo = random.next_f64() * 256.0;
+        let yo = random.next_f64() * 256.0;
+        let zo = random.next_f64() * 256.0;
+        let mut p = [0; 256];
+        for (i, p) in p.iter_mut().enumerate() {
+            *p = i as u8;
+        }
+        for i in 0..256 {
+            let j = random.next_u32(256 - i as u32) as usize;
+            p.swap(i, i + j);
+        }
+        ImprovedNoise { xo, yo, zo, p }
+    }
+
+    #[inline]
+    pub fn noise(&self, x: f64, y: f64, z: f64) -> f64 {
+        self.noise_with_y_scale(x, y, z, 0.0, 0.0)
+    }
+
+    /// Samples the noise with the y coordinate snapped to multiples of `y_scale`, which is how the
+    /// old blended noise gets its stretched look.
+    pub fn noise_with_y_scale(&self, x: f64, y: f64, z: f64, y_scale: f64, y_max: f64) -> f64 {
+        let x = x + self.xo;
+        let y = y + self.yo;
+        let z = z + self.zo;
+        let grid_x = math::floor(x);
+        let grid_y = math::floor(y);
+        let grid_z = math::floor(z);
+        let delta_x = x - grid_x as f64;
+        let delta_y = y - grid_y as f64;
+        let delta_z = z - grid_z as f64;
+        let y_offset = if y_scale != 0.0 {
+            let max = if y_max >= 0.0 && y_max < delta_y {
+                y_max
+            } else {
+                delta_y
+            };
+            math::floor(max / y_scale + 1.0e-7f32 as f64) as f64 * y_scale
+        } else {
+            0.0
+        };
+        self.sample_and_lerp(
+            grid_x,
+            grid_y,
+            grid_z,
+            delta_x,
+            delta_y - y_offset,
+            delta_z,
+            delta_y,
+        )
+    }
+
+    #[inline]
+    fn p(&self, index: i32) -> i32 {
+        self.p[(index & 0xff) as usize] as i32
+    }
+
+    #[inline]
+    fn grad_dot(index: i32, x: f64, y: f64, z: f64) -> f64 {
+        dot(&GRADIENT[(index & 15) as usize], x, y, z)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn sample_and_lerp(
+        &self,
+        grid_x: i32,
+        grid_y: i32,
+        grid_z: i32,
+        delta_x: f64,
+        weird_delta_y: f64,
+        delta_z: f64,
+        delta_y: f64,
+    ) -> f64 {
+        let x0 = self.p(grid_x);
+        let x1 = self.p(grid_x.wrapping_add(1));
+        let x0y0 = self.p(x0.wrapping_add(grid_y));
+        let x0y1 = self.p(x0.wrapping_add(grid_y).wrapping_add(1));
+        let x1y0 = self.p(x1.wrapping_add(grid_y));
+        let x1y1 = self.p(x1.wrapping_add(grid_y).wrapping_add(1));
+
+        let (dx, dy, dz) = (delta_x, weird_delta_y, delta_z);
+        let d000 = Self::grad_dot(self.p(x0y0.wrapping_add(grid_z)), dx, dy, dz);
+        let d100 = Self::grad_dot(self.p(x1y0.wrapping_add(grid_z)), dx - 1.0, dy, dz);
+        let d010 = Self::grad_dot(self.p(x0y1.wrapping_add(grid_z)), dx, dy - 1.0, dz);
+        let d110 = Self::grad_dot(self.p(x1y1.wrapping_add(grid_z)), dx - 1.0, dy - 1.0, dz);
+        let grid_z1 = grid_z.wrapping_add(1);
+        let d001 = Self::grad_dot(self.p(x0y0.wrapping_add(grid_z1)), dx, dy, dz - 1.0);
+        let d101 = Self::grad_dot(self.p(x1y0.wrapping_add(grid_z1)), dx - 1.0, dy, dz - 1.0);
+        let d011 = Self::grad_dot(self.p(x0y1.wrapping_add(grid_z1)), dx, dy - 1.0, dz - 1.0);
+        let d111 = Self::grad_dot(
+            self.p(x1y1.wrapping_add(grid_z1)),
+            dx - 1.0,
+            dy - 1.0,
+            dz - 1.0,
+        );
+
+        math::lerp3(
+            math::smoothstep(delta_x),
+            math::smoothstep(delta_y),
+            math::smoothstep(delta_z),
+            d000,
+            d100,
+            d010,
+            d110,
+            d001,
+            d101,
+            d011,
+            d111,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::noise::ImprovedNoise;
+    use crate::random_source::{LegacyRandomSource, XoroshiroRandomSource};
+
+    #[test]
+    fn test_improved_noise() {
+        // the expected values are from vanilla's `ImprovedNoise`
+        let noise = ImprovedNoise::new(&mut XoroshiroRandomSource::new(42));
+        assert_eq!(noise.xo, 190.83062484342904);
+        assert_eq!(noise.yo, 101.88674612737026);
+        assert_eq!(noise.zo, 151.323544791807);
+        assert_eq!(noise.noise(0.0, 0.0, 0.0), 0.14504255324954013);
+        assert_eq!(noise.noise(0.5, 1.25, -3.75), -0.2117674284960942);
+        assert_eq!(noise.noise(123.456, -78.9, 1000.1), 0.6176626319125775);
+        assert_eq!(noise.noise(-2048.3, 64.7, 31.9), -0.3390492318701094);
+        assert_eq!(
+            noise.noise_with_y_scale(10.5, 0.0, -7.5, 0.25, 0.5),
+            0.29610972880650716
+        );
+        assert_eq!(
+            noise.noise_with_y_scale(10.5, 0.0, -7.5, 0.25, -1.0),
+            0.524838439387424
+        );
+        assert_eq!(noise.noise(10.5, 0.0, -7.5), -0.1613476923553267);
+
+        let noise = ImprovedNoise::new(&mut LegacyRandomSource::new(42));
+        assert_eq!(noise.noise(0.5, 1.25, -3.75), -0.1250296668617726);
+    }
+}
@@ -0,0 +1,52 @@
+mod blended_noise;
+mod end_islands;
+mod improved_noise;
+mod normal_noise;
+mod perlin_noise;
+mod perlin_simplex_noise;
+mod simplex_noise;
+
+pub use blended_noise::*;
+pub use end_islands::*;
+pub use improved_noise::*;
+pub use normal_noise::*;
+pub use perlin_noise::*;
+pub use perlin_simplex_noise::*;
+pub use simplex_noise::*;
+
+use crate::random_source::PositionalRandomFactory;
+use datapack::data::density_function::NoiseParameters;
+use util::identifier::Identifier;
+
+pub(crate) const GRADIENT: [[i32; 3]; 16] = [
+    [1, 1, 0],
+    [-1, 1, 0],
+    [1, -1, 0],
+    [-1, -1, 0],
+    [1, 0, 1],
+    [-1, 0, 1],
+    [1, 0, -1],
+    [-1, 0, -1],
+    [0, 1, 1],
+    [0, -1, 1],
+    [0, 1, -1],
+    [0, -1, -1],
+    [1, 1, 0],
+    [0, -1, 1],
+    [-1, 1, 0],
+    [0, -1, -1],
+];
+
+#[inline]
+pub(crate) fn dot(gradient: &[i32; 3], x: f64, y: f64, z: f64) -> f64 {
+    gradient[0] as f64 * x + gradient[1] as f64 * y + gradient[2] as f64 * z
+}
+
+/// Creates the noise registered under `id`, seeded the same way as vanilla's `Noises.instantiate`.
+pub fn instantiate(
+    random: &impl PositionalRandomFactory,
+    id: &Identifier,
+    parameters: &NoiseParameters,
+) -> NormalNoise {
+    NormalNoise::create(&mut random.create_from_hash_of(id), parameters)
+}
@@ -0,0 +1,104 @@
+use crate::noise::PerlinNoise;
+use crate::random_source::RandomSource;
+use datapack::data::density_function::NoiseParameters;
+
+#[derive(Debug)]
+pub struct NormalNoise {
+    first: PerlinNoise,
+    second: PerlinNoise,
+    value_factor: f64,
+    max_value: f64,
+}
+
+impl NormalNoise {
+    const INPUT_FACTOR: f64 = 1.0181268882175227;
+
+    pub fn create(random: &mut impl RandomSource, parameters: &NoiseParameters) -> NormalNoise {
+        let first = PerlinNoise::create(random, parameters.first_octave, &parameters.amplitudes);
+        let second = PerlinNoise::create(random, parameters.first_octave, &parameters.amplitudes);
+        Self::from_perlin_noises(first, second, &parameters.amplitudes)
+    }
+
+    pub fn create_legacy_nether_biome(
+        random: &mut impl RandomSource,
+        parameters: &NoiseParameters,
+    ) -> NormalNoise {
+        let first = PerlinNoise::create_legacy_for_legacy_nether_biome(
+            random,
+            parameters.first_octave,
+            &parameters.amplitudes,
+        );
+        let second = PerlinNoise::create_legacy_for_legacy_nether_biome(
+            random,
+            parameters.first_octave,
+            &parameters.amplitudes,
+        );
+        Self::from_perlin_noises(first, second, &parameters.amplitudes)
+    }
+
+    fn from_perlin_noises(
+        first: PerlinNoise,
+        second: PerlinNoise,
+        amplitudes: &[f64],
+    ) -> NormalNoise {
+        let mut min_octave = i32::MAX;
+        let mut max_octave = i32::MIN;
+        for (index, &amplitude) in amplitudes.iter().enumerate() {
+            if amplitude != 0.0 {
+                min_octave = min_octave.min(index as i32);
+                max_octave = max_octave.max(index as i32);
+            }
+        }
+        // if all the amplitudes are zero this overflows, same as in vanilla
+        let value_factor =
+            0.16666666666666666 / Self::expected_deviation(max_octave.wrapping_sub(min_octave));
+        let max_value = (first.max_value() + second.max_value()) * value_factor;
+        NormalNoise {
+            first,
+            second,
+            value_factor,
+            max_value,
+        }
+    }
+
+    fn expected_deviation(octaves: i32) -> f64 {
+        0.1 * (1.0 + 1.0 / octaves.wrapping_add(1) as f64)
+    }
+
+    pub fn get_value(&self, x: f64, y: f64, z: f64) -> f64 {
+        let x2 = x * Self::INPUT_FACTOR;
+        let y2 = y * Self::INPUT_FACTOR;
+        let z2 = z * Self::INPUT_FACTOR;
+        (self.first.get_value(x, y, z) + self.second.get_value(x2, y2, z2)) * self.value_factor
+    }
+
+    #[inline]
+    pub fn max_value(&self) -> f64 {
+        self.max_value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::noise::NormalNoise;
+    use crate::random_source::XoroshiroRandomSource;
+    use datapack::data::density_function::NoiseParameters;
+
+    #[test]
+    fn test_normal_noise() {
+        // the expected values are from vanilla's `NormalNoise`
+        let parameters = NoiseParameters {
+            first_octave: -5,
+            amplitudes: vec![1.0, 1.0, 0.0, 0.5],
+        };
+        let noise = NormalNoise::create(&mut XoroshiroRandomSource::new(42), &parameters);
+        assert_eq!(noise.get_value(0.0, 0.0, 0.0), -0.10495587749856856);
+        assert_eq!(noise.get_value(0.5, 1.25, -3.75), 0.04302223606665582);
+        assert_eq!(
+            noise.get_value(123.456, -78.9, 1000.1),
+            -0.11086461854233905
+        );
+        assert_eq!(noise.get_value(-2048.3, 64.7, 31.9), -0.367942440126538);
+        assert_eq!(noise.max_value(), 4.444444444444445);
+    }
+}
@@ -0,0 +1,202 @@
+use crate::noise::ImprovedNoise;
+use crate::random_source::{PositionalRandomFactory, RandomSource};
+use std::ops::RangeInclusive;
+use util::math;
+
+#[derive(Debug)]
+pub struct PerlinNoise {
+    noise_levels: Vec<Option<ImprovedNoise>>,
+    first_octave: i32,
+    amplitudes: Vec<f64>,
+    lowest_freq_input_factor: f64,
+    lowest_freq_value_factor: f64,
+    max_value: f64,
+}
+
+impl PerlinNoise {
+    const ROUND_OFF: f64 = 33554432.0;
+
+    pub fn create(
+        random: &mut impl RandomSource,
+        first_octave: i32,
+        amplitudes: &[f64],
+    ) -> PerlinNoise {
+        let factory = random.fork_positional();
+        let noise_levels = amplitudes
+            .iter()
+            .enumerate()
+            .map(|(index, &amplitude)| {
+                (amplitude != 0.0).then(|| {
+                    let octave = first_octave + index as i32;
+                    ImprovedNoise::new(&mut factory.create_from_hash_of(format!("octave_{octave}")))
+                })
+            })
+            .collect();
+        Self::from_noise_levels(noise_levels, first_octave, amplitudes.to_vec())
+    }
+
+    pub fn create_legacy_for_blended_noise(
+        random: &mut impl RandomSource,
+        octaves: RangeInclusive<i32>,
+    ) -> PerlinNoise {
+        let first_octave = *octaves.start();
+        let mut amplitudes = vec![0.0; (octaves.end() - first_octave + 1) as usize];
+        for octave in octaves {
+            amplitudes[(octave - first_octave) as usize] = 1.0;
+        }
+        Self::create_legacy(random, first_octave, amplitudes)
+    }
+
+    pub fn create_legacy_for_legacy_nether_biome(
+        random: &mut impl RandomSource,
+        first_octave: i32,
+        amplitudes: &[f64],
+    ) -> PerlinNoise {
+        Self::create_legacy(random, first_octave, amplitudes.to_vec())
+    }
+
+    fn create_legacy(
+        random: &mut impl RandomSource,
+        first_octave: i32,
+        amplitudes: Vec<f64>,
+    ) -> PerlinNoise {
+        let octave_count = amplitudes.len() as i32;
+        let zero_octave_index = -first_octave;
+        assert!(
+            zero_octave_index >= octave_count - 1,
+            "Positive octaves are temporarily disabled"
+        );
+
+        // legacy noises are created from the highest frequency octave down, and always create the
+        // zero octave even if it's not used
+        let mut noise_levels: Vec<Option<ImprovedNoise>> =
+            amplitudes.iter().map(|_| None).collect();
+        let zero_octave = ImprovedNoise::new(random);
+        if zero_octave_index >= 0
+            && zero_octave_index < octave_count
+            && amplitudes[zero_octave_index as usize] != 0.0
+        {
+            noise_levels[zero_octave_index as usize] = Some(zero_octave);
+        }
+        for index in (0..zero_octave_index).rev() {
+            if index < octave_count && amplitudes[index as usize] != 0.0 {
+                noise_levels[index as usize] = Some(ImprovedNoise::new(random));
+            } else {
+                Self::skip_octave(random);
+            }
+        }
+
+        Self::from_noise_levels(noise_levels, first_octave, amplitudes)
+    }
+
+    fn skip_octave(random: &mut impl RandomSource) {
+        random.consume_count(262);
+    }
+
+    fn from_noise_levels(
+        noise_levels: Vec<Option<ImprovedNoise>>,
+        first_octave: i32,
+        amplitudes: Vec<f64>,
+    ) -> PerlinNoise {
+        let octave_count = amplitudes.len() as i32;
+        let mut noise = PerlinNoise {
+            noise_levels,
+            first_octave,
+            amplitudes,
+            lowest_freq_input_factor: 2.0f64.powi(first_octave),
+            lowest_freq_value_factor: 2.0f64.powi(octave_count - 1)
+                / (2.0f64.powi(octave_count) - 1.0),
+            max_value: 0.0,
+        };
+        noise.max_value = noise.edge_value(2.0);
+        noise
+    }
+
+    pub fn get_value(&self, x: f64, y: f64, z: f64) -> f64 {
+        let mut result = 0.0;
+        let mut input_factor = self.lowest_freq_input_factor;
+        let mut value_factor = self.lowest_freq_value_factor;
+        for (noise, amplitude) in self.noise_levels.iter().zip(&self.amplitudes) {
+            if let Some(noise) = noise {
+                let value = noise.noise(
+                    Self::wrap(x * input_factor),
+                    Self::wrap(y * input_factor),
+                    Self::wrap(z * input_factor),
+                );
+                result += amplitude * value * value_factor;
+            }
+            input_factor *= 2.0;
+            value_factor /= 2.0;
+        }
+        result
+    }
+
+    /// Returns the octave noise `index` octaves below the highest frequency octave.
+    #[inline]
+    pub fn get_octave_noise(&self, index: usize) -> Option<&ImprovedNoise> {
+        self.noise_levels[self.noise_levels.len() - 1 - index].as_ref()
+    }
+
+    #[inline]
+    pub fn first_octave(&self) -> i32 {
+        self.first_octave
+    }
+
+    #[inline]
+    pub fn amplitudes(&self) -> &[f64] {
+        &self.amplitudes
+    }
+
+    #[inline]
+    pub fn max_value(&self) -> f64 {
+        self.max_value
+    }
+
+    #[inline]
+    pub fn max_broken_value(&self, value: f64) -> f64 {
+        self.edge_value(value + 2.0)
+    }
+
+    fn edge_value(&self, value: f64) -> f64 {
+        let mut result = 0.0;
+        let mut value_factor = self.lowest_freq_value_factor;
+        for (noise, amplitude) in self.noise_levels.iter().zip(&self.amplitudes) {
+            if noise.is_some() {
+                result += amplitude * value * value_factor;
+            }
+            value_factor /= 2.0;
+        }
+        result
+    }
+
+    /// Keeps coordinates small enough to not lose precision, while keeping the noise continuous
+    /// for all practical coordinates.
+    #[inline]
+    pub fn wrap(value: f64) -> f64 {
+        value - math::lfloor(value / Self::ROUND_OFF + 0.5) as f64 * Self::ROUND_OFF
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::noise::PerlinNoise;
+    use crate::random_source::XoroshiroRandomSource;
+
+    #[test]
+    fn test_perlin_noise() {
+        // the expected values are from vanilla's `PerlinNoise`
+        let noise = PerlinNoise::create(
+            &mut XoroshiroRandomSource::new(42),
+            -3,
+            &[1.0, 0.0, 0.5, 2.0],
+        );
+        assert_eq!(noise.get_value(0.0, 0.0, 0.0), 0.16685916710460114);
+        assert_eq!(noise.get_value(0.5, 1.25, -3.75), 0.09101564764941496);
+        assert_eq!(
+            noise.get_value(123.456, -78.9, 1000.1),
+            -0.11126342252448805
+        );
+        assert_eq!(noise.get_value(-2048.3, 64.7, 31.9), 0.12234288519050049);
+        assert_eq!(noise.max_value(), 1.4666666666666666);
+    }
+}
@@ -0,0 +1,65 @@
+use crate::noise::SimplexNoise;
+use crate::random_source::RandomSource;
+
+/// Octaves of [`SimplexNoise`], used for the hardcoded biome temperature noises.
+#[derive(Debug)]
+pub struct PerlinSimplexNoise {
+    noise_levels: Vec<Option<SimplexNoise>>,
+    highest_freq_input_factor: f64,
+    highest_freq_value_factor: f64,
+}
+
+impl PerlinSimplexNoise {
+    /// Creates the noise with the given octaves, which must be sorted, unique and non-positive.
+    pub fn new(random: &mut impl RandomSource, octaves: &[i32]) -> PerlinSimplexNoise {
+        let first_octave = *octaves.first().expect("need some octaves");
+        let last_octave = *octaves.last().unwrap();
+        assert!(
+            last_octave <= 0,
+            "positive octaves are not supported by PerlinSimplexNoise"
+        );
+        let octave_count = last_octave - first_octave + 1;
+
+        // octaves are created from the zero octave towards the lowest frequency, at index
+        // `last_octave - octave`
+        let mut noise_levels: Vec<Option<SimplexNoise>> = (0..octave_count).map(|_| None).collect();
+        let zero_octave = SimplexNoise::new(random);
+        if last_octave >= 0 && last_octave < octave_count && octaves.contains(&0) {
+            noise_levels[last_octave as usize] = Some(zero_octave);
+        }
+        for index in last_octave + 1..octave_count {
+            if index >= 0 && octaves.contains(&(last_octave - index)) {
+                noise_levels[index as usize] = Some(SimplexNoise::new(random));
+            } else {
+                random.consume_count(262);
+            }
+        }
+
+        PerlinSimplexNoise {
+            noise_levels,
+            highest_freq_input_factor: 2.0f64.powi(last_octave),
+            highest_freq_value_factor: 1.0 / (2.0f64.powi(octave_count) - 1.0),
+        }
+    }
+
+    pub fn get_value(&self, x: f64, y: f64, use_origin: bool) -> f64 {
+        let mut result = 0.0;
+        let mut input_factor = self.highest_freq_input_factor;
+        let mut value_factor = self.highest_freq_value_factor;
+        for noise in &self.noise_levels {
+            if let Some(noise) = noise {
+                let (offset_x, offset_y) = if use_origin {
+                    (noise.xo, noise.yo)
+                } else {
+                    (0.0, 0.0)
+                };
+                result += noise
+                    .get_value_2d(x * input_factor + offset_x, y * input_factor + offset_y)
+                    * value_factor;
+            }
+            input_factor /= 2.0;
+            value_factor *= 2.0;
+        }
+        result
+    }
+}
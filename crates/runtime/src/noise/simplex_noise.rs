@@ -0,0 +1,70 @@
+use crate::noise::{dot, GRADIENT};
+use crate::random_source::RandomSource;
+use util::math;
+
+#[derive(Debug)]
+pub struct SimplexNoise {
+    pub xo: f64,
+    pub yo: f64,
+    pub zo: f64,
+    p: [u8; 256],
+}
+
+impl SimplexNoise {
+    const SQRT_3: f64 = 1.7320508075688772;
+    const F2: f64 = 0.5 * (Self::SQRT_3 - 1.0);
+    const G2: f64 = (3.0 - Self::SQRT_3) / 6.0;
+
+    pub fn new(random: &mut impl RandomSource) -> SimplexNoise {
+        let xo = random.next_f64() * 256.0;
+        let yo = random.next_f64() * 256.0;
+        let zo = random.next_f64() * 256.0;
+        let mut p = [0; 256];
+        for (i, p) in p.iter_mut().enumerate() {
+            *p = i as u8;
+        }
+        for i in 0..256 {
+            let j = random.next_u32(256 - i as u32) as usize;
+            p.swap(i, i + j);
+        }
+        SimplexNoise { xo, yo, zo, p }
+    }
+
+    #[inline]
+    fn p(&self, index: i32) -> i32 {
+        self.p[(index & 0xff) as usize] as i32
+    }
+
+    fn get_corner_noise_3d(gradient_index: i32, x: f64, y: f64, z: f64, offset: f64) -> f64 {
+        let mut value = offset - x * x - y * y - z * z;
+        if value < 0.0 {
+            0.0
+        } else {
+            value *= value;
+            value * value * dot(&GRADIENT[gradient_index as usize], x, y, z)
+        }
+    }
+
+    pub fn get_value_2d(&self, x: f64, y: f64) -> f64 {
+        let skew = (x + y) * Self::F2;
+        let cell_x = math::floor(x + skew);
+        let cell_y = math::floor(y + skew);
+        let unskew = cell_x.wrapping_add(cell_y) as f64 * Self::G2;
+        let x0 = x - (cell_x as f64 - unskew);
+        let y0 = y - (cell_y as f64 - unskew);
+        let (offset_x, offset_y) = if x0 > y0 { (1, 0) } else { (0, 1) };
+        let x1 = x0 - offset_x as f64 + Self::G2;
+        let y1 = y0 - offset_y as f64 + Self::G2;
+        let x2 = x0 - 1.0 + 2.0 * Self::G2;
+        let y2 = y0 - 1.0 + 2.0 * Self::G2;
+        let cell_x = cell_x & 0xff;
+        let cell_y = cell_y & 0xff;
+        let gradient0 = self.p(cell_x + self.p(cell_y)) % 12;
+        let gradient1 = self.p(cell_x + offset_x + self.p(cell_y + offset_y)) % 12;
+        let gradient2 = self.p(cell_x + 1 + self.p(cell_y + 1)) % 12;
+        let corner0 = Self::get_corner_noise_3d(gradient0, x0, y0, 0.0, 0.5);
+        let corner1 = Self::get_corner_noise_3d(gradient1, x1, y1, 0.0, 0.5);
+        let corner2 = Self::get_corner_noise_3d(gradient2, x2, y2, 0.0, 0.5);
+        70.0 * (corner0 + corner1 + corner2)
+    }
+}
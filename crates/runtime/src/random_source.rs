@@ -2,12 +2,19 @@ use glam::IVec3;
 use util::identifier::{Identifier, IdentifierBuf};
 
 pub trait RandomSource {
-    fn fork(&mut self) -> Self;
-    fn fork_positional(&mut self) -> impl PositionalRandomFactory;
+    fn fork(&mut self) -> impl RandomSource + use<Self>;
+    fn fork_positional(&mut self) -> impl PositionalRandomFactory + use<Self>;
     fn set_seed(&mut self, seed: u64);
     fn next_u32_unbounded(&mut self) -> u32;
     fn next_u32(&mut self, bound: u32) -> u32;
 
+    /// The next `bits` bits, as [`WorldgenRandom`] reads them from the random it wraps. These are
+    /// the high bits of the next long, except for random sources built on bits like
+    /// [`LegacyRandomSource`].
+    fn next_bits(&mut self, bits: u32) -> u32 {
+        (self.next_u64() >> (64 - bits)) as u32
+    }
+
     fn next_i32_between_inclusive(&mut self, min: i32, max: i32) -> i32 {
         assert!(min <= max);
         self.next_u32((max - min + 1) as u32) as i32 + min
@@ -63,12 +70,13 @@ impl LegacyRandomSource {
 
 impl RandomSource for LegacyRandomSource {
     #[inline]
-    fn fork(&mut self) -> Self {
+    #[allow(refining_impl_trait)]
+    fn fork(&mut self) -> LegacyRandomSource {
         LegacyRandomSource::new(self.next_u64())
     }
 
     #[inline]
-    fn fork_positional(&mut self) -> impl PositionalRandomFactory {
+    fn fork_positional(&mut self) -> impl PositionalRandomFactory + use<> {
         LegacyPositionalRandomFactory {
             seed: self.next_u64(),
         }
@@ -84,6 +92,11 @@ impl RandomSource for LegacyRandomSource {
         self.next(32)
     }
 
+    #[inline]
+    fn next_bits(&mut self, bits: u32) -> u32 {
+        self.next(bits)
+    }
+
     #[inline]
     fn next_u32(&mut self, bound: u32) -> u32 {
         assert!(bound > 0, "bound must be positive");
@@ -93,7 +106,8 @@ impl RandomSource for LegacyRandomSource {
             loop {
                 let u = self.next(31);
                 let r = u % bound;
-                if r + (bound - 1) <= u {
+                // equivalent to Java's `u - r + (bound - 1) >= 0` overflow check
+                if u - r + (bound - 1) <= i32::MAX as u32 {
                     return r;
                 }
             }
@@ -192,7 +206,7 @@ impl XoroshiroRandomSource {
     #[inline]
     pub fn new(seed: u64) -> XoroshiroRandomSource {
         let lo = seed ^ Self::SILVER_RATIO_64;
-        let hi = seed.wrapping_add(Self::GOLDEN_RATIO_64);
+        let hi = lo.wrapping_add(Self::GOLDEN_RATIO_64);
         Self::new128(Self::mix_stafford_13(lo), Self::mix_stafford_13(hi))
     }
 
@@ -212,12 +226,13 @@ impl XoroshiroRandomSource {
 
 impl RandomSource for XoroshiroRandomSource {
     #[inline]
-    fn fork(&mut self) -> Self {
+    #[allow(refining_impl_trait)]
+    fn fork(&mut self) -> XoroshiroRandomSource {
         XoroshiroRandomSource::new128(self.next_u64(), self.next_u64())
     }
 
     #[inline]
-    fn fork_positional(&mut self) -> impl PositionalRandomFactory {
+    fn fork_positional(&mut self) -> impl PositionalRandomFactory + use<> {
         XoroshiroPositionalRandomFactory {
             seed_lo: self.next_u64(),
             seed_hi: self.next_u64(),
@@ -306,16 +321,159 @@ fn next_gaussian(mut f64_source: impl FnMut() -> f64) -> (f64, f64) {
     }
 }
 
+/// Derives all its values from the [bits](RandomSource::next_bits) of another random source, the
+/// way [`LegacyRandomSource`] derives them from its own. Equivalent to vanilla's `WorldgenRandom`,
+/// which seeds carvers, features and structures.
+#[derive(Debug)]
+pub struct WorldgenRandom<R> {
+    random: R,
+    next_next_gaussian: Option<f64>,
+}
+
+impl<R: RandomSource> WorldgenRandom<R> {
+    #[inline]
+    pub fn new(random: R) -> WorldgenRandom<R> {
+        WorldgenRandom {
+            random,
+            next_next_gaussian: None,
+        }
+    }
+
+    #[inline]
+    fn next(&mut self, bits: u32) -> u32 {
+        self.random.next_bits(bits)
+    }
+
+    /// Seeds the random for a carver or structure starting in a chunk,
+    /// `WorldgenRandom.setLargeFeatureSeed`.
+    pub fn set_large_feature_seed(&mut self, seed: u64, chunk_x: i32, chunk_z: i32) {
+        self.set_seed(seed);
+        let x_multiplier = self.next_u64();
+        let z_multiplier = self.next_u64();
+        self.set_seed(
+            (chunk_x as i64 as u64).wrapping_mul(x_multiplier)
+                ^ (chunk_z as i64 as u64).wrapping_mul(z_multiplier)
+                ^ seed,
+        );
+    }
+
+    /// Seeds the random for decorating the chunk with the given minimum block coordinates,
+    /// returning the seed the features of the chunk are seeded from.
+    /// `WorldgenRandom.setDecorationSeed`.
+    pub fn set_decoration_seed(&mut self, seed: u64, min_block_x: i32, min_block_z: i32) -> u64 {
+        self.set_seed(seed);
+        let x_multiplier = self.next_u64() | 1;
+        let z_multiplier = self.next_u64() | 1;
+        let decoration_seed = (min_block_x as i64 as u64)
+            .wrapping_mul(x_multiplier)
+            .wrapping_add((min_block_z as i64 as u64).wrapping_mul(z_multiplier))
+            ^ seed;
+        self.set_seed(decoration_seed);
+        decoration_seed
+    }
+
+    /// Seeds the random for placing the feature with the given index in a decoration step,
+    /// `WorldgenRandom.setFeatureSeed`.
+    pub fn set_feature_seed(&mut self, decoration_seed: u64, index: usize, step: usize) {
+        self.set_seed(
+            decoration_seed
+                .wrapping_add(index as u64)
+                .wrapping_add(10000 * step as u64),
+        );
+    }
+}
+
+impl<R: RandomSource> RandomSource for WorldgenRandom<R> {
+    #[inline]
+    fn fork(&mut self) -> impl RandomSource + use<R> {
+        self.random.fork()
+    }
+
+    #[inline]
+    fn fork_positional(&mut self) -> impl PositionalRandomFactory + use<R> {
+        self.random.fork_positional()
+    }
+
+    /// Seeds the wrapped random. Like vanilla, this keeps the pending gaussian.
+    #[inline]
+    fn set_seed(&mut self, seed: u64) {
+        self.random.set_seed(seed);
+    }
+
+    #[inline]
+    fn next_u32_unbounded(&mut self) -> u32 {
+        self.next(32)
+    }
+
+    /// Like vanilla, where `WorldgenRandom` is itself a legacy random, its bits are its own.
+    #[inline]
+    fn next_bits(&mut self, bits: u32) -> u32 {
+        self.next(bits)
+    }
+
+    #[inline]
+    fn next_u32(&mut self, bound: u32) -> u32 {
+        assert!(bound > 0, "bound must be positive");
+        if bound.is_power_of_two() {
+            ((bound as u64 * self.next(31) as u64) >> 31) as u32
+        } else {
+            loop {
+                let u = self.next(31);
+                let r = u % bound;
+                if u - r + (bound - 1) <= i32::MAX as u32 {
+                    return r;
+                }
+            }
+        }
+    }
+
+    #[inline]
+    fn next_u64(&mut self) -> u64 {
+        let hi = self.next(32);
+        let lo = self.next(32);
+        (((hi as u64) << 32) as i64).wrapping_add(lo as i32 as i64) as u64
+    }
+
+    #[inline]
+    fn next_bool(&mut self) -> bool {
+        self.next(1) != 0
+    }
+
+    #[inline]
+    fn next_f32(&mut self) -> f32 {
+        (self.next(24) as f32) * (1.0 / (1 << 24) as f32)
+    }
+
+    #[inline]
+    fn next_f64(&mut self) -> f64 {
+        let hi = self.next(26);
+        let lo = self.next(27);
+        let l = ((hi as u64) << 27) + lo as u64;
+        (l as f64) * (1.0 / (1u64 << 53) as f64)
+    }
+
+    #[inline]
+    fn next_gaussian(&mut self) -> f64 {
+        if let Some(next_next_gaussian) = self.next_next_gaussian.take() {
+            next_next_gaussian
+        } else {
+            let (g1, g2) = next_gaussian(|| self.next_f64());
+            self.next_next_gaussian = Some(g2);
+            g1
+        }
+    }
+}
+
 pub trait PositionalRandomFactory {
     type Hash;
 
-    fn at(&self, pos: IVec3) -> impl RandomSource;
-    fn create_from_seed(&self, seed: u64) -> impl RandomSource;
-    fn create_from_hash(&self, hash: Self::Hash) -> impl RandomSource;
+    fn at(&self, pos: IVec3) -> impl RandomSource + use<Self>;
+    fn create_from_seed(&self, seed: u64) -> impl RandomSource + use<Self>;
+    fn create_from_hash(&self, hash: Self::Hash) -> impl RandomSource + use<Self>;
     fn hash<T>(&self, value: T) -> Self::Hash
     where
         T: Hashable;
-    fn create_from_hash_of<T>(&self, value: T) -> impl RandomSource
+    fn create_from_hash_of<T>(&self, value: T) -> impl RandomSource + use<Self, T>
     where
         T: Hashable,
     {
@@ -328,6 +486,21 @@ pub trait Hashable {
     fn chars_utf16(&self) -> impl Iterator<Item = u16>;
 }
 
+impl<T> Hashable for &T
+where
+    T: Hashable + ?Sized,
+{
+    #[inline]
+    fn digest_md5(&self, context: &mut md5::Context) {
+        T::digest_md5(self, context)
+    }
+
+    #[inline]
+    fn chars_utf16(&self) -> impl Iterator<Item = u16> {
+        T::chars_utf16(self)
+    }
+}
+
 impl Hashable for str {
     #[inline]
     fn digest_md5(&self, context: &mut md5::Context) {
@@ -383,26 +556,27 @@ impl Hashable for IdentifierBuf {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct LegacyPositionalRandomFactory {
     seed: u64,
 }
 
+#[allow(refining_impl_trait)]
 impl PositionalRandomFactory for LegacyPositionalRandomFactory {
     type Hash = i32;
 
     #[inline]
-    fn at(&self, pos: IVec3) -> impl RandomSource {
+    fn at(&self, pos: IVec3) -> LegacyRandomSource {
         LegacyRandomSource::new(get_seed(pos) ^ self.seed)
     }
 
     #[inline]
-    fn create_from_seed(&self, seed: u64) -> impl RandomSource {
+    fn create_from_seed(&self, seed: u64) -> LegacyRandomSource {
         LegacyRandomSource::new(seed)
     }
 
     #[inline]
-    fn create_from_hash(&self, hash: i32) -> impl RandomSource {
+    fn create_from_hash(&self, hash: i32) -> LegacyRandomSource {
         LegacyRandomSource::new(hash as i64 as u64 ^ self.seed)
     }
 
@@ -418,27 +592,28 @@ impl PositionalRandomFactory for LegacyPositionalRandomFactory {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct XoroshiroPositionalRandomFactory {
     seed_lo: u64,
     seed_hi: u64,
 }
 
+#[allow(refining_impl_trait)]
 impl PositionalRandomFactory for XoroshiroPositionalRandomFactory {
     type Hash = [u8; 16];
 
     #[inline]
-    fn at(&self, pos: IVec3) -> impl RandomSource {
+    fn at(&self, pos: IVec3) -> XoroshiroRandomSource {
         XoroshiroRandomSource::new128(get_seed(pos) ^ self.seed_lo, self.seed_hi)
     }
 
     #[inline]
-    fn create_from_seed(&self, seed: u64) -> impl RandomSource {
+    fn create_from_seed(&self, seed: u64) -> XoroshiroRandomSource {
         XoroshiroRandomSource::new128(seed ^ self.seed_lo, seed ^ self.seed_hi)
     }
 
     #[inline]
-    fn create_from_hash(&self, hash: [u8; 16]) -> impl RandomSource {
+    fn create_from_hash(&self, hash: [u8; 16]) -> XoroshiroRandomSource {
         let mut lower_hash = [0; 8];
         lower_hash.copy_from_slice(&hash[..8]);
         let lower = u64::from_be_bytes(lower_hash);
@@ -458,6 +633,164 @@ impl PositionalRandomFactory for XoroshiroPositionalRandomFactory {
     }
 }
 
+/// Either kind of random source, for worlds that pick theirs at runtime with
+/// `legacy_random_source`.
+#[derive(Debug)]
+pub enum AnyRandomSource {
+    Legacy(LegacyRandomSource),
+    Xoroshiro(XoroshiroRandomSource),
+}
+
+impl AnyRandomSource {
+    pub fn new(seed: u64, legacy: bool) -> AnyRandomSource {
+        if legacy {
+            AnyRandomSource::Legacy(LegacyRandomSource::new(seed))
+        } else {
+            AnyRandomSource::Xoroshiro(XoroshiroRandomSource::new(seed))
+        }
+    }
+}
+
+macro_rules! delegate_random_source {
+    ($($name:ident($($arg:ident: $ty:ty),*) -> $ret:ty;)*) => {
+        $(
+            #[inline]
+            fn $name(&mut self, $($arg: $ty),*) -> $ret {
+                match self {
+                    AnyRandomSource::Legacy(random) => random.$name($($arg),*),
+                    AnyRandomSource::Xoroshiro(random) => random.$name($($arg),*),
+                }
+            }
+        )*
+    };
+}
+
+#[allow(refining_impl_trait)]
+impl RandomSource for AnyRandomSource {
+    #[inline]
+    fn fork(&mut self) -> Self {
+        match self {
+            AnyRandomSource::Legacy(random) => AnyRandomSource::Legacy(random.fork()),
+            AnyRandomSource::Xoroshiro(random) => AnyRandomSource::Xoroshiro(random.fork()),
+        }
+    }
+
+    #[inline]
+    fn fork_positional(&mut self) -> AnyPositionalRandomFactory {
+        AnyPositionalRandomFactory(match self {
+            AnyRandomSource::Legacy(random) => {
+                AnyPositionalRandomFactoryImpl::Legacy(LegacyPositionalRandomFactory {
+                    seed: random.next_u64(),
+                })
+            }
+            AnyRandomSource::Xoroshiro(random) => {
+                AnyPositionalRandomFactoryImpl::Xoroshiro(XoroshiroPositionalRandomFactory {
+                    seed_lo: random.next_u64(),
+                    seed_hi: random.next_u64(),
+                })
+            }
+        })
+    }
+
+    delegate_random_source! {
+        set_seed(seed: u64) -> ();
+        next_u32_unbounded() -> u32;
+        next_u32(bound: u32) -> u32;
+        next_bits(bits: u32) -> u32;
+        next_u64() -> u64;
+        next_bool() -> bool;
+        next_f32() -> f32;
+        next_f64() -> f64;
+        next_gaussian() -> f64;
+        consume_count(count: u64) -> ();
+    }
+}
+
+/// The positional random factory of an [`AnyRandomSource`], equivalent to vanilla's
+/// `RandomState.random` and the factories forked from it.
+#[derive(Debug, Clone)]
+pub struct AnyPositionalRandomFactory(AnyPositionalRandomFactoryImpl);
+
+#[derive(Debug, Clone)]
+enum AnyPositionalRandomFactoryImpl {
+    Legacy(LegacyPositionalRandomFactory),
+    Xoroshiro(XoroshiroPositionalRandomFactory),
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct AnyRandomHash(AnyRandomHashImpl);
+
+#[derive(Debug, Copy, Clone)]
+enum AnyRandomHashImpl {
+    Legacy(i32),
+    Xoroshiro([u8; 16]),
+}
+
+impl AnyPositionalRandomFactory {
+    /// Equivalent of `RandomState.random`, the factory all of a world's positional randoms are
+    /// forked from.
+    pub fn new(seed: u64, legacy: bool) -> AnyPositionalRandomFactory {
+        AnyRandomSource::new(seed, legacy).fork_positional()
+    }
+}
+
+#[allow(refining_impl_trait)]
+impl PositionalRandomFactory for AnyPositionalRandomFactory {
+    type Hash = AnyRandomHash;
+
+    #[inline]
+    fn at(&self, pos: IVec3) -> AnyRandomSource {
+        match &self.0 {
+            AnyPositionalRandomFactoryImpl::Legacy(factory) => {
+                AnyRandomSource::Legacy(factory.at(pos))
+            }
+            AnyPositionalRandomFactoryImpl::Xoroshiro(factory) => {
+                AnyRandomSource::Xoroshiro(factory.at(pos))
+            }
+        }
+    }
+
+    #[inline]
+    fn create_from_seed(&self, seed: u64) -> AnyRandomSource {
+        match &self.0 {
+            AnyPositionalRandomFactoryImpl::Legacy(factory) => {
+                AnyRandomSource::Legacy(factory.create_from_seed(seed))
+            }
+            AnyPositionalRandomFactoryImpl::Xoroshiro(factory) => {
+                AnyRandomSource::Xoroshiro(factory.create_from_seed(seed))
+            }
+        }
+    }
+
+    #[inline]
+    fn create_from_hash(&self, hash: AnyRandomHash) -> AnyRandomSource {
+        match (&self.0, hash.0) {
+            (AnyPositionalRandomFactoryImpl::Legacy(factory), AnyRandomHashImpl::Legacy(hash)) => {
+                AnyRandomSource::Legacy(factory.create_from_hash(hash))
+            }
+            (
+                AnyPositionalRandomFactoryImpl::Xoroshiro(factory),
+                AnyRandomHashImpl::Xoroshiro(hash),
+            ) => AnyRandomSource::Xoroshiro(factory.create_from_hash(hash)),
+            _ => panic!("hash from a different kind of random factory"),
+        }
+    }
+
+    fn hash<T>(&self, value: T) -> AnyRandomHash
+    where
+        T: Hashable,
+    {
+        AnyRandomHash(match &self.0 {
+            AnyPositionalRandomFactoryImpl::Legacy(factory) => {
+                AnyRandomHashImpl::Legacy(factory.hash(value))
+            }
+            AnyPositionalRandomFactoryImpl::Xoroshiro(factory) => {
+                AnyRandomHashImpl::Xoroshiro(factory.hash(value))
+            }
+        })
+    }
+}
+
 fn get_seed(pos: IVec3) -> u64 {
     let mut n =
         pos.x.wrapping_mul(3129871) as i64 ^ (pos.z as i64).wrapping_mul(116129781) ^ pos.y as i64;
@@ -467,3 +800,32 @@ fn get_seed(pos: IVec3) -> u64 {
         .wrapping_add(n.wrapping_mul(11));
     (n >> 16) as u64
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::random_source::{RandomSource, WorldgenRandom, XoroshiroRandomSource};
+
+    #[test]
+    fn test_xoroshiro_worldgen_random() {
+        // values from vanilla's `WorldgenRandom` wrapping a `XoroshiroRandomSource`
+        let mut random = WorldgenRandom::new(XoroshiroRandomSource::new(12345));
+        assert_eq!(random.next_u32_unbounded() as i32, -1890232152);
+        assert_eq!(random.next_u32(10), 7);
+        assert_eq!(random.next_u32(16), 3);
+        assert_eq!(random.next_u64() as i64, 1226499897444815888);
+        assert_eq!(random.next_f32(), 0.19141972);
+        assert_eq!(random.next_f64(), 0.06009500937578072);
+        assert!(random.next_bool());
+
+        let decoration_seed = random.set_decoration_seed(12345, -32, 48);
+        assert_eq!(decoration_seed as i64, -2476831614839651223);
+        assert_eq!(random.next_u32(100), 13);
+        random.set_feature_seed(decoration_seed, 3, 2);
+        assert_eq!(random.next_u32(100), 75);
+        random.set_large_feature_seed(12345, 5, -7);
+        assert_eq!(random.next_u32(100), 73);
+        let mut fork = random.fork();
+        assert_eq!(fork.next_u64() as i64, 3700693224135881804);
+        assert_eq!(random.next_u32(100), 29);
+    }
+}
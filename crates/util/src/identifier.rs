@@ -184,7 +184,7 @@ impl PartialEq for Identifier {
         let other = other
             .value
             .strip_prefix("minecraft:")
-            .unwrap_or(&self.value);
+            .unwrap_or(&other.value);
         this == other
     }
 }
@@ -2,5 +2,6 @@ pub mod add_only_map;
 pub mod direction;
 pub mod heightmap_type;
 pub mod identifier;
+pub mod math;
 pub mod ranged;
 pub mod user_data;
@@ -0,0 +1,147 @@
+//! Ports of vanilla's `Mth` helpers. These are written to give bit-identical results to the Java
+//! implementations, so be careful when "simplifying" them.
+
+use num::Float;
+use std::sync::OnceLock;
+
+#[inline]
+pub fn floor(value: f64) -> i32 {
+    // `as` saturates and maps NaN to 0, just like a Java `(int)` cast
+    value.floor() as i32
+}
+
+#[inline]
+pub fn lfloor(value: f64) -> i64 {
+    value.floor() as i64
+}
+
+/// Java's `Math.round`, which rounds ties towards positive infinity.
+#[inline]
+pub fn lround(value: f64) -> i64 {
+    let floor = value.floor();
+    // the difference is exact, unlike `value + 0.5`
+    if value - floor >= 0.5 {
+        floor as i64 + 1
+    } else {
+        floor as i64
+    }
+}
+
+/// Rounds `value` down to a multiple of `step`.
+#[inline]
+pub fn quantize(value: f64, step: i32) -> i32 {
+    floor(value / step as f64) * step
+}
+
+#[inline]
+pub fn clamp<T: Float>(value: T, min: T, max: T) -> T {
+    if value < min {
+        min
+    } else {
+        value.min(max)
+    }
+}
+
+#[inline]
+pub fn lerp<T: Float>(delta: T, start: T, end: T) -> T {
+    start + delta * (end - start)
+}
+
+#[inline]
+pub fn lerp2(delta1: f64, delta2: f64, x0y0: f64, x1y0: f64, x0y1: f64, x1y1: f64) -> f64 {
+    lerp(delta2, lerp(delta1, x0y0, x1y0), lerp(delta1, x0y1, x1y1))
+}
+
+#[allow(clippy::too_many_arguments)]
+#[inline]
+pub fn lerp3(
+    delta1: f64,
+    delta2: f64,
+    delta3: f64,
+    x0y0z0: f64,
+    x1y0z0: f64,
+    x0y1z0: f64,
+    x1y1z0: f64,
+    x0y0z1: f64,
+    x1y0z1: f64,
+    x0y1z1: f64,
+    x1y1z1: f64,
+) -> f64 {
+    lerp(
+        delta3,
+        lerp2(delta1, delta2, x0y0z0, x1y0z0, x0y1z0, x1y1z0),
+        lerp2(delta1, delta2, x0y0z1, x1y0z1, x0y1z1, x1y1z1),
+    )
+}
+
+#[inline]
+pub fn clamped_lerp(start: f64, end: f64, delta: f64) -> f64 {
+    if delta < 0.0 {
+        start
+    } else if delta > 1.0 {
+        end
+    } else {
+        lerp(delta, start, end)
+    }
+}
+
+#[inline]
+pub fn inverse_lerp(value: f64, start: f64, end: f64) -> f64 {
+    (value - start) / (end - start)
+}
+
+#[inline]
+pub fn map(input: f64, in_min: f64, in_max: f64, out_min: f64, out_max: f64) -> f64 {
+    lerp(inverse_lerp(input, in_min, in_max), out_min, out_max)
+}
+
+#[inline]
+pub fn clamped_map(input: f64, in_min: f64, in_max: f64, out_min: f64, out_max: f64) -> f64 {
+    clamped_lerp(out_min, out_max, inverse_lerp(input, in_min, in_max))
+}
+
+/// `Mth.sin`, which looks the sine up in a table of 65536 samples instead of computing it.
+#[inline]
+pub fn sin(value: f32) -> f32 {
+    sin_table()[((value * 10430.378) as i32 & 65535) as usize]
+}
+
+/// `Mth.cos`, which looks the cosine up in the same table as [`sin`].
+#[inline]
+pub fn cos(value: f32) -> f32 {
+    sin_table()[((value * 10430.378 + 16384.0) as i32 & 65535) as usize]
+}
+
+fn sin_table() -> &'static [f32; 65536] {
+    static SIN: OnceLock<Box<[f32; 65536]>> = OnceLock::new();
+    SIN.get_or_init(|| {
+        let mut table = Box::new([0.0; 65536]);
+        for (index, value) in table.iter_mut().enumerate() {
+            *value = (index as f64 * std::f64::consts::PI * 2.0 / 65536.0).sin() as f32;
+        }
+        table
+    })
+}
+
+#[inline]
+pub fn smoothstep(value: f64) -> f64 {
+    value * value * value * (value * (value * 6.0 - 15.0) + 10.0)
+}
+
+/// Returns the first index in `min..max` for which `predicate` is true, or `max` if there is none.
+/// The predicate must be false for a prefix of the range and true for the rest.
+#[inline]
+pub fn binary_search(mut min: i32, max: i32, mut predicate: impl FnMut(i32) -> bool) -> i32 {
+    let mut len = max - min;
+    while len > 0 {
+        let half = len / 2;
+        let mid = min + half;
+        if predicate(mid) {
+            len = half;
+        } else {
+            min = mid + 1;
+            len -= half + 1;
+        }
+    }
+    min
+}